@@ -0,0 +1,2 @@
+-- Per-phase dependency policy as JSON, e.g. {"allow_new": false, "max_bump": "patch"}
+ALTER TABLE phases ADD COLUMN dep_policy TEXT;
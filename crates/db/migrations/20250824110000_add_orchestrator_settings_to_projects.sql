@@ -0,0 +1,6 @@
+-- Per-project test settings for the orchestrator's cold/warm runs
+-- orchestrator_test_env holds KEY=VALUE lines; {cache_dir} expands to the orchestrator cache
+ALTER TABLE projects ADD COLUMN orchestrator_test_command TEXT;
+ALTER TABLE projects ADD COLUMN orchestrator_cold_timeout_sec INTEGER;
+ALTER TABLE projects ADD COLUMN orchestrator_warm_timeout_sec INTEGER;
+ALTER TABLE projects ADD COLUMN orchestrator_test_env TEXT;
@@ -0,0 +1,2 @@
+-- When set, patches touching files outside the phase allowlist/denylist are refused before git apply
+ALTER TABLE phases ADD COLUMN reject_out_of_scope INTEGER;
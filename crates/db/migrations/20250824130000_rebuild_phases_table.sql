@@ -0,0 +1,43 @@
+-- Rebuild phases around task ownership: the original table required project_id/name,
+-- which the task-scoped columns added later never populated. The textual phase_id is
+-- dropped in favour of the BLOB primary key, and enum columns get CHECK constraints.
+CREATE TABLE phases_new (
+    id BLOB PRIMARY KEY,
+    task_id BLOB NOT NULL,
+    type TEXT NOT NULL DEFAULT 'prompt' CHECK (type IN ('prompt','fix','hardening')),
+    status TEXT NOT NULL DEFAULT 'idle' CHECK (status IN ('idle','running','pass','fail')),
+    allowlist TEXT NOT NULL DEFAULT '[]',
+    denylist TEXT NOT NULL DEFAULT '[]',
+    agent_override TEXT,
+    warm_kpi_budget REAL,
+    dep_policy TEXT,
+    reject_out_of_scope INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'subsec')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'subsec')),
+    FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+INSERT INTO phases_new (id, task_id, type, status, allowlist, denylist, agent_override,
+                        warm_kpi_budget, dep_policy, reject_out_of_scope, created_at, updated_at)
+SELECT COALESCE(id, randomblob(16)),
+       task_id,
+       COALESCE(type, name, 'prompt'),
+       COALESCE(status, 'idle'),
+       COALESCE(allowlist, '[]'),
+       COALESCE(denylist, '[]'),
+       agent_override,
+       warm_kpi_budget,
+       dep_policy,
+       COALESCE(reject_out_of_scope, 0),
+       created_at,
+       COALESCE(updated_at, created_at)
+FROM phases
+WHERE task_id IS NOT NULL AND task_id IN (SELECT id FROM tasks);
+
+DROP TABLE phases;
+ALTER TABLE phases_new RENAME TO phases;
+
+CREATE INDEX idx_phases_task_created ON phases(task_id, created_at);
+
+-- Attempts may only point at phases that still exist
+UPDATE task_attempts SET phase_id = NULL WHERE phase_id NOT IN (SELECT id FROM phases);
@@ -0,0 +1,5 @@
+-- Each orchestrator run writes its own artifacts directory; rows recorded before
+-- this migration have no run and lived directly in the attempt's directory
+ALTER TABLE attempt_artifacts ADD COLUMN execution_process_id BLOB REFERENCES execution_processes(id) ON DELETE SET NULL;
+ALTER TABLE attempt_artifacts ADD COLUMN size_bytes INTEGER;
+CREATE INDEX IF NOT EXISTS idx_attempt_artifacts_execution_process ON attempt_artifacts(execution_process_id);
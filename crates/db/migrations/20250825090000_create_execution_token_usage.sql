@@ -0,0 +1,16 @@
+PRAGMA foreign_keys = ON;
+
+-- Tokens each execution process consumed, one row per model; `model` is ''
+-- when the agent did not report one
+CREATE TABLE execution_token_usage (
+    execution_process_id BLOB NOT NULL,
+    model                TEXT NOT NULL DEFAULT '',
+    turns                INTEGER NOT NULL DEFAULT 0,
+    input_tokens         INTEGER NOT NULL DEFAULT 0,
+    output_tokens        INTEGER NOT NULL DEFAULT 0,
+    cache_read_tokens    INTEGER NOT NULL DEFAULT 0,
+    cache_write_tokens   INTEGER NOT NULL DEFAULT 0,
+    updated_at           TEXT NOT NULL DEFAULT (datetime('now', 'subsec')),
+    PRIMARY KEY (execution_process_id, model),
+    FOREIGN KEY (execution_process_id) REFERENCES execution_processes(id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Tool calls a supervised agent may make without asking, one rule per line:
+-- `Tool` or `Tool(pattern)`, where `*` in the pattern matches any text
+ALTER TABLE projects ADD COLUMN auto_approve_rules TEXT;
@@ -7,6 +7,9 @@ use sqlx::{
 use utils::assets::asset_dir;
 
 pub mod models;
+/// Fixtures for tests of this and dependent crates, see the `test-support` feature
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 
 #[derive(Clone)]
 pub struct DBService {
@@ -25,6 +28,19 @@ impl DBService {
         Ok(DBService { pool })
     }
 
+    /// A migrated database that lives as long as the pool, for tests
+    pub async fn new_in_memory() -> Result<DBService, Error> {
+        // Each connection to `:memory:` would open a database of its own
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:")?)
+            .await?;
+        sqlx::migrate!("./migrations").run(&pool).await?;
+        Ok(DBService { pool })
+    }
+
     pub async fn new_with_after_connect<F>(after_connect: F) -> Result<DBService, Error>
     where
         F: for<'a> Fn(
@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// A file written by an orchestrator run, e.g. `kpi.json` or `snippets.log`
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, TS)]
+pub struct AttemptArtifact {
+    pub id: Uuid,
+    pub attempt_id: Uuid,
+    /// The run that wrote it; `None` for artifacts recorded before runs were kept apart
+    pub execution_process_id: Option<Uuid>,
+    pub kind: String, // file name within the run's artifacts directory
+    #[serde(skip)]
+    pub path: String, // absolute path on disk, never sent to clients
+    pub size_bytes: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+pub struct CreateAttemptArtifact {
+    pub attempt_id: Uuid,
+    pub execution_process_id: Option<Uuid>,
+    pub kind: String,
+    pub path: String,
+    pub size_bytes: Option<i64>,
+}
+
+impl AttemptArtifact {
+    pub async fn create(
+        pool: &SqlitePool,
+        data: &CreateAttemptArtifact,
+        artifact_id: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            AttemptArtifact,
+            r#"INSERT INTO attempt_artifacts (id, attempt_id, execution_process_id, kind, path, size_bytes)
+               VALUES ($1, $2, $3, $4, $5, $6)
+               RETURNING id as "id!: Uuid",
+                         attempt_id as "attempt_id!: Uuid",
+                         execution_process_id as "execution_process_id?: Uuid",
+                         kind,
+                         path,
+                         size_bytes,
+                         created_at as "created_at!: DateTime<Utc>""#,
+            artifact_id,
+            data.attempt_id,
+            data.execution_process_id,
+            data.kind,
+            data.path,
+            data.size_bytes
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn find_by_id(pool: &SqlitePool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            AttemptArtifact,
+            r#"SELECT id as "id!: Uuid",
+                      attempt_id as "attempt_id!: Uuid",
+                      execution_process_id as "execution_process_id?: Uuid",
+                      kind,
+                      path,
+                      size_bytes,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM attempt_artifacts
+               WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// All artifacts recorded for an attempt, oldest first
+    pub async fn find_by_attempt_id(
+        pool: &SqlitePool,
+        attempt_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            AttemptArtifact,
+            r#"SELECT id as "id!: Uuid",
+                      attempt_id as "attempt_id!: Uuid",
+                      execution_process_id as "execution_process_id?: Uuid",
+                      kind,
+                      path,
+                      size_bytes,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM attempt_artifacts
+               WHERE attempt_id = $1
+               ORDER BY created_at ASC, kind ASC"#,
+            attempt_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Artifacts written by one orchestrator run, oldest first
+    pub async fn find_by_execution_process_id(
+        pool: &SqlitePool,
+        execution_process_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            AttemptArtifact,
+            r#"SELECT id as "id!: Uuid",
+                      attempt_id as "attempt_id!: Uuid",
+                      execution_process_id as "execution_process_id?: Uuid",
+                      kind,
+                      path,
+                      size_bytes,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM attempt_artifacts
+               WHERE execution_process_id = $1
+               ORDER BY created_at ASC, kind ASC"#,
+            execution_process_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
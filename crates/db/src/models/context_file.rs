@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use ts_rs::TS;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, TS)]
+pub struct ProjectContextFile {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub filename: String,
+    pub mime: String,
+    pub size_bytes: i64,
+    pub sha256: String,
+    pub stored_path: String, // relative to the data root, e.g. blob/{sha256}/{filename}
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, TS)]
+pub struct TaskContextFile {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub filename: String,
+    pub mime: String,
+    pub size_bytes: i64,
+    pub sha256: String,
+    pub stored_path: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ProjectContextFile {
+    pub async fn find_by_project_id(
+        pool: &SqlitePool,
+        project_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            ProjectContextFile,
+            r#"SELECT id as "id!: Uuid",
+                      project_id as "project_id!: Uuid",
+                      filename,
+                      mime,
+                      size_bytes,
+                      sha256,
+                      stored_path,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM project_context_files
+               WHERE project_id = $1
+               ORDER BY created_at ASC, filename ASC"#,
+            project_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn find_by_id(pool: &SqlitePool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            ProjectContextFile,
+            r#"SELECT id as "id!: Uuid",
+                      project_id as "project_id!: Uuid",
+                      filename,
+                      mime,
+                      size_bytes,
+                      sha256,
+                      stored_path,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM project_context_files
+               WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn delete(pool: &SqlitePool, id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM project_context_files WHERE id = $1", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+impl TaskContextFile {
+    pub async fn find_by_task_id(
+        pool: &SqlitePool,
+        task_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            TaskContextFile,
+            r#"SELECT id as "id!: Uuid",
+                      task_id as "task_id!: Uuid",
+                      filename,
+                      mime,
+                      size_bytes,
+                      sha256,
+                      stored_path,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM task_context_files
+               WHERE task_id = $1
+               ORDER BY created_at ASC, filename ASC"#,
+            task_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn find_by_id(pool: &SqlitePool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            TaskContextFile,
+            r#"SELECT id as "id!: Uuid",
+                      task_id as "task_id!: Uuid",
+                      filename,
+                      mime,
+                      size_bytes,
+                      sha256,
+                      stored_path,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM task_context_files
+               WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Attach a project upload to a task; the blob is shared, not copied.
+    /// Attaching the same content twice returns the existing row.
+    pub async fn attach_from_project(
+        pool: &SqlitePool,
+        task_id: Uuid,
+        file: &ProjectContextFile,
+    ) -> Result<Self, sqlx::Error> {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            r#"INSERT OR IGNORE INTO task_context_files (id, task_id, filename, mime, size_bytes, sha256, stored_path)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+            id,
+            task_id,
+            file.filename,
+            file.mime,
+            file.size_bytes,
+            file.sha256,
+            file.stored_path
+        )
+        .execute(pool)
+        .await?;
+        sqlx::query_as!(
+            TaskContextFile,
+            r#"SELECT id as "id!: Uuid",
+                      task_id as "task_id!: Uuid",
+                      filename,
+                      mime,
+                      size_bytes,
+                      sha256,
+                      stored_path,
+                      created_at as "created_at!: DateTime<Utc>"
+               FROM task_context_files
+               WHERE task_id = $1 AND sha256 = $2
+               LIMIT 1"#,
+            task_id,
+            file.sha256
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn delete(pool: &SqlitePool, id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM task_context_files WHERE id = $1", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Content hashes still referenced by a project or task upload
+pub async fn referenced_hashes(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query_scalar!(
+        r#"SELECT sha256 as "sha256!" FROM project_context_files
+           UNION
+           SELECT sha256 as "sha256!" FROM task_context_files"#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Where an upload's content is stored, whichever project or task file it belongs to
+#[derive(Debug, Clone)]
+pub struct StoredBlob {
+    pub sha256: String,
+    pub mime: String,
+    pub stored_path: String,
+}
+
+/// Every stored blob referenced by a project or task upload
+pub async fn stored_blobs(pool: &SqlitePool) -> Result<Vec<StoredBlob>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredBlob,
+        r#"SELECT sha256 as "sha256!", mime as "mime!", stored_path as "stored_path!" FROM project_context_files
+           UNION
+           SELECT sha256 as "sha256!", mime as "mime!", stored_path as "stored_path!" FROM task_context_files"#
+    )
+    .fetch_all(pool)
+    .await
+}
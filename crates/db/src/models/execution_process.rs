@@ -26,6 +26,7 @@ pub enum ExecutionProcessRunReason {
     CleanupScript,
     CodingAgent,
     DevServer,
+    Orchestrator,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, TS)]
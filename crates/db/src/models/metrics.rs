@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::{
+    execution_process::ExecutionProcessRunReason,
+    task_attempt::AttemptTelemetry,
+    token_usage::{ExecutionTokenUsage, ModelTokenUsage},
+};
+
+/// Restricts metrics to attempts of one project and/or created in `[since, until)`
+#[derive(Debug, Clone, Default, Deserialize, TS)]
+pub struct MetricsFilter {
+    pub project_id: Option<Uuid>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+pub struct MetricsSummary {
+    pub attempts: i64,
+    pub profiles: Vec<ProfileMetrics>,
+    pub durations: Vec<DurationMetrics>,
+    pub validators: ValidatorMetrics,
+    pub tokens: TokenMetrics,
+    pub test_times: TestTimeMetrics,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+pub struct ProfileMetrics {
+    pub profile: String,
+    pub attempts: i64,
+    /// Attempts merged directly or through a merged PR
+    pub merged: i64,
+    /// Unmerged attempts whose latest execution failed
+    pub failed: i64,
+    /// `merged / (merged + failed)`; `None` until an attempt has settled
+    pub success_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, TS)]
+pub struct DurationStats {
+    pub count: i64,
+    pub sum_sec: f64,
+    pub mean_sec: Option<f64>,
+    pub p50_sec: Option<f64>,
+    pub p90_sec: Option<f64>,
+    pub p99_sec: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+pub struct DurationMetrics {
+    pub run_reason: ExecutionProcessRunReason,
+    #[serde(flatten)]
+    pub stats: DurationStats,
+}
+
+/// Share of orchestrator runs each validator passed, in `0.0..=1.0`
+#[derive(Debug, Clone, Default, Serialize, TS)]
+pub struct ValidatorMetrics {
+    pub runs: i64,
+    pub scope: Option<f64>,
+    pub dep: Option<f64>,
+    pub api: Option<f64>,
+    pub det: Option<f64>,
+    pub kpi: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, TS)]
+pub struct TokenMetrics {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub models: Vec<ModelTokenUsage>,
+    /// Spend on the models with a price; filled in from the price table
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, TS)]
+pub struct TestTimeMetrics {
+    pub cold: DurationStats,
+    pub warm: DurationStats,
+    pub cache_hit_count: i64,
+}
+
+impl DurationStats {
+    /// Mean and nearest-rank percentiles of the samples
+    pub fn from_samples(mut samples: Vec<f64>) -> Self {
+        samples.retain(|s| s.is_finite());
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(f64::total_cmp);
+        let sum: f64 = samples.iter().sum();
+        let percentile = |p: f64| {
+            let rank = (p * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+        Self {
+            count: samples.len() as i64,
+            sum_sec: sum,
+            mean_sec: Some(sum / samples.len() as f64),
+            p50_sec: Some(percentile(0.5)),
+            p90_sec: Some(percentile(0.9)),
+            p99_sec: Some(percentile(0.99)),
+        }
+    }
+}
+
+fn pass_rate(verdicts: &[Option<bool>]) -> Option<f64> {
+    let known: Vec<bool> = verdicts.iter().flatten().copied().collect();
+    if known.is_empty() {
+        return None;
+    }
+    Some(known.iter().filter(|v| **v).count() as f64 / known.len() as f64)
+}
+
+impl MetricsSummary {
+    /// Aggregate attempt, execution and orchestrator telemetry. Every figure
+    /// covers the attempts matched by `filter`; timestamps are normalized with
+    /// `datetime()` so RFC 3339 bounds compare against SQLite's own format.
+    pub async fn load(pool: &SqlitePool, filter: &MetricsFilter) -> Result<Self, sqlx::Error> {
+        let profiles = sqlx::query!(
+            r#"SELECT ta.profile as "profile!",
+                      COUNT(*) as "attempts!: i64",
+                      SUM(CASE WHEN EXISTS (
+                              SELECT 1 FROM merges m
+                              WHERE m.task_attempt_id = ta.id
+                                AND (m.merge_type = 'direct' OR m.pr_status = 'merged'))
+                          THEN 1 ELSE 0 END) as "merged!: i64",
+                      SUM(CASE WHEN NOT EXISTS (
+                              SELECT 1 FROM merges m
+                              WHERE m.task_attempt_id = ta.id
+                                AND (m.merge_type = 'direct' OR m.pr_status = 'merged'))
+                            AND (SELECT ep.status FROM execution_processes ep
+                                 WHERE ep.task_attempt_id = ta.id AND ep.run_reason != 'devserver'
+                                 ORDER BY ep.created_at DESC LIMIT 1) = 'failed'
+                          THEN 1 ELSE 0 END) as "failed!: i64"
+               FROM task_attempts ta
+               JOIN tasks t ON t.id = ta.task_id
+               WHERE ($1 IS NULL OR t.project_id = $1)
+                 AND ($2 IS NULL OR datetime(ta.created_at) >= datetime($2))
+                 AND ($3 IS NULL OR datetime(ta.created_at) < datetime($3))
+               GROUP BY ta.profile
+               ORDER BY ta.profile"#,
+            filter.project_id,
+            filter.since,
+            filter.until
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let settled = row.merged + row.failed;
+            ProfileMetrics {
+                profile: row.profile,
+                attempts: row.attempts,
+                merged: row.merged,
+                failed: row.failed,
+                success_rate: (settled > 0).then(|| row.merged as f64 / settled as f64),
+            }
+        })
+        .collect::<Vec<_>>();
+
+        let samples = sqlx::query!(
+            r#"SELECT ep.run_reason as "run_reason!: ExecutionProcessRunReason",
+                      (julianday(ep.completed_at) - julianday(ep.started_at)) * 86400.0 as "duration_sec!: f64"
+               FROM execution_processes ep
+               JOIN task_attempts ta ON ta.id = ep.task_attempt_id
+               JOIN tasks t ON t.id = ta.task_id
+               WHERE ep.completed_at IS NOT NULL
+                 AND ($1 IS NULL OR t.project_id = $1)
+                 AND ($2 IS NULL OR datetime(ta.created_at) >= datetime($2))
+                 AND ($3 IS NULL OR datetime(ta.created_at) < datetime($3))"#,
+            filter.project_id,
+            filter.since,
+            filter.until
+        )
+        .fetch_all(pool)
+        .await?;
+        let mut by_reason: BTreeMap<String, (ExecutionProcessRunReason, Vec<f64>)> =
+            BTreeMap::new();
+        for row in samples {
+            let key = format!("{:?}", row.run_reason);
+            by_reason
+                .entry(key)
+                .or_insert_with(|| (row.run_reason.clone(), Vec::new()))
+                .1
+                .push(row.duration_sec);
+        }
+        let durations = by_reason
+            .into_values()
+            .map(|(run_reason, samples)| DurationMetrics {
+                run_reason,
+                stats: DurationStats::from_samples(samples),
+            })
+            .collect();
+
+        // cold_sec is only written by orchestrator runs, so it marks attempts that had one
+        let telemetry = sqlx::query_as!(
+            AttemptTelemetry,
+            r#"SELECT ta.prompt_tokens,
+                      ta.completion_tokens,
+                      ta.cold_sec AS "cold_sec: f64",
+                      ta.warm_sec AS "warm_sec: f64",
+                      ta.cache_hit_count,
+                      ta.scope_pass AS "scope_pass: bool",
+                      ta.dep_pass AS "dep_pass: bool",
+                      ta.api_pass AS "api_pass: bool",
+                      ta.det_pass AS "det_pass: bool",
+                      ta.kpi_pass AS "kpi_pass: bool"
+               FROM task_attempts ta
+               JOIN tasks t ON t.id = ta.task_id
+               WHERE ($1 IS NULL OR t.project_id = $1)
+                 AND ($2 IS NULL OR datetime(ta.created_at) >= datetime($2))
+                 AND ($3 IS NULL OR datetime(ta.created_at) < datetime($3))"#,
+            filter.project_id,
+            filter.since,
+            filter.until
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let tokens = TokenMetrics {
+            prompt_tokens: telemetry.iter().filter_map(|r| r.prompt_tokens).sum(),
+            completion_tokens: telemetry.iter().filter_map(|r| r.completion_tokens).sum(),
+            models: ExecutionTokenUsage::find_by_filter(pool, filter).await?,
+            cost_usd: None,
+        };
+        let runs: Vec<_> = telemetry.iter().filter(|r| r.cold_sec.is_some()).collect();
+        let rate = |verdict: fn(&AttemptTelemetry) -> Option<bool>| {
+            pass_rate(&runs.iter().map(|r| verdict(r)).collect::<Vec<_>>())
+        };
+        let validators = ValidatorMetrics {
+            runs: runs.len() as i64,
+            scope: rate(|r| r.scope_pass),
+            dep: rate(|r| r.dep_pass),
+            api: rate(|r| r.api_pass),
+            det: rate(|r| r.det_pass),
+            kpi: rate(|r| r.kpi_pass),
+        };
+        let test_times = TestTimeMetrics {
+            cold: DurationStats::from_samples(runs.iter().filter_map(|r| r.cold_sec).collect()),
+            warm: DurationStats::from_samples(runs.iter().filter_map(|r| r.warm_sec).collect()),
+            cache_hit_count: runs.iter().filter_map(|r| r.cache_hit_count).sum(),
+        };
+
+        Ok(Self {
+            attempts: profiles.iter().map(|p| p.attempts).sum(),
+            profiles,
+            durations,
+            validators,
+            tokens,
+            test_times,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_stats_use_nearest_rank_percentiles() {
+        let stats = DurationStats::from_samples((1..=10).rev().map(f64::from).collect());
+        assert_eq!(stats.count, 10);
+        assert_eq!(stats.sum_sec, 55.0);
+        assert_eq!(stats.mean_sec, Some(5.5));
+        assert_eq!(stats.p50_sec, Some(5.0));
+        assert_eq!(stats.p90_sec, Some(9.0));
+        assert_eq!(stats.p99_sec, Some(10.0));
+
+        let single = DurationStats::from_samples(vec![2.5]);
+        assert_eq!(single.p50_sec, Some(2.5));
+        assert_eq!(single.p99_sec, Some(2.5));
+    }
+
+    #[test]
+    fn duration_stats_ignore_samples_that_are_not_finite() {
+        let stats = DurationStats::from_samples(vec![f64::NAN, 4.0, f64::INFINITY]);
+        assert_eq!(stats.count, 1);
+        assert_eq!(stats.sum_sec, 4.0);
+        assert_eq!(
+            DurationStats::from_samples(vec![f64::NAN]),
+            DurationStats::default()
+        );
+        assert_eq!(
+            DurationStats::from_samples(Vec::new()),
+            DurationStats::default()
+        );
+    }
+
+    #[test]
+    fn pass_rate_counts_only_runs_with_a_verdict() {
+        assert_eq!(pass_rate(&[]), None);
+        assert_eq!(pass_rate(&[None, None]), None);
+        assert_eq!(
+            pass_rate(&[Some(true), None, Some(false), Some(true)]),
+            Some(2.0 / 3.0)
+        );
+        assert_eq!(pass_rate(&[Some(false)]), Some(0.0));
+    }
+}
@@ -1,9 +1,14 @@
+pub mod attempt_artifact;
+pub mod context_file;
 pub mod execution_process;
 pub mod execution_process_logs;
 pub mod executor_session;
 pub mod image;
 pub mod merge;
+pub mod metrics;
+pub mod phase;
 pub mod project;
 pub mod task;
 pub mod task_attempt;
 pub mod task_template;
+pub mod token_usage;
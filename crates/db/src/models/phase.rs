@@ -0,0 +1,571 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, SqlitePool, Type, types::Json};
+use thiserror::Error;
+use ts_rs::TS;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum PhaseError {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error("Phase not found")]
+    NotFound,
+    #[error("Invalid phase transition from {from:?} to {to:?}")]
+    InvalidTransition { from: PhaseStatus, to: PhaseStatus },
+}
+
+#[derive(Debug, Clone, Copy, Type, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[sqlx(type_name = "phase_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PhaseType {
+    Prompt,
+    Fix,
+    Hardening,
+}
+
+#[derive(Debug, Clone, Copy, Type, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[sqlx(type_name = "phase_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PhaseStatus {
+    Idle,
+    Running,
+    Pass,
+    Fail,
+}
+
+impl PhaseStatus {
+    /// idle → running → pass/fail; a finished phase may be retried (→ running)
+    pub fn can_transition_to(self, to: PhaseStatus) -> bool {
+        matches!(
+            (self, to),
+            (PhaseStatus::Idle, PhaseStatus::Running)
+                | (PhaseStatus::Running, PhaseStatus::Pass)
+                | (PhaseStatus::Running, PhaseStatus::Fail)
+                | (PhaseStatus::Pass, PhaseStatus::Running)
+                | (PhaseStatus::Fail, PhaseStatus::Running)
+        )
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, TS)]
+pub struct Phase {
+    pub id: Uuid,
+    pub task_id: Uuid, // Foreign key to Task
+    #[serde(rename = "type")]
+    pub phase_type: PhaseType,
+    pub status: PhaseStatus,
+    #[ts(type = "Array<string>")]
+    pub allowlist: Json<Vec<String>>, // Gitignore-style patterns the patch may touch
+    #[ts(type = "Array<string>")]
+    pub denylist: Json<Vec<String>>, // Gitignore-style patterns the patch must not touch
+    pub agent_override: Option<String>,
+    pub warm_kpi_budget: Option<f64>, // Seconds; falls back to the validator default
+    #[ts(type = "JsonValue | null")]
+    pub dep_policy: Option<Json<Value>>,
+    pub reject_out_of_scope: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize, TS)]
+pub struct CreatePhase {
+    #[serde(rename = "type")]
+    pub phase_type: Option<PhaseType>,
+}
+
+/// Fields omitted from the request are left unchanged; an explicit `null`
+/// clears the nullable ones. Status changes go through [`Phase::transition`].
+#[derive(Debug, Default, Deserialize, TS)]
+pub struct UpdatePhase {
+    #[serde(rename = "type")]
+    pub phase_type: Option<PhaseType>,
+    pub status: Option<PhaseStatus>,
+    pub allowlist: Option<Vec<String>>,
+    pub denylist: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[ts(optional)]
+    pub agent_override: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[ts(optional)]
+    pub warm_kpi_budget: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[ts(optional, type = "JsonValue | null")]
+    pub dep_policy: Option<Option<Value>>,
+    pub reject_out_of_scope: Option<bool>,
+}
+
+/// Distinguish a missing field (`None`) from an explicit `null` (`Some(None)`)
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl Phase {
+    pub async fn find_by_id(pool: &SqlitePool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Phase,
+            r#"SELECT id as "id!: Uuid", task_id as "task_id!: Uuid", type as "phase_type!: PhaseType", status as "status!: PhaseStatus", allowlist as "allowlist!: Json<Vec<String>>", denylist as "denylist!: Json<Vec<String>>", agent_override, warm_kpi_budget, dep_policy as "dep_policy: Json<Value>", reject_out_of_scope as "reject_out_of_scope!: bool", created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>"
+               FROM phases
+               WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn find_by_rowid(pool: &SqlitePool, rowid: i64) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Phase,
+            r#"SELECT id as "id!: Uuid", task_id as "task_id!: Uuid", type as "phase_type!: PhaseType", status as "status!: PhaseStatus", allowlist as "allowlist!: Json<Vec<String>>", denylist as "denylist!: Json<Vec<String>>", agent_override, warm_kpi_budget, dep_policy as "dep_policy: Json<Value>", reject_out_of_scope as "reject_out_of_scope!: bool", created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>"
+               FROM phases
+               WHERE rowid = $1"#,
+            rowid
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn find_by_task_id(
+        pool: &SqlitePool,
+        task_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Phase,
+            r#"SELECT id as "id!: Uuid", task_id as "task_id!: Uuid", type as "phase_type!: PhaseType", status as "status!: PhaseStatus", allowlist as "allowlist!: Json<Vec<String>>", denylist as "denylist!: Json<Vec<String>>", agent_override, warm_kpi_budget, dep_policy as "dep_policy: Json<Value>", reject_out_of_scope as "reject_out_of_scope!: bool", created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>"
+               FROM phases
+               WHERE task_id = $1
+               ORDER BY created_at ASC, rowid ASC"#,
+            task_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// The phase an attempt was started for, if any
+    pub async fn find_by_attempt_id(
+        pool: &SqlitePool,
+        attempt_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Phase,
+            r#"SELECT p.id as "id!: Uuid", p.task_id as "task_id!: Uuid", p.type as "phase_type!: PhaseType", p.status as "status!: PhaseStatus", p.allowlist as "allowlist!: Json<Vec<String>>", p.denylist as "denylist!: Json<Vec<String>>", p.agent_override, p.warm_kpi_budget, p.dep_policy as "dep_policy: Json<Value>", p.reject_out_of_scope as "reject_out_of_scope!: bool", p.created_at as "created_at!: DateTime<Utc>", p.updated_at as "updated_at!: DateTime<Utc>"
+               FROM phases p
+               JOIN task_attempts ta ON ta.phase_id = p.id
+               WHERE ta.id = $1"#,
+            attempt_id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Running phases without a running process in any attempt linked to
+    /// them, left behind when the server stopped in the middle of a run
+    pub async fn find_stale_running(pool: &SqlitePool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Phase,
+            r#"SELECT p.id as "id!: Uuid", p.task_id as "task_id!: Uuid", p.type as "phase_type!: PhaseType", p.status as "status!: PhaseStatus", p.allowlist as "allowlist!: Json<Vec<String>>", p.denylist as "denylist!: Json<Vec<String>>", p.agent_override, p.warm_kpi_budget, p.dep_policy as "dep_policy: Json<Value>", p.reject_out_of_scope as "reject_out_of_scope!: bool", p.created_at as "created_at!: DateTime<Utc>", p.updated_at as "updated_at!: DateTime<Utc>"
+               FROM phases p
+               WHERE p.status = 'running'
+                 AND NOT EXISTS (
+                   SELECT 1 FROM task_attempts ta
+                   JOIN execution_processes ep ON ep.task_attempt_id = ta.id
+                   WHERE ta.phase_id = p.id AND ep.status = 'running')"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn create(
+        pool: &SqlitePool,
+        task_id: Uuid,
+        data: &CreatePhase,
+        phase_id: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        let phase_type = data.phase_type.unwrap_or(PhaseType::Prompt);
+        sqlx::query_as!(
+            Phase,
+            r#"INSERT INTO phases (id, task_id, type)
+               VALUES ($1, $2, $3)
+               RETURNING id as "id!: Uuid", task_id as "task_id!: Uuid", type as "phase_type!: PhaseType", status as "status!: PhaseStatus", allowlist as "allowlist!: Json<Vec<String>>", denylist as "denylist!: Json<Vec<String>>", agent_override, warm_kpi_budget, dep_policy as "dep_policy: Json<Value>", reject_out_of_scope as "reject_out_of_scope!: bool", created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>""#,
+            phase_id,
+            task_id,
+            phase_type
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Apply every field of `data` except `status`
+    pub async fn update(
+        pool: &SqlitePool,
+        id: Uuid,
+        data: &UpdatePhase,
+    ) -> Result<Self, PhaseError> {
+        let existing = Self::find_by_id(pool, id)
+            .await?
+            .ok_or(PhaseError::NotFound)?;
+
+        let phase_type = data.phase_type.unwrap_or(existing.phase_type);
+        let allowlist = Json(data.allowlist.clone().unwrap_or(existing.allowlist.0));
+        let denylist = Json(data.denylist.clone().unwrap_or(existing.denylist.0));
+        let agent_override = data
+            .agent_override
+            .clone()
+            .unwrap_or(existing.agent_override);
+        let warm_kpi_budget = data.warm_kpi_budget.unwrap_or(existing.warm_kpi_budget);
+        let dep_policy = match &data.dep_policy {
+            Some(v) => v.clone().map(Json),
+            None => existing.dep_policy,
+        };
+        let reject_out_of_scope = data
+            .reject_out_of_scope
+            .unwrap_or(existing.reject_out_of_scope);
+
+        Ok(sqlx::query_as!(
+            Phase,
+            r#"UPDATE phases
+               SET type = $2, allowlist = $3, denylist = $4, agent_override = $5, warm_kpi_budget = $6, dep_policy = $7, reject_out_of_scope = $8, updated_at = datetime('now', 'subsec')
+               WHERE id = $1
+               RETURNING id as "id!: Uuid", task_id as "task_id!: Uuid", type as "phase_type!: PhaseType", status as "status!: PhaseStatus", allowlist as "allowlist!: Json<Vec<String>>", denylist as "denylist!: Json<Vec<String>>", agent_override, warm_kpi_budget, dep_policy as "dep_policy: Json<Value>", reject_out_of_scope as "reject_out_of_scope!: bool", created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>""#,
+            id,
+            phase_type,
+            allowlist,
+            denylist,
+            agent_override,
+            warm_kpi_budget,
+            dep_policy,
+            reject_out_of_scope
+        )
+        .fetch_one(pool)
+        .await?)
+    }
+
+    /// Move a phase to `to`, rejecting edges outside the transition graph.
+    /// The update is conditional on the status we validated against, so two
+    /// concurrent transitions cannot both succeed.
+    pub async fn transition(
+        pool: &SqlitePool,
+        id: Uuid,
+        to: PhaseStatus,
+    ) -> Result<Self, PhaseError> {
+        let phase = Self::find_by_id(pool, id)
+            .await?
+            .ok_or(PhaseError::NotFound)?;
+        if !phase.status.can_transition_to(to) {
+            return Err(PhaseError::InvalidTransition {
+                from: phase.status,
+                to,
+            });
+        }
+
+        let from = phase.status;
+        let result = sqlx::query!(
+            "UPDATE phases SET status = $2, updated_at = datetime('now', 'subsec') WHERE id = $1 AND status = $3",
+            id,
+            to,
+            from
+        )
+        .execute(pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            let current = Self::find_by_id(pool, id)
+                .await?
+                .ok_or(PhaseError::NotFound)?;
+            return Err(PhaseError::InvalidTransition {
+                from: current.status,
+                to,
+            });
+        }
+
+        Self::find_by_id(pool, id)
+            .await?
+            .ok_or(PhaseError::NotFound)
+    }
+
+    /// Settle the attempt's phase once its run ends; stopped and failed runs
+    /// count as a failed phase so it can be retried. Returns whether the task
+    /// is ready for review: always for runs outside a phase, otherwise only
+    /// once every phase of the task has passed.
+    pub async fn finish_attempt(
+        pool: &SqlitePool,
+        attempt_id: Uuid,
+        passed: bool,
+    ) -> Result<bool, PhaseError> {
+        let Some(phase) = Self::find_by_attempt_id(pool, attempt_id).await? else {
+            return Ok(true);
+        };
+        if phase.status == PhaseStatus::Running {
+            let to = if passed {
+                PhaseStatus::Pass
+            } else {
+                PhaseStatus::Fail
+            };
+            Self::transition(pool, phase.id, to).await?;
+        }
+        Ok(Self::all_passed(pool, phase.task_id).await?)
+    }
+
+    /// Whether the task's phases have all passed. A failed phase counts as
+    /// repaired once a later fix phase passed, and fix phases that never had
+    /// to run (still idle) do not hold the task back.
+    pub async fn all_passed(pool: &SqlitePool, task_id: Uuid) -> Result<bool, sqlx::Error> {
+        let pending = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!: i64"
+               FROM phases p
+               WHERE p.task_id = $1
+                 AND (
+                   p.status = 'running'
+                   OR (p.status = 'idle' AND p.type != 'fix')
+                   OR (p.status = 'fail' AND NOT EXISTS (
+                         SELECT 1 FROM phases f
+                         WHERE f.task_id = p.task_id AND f.type = 'fix' AND f.status = 'pass'
+                           AND f.created_at > p.created_at))
+                 )"#,
+            task_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(pending == 0)
+    }
+
+    /// Link an attempt to the phase and move the phase to running in one
+    /// transaction, so the phase never runs without its attempt or vice versa
+    pub async fn start_attempt(
+        pool: &SqlitePool,
+        id: Uuid,
+        attempt_id: Uuid,
+    ) -> Result<Self, PhaseError> {
+        let phase = Self::find_by_id(pool, id)
+            .await?
+            .ok_or(PhaseError::NotFound)?;
+        let to = PhaseStatus::Running;
+        if !phase.status.can_transition_to(to) {
+            return Err(PhaseError::InvalidTransition {
+                from: phase.status,
+                to,
+            });
+        }
+
+        let from = phase.status;
+        let mut tx = pool.begin().await?;
+        let result = sqlx::query!(
+            "UPDATE phases SET status = $2, updated_at = datetime('now', 'subsec') WHERE id = $1 AND status = $3",
+            id,
+            to,
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            drop(tx);
+            let current = Self::find_by_id(pool, id)
+                .await?
+                .ok_or(PhaseError::NotFound)?;
+            return Err(PhaseError::InvalidTransition {
+                from: current.status,
+                to,
+            });
+        }
+        sqlx::query!(
+            "UPDATE task_attempts SET phase_id = $1, updated_at = datetime('now', 'subsec') WHERE id = $2",
+            id,
+            attempt_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Self::find_by_id(pool, id)
+            .await?
+            .ok_or(PhaseError::NotFound)
+    }
+
+    pub async fn delete(pool: &SqlitePool, id: Uuid) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            "UPDATE task_attempts SET phase_id = NULL WHERE phase_id = $1",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query!("DELETE FROM phases WHERE id = $1", id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DBService,
+        models::execution_process::{ExecutionProcess, ExecutionProcessStatus},
+        test_support::seed_execution_process,
+    };
+
+    #[test]
+    fn phases_run_once_at_a_time_and_can_be_retried() {
+        use PhaseStatus::*;
+        let allowed = [
+            (Idle, Running),
+            (Running, Pass),
+            (Running, Fail),
+            (Pass, Running),
+            (Fail, Running),
+        ];
+        for from in [Idle, Running, Pass, Fail] {
+            for to in [Idle, Running, Pass, Fail] {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{from:?} -> {to:?}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn start_attempt_links_the_attempt_only_when_the_phase_starts() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let seeded = seed_execution_process(&db.pool).await;
+        let phase = Phase::create(
+            &db.pool,
+            seeded.task_id,
+            &CreatePhase::default(),
+            Uuid::new_v4(),
+        )
+        .await
+        .unwrap();
+
+        let started = Phase::start_attempt(&db.pool, phase.id, seeded.task_attempt_id)
+            .await
+            .unwrap();
+        assert_eq!(started.status, PhaseStatus::Running);
+        let linked = Phase::find_by_attempt_id(&db.pool, seeded.task_attempt_id)
+            .await
+            .unwrap();
+        assert_eq!(linked.map(|p| p.id), Some(phase.id));
+
+        // A second attempt cannot start the running phase and stays unlinked
+        let second = Uuid::new_v4();
+        sqlx::query("INSERT INTO task_attempts (id, task_id) VALUES ($1, $2)")
+            .bind(second)
+            .bind(seeded.task_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let err = Phase::start_attempt(&db.pool, phase.id, second)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            PhaseError::InvalidTransition {
+                from: PhaseStatus::Running,
+                to: PhaseStatus::Running,
+            }
+        ));
+        assert!(
+            Phase::find_by_attempt_id(&db.pool, second)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn finish_attempt_settles_the_running_phase() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let seeded = seed_execution_process(&db.pool).await;
+        assert!(
+            Phase::finish_attempt(&db.pool, seeded.task_attempt_id, false)
+                .await
+                .unwrap()
+        );
+
+        let phase = Phase::create(
+            &db.pool,
+            seeded.task_id,
+            &CreatePhase::default(),
+            Uuid::new_v4(),
+        )
+        .await
+        .unwrap();
+        Phase::start_attempt(&db.pool, phase.id, seeded.task_attempt_id)
+            .await
+            .unwrap();
+        assert!(
+            !Phase::finish_attempt(&db.pool, seeded.task_attempt_id, false)
+                .await
+                .unwrap()
+        );
+        let failed = Phase::find_by_id(&db.pool, phase.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.status, PhaseStatus::Fail);
+
+        Phase::transition(&db.pool, phase.id, PhaseStatus::Running)
+            .await
+            .unwrap();
+        assert!(
+            Phase::finish_attempt(&db.pool, seeded.task_attempt_id, true)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn only_phases_without_a_running_process_are_stale() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let seeded = seed_execution_process(&db.pool).await;
+        // An idle phase has no run to lose
+        Phase::create(
+            &db.pool,
+            seeded.task_id,
+            &CreatePhase::default(),
+            Uuid::new_v4(),
+        )
+        .await
+        .unwrap();
+        let running = Phase::create(
+            &db.pool,
+            seeded.task_id,
+            &CreatePhase::default(),
+            Uuid::new_v4(),
+        )
+        .await
+        .unwrap();
+        Phase::start_attempt(&db.pool, running.id, seeded.task_attempt_id)
+            .await
+            .unwrap();
+        assert!(
+            Phase::find_stale_running(&db.pool)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        ExecutionProcess::update_completion(
+            &db.pool,
+            seeded.execution_process_id,
+            ExecutionProcessStatus::Failed,
+            None,
+        )
+        .await
+        .unwrap();
+        let stale: Vec<Uuid> = Phase::find_stale_running(&db.pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(stale, vec![running.id]);
+    }
+}
@@ -30,6 +30,11 @@ pub struct Project {
     pub dev_script: Option<String>,
     pub cleanup_script: Option<String>,
     pub copy_files: Option<String>,
+    pub orchestrator_test_command: Option<String>,
+    pub orchestrator_cold_timeout_sec: Option<i64>,
+    pub orchestrator_warm_timeout_sec: Option<i64>,
+    pub orchestrator_test_env: Option<String>,
+    pub auto_approve_rules: Option<String>,
 
     #[ts(type = "Date")]
     pub created_at: DateTime<Utc>,
@@ -46,6 +51,11 @@ pub struct CreateProject {
     pub dev_script: Option<String>,
     pub cleanup_script: Option<String>,
     pub copy_files: Option<String>,
+    pub orchestrator_test_command: Option<String>,
+    pub orchestrator_cold_timeout_sec: Option<i64>,
+    pub orchestrator_warm_timeout_sec: Option<i64>,
+    pub orchestrator_test_env: Option<String>,
+    pub auto_approve_rules: Option<String>,
 }
 
 #[derive(Debug, Deserialize, TS)]
@@ -56,6 +66,11 @@ pub struct UpdateProject {
     pub dev_script: Option<String>,
     pub cleanup_script: Option<String>,
     pub copy_files: Option<String>,
+    pub orchestrator_test_command: Option<String>,
+    pub orchestrator_cold_timeout_sec: Option<i64>,
+    pub orchestrator_warm_timeout_sec: Option<i64>,
+    pub orchestrator_test_env: Option<String>,
+    pub auto_approve_rules: Option<String>,
 }
 
 #[derive(Debug, Serialize, TS)]
@@ -67,6 +82,11 @@ pub struct ProjectWithBranch {
     pub dev_script: Option<String>,
     pub cleanup_script: Option<String>,
     pub copy_files: Option<String>,
+    pub orchestrator_test_command: Option<String>,
+    pub orchestrator_cold_timeout_sec: Option<i64>,
+    pub orchestrator_warm_timeout_sec: Option<i64>,
+    pub orchestrator_test_env: Option<String>,
+    pub auto_approve_rules: Option<String>,
     pub current_branch: Option<String>,
 
     #[ts(type = "Date")]
@@ -85,6 +105,11 @@ impl ProjectWithBranch {
             dev_script: project.dev_script,
             cleanup_script: project.cleanup_script,
             copy_files: project.copy_files,
+            orchestrator_test_command: project.orchestrator_test_command,
+            orchestrator_cold_timeout_sec: project.orchestrator_cold_timeout_sec,
+            orchestrator_warm_timeout_sec: project.orchestrator_warm_timeout_sec,
+            orchestrator_test_env: project.orchestrator_test_env,
+            auto_approve_rules: project.auto_approve_rules,
             current_branch,
             created_at: project.created_at,
             updated_at: project.updated_at,
@@ -110,7 +135,7 @@ impl Project {
     pub async fn find_all(pool: &SqlitePool) -> Result<Vec<Self>, sqlx::Error> {
         sqlx::query_as!(
             Project,
-            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects ORDER BY created_at DESC"#
+            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, orchestrator_test_command, orchestrator_cold_timeout_sec, orchestrator_warm_timeout_sec, orchestrator_test_env, auto_approve_rules, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects ORDER BY created_at DESC"#
         )
         .fetch_all(pool)
         .await
@@ -119,7 +144,7 @@ impl Project {
     pub async fn find_by_id(pool: &SqlitePool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
         sqlx::query_as!(
             Project,
-            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects WHERE id = $1"#,
+            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, orchestrator_test_command, orchestrator_cold_timeout_sec, orchestrator_warm_timeout_sec, orchestrator_test_env, auto_approve_rules, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects WHERE id = $1"#,
             id
         )
         .fetch_optional(pool)
@@ -132,7 +157,7 @@ impl Project {
     ) -> Result<Option<Self>, sqlx::Error> {
         sqlx::query_as!(
             Project,
-            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects WHERE git_repo_path = $1"#,
+            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, orchestrator_test_command, orchestrator_cold_timeout_sec, orchestrator_warm_timeout_sec, orchestrator_test_env, auto_approve_rules, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects WHERE git_repo_path = $1"#,
             git_repo_path
         )
         .fetch_optional(pool)
@@ -146,7 +171,7 @@ impl Project {
     ) -> Result<Option<Self>, sqlx::Error> {
         sqlx::query_as!(
             Project,
-            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects WHERE git_repo_path = $1 AND id != $2"#,
+            r#"SELECT id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, orchestrator_test_command, orchestrator_cold_timeout_sec, orchestrator_warm_timeout_sec, orchestrator_test_env, auto_approve_rules, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>" FROM projects WHERE git_repo_path = $1 AND id != $2"#,
             git_repo_path,
             exclude_id
         )
@@ -161,14 +186,19 @@ impl Project {
     ) -> Result<Self, sqlx::Error> {
         sqlx::query_as!(
             Project,
-            r#"INSERT INTO projects (id, name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>""#,
+            r#"INSERT INTO projects (id, name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, orchestrator_test_command, orchestrator_cold_timeout_sec, orchestrator_warm_timeout_sec, orchestrator_test_env, auto_approve_rules) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, orchestrator_test_command, orchestrator_cold_timeout_sec, orchestrator_warm_timeout_sec, orchestrator_test_env, auto_approve_rules, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>""#,
             project_id,
             data.name,
             data.git_repo_path,
             data.setup_script,
             data.dev_script,
             data.cleanup_script,
-            data.copy_files
+            data.copy_files,
+            data.orchestrator_test_command,
+            data.orchestrator_cold_timeout_sec,
+            data.orchestrator_warm_timeout_sec,
+            data.orchestrator_test_env,
+            data.auto_approve_rules
         )
         .fetch_one(pool)
         .await
@@ -183,17 +213,27 @@ impl Project {
         dev_script: Option<String>,
         cleanup_script: Option<String>,
         copy_files: Option<String>,
+        orchestrator_test_command: Option<String>,
+        orchestrator_cold_timeout_sec: Option<i64>,
+        orchestrator_warm_timeout_sec: Option<i64>,
+        orchestrator_test_env: Option<String>,
+        auto_approve_rules: Option<String>,
     ) -> Result<Self, sqlx::Error> {
         sqlx::query_as!(
             Project,
-            r#"UPDATE projects SET name = $2, git_repo_path = $3, setup_script = $4, dev_script = $5, cleanup_script = $6, copy_files = $7 WHERE id = $1 RETURNING id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>""#,
+            r#"UPDATE projects SET name = $2, git_repo_path = $3, setup_script = $4, dev_script = $5, cleanup_script = $6, copy_files = $7, orchestrator_test_command = $8, orchestrator_cold_timeout_sec = $9, orchestrator_warm_timeout_sec = $10, orchestrator_test_env = $11, auto_approve_rules = $12 WHERE id = $1 RETURNING id as "id!: Uuid", name, git_repo_path, setup_script, dev_script, cleanup_script, copy_files, orchestrator_test_command, orchestrator_cold_timeout_sec, orchestrator_warm_timeout_sec, orchestrator_test_env, auto_approve_rules, created_at as "created_at!: DateTime<Utc>", updated_at as "updated_at!: DateTime<Utc>""#,
             id,
             name,
             git_repo_path,
             setup_script,
             dev_script,
             cleanup_script,
-            copy_files
+            copy_files,
+            orchestrator_test_command,
+            orchestrator_cold_timeout_sec,
+            orchestrator_warm_timeout_sec,
+            orchestrator_test_env,
+            auto_approve_rules
         )
         .fetch_one(pool)
         .await
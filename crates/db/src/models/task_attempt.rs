@@ -84,6 +84,21 @@ pub struct CreateTaskAttempt {
     pub base_branch: String,
 }
 
+/// Orchestrator verdicts and timings for an attempt; `None` until a run records them
+#[derive(Debug, Clone, Default, FromRow, Serialize, Deserialize, TS)]
+pub struct AttemptTelemetry {
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub cold_sec: Option<f64>,
+    pub warm_sec: Option<f64>,
+    pub cache_hit_count: Option<i64>,
+    pub scope_pass: Option<bool>,
+    pub dep_pass: Option<bool>,
+    pub api_pass: Option<bool>,
+    pub det_pass: Option<bool>,
+    pub kpi_pass: Option<bool>,
+}
+
 impl TaskAttempt {
     pub async fn parent_task(&self, pool: &SqlitePool) -> Result<Option<Task>, sqlx::Error> {
         Task::find_by_id(pool, self.task_id).await
@@ -221,6 +236,69 @@ impl TaskAttempt {
         Ok(())
     }
 
+    pub async fn find_telemetry(
+        pool: &SqlitePool,
+        attempt_id: Uuid,
+    ) -> Result<Option<AttemptTelemetry>, sqlx::Error> {
+        sqlx::query_as!(
+            AttemptTelemetry,
+            r#"SELECT prompt_tokens,
+                      completion_tokens,
+                      cold_sec AS "cold_sec: f64",
+                      warm_sec AS "warm_sec: f64",
+                      cache_hit_count,
+                      scope_pass AS "scope_pass: bool",
+                      dep_pass AS "dep_pass: bool",
+                      api_pass AS "api_pass: bool",
+                      det_pass AS "det_pass: bool",
+                      kpi_pass AS "kpi_pass: bool"
+               FROM task_attempts
+               WHERE id = $1"#,
+            attempt_id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Record the timings and validator verdicts of an orchestrator run
+    pub async fn update_telemetry(
+        pool: &SqlitePool,
+        attempt_id: Uuid,
+        telemetry: &AttemptTelemetry,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        sqlx::query!(
+            r#"UPDATE task_attempts
+               SET prompt_tokens = COALESCE($1, prompt_tokens),
+                   completion_tokens = COALESCE($2, completion_tokens),
+                   cold_sec = $3,
+                   warm_sec = $4,
+                   cache_hit_count = $5,
+                   scope_pass = $6,
+                   dep_pass = $7,
+                   api_pass = $8,
+                   det_pass = $9,
+                   kpi_pass = $10,
+                   updated_at = $11
+               WHERE id = $12"#,
+            telemetry.prompt_tokens,
+            telemetry.completion_tokens,
+            telemetry.cold_sec,
+            telemetry.warm_sec,
+            telemetry.cache_hit_count,
+            telemetry.scope_pass,
+            telemetry.dep_pass,
+            telemetry.api_pass,
+            telemetry.det_pass,
+            telemetry.kpi_pass,
+            now,
+            attempt_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
     /// Helper function to mark a worktree as deleted in the database
     pub async fn mark_worktree_deleted(
         pool: &SqlitePool,
@@ -439,3 +517,64 @@ impl TaskAttempt {
         Ok((result.attempt_id, result.task_id, result.project_id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DBService, test_support::seed_execution_process};
+
+    #[tokio::test]
+    async fn telemetry_round_trips_and_keeps_recorded_tokens() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let attempt_id = seed_execution_process(&db.pool).await.task_attempt_id;
+
+        let telemetry = AttemptTelemetry {
+            prompt_tokens: Some(1200),
+            completion_tokens: Some(300),
+            cold_sec: Some(12.5),
+            warm_sec: Some(3.25),
+            cache_hit_count: Some(4),
+            scope_pass: Some(true),
+            dep_pass: Some(false),
+            api_pass: Some(true),
+            det_pass: Some(true),
+            kpi_pass: Some(false),
+        };
+        TaskAttempt::update_telemetry(&db.pool, attempt_id, &telemetry)
+            .await
+            .unwrap();
+        let stored = TaskAttempt::find_telemetry(&db.pool, attempt_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&stored).unwrap(),
+            serde_json::to_value(&telemetry).unwrap()
+        );
+
+        // A run that did not count tokens leaves the recorded ones in place
+        let rerun = AttemptTelemetry {
+            prompt_tokens: None,
+            completion_tokens: None,
+            warm_sec: Some(2.0),
+            ..telemetry.clone()
+        };
+        TaskAttempt::update_telemetry(&db.pool, attempt_id, &rerun)
+            .await
+            .unwrap();
+        let stored = TaskAttempt::find_telemetry(&db.pool, attempt_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.prompt_tokens, Some(1200));
+        assert_eq!(stored.completion_tokens, Some(300));
+        assert_eq!(stored.warm_sec, Some(2.0));
+
+        assert!(
+            TaskAttempt::find_telemetry(&db.pool, Uuid::new_v4())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use ts_rs::TS;
+use utils::log_msg::TokenUsage;
+use uuid::Uuid;
+
+use super::metrics::MetricsFilter;
+
+/// Tokens consumed with one model, summed over the execution processes of an
+/// execution, attempt, task or metrics filter
+#[derive(Debug, Clone, Serialize, TS)]
+pub struct ModelTokenUsage {
+    /// Empty when the agent did not report a model
+    pub model: String,
+    pub turns: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cache_write_tokens: i64,
+    /// Filled in from the price table; `None` when the model has no price
+    pub cost_usd: Option<f64>,
+}
+
+pub struct ExecutionTokenUsage;
+
+impl ExecutionTokenUsage {
+    /// Add one turn's usage to its execution process and to the token totals
+    /// of the process's attempt
+    pub async fn record(
+        pool: &SqlitePool,
+        execution_process_id: Uuid,
+        usage: &TokenUsage,
+    ) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let model = usage.model.clone().unwrap_or_default();
+        let input_tokens = usage.input_tokens as i64;
+        let output_tokens = usage.output_tokens as i64;
+        let cache_read_tokens = usage.cache_read_tokens as i64;
+        let cache_write_tokens = usage.cache_write_tokens as i64;
+        let prompt_tokens = input_tokens + cache_read_tokens + cache_write_tokens;
+
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            r#"INSERT INTO execution_token_usage (
+                   execution_process_id, model, turns, input_tokens, output_tokens,
+                   cache_read_tokens, cache_write_tokens, updated_at
+               )
+               VALUES ($1, $2, 1, $3, $4, $5, $6, $7)
+               ON CONFLICT (execution_process_id, model) DO UPDATE
+               SET turns = turns + 1,
+                   input_tokens = input_tokens + EXCLUDED.input_tokens,
+                   output_tokens = output_tokens + EXCLUDED.output_tokens,
+                   cache_read_tokens = cache_read_tokens + EXCLUDED.cache_read_tokens,
+                   cache_write_tokens = cache_write_tokens + EXCLUDED.cache_write_tokens,
+                   updated_at = EXCLUDED.updated_at"#,
+            execution_process_id,
+            model,
+            input_tokens,
+            output_tokens,
+            cache_read_tokens,
+            cache_write_tokens,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"UPDATE task_attempts
+               SET prompt_tokens = COALESCE(prompt_tokens, 0) + $1,
+                   completion_tokens = COALESCE(completion_tokens, 0) + $2,
+                   updated_at = $3
+               WHERE id = (SELECT task_attempt_id FROM execution_processes WHERE id = $4)"#,
+            prompt_tokens,
+            output_tokens,
+            now,
+            execution_process_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+
+    pub async fn find_by_execution_process_id(
+        pool: &SqlitePool,
+        execution_process_id: Uuid,
+    ) -> Result<Vec<ModelTokenUsage>, sqlx::Error> {
+        sqlx::query_as!(
+            ModelTokenUsage,
+            r#"SELECT model,
+                      turns,
+                      input_tokens,
+                      output_tokens,
+                      cache_read_tokens,
+                      cache_write_tokens,
+                      NULL as "cost_usd: f64"
+               FROM execution_token_usage
+               WHERE execution_process_id = $1
+               ORDER BY model"#,
+            execution_process_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn find_by_task_attempt_id(
+        pool: &SqlitePool,
+        task_attempt_id: Uuid,
+    ) -> Result<Vec<ModelTokenUsage>, sqlx::Error> {
+        sqlx::query_as!(
+            ModelTokenUsage,
+            r#"SELECT u.model as "model!",
+                      SUM(u.turns) as "turns!: i64",
+                      SUM(u.input_tokens) as "input_tokens!: i64",
+                      SUM(u.output_tokens) as "output_tokens!: i64",
+                      SUM(u.cache_read_tokens) as "cache_read_tokens!: i64",
+                      SUM(u.cache_write_tokens) as "cache_write_tokens!: i64",
+                      NULL as "cost_usd: f64"
+               FROM execution_token_usage u
+               JOIN execution_processes ep ON ep.id = u.execution_process_id
+               WHERE ep.task_attempt_id = $1
+               GROUP BY u.model
+               ORDER BY u.model"#,
+            task_attempt_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn find_by_task_id(
+        pool: &SqlitePool,
+        task_id: Uuid,
+    ) -> Result<Vec<ModelTokenUsage>, sqlx::Error> {
+        sqlx::query_as!(
+            ModelTokenUsage,
+            r#"SELECT u.model as "model!",
+                      SUM(u.turns) as "turns!: i64",
+                      SUM(u.input_tokens) as "input_tokens!: i64",
+                      SUM(u.output_tokens) as "output_tokens!: i64",
+                      SUM(u.cache_read_tokens) as "cache_read_tokens!: i64",
+                      SUM(u.cache_write_tokens) as "cache_write_tokens!: i64",
+                      NULL as "cost_usd: f64"
+               FROM execution_token_usage u
+               JOIN execution_processes ep ON ep.id = u.execution_process_id
+               JOIN task_attempts ta ON ta.id = ep.task_attempt_id
+               WHERE ta.task_id = $1
+               GROUP BY u.model
+               ORDER BY u.model"#,
+            task_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Usage of the attempts matched by `filter`, as in `MetricsSummary::load`
+    pub async fn find_by_filter(
+        pool: &SqlitePool,
+        filter: &MetricsFilter,
+    ) -> Result<Vec<ModelTokenUsage>, sqlx::Error> {
+        sqlx::query_as!(
+            ModelTokenUsage,
+            r#"SELECT u.model as "model!",
+                      SUM(u.turns) as "turns!: i64",
+                      SUM(u.input_tokens) as "input_tokens!: i64",
+                      SUM(u.output_tokens) as "output_tokens!: i64",
+                      SUM(u.cache_read_tokens) as "cache_read_tokens!: i64",
+                      SUM(u.cache_write_tokens) as "cache_write_tokens!: i64",
+                      NULL as "cost_usd: f64"
+               FROM execution_token_usage u
+               JOIN execution_processes ep ON ep.id = u.execution_process_id
+               JOIN task_attempts ta ON ta.id = ep.task_attempt_id
+               JOIN tasks t ON t.id = ta.task_id
+               WHERE ($1 IS NULL OR t.project_id = $1)
+                 AND ($2 IS NULL OR datetime(ta.created_at) >= datetime($2))
+                 AND ($3 IS NULL OR datetime(ta.created_at) < datetime($3))
+               GROUP BY u.model
+               ORDER BY u.model"#,
+            filter.project_id,
+            filter.since,
+            filter.until
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DBService, test_support::seed_execution_process};
+
+    fn usage(model: Option<&str>, input: u64, output: u64, cache_read: u64) -> TokenUsage {
+        TokenUsage {
+            model: model.map(str::to_string),
+            input_tokens: input,
+            output_tokens: output,
+            cache_read_tokens: cache_read,
+            cache_write_tokens: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn record_sums_turns_per_model_and_attempt_totals() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let seeded = seed_execution_process(&db.pool).await;
+        let process_id = seeded.execution_process_id;
+
+        for turn in [
+            usage(Some("sonnet"), 10, 5, 100),
+            usage(Some("sonnet"), 20, 7, 0),
+            usage(Some("haiku"), 3, 1, 0),
+            usage(None, 1, 1, 0),
+        ] {
+            ExecutionTokenUsage::record(&db.pool, process_id, &turn)
+                .await
+                .unwrap();
+        }
+
+        let rows = ExecutionTokenUsage::find_by_execution_process_id(&db.pool, process_id)
+            .await
+            .unwrap();
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r.model.as_str(),
+                    r.turns,
+                    r.input_tokens,
+                    r.output_tokens,
+                    r.cache_read_tokens,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("", 1, 1, 1, 0),
+                ("haiku", 1, 3, 1, 0),
+                ("sonnet", 2, 30, 12, 100),
+            ]
+        );
+
+        let by_attempt =
+            ExecutionTokenUsage::find_by_task_attempt_id(&db.pool, seeded.task_attempt_id)
+                .await
+                .unwrap();
+        assert_eq!(by_attempt.len(), 3);
+
+        let (prompt, completion): (Option<i64>, Option<i64>) = sqlx::query_as(
+            "SELECT prompt_tokens, completion_tokens FROM task_attempts WHERE id = $1",
+        )
+        .bind(seeded.task_attempt_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        // Prompt tokens include the cached ones
+        assert_eq!(prompt, Some(134));
+        assert_eq!(completion, Some(14));
+    }
+}
@@ -0,0 +1,132 @@
+use executors::{
+    actions::{ExecutorAction, ExecutorActionType, orchestrator::OrchestratorRequest},
+    profile::ProfileVariantLabel,
+};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::models::{
+    execution_process::{CreateExecutionProcess, ExecutionProcess, ExecutionProcessRunReason},
+    project::{CreateProject, Project},
+    task::{CreateTask, Task},
+    task_attempt::{CreateTaskAttempt, TaskAttempt},
+};
+
+/// Ids of the rows inserted by [`seed_execution_process`]
+pub struct Seeded {
+    pub project_id: Uuid,
+    pub task_id: Uuid,
+    pub task_attempt_id: Uuid,
+    pub execution_process_id: Uuid,
+}
+
+/// Insert a project with one task, attempt and coding agent process
+pub async fn seed_execution_process(pool: &SqlitePool) -> Seeded {
+    let seeded = Seeded {
+        project_id: Uuid::new_v4(),
+        task_id: Uuid::new_v4(),
+        task_attempt_id: Uuid::new_v4(),
+        execution_process_id: Uuid::new_v4(),
+    };
+    sqlx::query("INSERT INTO projects (id, name, git_repo_path) VALUES ($1, 'project', $2)")
+        .bind(seeded.project_id)
+        .bind(format!("/repos/{}", seeded.project_id))
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO tasks (id, project_id, title) VALUES ($1, $2, 'task')")
+        .bind(seeded.task_id)
+        .bind(seeded.project_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO task_attempts (id, task_id, profile) VALUES ($1, $2, 'claude-code')")
+        .bind(seeded.task_attempt_id)
+        .bind(seeded.task_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO execution_processes (id, task_attempt_id, run_reason, executor_action)
+         VALUES ($1, $2, 'codingagent', '{}')",
+    )
+    .bind(seeded.execution_process_id)
+    .bind(seeded.task_attempt_id)
+    .execute(pool)
+    .await
+    .unwrap();
+    seeded
+}
+
+/// Create a project with one task through the model constructors
+pub async fn seed_task(pool: &SqlitePool) -> Task {
+    let project = Project::create(
+        pool,
+        &CreateProject {
+            name: "project".to_string(),
+            git_repo_path: format!("/repos/{}", Uuid::new_v4()),
+            use_existing_repo: true,
+            setup_script: None,
+            dev_script: None,
+            cleanup_script: None,
+            copy_files: None,
+            orchestrator_test_command: None,
+            orchestrator_cold_timeout_sec: None,
+            orchestrator_warm_timeout_sec: None,
+            orchestrator_test_env: None,
+            auto_approve_rules: None,
+        },
+        Uuid::new_v4(),
+    )
+    .await
+    .unwrap();
+    Task::create(
+        pool,
+        &CreateTask {
+            project_id: project.id,
+            title: "task".to_string(),
+            description: None,
+            parent_task_attempt: None,
+            image_ids: None,
+        },
+        Uuid::new_v4(),
+    )
+    .await
+    .unwrap()
+}
+
+/// Create an attempt of `task_id` on `main`
+pub async fn seed_attempt(pool: &SqlitePool, task_id: Uuid) -> TaskAttempt {
+    TaskAttempt::create(
+        pool,
+        &CreateTaskAttempt {
+            profile: "claude-code".to_string(),
+            base_branch: "main".to_string(),
+        },
+        task_id,
+    )
+    .await
+    .unwrap()
+}
+
+/// Create a running orchestrator execution process for the attempt
+pub async fn seed_orchestrator_run(pool: &SqlitePool, attempt_id: Uuid) -> ExecutionProcess {
+    let action = ExecutorAction::new(
+        ExecutorActionType::OrchestratorRequest(OrchestratorRequest {
+            prompt: "prompt".to_string(),
+            profile_variant_label: ProfileVariantLabel::default("claude-code".to_string()),
+        }),
+        None,
+    );
+    ExecutionProcess::create(
+        pool,
+        &CreateExecutionProcess {
+            task_attempt_id: attempt_id,
+            executor_action: action,
+            run_reason: ExecutionProcessRunReason::Orchestrator,
+        },
+        Uuid::new_v4(),
+    )
+    .await
+    .unwrap()
+}
@@ -7,6 +7,7 @@ use db::{
     DBService,
     models::{
         execution_process::{ExecutionProcess, ExecutionProcessRunReason, ExecutionProcessStatus},
+        phase::{Phase, PhaseStatus},
         task::{Task, TaskStatus},
         task_attempt::{TaskAttempt, TaskAttemptError},
     },
@@ -17,9 +18,11 @@ use git2::Error as Git2Error;
 use serde_json::Value;
 use services::services::{
     analytics::AnalyticsService,
+    approvals::Approvals,
     auth::{AuthError, AuthService},
     config::{Config, ConfigError},
     container::{ContainerError, ContainerService},
+    context_file::ContextFileService,
     events::{EventError, EventService},
     filesystem::{FilesystemError, FilesystemService},
     filesystem_watcher::FilesystemWatcherError,
@@ -92,12 +95,16 @@ pub trait Deployment: Clone + Send + Sync + 'static {
 
     fn image(&self) -> &ImageService;
 
+    fn context_files(&self) -> &ContextFileService;
+
     fn filesystem(&self) -> &FilesystemService;
 
     fn msg_stores(&self) -> &Arc<RwLock<HashMap<Uuid, Arc<MsgStore>>>>;
 
     fn events(&self) -> &EventService;
 
+    fn approvals(&self) -> &Approvals;
+
     async fn update_sentry_scope(&self) -> Result<(), DeploymentError> {
         let user_id = self.user_id();
         let config = self.config().read().await;
@@ -158,6 +165,7 @@ pub trait Deployment: Clone + Send + Sync + 'static {
                 ExecutionProcessRunReason::CodingAgent
                     | ExecutionProcessRunReason::SetupScript
                     | ExecutionProcessRunReason::CleanupScript
+                    | ExecutionProcessRunReason::Orchestrator
             ) && let Ok(Some(task_attempt)) =
                 TaskAttempt::find_by_id(&self.db().pool, process.task_attempt_id).await
                 && let Ok(Some(task)) = task_attempt.parent_task(&self.db().pool).await
@@ -170,6 +178,14 @@ pub trait Deployment: Clone + Send + Sync + 'static {
                 );
             }
         }
+        // A phase whose run was cut short would stay running and refuse every
+        // retry, so fail it like a stopped run
+        for phase in Phase::find_stale_running(&self.db().pool).await? {
+            match Phase::transition(&self.db().pool, phase.id, PhaseStatus::Fail).await {
+                Ok(_) => tracing::info!("Marked orphaned phase {} as failed", phase.id),
+                Err(e) => tracing::error!("Failed to fail orphaned phase {}: {}", phase.id, e),
+            }
+        }
         Ok(())
     }
 
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use command_group::AsyncGroupChild;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
+use utils::redact::Redactor;
 
 use crate::{
     actions::Executable,
@@ -16,15 +17,28 @@ pub struct CodingAgentFollowUpRequest {
     pub prompt: String,
     pub session_id: String,
     pub profile_variant_label: ProfileVariantLabel,
+    /// Where a supervised agent posts approval requests; set by the container
+    /// at spawn time and never stored
+    #[serde(skip)]
+    #[ts(skip)]
+    pub approval_url: Option<String>,
 }
 
 #[async_trait]
 impl Executable for CodingAgentFollowUpRequest {
-    async fn spawn(&self, current_dir: &PathBuf) -> Result<AsyncGroupChild, ExecutorError> {
-        let agent = CodingAgent::from_profile_variant_label(&self.profile_variant_label)?;
+    async fn spawn(
+        &self,
+        current_dir: &PathBuf,
+    ) -> Result<(AsyncGroupChild, Redactor), ExecutorError> {
+        let (mut agent, env) =
+            CodingAgent::with_env_from_profile_variant_label(&self.profile_variant_label)?;
+        if let Some(url) = &self.approval_url {
+            agent.set_approval_url(url.clone());
+        }
 
-        agent
+        let child = agent
             .spawn_follow_up(current_dir, &self.prompt, &self.session_id)
-            .await
+            .await?;
+        Ok((child, env.redactor()))
     }
 }
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use command_group::AsyncGroupChild;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
+use utils::redact::Redactor;
 
 use crate::{
     actions::Executable,
@@ -15,12 +16,25 @@ use crate::{
 pub struct CodingAgentInitialRequest {
     pub prompt: String,
     pub profile_variant_label: ProfileVariantLabel,
+    /// Where a supervised agent posts approval requests; set by the container
+    /// at spawn time and never stored
+    #[serde(skip)]
+    #[ts(skip)]
+    pub approval_url: Option<String>,
 }
 
 #[async_trait]
 impl Executable for CodingAgentInitialRequest {
-    async fn spawn(&self, current_dir: &PathBuf) -> Result<AsyncGroupChild, ExecutorError> {
-        let agent = CodingAgent::from_profile_variant_label(&self.profile_variant_label)?;
-        agent.spawn(current_dir, &self.prompt).await
+    async fn spawn(
+        &self,
+        current_dir: &PathBuf,
+    ) -> Result<(AsyncGroupChild, Redactor), ExecutorError> {
+        let (mut agent, env) =
+            CodingAgent::with_env_from_profile_variant_label(&self.profile_variant_label)?;
+        if let Some(url) = &self.approval_url {
+            agent.set_approval_url(url.clone());
+        }
+        let child = agent.spawn(current_dir, &self.prompt).await?;
+        Ok((child, env.redactor()))
     }
 }
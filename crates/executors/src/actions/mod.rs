@@ -1,20 +1,25 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use async_trait::async_trait;
 use command_group::AsyncGroupChild;
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
+use tokio::process::ChildStdin;
 use ts_rs::TS;
+use utils::{msg_store::MsgStore, redact::Redactor};
 
 use crate::{
     actions::{
         coding_agent_follow_up::CodingAgentFollowUpRequest,
-        coding_agent_initial::CodingAgentInitialRequest, script::ScriptRequest,
+        coding_agent_initial::CodingAgentInitialRequest, orchestrator::OrchestratorRequest,
+        script::ScriptRequest,
     },
-    executors::ExecutorError,
+    executors::{AgentStdin, CodingAgent, ExecutorError, StandardCodingAgentExecutor},
+    profile::ProfileVariantLabel,
 };
 pub mod coding_agent_follow_up;
 pub mod coding_agent_initial;
+pub mod orchestrator;
 pub mod script;
 
 #[enum_dispatch]
@@ -24,6 +29,7 @@ pub enum ExecutorActionType {
     CodingAgentInitialRequest,
     CodingAgentFollowUpRequest,
     ScriptRequest,
+    OrchestratorRequest,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -44,17 +50,82 @@ impl ExecutorAction {
     pub fn next_action(&self) -> Option<&Box<ExecutorAction>> {
         self.next_action.as_ref()
     }
+
+    fn profile_variant_label(&self) -> Option<&ProfileVariantLabel> {
+        match &self.typ {
+            ExecutorActionType::CodingAgentInitialRequest(request) => {
+                Some(&request.profile_variant_label)
+            }
+            ExecutorActionType::CodingAgentFollowUpRequest(request) => {
+                Some(&request.profile_variant_label)
+            }
+            _ => None,
+        }
+    }
+
+    /// The action with the URL its agent posts approval requests to, if it runs one
+    pub fn with_approval_url(&self, url: String) -> Self {
+        let mut action = self.clone();
+        match &mut action.typ {
+            ExecutorActionType::CodingAgentInitialRequest(request) => {
+                request.approval_url = Some(url)
+            }
+            ExecutorActionType::CodingAgentFollowUpRequest(request) => {
+                request.approval_url = Some(url)
+            }
+            _ => {}
+        }
+        action
+    }
+
+    /// Hand the stdin `spawn` left open to the action's agent
+    pub fn manage_stdin(&self, stdin: AgentStdin, msg_store: Arc<MsgStore>) {
+        match self
+            .profile_variant_label()
+            .map(CodingAgent::from_profile_variant_label)
+        {
+            Some(Ok(agent)) => agent.manage_stdin(stdin, msg_store),
+            resolved => {
+                if let Some(Err(e)) = resolved {
+                    tracing::error!("Failed to resolve agent for stdin: {}", e);
+                }
+                tokio::spawn(async move { stdin.lock().await.take() });
+            }
+        }
+    }
+
+    /// Write a user message to the action's running agent
+    pub async fn send_message(
+        &self,
+        stdin: Option<&mut ChildStdin>,
+        message: &str,
+    ) -> Result<(), ExecutorError> {
+        let label = self
+            .profile_variant_label()
+            .ok_or(ExecutorError::MessagesNotSupported)?;
+        CodingAgent::from_profile_variant_label(label)?
+            .send_message(stdin, message)
+            .await
+    }
 }
 
 #[async_trait]
 #[enum_dispatch(ExecutorActionType)]
 pub trait Executable {
-    async fn spawn(&self, current_dir: &PathBuf) -> Result<AsyncGroupChild, ExecutorError>;
+    /// Start the action, with a redactor for the secrets its environment
+    /// resolved to so they can be kept out of stored logs
+    async fn spawn(
+        &self,
+        current_dir: &PathBuf,
+    ) -> Result<(AsyncGroupChild, Redactor), ExecutorError>;
 }
 
 #[async_trait]
 impl Executable for ExecutorAction {
-    async fn spawn(&self, current_dir: &PathBuf) -> Result<AsyncGroupChild, ExecutorError> {
+    async fn spawn(
+        &self,
+        current_dir: &PathBuf,
+    ) -> Result<(AsyncGroupChild, Redactor), ExecutorError> {
         self.typ.spawn(current_dir).await
     }
 }
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use command_group::AsyncGroupChild;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use utils::redact::Redactor;
+
+use crate::{actions::Executable, executors::ExecutorError, profile::ProfileVariantLabel};
+
+/// An orchestrator run (agent → patch → apply → tests → validators). It is
+/// driven in-process by the container service rather than spawned as a child.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+pub struct OrchestratorRequest {
+    pub prompt: String,
+    pub profile_variant_label: ProfileVariantLabel,
+}
+
+#[async_trait]
+impl Executable for OrchestratorRequest {
+    async fn spawn(
+        &self,
+        _current_dir: &PathBuf,
+    ) -> Result<(AsyncGroupChild, Redactor), ExecutorError> {
+        Err(ExecutorError::NotSpawnable(
+            "orchestrator runs are started through ContainerService::start_orchestrator"
+                .to_string(),
+        ))
+    }
+}
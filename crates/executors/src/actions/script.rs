@@ -5,7 +5,7 @@ use command_group::{AsyncCommandGroup, AsyncGroupChild};
 use serde::{Deserialize, Serialize};
 use tokio::process::Command;
 use ts_rs::TS;
-use utils::shell::get_shell_command;
+use utils::{redact::Redactor, shell::get_shell_command};
 
 use crate::{actions::Executable, executors::ExecutorError};
 
@@ -30,7 +30,10 @@ pub struct ScriptRequest {
 
 #[async_trait]
 impl Executable for ScriptRequest {
-    async fn spawn(&self, current_dir: &PathBuf) -> Result<AsyncGroupChild, ExecutorError> {
+    async fn spawn(
+        &self,
+        current_dir: &PathBuf,
+    ) -> Result<(AsyncGroupChild, Redactor), ExecutorError> {
         let (shell_cmd, shell_arg) = get_shell_command();
         let mut command = Command::new(shell_cmd);
         command
@@ -43,6 +46,7 @@ impl Executable for ScriptRequest {
 
         let child = command.group_spawn()?;
 
-        Ok(child)
+        // Scripts run with the server's own environment; nothing to redact
+        Ok((child, Redactor::default()))
     }
 }
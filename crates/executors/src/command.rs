@@ -7,6 +7,10 @@ pub struct CommandBuilder {
     pub base: String,
     /// Optional parameters to append to the base command
     pub params: Option<Vec<String>>,
+    /// Environment resolved from the profile variant at spawn time; never stored in profiles
+    #[serde(skip)]
+    #[ts(skip)]
+    pub env: Vec<(String, String)>,
 }
 
 impl CommandBuilder {
@@ -14,6 +18,7 @@ impl CommandBuilder {
         Self {
             base: base.into(),
             params: None,
+            env: Vec::new(),
         }
     }
 
@@ -0,0 +1,308 @@
+//! Environment variables for profile variants.
+//!
+//! Values in a variant's `env` map and `env_file` may reference secrets as
+//! `${secret:NAME}`; the secrets live in `secrets.json` under the asset dir
+//! rather than in `profiles.json`, and their values are redacted from stored logs.
+
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+use utils::{path::expand_tilde, redact::Redactor};
+
+use crate::{
+    executors::ExecutorError,
+    profile::{ProfileConfigs, ProfileVariantLabel, VariantAgentConfig},
+};
+
+const SECRET_PREFIX: &str = "${secret:";
+
+/// Environment a profile variant runs its agent with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentEnv {
+    pub vars: Vec<(String, String)>,
+    /// Secret values substituted into `vars`
+    pub secrets: Vec<String>,
+}
+
+/// Named secret values, stored as a JSON object
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Secrets(BTreeMap<String, String>);
+
+impl Secrets {
+    /// Read `secrets.json` from the asset dir; a missing file holds no secrets
+    pub fn load() -> Result<Self, ExecutorError> {
+        Self::load_from(&utils::assets::secrets_path())
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, ExecutorError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path)
+                .map_err(ExecutorError::Io)?
+                .permissions()
+                .mode();
+            if mode & 0o077 != 0 {
+                return Err(ExecutorError::Env(format!(
+                    "{} is accessible by other users; run `chmod 600` on it",
+                    path.display()
+                )));
+            }
+        }
+        let content = fs::read_to_string(path).map_err(ExecutorError::Io)?;
+        Ok(Self(serde_json::from_str(&content)?))
+    }
+
+    /// Write the secrets to `path`, readable and writable by its owner only
+    pub fn save_to(&self, path: &Path) -> Result<(), ExecutorError> {
+        let content = serde_json::to_string_pretty(&self.0)?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            // `mode` only applies to new files
+            if path.exists() {
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                    .map_err(ExecutorError::Io)?;
+            }
+        }
+        options
+            .open(path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(ExecutorError::Io)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, name: String, value: String) {
+        self.0.insert(name, value);
+    }
+}
+
+/// Parse `.env`-style content: `KEY=VALUE` lines with optional `export ` and
+/// surrounding quotes; blank lines and `#` comments are skipped
+pub fn parse_env_file(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = [('"', '"'), ('\'', '\'')]
+                .iter()
+                .find_map(|(open, close)| {
+                    value
+                        .strip_prefix(*open)
+                        .and_then(|v| v.strip_suffix(*close))
+                })
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Replace each `${secret:NAME}` in `value`, recording the values substituted
+fn substitute(
+    value: &str,
+    secrets: &mut Option<Secrets>,
+    used: &mut Vec<String>,
+) -> Result<String, ExecutorError> {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(SECRET_PREFIX) {
+        let name_start = start + SECRET_PREFIX.len();
+        let Some(len) = rest[name_start..].find('}') else {
+            break;
+        };
+        let name = &rest[name_start..name_start + len];
+        if secrets.is_none() {
+            *secrets = Some(Secrets::load()?);
+        }
+        let secret = secrets
+            .as_ref()
+            .and_then(|s| s.get(name))
+            .ok_or_else(|| ExecutorError::Env(format!("Unknown secret: {name}")))?;
+        out.push_str(&rest[..start]);
+        out.push_str(secret);
+        used.push(secret.to_string());
+        rest = &rest[name_start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+impl AgentEnv {
+    /// Resolve the environment of a profile variant. A named variant is layered
+    /// over the profile's default: its `env_file` and `env` entries override the
+    /// default's.
+    pub fn from_profile_variant_label(label: &ProfileVariantLabel) -> Result<Self, ExecutorError> {
+        let profiles = ProfileConfigs::get_cached();
+        let profile = profiles.get_profile(&label.profile).ok_or_else(|| {
+            ExecutorError::UnknownExecutorType(format!("Unknown profile: {}", label.profile))
+        })?;
+        let mut layers = vec![&profile.default];
+        if let Some(variant) = &label.variant {
+            layers.push(profile.get_variant(variant).ok_or_else(|| {
+                ExecutorError::UnknownExecutorType(format!("Unknown mode: {variant}"))
+            })?);
+        }
+        Self::resolve(&layers)
+    }
+
+    pub fn resolve(layers: &[&VariantAgentConfig]) -> Result<Self, ExecutorError> {
+        let mut vars = BTreeMap::new();
+        for layer in layers {
+            if let Some(env_file) = &layer.env_file {
+                let path = expand_tilde(env_file);
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| ExecutorError::Env(format!("{}: {e}", path.display())))?;
+                vars.extend(parse_env_file(&content));
+            }
+            vars.extend(
+                layer
+                    .env
+                    .iter()
+                    .flatten()
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+        }
+
+        let mut secrets = None;
+        let mut used = Vec::new();
+        let vars = vars
+            .into_iter()
+            .map(|(key, value)| Ok((key, substitute(&value, &mut secrets, &mut used)?)))
+            .collect::<Result<Vec<_>, ExecutorError>>()?;
+        Ok(Self {
+            vars,
+            secrets: used,
+        })
+    }
+
+    pub fn redactor(&self) -> Redactor {
+        Redactor::new(self.secrets.clone())
+    }
+
+    /// `vars` with secret values masked, safe to store and hash
+    pub fn redacted_vars(&self) -> Vec<(String, String)> {
+        let redactor = self.redactor();
+        self.vars
+            .iter()
+            .map(|(key, value)| (key.clone(), redactor.redact(value)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(json: serde_json::Value) -> VariantAgentConfig {
+        let mut config = serde_json::json!({
+            "label": "test",
+            "mcp_config_path": null,
+            "AMP": {"command": {"base": "amp", "params": null}, "append_prompt": null}
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(json.as_object().unwrap().clone());
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn env_file_parsing() {
+        let vars = parse_env_file(
+            "# endpoint\nexport BASE_URL=\"https://proxy.local\"\n\nMODEL='fast'\nEMPTY=\nnot a var\n",
+        );
+        assert_eq!(
+            vars,
+            vec![
+                ("BASE_URL".to_string(), "https://proxy.local".to_string()),
+                ("MODEL".to_string(), "fast".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_env_is_layered_over_the_default() {
+        let dir = std::env::temp_dir().join(format!("vk-env-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let env_file = dir.join("default.env");
+        fs::write(&env_file, "MODEL=slow\nREGION=eu\n").unwrap();
+
+        let default = variant(serde_json::json!({
+            "env_file": env_file.to_string_lossy(),
+            "env": {"PROXY": "http://proxy"}
+        }));
+        let fast = variant(serde_json::json!({"env": {"MODEL": "fast"}}));
+        let env = AgentEnv::resolve(&[&default, &fast]).unwrap();
+        assert_eq!(
+            env.vars,
+            vec![
+                ("MODEL".to_string(), "fast".to_string()),
+                ("PROXY".to_string(), "http://proxy".to_string()),
+                ("REGION".to_string(), "eu".to_string()),
+            ]
+        );
+        assert!(env.secrets.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn secrets_are_substituted_and_recorded() {
+        let mut secrets = Some(Secrets::default());
+        secrets
+            .as_mut()
+            .unwrap()
+            .insert("openai".to_string(), "sk-test-123".to_string());
+        let mut used = Vec::new();
+        let value = substitute("Bearer ${secret:openai}", &mut secrets, &mut used).unwrap();
+        assert_eq!(value, "Bearer sk-test-123");
+        assert_eq!(used, vec!["sk-test-123".to_string()]);
+        assert!(substitute("${secret:missing}", &mut secrets, &mut used).is_err());
+
+        let env = AgentEnv {
+            vars: vec![("OPENAI_API_KEY".to_string(), value)],
+            secrets: used,
+        };
+        assert_eq!(env.redactor().redact("key=sk-test-123"), "key=[REDACTED]");
+        assert_eq!(
+            env.redacted_vars(),
+            vec![(
+                "OPENAI_API_KEY".to_string(),
+                "Bearer [REDACTED]".to_string()
+            )]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secrets_file_must_be_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("vk-secrets-{}.json", std::process::id()));
+        let mut secrets = Secrets::default();
+        secrets.insert("key".to_string(), "value".to_string());
+        secrets.save_to(&path).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(Secrets::load_from(&path).unwrap(), secrets);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(Secrets::load_from(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,14 +1,19 @@
-use std::{collections::HashMap, path::PathBuf, process::Stdio, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    process::Stdio,
+    sync::Arc,
+};
 
 use async_trait::async_trait;
 use command_group::{AsyncCommandGroup, AsyncGroupChild};
 use futures::StreamExt;
 use json_patch::Patch;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncWriteExt, process::Command};
+use tokio::{io::AsyncWriteExt, process::Command, task::JoinHandle};
 use ts_rs::TS;
 use utils::{
-    diff::create_unified_diff, msg_store::MsgStore, path::make_path_relative,
+    diff::create_unified_diff, log_msg::TokenUsage, msg_store::MsgStore, path::make_path_relative,
     shell::get_shell_command,
 };
 
@@ -51,6 +56,8 @@ impl StandardCodingAgentExecutor for Amp {
             .arg(shell_arg)
             .arg(amp_command);
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         // feed the prompt in, then close the pipe so `amp` sees EOF
@@ -88,6 +95,8 @@ impl StandardCodingAgentExecutor for Amp {
             .arg(shell_arg)
             .arg(&amp_command);
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         // Feed the prompt in, then close the pipe so amp sees EOF
@@ -99,18 +108,24 @@ impl StandardCodingAgentExecutor for Amp {
         Ok(child)
     }
 
-    fn normalize_logs(&self, raw_logs_msg_store: Arc<MsgStore>, current_dir: &PathBuf) {
+    fn normalize_logs(
+        &self,
+        raw_logs_msg_store: Arc<MsgStore>,
+        current_dir: &PathBuf,
+    ) -> Vec<JoinHandle<()>> {
         let entry_index_provider = EntryIndexProvider::start_from(&raw_logs_msg_store);
 
         // Process stderr logs using the standard stderr processor
-        normalize_stderr_logs(raw_logs_msg_store.clone(), entry_index_provider.clone());
+        let stderr =
+            normalize_stderr_logs(raw_logs_msg_store.clone(), entry_index_provider.clone());
 
         // Process stdout logs (Amp's JSON output)
         let current_dir = current_dir.clone();
-        tokio::spawn(async move {
+        let stdout = tokio::spawn(async move {
             let mut s = raw_logs_msg_store.stdout_lines_stream();
 
             let mut seen_amp_message_ids: HashMap<usize, Vec<usize>> = HashMap::new();
+            let mut counted_amp_message_ids: HashSet<usize> = HashSet::new();
             while let Some(Ok(line)) = s.next().await {
                 let trimmed = line.trim();
                 match serde_json::from_str(trimmed) {
@@ -122,6 +137,13 @@ impl StandardCodingAgentExecutor for Amp {
                             for (amp_message_id, message) in messages {
                                 let role = &message.role;
 
+                                // Messages are re-sent as they stream; count each once complete
+                                if let Some(usage) = message.token_usage()
+                                    && counted_amp_message_ids.insert(amp_message_id)
+                                {
+                                    raw_logs_msg_store.push_usage(usage);
+                                }
+
                                 for (content_index, content_item) in
                                     message.content.iter().enumerate()
                                 {
@@ -202,6 +224,8 @@ impl StandardCodingAgentExecutor for Amp {
                 };
             }
         });
+
+        vec![stderr, stdout]
     }
 }
 
@@ -265,6 +289,38 @@ pub struct AmpMessage {
     pub content: Vec<AmpContentItem>,
     pub state: Option<serde_json::Value>,
     pub meta: Option<AmpMeta>,
+    #[serde(default)]
+    pub usage: Option<AmpUsage>,
+}
+
+impl AmpMessage {
+    /// Token usage of a completed assistant message
+    pub fn token_usage(&self) -> Option<TokenUsage> {
+        let complete = self
+            .state
+            .as_ref()
+            .and_then(|state| state.get("type"))
+            .and_then(|t| t.as_str())
+            == Some("complete");
+        let usage = self.usage.as_ref().filter(|_| complete)?;
+        Some(TokenUsage {
+            model: usage.model.clone(),
+            input_tokens: usage.input_tokens.unwrap_or(0),
+            output_tokens: usage.output_tokens.unwrap_or(0),
+            cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+            cache_write_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AmpUsage {
+    pub model: Option<String>,
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub cache_creation_input_tokens: Option<u64>,
+    pub cache_read_input_tokens: Option<u64>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
@@ -1,25 +1,32 @@
-use std::{path::PathBuf, process::Stdio, sync::Arc};
+use std::{collections::HashSet, path::PathBuf, process::Stdio, sync::Arc};
 
 use async_trait::async_trait;
 use command_group::{AsyncCommandGroup, AsyncGroupChild};
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncWriteExt, process::Command};
+use tokio::{
+    io::AsyncWriteExt,
+    process::{ChildStdin, Command},
+    task::JoinHandle,
+};
 use ts_rs::TS;
 use utils::{
+    approvals::APPROVAL_URL_ENV,
     diff::{concatenate_diff_hunks, create_unified_diff, create_unified_diff_hunk},
-    log_msg::LogMsg,
+    log_msg::{LogMsg, TokenUsage},
     msg_store::MsgStore,
     path::make_path_relative,
-    shell::get_shell_command,
+    shell::{get_shell_command, shell_quote},
 };
 
 use crate::{
     command::CommandBuilder,
-    executors::{ExecutorError, StandardCodingAgentExecutor},
+    executors::{AgentStdin, ExecutorError, StandardCodingAgentExecutor},
     logs::{
         ActionType, FileChange, NormalizedEntry, NormalizedEntryType, TodoItem,
+        approval_processor::normalize_approvals,
         stderr_processor::normalize_stderr_logs,
+        user_message_processor::normalize_user_messages,
         utils::{EntryIndexProvider, patch::ConversationPatch},
     },
 };
@@ -30,6 +37,146 @@ pub struct ClaudeCode {
     pub command: CommandBuilder,
     pub append_prompt: Option<String>,
     pub plan: bool,
+    /// Ask the vibe-kanban backend before running tools that need permission,
+    /// instead of skipping permission checks
+    #[serde(default)]
+    pub approvals: bool,
+    /// Set at spawn time; never stored in profiles
+    #[serde(skip)]
+    #[ts(skip)]
+    pub approval_url: Option<String>,
+    /// Read the prompt as stream-json and keep stdin open, so messages can be
+    /// sent while the agent runs
+    #[serde(default)]
+    pub streaming_input: bool,
+}
+
+/// MCP server name the permission prompt tool is registered under
+const APPROVAL_SERVER: &str = "vibe_kanban_approvals";
+
+impl ClaudeCode {
+    /// Arguments routing permission prompts to the approvals MCP server, which
+    /// forwards them to the backend and waits for a decision
+    fn approval_args(&self) -> Vec<String> {
+        if !self.approvals {
+            return Vec::new();
+        }
+        let Some(url) = &self.approval_url else {
+            tracing::warn!(
+                "No approval URL for a supervised Claude run; tools needing permission will be denied"
+            );
+            return Vec::new();
+        };
+        // The app binary serves the approvals MCP server when started with the
+        // approval URL in its environment
+        let exe = match std::env::current_exe() {
+            Ok(exe) => exe,
+            Err(e) => {
+                tracing::warn!(
+                    "Cannot locate the app binary for the approvals MCP server: {e}; tools needing permission will be denied"
+                );
+                return Vec::new();
+            }
+        };
+        let mcp_config = serde_json::json!({
+            "mcpServers": {
+                APPROVAL_SERVER: {
+                    "command": exe,
+                    "args": [],
+                    "env": {APPROVAL_URL_ENV: url},
+                }
+            }
+        });
+        vec![
+            "--permission-prompt-tool".to_string(),
+            format!("mcp__{APPROVAL_SERVER}__approve"),
+            "--mcp-config".to_string(),
+            shell_quote(&mcp_config.to_string()),
+        ]
+    }
+
+    /// Arguments added to the profile's command for both initial and follow-up runs
+    fn extra_args(&self) -> Vec<String> {
+        let mut args = self.approval_args();
+        if self.streaming_input {
+            args.push("--input-format=stream-json".to_string());
+        }
+        args
+    }
+
+    /// Feed the prompt in. Plain runs close the pipe so Claude sees EOF;
+    /// streaming runs leave it open for `manage_stdin`.
+    async fn send_prompt(
+        &self,
+        child: &mut AsyncGroupChild,
+        prompt: &str,
+    ) -> Result<(), ExecutorError> {
+        let Some(mut stdin) = child.inner().stdin.take() else {
+            return Ok(());
+        };
+        if self.streaming_input {
+            stdin
+                .write_all(user_message_line(prompt).as_bytes())
+                .await?;
+            child.inner().stdin = Some(stdin);
+        } else {
+            stdin.write_all(prompt.as_bytes()).await?;
+            stdin.shutdown().await?;
+        }
+        Ok(())
+    }
+}
+
+/// A user turn in Claude's stream-json input format
+fn user_message_line(text: &str) -> String {
+    let message = serde_json::json!({
+        "type": "user",
+        "message": {"role": "user", "content": [{"type": "text", "text": text}]},
+    });
+    format!("{message}\n")
+}
+
+/// Close a streaming run's stdin once Claude has answered the prompt and every
+/// message sent since, so it exits as a plain run would
+async fn close_after_last_result(stdin: AgentStdin, msg_store: Arc<MsgStore>) {
+    let mut stream = msg_store.history_plus_stream();
+    let mut buffer = String::new();
+    let mut results = 0;
+
+    while let Some(Ok(msg)) = stream.next().await {
+        match msg {
+            LogMsg::Stdout(chunk) => {
+                buffer.push_str(&chunk);
+                while let Some(end) = buffer.find('\n') {
+                    let line: String = buffer.drain(..=end).collect();
+                    if !matches!(
+                        serde_json::from_str::<ClaudeJson>(line.trim()),
+                        Ok(ClaudeJson::Result { .. })
+                    ) {
+                        continue;
+                    }
+                    results += 1;
+                    // Messages are recorded while the lock is held, so none
+                    // can slip in between counting them and closing
+                    let mut guard = stdin.lock().await;
+                    let turns = 1 + msg_store
+                        .get_history()
+                        .iter()
+                        .filter(|msg| matches!(msg, LogMsg::UserMessage(_)))
+                        .count();
+                    if results >= turns {
+                        guard.take();
+                        return;
+                    }
+                }
+            }
+            LogMsg::Finished => {
+                stdin.lock().await.take();
+                return;
+            }
+            _ => {}
+        }
+    }
 }
 
 #[async_trait]
@@ -40,11 +187,13 @@ impl StandardCodingAgentExecutor for ClaudeCode {
         prompt: &str,
     ) -> Result<AsyncGroupChild, ExecutorError> {
         let (shell_cmd, shell_arg) = get_shell_command();
+        let mut parts = vec![self.command.build_initial()];
+        parts.extend(self.extra_args());
+        let base_command = parts.join(" ");
         let claude_command = if self.plan {
-            let base_command = self.command.build_initial();
             create_watchkill_script(&base_command)
         } else {
-            self.command.build_initial()
+            base_command
         };
 
         let combined_prompt = utils::text::combine_prompt(&self.append_prompt, prompt);
@@ -59,13 +208,10 @@ impl StandardCodingAgentExecutor for ClaudeCode {
             .arg(shell_arg)
             .arg(&claude_command);
 
-        let mut child = command.group_spawn()?;
+        command.envs(self.command.env.clone());
 
-        // Feed the prompt in, then close the pipe so Claude sees EOF
-        if let Some(mut stdin) = child.inner().stdin.take() {
-            stdin.write_all(combined_prompt.as_bytes()).await?;
-            stdin.shutdown().await?;
-        }
+        let mut child = command.group_spawn()?;
+        self.send_prompt(&mut child, &combined_prompt).await?;
 
         Ok(child)
     }
@@ -78,14 +224,13 @@ impl StandardCodingAgentExecutor for ClaudeCode {
     ) -> Result<AsyncGroupChild, ExecutorError> {
         let (shell_cmd, shell_arg) = get_shell_command();
         // Build follow-up command with --resume {session_id}
+        let mut args = vec!["--resume".to_string(), session_id.to_string()];
+        args.extend(self.extra_args());
         let claude_command = if self.plan {
-            let base_command = self
-                .command
-                .build_follow_up(&["--resume".to_string(), session_id.to_string()]);
+            let base_command = self.command.build_follow_up(&args);
             create_watchkill_script(&base_command)
         } else {
-            self.command
-                .build_follow_up(&["--resume".to_string(), session_id.to_string()])
+            self.command.build_follow_up(&args)
         };
 
         let combined_prompt = utils::text::combine_prompt(&self.append_prompt, prompt);
@@ -100,30 +245,61 @@ impl StandardCodingAgentExecutor for ClaudeCode {
             .arg(shell_arg)
             .arg(&claude_command);
 
-        let mut child = command.group_spawn()?;
+        command.envs(self.command.env.clone());
 
-        // Feed the followup prompt in, then close the pipe
-        if let Some(mut stdin) = child.inner().stdin.take() {
-            stdin.write_all(combined_prompt.as_bytes()).await?;
-            stdin.shutdown().await?;
-        }
+        let mut child = command.group_spawn()?;
+        self.send_prompt(&mut child, &combined_prompt).await?;
 
         Ok(child)
     }
 
-    fn normalize_logs(&self, msg_store: Arc<MsgStore>, current_dir: &PathBuf) {
+    fn normalize_logs(
+        &self,
+        msg_store: Arc<MsgStore>,
+        current_dir: &PathBuf,
+    ) -> Vec<JoinHandle<()>> {
         let entry_index_provider = EntryIndexProvider::start_from(&msg_store);
 
         // Process stdout logs (Claude's JSON output)
-        ClaudeLogProcessor::process_logs(
+        let stdout = ClaudeLogProcessor::process_logs(
             self,
             msg_store.clone(),
             current_dir,
             entry_index_provider.clone(),
         );
 
+        let approvals = normalize_approvals(msg_store.clone(), entry_index_provider.clone());
+        let user_messages =
+            normalize_user_messages(msg_store.clone(), entry_index_provider.clone());
+
         // Process stderr logs using the standard stderr processor
-        normalize_stderr_logs(msg_store, entry_index_provider);
+        let stderr = normalize_stderr_logs(msg_store, entry_index_provider);
+
+        vec![stdout, approvals, user_messages, stderr]
+    }
+
+    fn manage_stdin(&self, stdin: AgentStdin, msg_store: Arc<MsgStore>) {
+        if self.streaming_input {
+            tokio::spawn(close_after_last_result(stdin, msg_store));
+        } else {
+            tokio::spawn(async move { stdin.lock().await.take() });
+        }
+    }
+
+    /// Sent mid-turn, a message is queued and answered as the next turn
+    async fn send_message(
+        &self,
+        stdin: Option<&mut ChildStdin>,
+        message: &str,
+    ) -> Result<(), ExecutorError> {
+        if !self.streaming_input {
+            return Err(ExecutorError::MessagesNotSupported);
+        }
+        let stdin = stdin.ok_or(ExecutorError::StdinClosed)?;
+        stdin
+            .write_all(user_message_line(message).as_bytes())
+            .await
+            .map_err(ExecutorError::Io)
     }
 }
 
@@ -153,11 +329,16 @@ exit "$exit_code"
 /// Handles log processing and interpretation for Claude executor
 struct ClaudeLogProcessor {
     model_name: Option<String>,
+    /// Ids of assistant messages whose usage has been reported
+    counted_messages: HashSet<String>,
 }
 
 impl ClaudeLogProcessor {
     fn new() -> Self {
-        Self { model_name: None }
+        Self {
+            model_name: None,
+            counted_messages: HashSet::new(),
+        }
     }
 
     /// Process raw logs and convert them to normalized entries with patches
@@ -166,7 +347,7 @@ impl ClaudeLogProcessor {
         msg_store: Arc<MsgStore>,
         current_dir: &PathBuf,
         entry_index_provider: EntryIndexProvider,
-    ) {
+    ) -> JoinHandle<()> {
         let current_dir_clone = current_dir.clone();
         tokio::spawn(async move {
             let mut stream = msg_store.history_plus_stream();
@@ -178,7 +359,12 @@ impl ClaudeLogProcessor {
             while let Some(Ok(msg)) = stream.next().await {
                 let chunk = match msg {
                     LogMsg::Stdout(x) => x,
-                    LogMsg::JsonPatch(_) | LogMsg::SessionId(_) | LogMsg::Stderr(_) => continue,
+                    LogMsg::JsonPatch(_)
+                    | LogMsg::SessionId(_)
+                    | LogMsg::Usage(_)
+                    | LogMsg::Approval(_)
+                    | LogMsg::UserMessage(_)
+                    | LogMsg::Stderr(_) => continue,
                     LogMsg::Finished => break,
                 };
 
@@ -223,6 +409,10 @@ impl ClaudeLogProcessor {
                                     ConversationPatch::add_normalized_entry(patch_id, entry);
                                 msg_store.push_patch(patch);
                             }
+
+                            if let Some(usage) = processor.extract_usage(&claude_json) {
+                                msg_store.push_usage(usage);
+                            }
                         }
                         Err(_) => {
                             // Handle non-JSON output as raw system message
@@ -260,7 +450,7 @@ impl ClaudeLogProcessor {
                 let patch = ConversationPatch::add_normalized_entry(patch_id, entry);
                 msg_store.push_patch(patch);
             }
-        });
+        })
     }
 
     /// Extract session ID from Claude JSON
@@ -276,6 +466,28 @@ impl ClaudeLogProcessor {
         }
     }
 
+    /// Token usage of an assistant message. The CLI emits one event per content
+    /// block of a message, each repeating the message's usage, so a message id
+    /// is only counted once.
+    fn extract_usage(&mut self, claude_json: &ClaudeJson) -> Option<TokenUsage> {
+        let ClaudeJson::Assistant { message, .. } = claude_json else {
+            return None;
+        };
+        let usage = message.usage.as_ref()?;
+        if let Some(id) = &message.id
+            && !self.counted_messages.insert(id.clone())
+        {
+            return None;
+        }
+        Some(TokenUsage {
+            model: message.model.clone().or_else(|| self.model_name.clone()),
+            input_tokens: usage.input_tokens.unwrap_or(0),
+            output_tokens: usage.output_tokens.unwrap_or(0),
+            cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+            cache_write_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
+        })
+    }
+
     /// Convert Claude JSON to normalized entries
     fn to_normalized_entries(
         &mut self,
@@ -631,6 +843,16 @@ pub struct ClaudeMessage {
     pub model: Option<String>,
     pub content: Vec<ClaudeContentItem>,
     pub stop_reason: Option<String>,
+    #[serde(default)]
+    pub usage: Option<ClaudeUsage>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ClaudeUsage {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub cache_creation_input_tokens: Option<u64>,
+    pub cache_read_input_tokens: Option<u64>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
@@ -816,6 +1038,27 @@ mod tests {
         assert_eq!(entries[0].content, "Hello world");
     }
 
+    #[test]
+    fn test_usage_is_counted_once_per_message() {
+        let text_block = r#"{"type":"assistant","message":{"id":"msg_1","role":"assistant","model":"claude-sonnet-4-20250514","content":[{"type":"text","text":"Reading"}],"usage":{"input_tokens":12,"cache_creation_input_tokens":300,"cache_read_input_tokens":4000,"output_tokens":85}}}"#;
+        let tool_block = r#"{"type":"assistant","message":{"id":"msg_1","role":"assistant","model":"claude-sonnet-4-20250514","content":[{"type":"tool_use","id":"t1","name":"Read","input":{"file_path":"a.rs"}}],"usage":{"input_tokens":12,"cache_creation_input_tokens":300,"cache_read_input_tokens":4000,"output_tokens":85}}}"#;
+        let mut processor = ClaudeLogProcessor::new();
+
+        let parsed: ClaudeJson = serde_json::from_str(text_block).unwrap();
+        assert_eq!(
+            processor.extract_usage(&parsed),
+            Some(TokenUsage {
+                model: Some("claude-sonnet-4-20250514".to_string()),
+                input_tokens: 12,
+                output_tokens: 85,
+                cache_read_tokens: 4000,
+                cache_write_tokens: 300,
+            })
+        );
+        let parsed: ClaudeJson = serde_json::from_str(tool_block).unwrap();
+        assert_eq!(processor.extract_usage(&parsed), None);
+    }
+
     #[test]
     fn test_result_message_ignored() {
         let result_json = r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":6059,"result":"Final result"}"#;
@@ -921,6 +1164,38 @@ mod tests {
         assert_eq!(absolute_result, "src/main.rs");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_approval_mcp_config_survives_the_shell() {
+        let executor = ClaudeCode {
+            command: CommandBuilder::new(""),
+            plan: false,
+            append_prompt: None,
+            approvals: true,
+            approval_url: Some("http://127.0.0.1:1/it's/approvals".to_string()),
+            streaming_input: false,
+        };
+        let args = executor.approval_args();
+        assert_eq!(args[2], "--mcp-config");
+
+        let (shell, shell_arg) = get_shell_command();
+        let output = std::process::Command::new(shell)
+            .arg(shell_arg)
+            .arg(format!("printf '%s' {}", args[3]))
+            .output()
+            .unwrap();
+        let config: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let server = &config["mcpServers"][APPROVAL_SERVER];
+        assert_eq!(
+            server["command"],
+            std::env::current_exe().unwrap().to_string_lossy().as_ref()
+        );
+        assert_eq!(
+            server["env"][APPROVAL_URL_ENV],
+            "http://127.0.0.1:1/it's/approvals"
+        );
+    }
+
     #[tokio::test]
     async fn test_streaming_patch_generation() {
         use std::sync::Arc;
@@ -931,6 +1206,9 @@ mod tests {
             command: CommandBuilder::new(""),
             plan: false,
             append_prompt: None,
+            approvals: false,
+            approval_url: None,
+            streaming_input: false,
         };
         let msg_store = Arc::new(MsgStore::new());
         let current_dir = std::path::PathBuf::from("/tmp/test-worktree");
@@ -1031,4 +1309,44 @@ mod tests {
 
         // ToolResult entry is ignored - no third entry
     }
+
+    #[tokio::test]
+    async fn test_streaming_stdin_stays_open_for_sent_messages() {
+        let mut cat = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdin: AgentStdin = Arc::new(tokio::sync::Mutex::new(cat.stdin.take()));
+        let msg_store = Arc::new(MsgStore::new());
+        let result = "{\"type\":\"result\",\"subtype\":\"success\"}\n".to_string();
+
+        let closer = tokio::spawn(close_after_last_result(stdin.clone(), msg_store.clone()));
+        msg_store.push_user_message("Also update the docs".to_string());
+        msg_store.push_stdout(result.clone());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        // The sent message has not been answered yet
+        assert!(stdin.lock().await.is_some());
+
+        msg_store.push_stdout(result);
+        tokio::time::timeout(std::time::Duration::from_secs(5), closer)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stdin.lock().await.is_none());
+        let _ = cat.wait().await;
+    }
+
+    #[test]
+    fn test_user_message_line() {
+        let line = user_message_line("Keep the public API unchanged");
+        assert!(line.ends_with('\n'));
+        let parsed: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(parsed["type"], "user");
+        assert_eq!(parsed["message"]["role"], "user");
+        assert_eq!(
+            parsed["message"]["content"][0]["text"],
+            "Keep the public API unchanged"
+        );
+    }
 }
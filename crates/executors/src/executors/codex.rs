@@ -1,14 +1,20 @@
-use std::{path::PathBuf, process::Stdio, sync::Arc};
+use std::{collections::HashSet, path::PathBuf, process::Stdio, sync::Arc};
 
 use async_trait::async_trait;
 use command_group::{AsyncCommandGroup, AsyncGroupChild};
 use futures::StreamExt;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncWriteExt, process::Command};
+use tokio::{
+    io::AsyncWriteExt,
+    process::{ChildStdin, Command},
+    task::JoinHandle,
+};
 use ts_rs::TS;
 use utils::{
+    approvals::{ApprovalStatus, ToolApproval},
     diff::{concatenate_diff_hunks, extract_unified_diff_hunks},
+    log_msg::{LogMsg, TokenUsage},
     msg_store::MsgStore,
     path::make_path_relative,
     shell::get_shell_command,
@@ -16,19 +22,26 @@ use utils::{
 
 use crate::{
     command::CommandBuilder,
-    executors::{ExecutorError, StandardCodingAgentExecutor},
+    executors::{AgentStdin, ExecutorError, StandardCodingAgentExecutor},
     logs::{
         ActionType, FileChange, NormalizedEntry, NormalizedEntryType,
+        approval_processor::normalize_approvals,
+        user_message_processor::normalize_user_messages,
         utils::{EntryIndexProvider, patch::ConversationPatch},
     },
 };
 
+/// Tool names approval requests are reported under, shared with Claude so
+/// auto-approve rules apply to both
+const EXEC_TOOL: &str = "Bash";
+const PATCH_TOOL: &str = "Edit";
+
 /// Handles session management for Codex executor
 pub struct SessionHandler;
 
 impl SessionHandler {
     /// Start monitoring stderr lines for session ID extraction
-    pub fn start_session_id_extraction(msg_store: Arc<MsgStore>) {
+    pub fn start_session_id_extraction(msg_store: Arc<MsgStore>) -> JoinHandle<()> {
         tokio::spawn(async move {
             let mut stderr_lines_stream = msg_store.stderr_lines_stream();
 
@@ -37,7 +50,7 @@ impl SessionHandler {
                     msg_store.push_session_id(session_id);
                 }
             }
-        });
+        })
     }
 
     /// Extract session ID from codex stderr output
@@ -109,6 +122,33 @@ impl SessionHandler {
 pub struct Codex {
     pub command: CommandBuilder,
     pub append_prompt: Option<String>,
+    /// The command runs `codex proto`: the prompt is submitted as a protocol
+    /// message and stdin stays open to answer approval requests and take
+    /// messages while the task runs
+    #[serde(default)]
+    pub approvals: bool,
+}
+
+impl Codex {
+    /// Feed the prompt in. Plain runs close the pipe so codex sees EOF; supervised
+    /// runs leave it open for `manage_stdin`.
+    async fn send_prompt(
+        &self,
+        child: &mut AsyncGroupChild,
+        prompt: &str,
+    ) -> Result<(), ExecutorError> {
+        let Some(mut stdin) = child.inner().stdin.take() else {
+            return Ok(());
+        };
+        if self.approvals {
+            stdin.write_all(user_input("0", prompt).as_bytes()).await?;
+            child.inner().stdin = Some(stdin);
+        } else {
+            stdin.write_all(prompt.as_bytes()).await?;
+            stdin.shutdown().await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -135,13 +175,10 @@ impl StandardCodingAgentExecutor for Codex {
             .env("NODE_NO_WARNINGS", "1")
             .env("RUST_LOG", "info");
 
-        let mut child = command.group_spawn()?;
+        command.envs(self.command.env.clone());
 
-        // Feed the prompt in, then close the pipe so codex sees EOF
-        if let Some(mut stdin) = child.inner().stdin.take() {
-            stdin.write_all(combined_prompt.as_bytes()).await?;
-            stdin.shutdown().await?;
-        }
+        let mut child = command.group_spawn()?;
+        self.send_prompt(&mut child, &combined_prompt).await?;
 
         Ok(child)
     }
@@ -178,27 +215,33 @@ impl StandardCodingAgentExecutor for Codex {
             .env("NODE_NO_WARNINGS", "1")
             .env("RUST_LOG", "info");
 
-        let mut child = command.group_spawn()?;
+        command.envs(self.command.env.clone());
 
-        // Feed the prompt in, then close the pipe so codex sees EOF
-        if let Some(mut stdin) = child.inner().stdin.take() {
-            stdin.write_all(combined_prompt.as_bytes()).await?;
-            stdin.shutdown().await?;
-        }
+        let mut child = command.group_spawn()?;
+        self.send_prompt(&mut child, &combined_prompt).await?;
 
         Ok(child)
     }
 
-    fn normalize_logs(&self, msg_store: Arc<MsgStore>, current_dir: &PathBuf) {
+    fn normalize_logs(
+        &self,
+        msg_store: Arc<MsgStore>,
+        current_dir: &PathBuf,
+    ) -> Vec<JoinHandle<()>> {
         let entry_index_provider = EntryIndexProvider::start_from(&msg_store);
 
         // Process stderr logs for session extraction only (errors come through JSONL)
-        SessionHandler::start_session_id_extraction(msg_store.clone());
+        let session_id = SessionHandler::start_session_id_extraction(msg_store.clone());
+
+        let approvals = normalize_approvals(msg_store.clone(), entry_index_provider.clone());
+        let user_messages =
+            normalize_user_messages(msg_store.clone(), entry_index_provider.clone());
 
         // Process stdout logs (Codex's JSONL output)
         let current_dir = current_dir.clone();
-        tokio::spawn(async move {
+        let stdout = tokio::spawn(async move {
             let mut stream = msg_store.stdout_lines_stream();
+            let mut model = None;
 
             while let Some(Ok(line)) = stream.next().await {
                 let trimmed = line.trim();
@@ -206,16 +249,43 @@ impl StandardCodingAgentExecutor for Codex {
                     continue;
                 }
 
-                if let Ok(entries) = serde_json::from_str::<CodexJson>(trimmed).map(|codex_json| {
-                    codex_json
+                if let Ok(codex_json) = serde_json::from_str::<CodexJson>(trimmed) {
+                    if let CodexJson::SystemConfig {
+                        model: Some(configured),
+                        ..
+                    } = &codex_json
+                    {
+                        model = Some(configured.clone());
+                    }
+                    // `codex proto` reports its session on stdout rather than stderr
+                    if let CodexJson::StructuredMessage {
+                        msg:
+                            CodexMsgContent::SessionConfigured {
+                                session_id,
+                                model: configured,
+                            },
+                        ..
+                    } = &codex_json
+                    {
+                        msg_store.push_session_id(session_id.clone());
+                        if configured.is_some() {
+                            model = configured.clone();
+                        }
+                    }
+                    for entry in codex_json
                         .to_normalized_entries(&current_dir)
                         .unwrap_or_default()
-                }) {
-                    for entry in entries {
+                    {
                         let new_id = entry_index_provider.next();
                         let patch = ConversationPatch::add_normalized_entry(new_id, entry);
                         msg_store.push_patch(patch);
                     }
+                    if let Some(usage) = codex_json.token_usage(model.as_deref()) {
+                        msg_store.push_usage(usage);
+                    }
+                    if let Some(approval) = codex_json.approval_request() {
+                        msg_store.push_approval(approval);
+                    }
                 } else {
                     // Handle malformed JSON as raw output
                     let entry = NormalizedEntry {
@@ -231,6 +301,117 @@ impl StandardCodingAgentExecutor for Codex {
                 }
             }
         });
+
+        vec![session_id, approvals, user_messages, stdout]
+    }
+
+    fn manage_stdin(&self, stdin: AgentStdin, msg_store: Arc<MsgStore>) {
+        if self.approvals {
+            tokio::spawn(answer_approvals(stdin, msg_store));
+        } else {
+            tokio::spawn(async move { stdin.lock().await.take() });
+        }
+    }
+
+    /// Submitted while the task runs, a message is injected into it; once the
+    /// task is complete stdin is closed and messages are refused
+    async fn send_message(
+        &self,
+        stdin: Option<&mut ChildStdin>,
+        message: &str,
+    ) -> Result<(), ExecutorError> {
+        if !self.approvals {
+            return Err(ExecutorError::MessagesNotSupported);
+        }
+        let stdin = stdin.ok_or(ExecutorError::StdinClosed)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        stdin
+            .write_all(user_input(&id, message).as_bytes())
+            .await
+            .map_err(ExecutorError::Io)
+    }
+}
+
+/// A `user_input` submission line for `codex proto`
+fn user_input(id: &str, text: &str) -> String {
+    let submission = serde_json::json!({
+        "id": id,
+        "op": {"type": "user_input", "items": [{"type": "text", "text": text}]},
+    });
+    format!("{submission}\n")
+}
+
+/// Write approval decisions to a supervised session, then close its stdin once
+/// the task is over so `codex proto` exits
+async fn answer_approvals(stdin: AgentStdin, msg_store: Arc<MsgStore>) {
+    let mut stream = msg_store.history_plus_stream();
+    let mut buffer = String::new();
+    let mut answered = HashSet::new();
+
+    while let Some(Ok(msg)) = stream.next().await {
+        match msg {
+            LogMsg::Approval(approval) if !approval.status.is_pending() => {
+                let Some((submission_id, _)) = approval.id.split_once(':') else {
+                    continue;
+                };
+                if !answered.insert(approval.id.clone()) {
+                    continue;
+                }
+                let op = serde_json::json!({
+                    "id": format!("approval-{}", answered.len()),
+                    "op": {
+                        "type": if approval.tool_name == PATCH_TOOL { "patch_approval" } else { "exec_approval" },
+                        "id": submission_id,
+                        "decision": if approval.status == ApprovalStatus::Approved { "approved" } else { "denied" },
+                    },
+                });
+                let mut guard = stdin.lock().await;
+                let Some(writer) = guard.as_mut() else {
+                    return;
+                };
+                if writer
+                    .write_all(format!("{op}\n").as_bytes())
+                    .await
+                    .is_err()
+                {
+                    guard.take();
+                    return;
+                }
+            }
+            LogMsg::Stdout(chunk) => {
+                buffer.push_str(&chunk);
+                while let Some(end) = buffer.find('\n') {
+                    let line: String = buffer.drain(..=end).collect();
+                    if let Ok(CodexJson::StructuredMessage {
+                        msg: CodexMsgContent::TaskComplete { .. } | CodexMsgContent::Error { .. },
+                        ..
+                    }) = serde_json::from_str::<CodexJson>(line.trim())
+                    {
+                        stdin.lock().await.take();
+                        return;
+                    }
+                }
+            }
+            LogMsg::Finished => {
+                stdin.lock().await.take();
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Approval ids carry the submission id Codex expects decisions to name, ahead
+/// of the call id that makes them unique
+fn approval_id(submission_id: &str, call_id: Option<&str>) -> String {
+    format!("{submission_id}:{}", call_id.unwrap_or_default())
+}
+
+/// The script of a `bash -lc <script>` command, or the command joined
+fn shell_command(command: &[String]) -> String {
+    match command {
+        [shell, flag, script] if shell.ends_with("sh") && flag.starts_with('-') => script.clone(),
+        _ => command.join(" "),
     }
 }
 
@@ -379,6 +560,13 @@ pub enum CodexMsgContent {
         value: serde_json::Value,
     },
 
+    #[serde(rename = "session_configured")]
+    SessionConfigured {
+        session_id: String,
+        #[serde(default)]
+        model: Option<String>,
+    },
+
     #[serde(rename = "task_started")]
     TaskStarted,
     #[serde(rename = "task_complete")]
@@ -552,50 +740,6 @@ impl CodexJson {
                             metadata: None,
                         }])
                     }
-                    CodexMsgContent::ExecApprovalRequest {
-                        command,
-                        cwd,
-                        reason,
-                        ..
-                    } => {
-                        let command_str = command.join(" ");
-                        let mut parts = vec![format!("command: `{}`", command_str)];
-                        if let Some(c) = cwd {
-                            parts.push(format!("cwd: {c}"));
-                        }
-                        if let Some(r) = reason {
-                            parts.push(format!("reason: {r}"));
-                        }
-                        let content =
-                            format!("Execution approval requested — {}", parts.join("  "));
-                        Some(vec![NormalizedEntry {
-                            timestamp: None,
-                            entry_type: NormalizedEntryType::SystemMessage,
-                            content,
-                            metadata: None,
-                        }])
-                    }
-                    CodexMsgContent::ApplyPatchApprovalRequest {
-                        changes,
-                        reason,
-                        grant_root,
-                        ..
-                    } => {
-                        let mut parts = vec![format!("files: {}", changes.len())];
-                        if let Some(root) = grant_root {
-                            parts.push(format!("grant_root: {root}"));
-                        }
-                        if let Some(r) = reason {
-                            parts.push(format!("reason: {r}"));
-                        }
-                        let content = format!("Patch approval requested — {}", parts.join("  "));
-                        Some(vec![NormalizedEntry {
-                            timestamp: None,
-                            entry_type: NormalizedEntryType::SystemMessage,
-                            content,
-                            metadata: None,
-                        }])
-                    }
                     CodexMsgContent::PlanUpdate { value } => Some(vec![NormalizedEntry {
                         timestamp: None,
                         entry_type: NormalizedEntryType::SystemMessage,
@@ -603,6 +747,10 @@ impl CodexJson {
                         metadata: Some(value.clone()),
                     }]),
 
+                    // Approval requests are pushed as approvals; see `approval_request`
+                    CodexMsgContent::ExecApprovalRequest { .. }
+                    | CodexMsgContent::ApplyPatchApprovalRequest { .. } => None,
+
                     // Ignored message types
                     CodexMsgContent::AgentReasoningRawContent { .. }
                     | CodexMsgContent::AgentReasoningRawContentDelta { .. }
@@ -612,6 +760,7 @@ impl CodexJson {
                     | CodexMsgContent::PatchApplyEnd { .. }
                     | CodexMsgContent::McpToolCallEnd { .. }
                     | CodexMsgContent::TaskStarted
+                    | CodexMsgContent::SessionConfigured { .. }
                     | CodexMsgContent::TaskComplete { .. }
                     | CodexMsgContent::TokenCount { .. }
                     | CodexMsgContent::TurnDiff { .. }
@@ -622,6 +771,80 @@ impl CodexJson {
         }
     }
 
+    /// Token usage of a turn, from a `token_count` message. Codex counts cached
+    /// tokens as part of the input, so they are split out here.
+    pub fn token_usage(&self, model: Option<&str>) -> Option<TokenUsage> {
+        let CodexJson::StructuredMessage {
+            msg:
+                CodexMsgContent::TokenCount {
+                    input_tokens,
+                    cached_input_tokens,
+                    output_tokens,
+                    ..
+                },
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let cached = cached_input_tokens.unwrap_or(0);
+        let usage = TokenUsage {
+            model: model.map(str::to_string),
+            input_tokens: input_tokens.unwrap_or(0).saturating_sub(cached),
+            output_tokens: output_tokens.unwrap_or(0),
+            cache_read_tokens: cached,
+            cache_write_tokens: 0,
+        };
+        (!usage.is_empty()).then_some(usage)
+    }
+
+    /// A tool call Codex is waiting on a decision for
+    pub fn approval_request(&self) -> Option<ToolApproval> {
+        let CodexJson::StructuredMessage { id, msg } = self else {
+            return None;
+        };
+        match msg {
+            CodexMsgContent::ExecApprovalRequest {
+                call_id,
+                command,
+                cwd,
+                reason,
+            } => Some(ToolApproval::pending(
+                approval_id(id, call_id.as_deref()),
+                EXEC_TOOL.to_string(),
+                serde_json::json!({
+                    "command": shell_command(command),
+                    "cwd": cwd,
+                    "reason": reason,
+                }),
+            )),
+            CodexMsgContent::ApplyPatchApprovalRequest {
+                call_id,
+                changes,
+                reason,
+                grant_root,
+            } => {
+                let mut files: Vec<&String> = changes.keys().collect();
+                files.sort();
+                let mut input = serde_json::json!({
+                    "files": files,
+                    "reason": reason,
+                    "grant_root": grant_root,
+                });
+                // Path rules can only vouch for a patch touching a single file
+                if let [file] = files.as_slice() {
+                    input["path"] = serde_json::Value::String(file.to_string());
+                }
+                Some(ToolApproval::pending(
+                    approval_id(id, call_id.as_deref()),
+                    PATCH_TOOL.to_string(),
+                    input,
+                ))
+            }
+            _ => None,
+        }
+    }
+
     /// Format system configuration message for display
     fn format_config_message(&self) -> Option<String> {
         if let CodexJson::SystemConfig {
@@ -1054,6 +1277,63 @@ invalid json line here
         assert!(entries[0].content.contains("model: codex-mini-latest"));
     }
 
+    #[test]
+    fn test_codex_token_count_usage() {
+        let token_count = r#"{"id":"1","msg":{"type":"token_count","input_tokens":5200,"cached_input_tokens":4800,"output_tokens":310,"reasoning_output_tokens":192,"total_tokens":5510}}"#;
+
+        let parsed = test_codex_json_parsing(token_count).unwrap();
+        assert_eq!(
+            parsed.token_usage(Some("codex-mini-latest")),
+            Some(TokenUsage {
+                model: Some("codex-mini-latest".to_string()),
+                input_tokens: 400,
+                output_tokens: 310,
+                cache_read_tokens: 4800,
+                cache_write_tokens: 0,
+            })
+        );
+
+        let agent_message = r#"{"id":"1","msg":{"type":"agent_message","message":"Done"}}"#;
+        let parsed = test_codex_json_parsing(agent_message).unwrap();
+        assert_eq!(parsed.token_usage(None), None);
+    }
+
+    #[test]
+    fn test_codex_approval_requests() {
+        let exec = r#"{"id":"3","msg":{"type":"exec_approval_request","call_id":"call_1","command":["bash","-lc","npm test"],"cwd":"/tmp","reason":null}}"#;
+        let parsed = test_codex_json_parsing(exec).unwrap();
+        assert!(
+            parsed
+                .to_normalized_entries(&PathBuf::from("/tmp"))
+                .is_none()
+        );
+        let approval = parsed.approval_request().unwrap();
+        assert_eq!(approval.id, "3:call_1");
+        assert_eq!(approval.tool_name, "Bash");
+        assert_eq!(approval.primary_argument(), Some("npm test"));
+        assert!(approval.status.is_pending());
+
+        let patch = r#"{"id":"4","msg":{"type":"apply_patch_approval_request","call_id":"call_2","changes":{"/tmp/a.rs":{"add":{"content":"fn a() {}"}},"/tmp/b.rs":{"delete":{}}},"reason":null,"grant_root":null}}"#;
+        let approval = test_codex_json_parsing(patch)
+            .unwrap()
+            .approval_request()
+            .unwrap();
+        assert_eq!(approval.tool_name, "Edit");
+        assert_eq!(
+            approval.input["files"],
+            serde_json::json!(["/tmp/a.rs", "/tmp/b.rs"])
+        );
+        assert_eq!(approval.primary_argument(), None);
+
+        let agent_message = r#"{"id":"1","msg":{"type":"agent_message","message":"Done"}}"#;
+        assert!(
+            test_codex_json_parsing(agent_message)
+                .unwrap()
+                .approval_request()
+                .is_none()
+        );
+    }
+
     #[test]
     fn test_codex_json_prompt_parsing() {
         let prompt_json = r#"{"prompt":"project_id: f61fbd6a-9552-4b68-a1fe-10561f028dfc\n\nTask title: describe this repo"}"#;
@@ -5,7 +5,7 @@ use async_trait::async_trait;
 use command_group::{AsyncCommandGroup, AsyncGroupChild};
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncWriteExt, process::Command};
+use tokio::{io::AsyncWriteExt, process::Command, task::JoinHandle};
 use ts_rs::TS;
 use utils::{
     diff::{
@@ -56,6 +56,8 @@ impl StandardCodingAgentExecutor for Cursor {
             .arg(shell_arg)
             .arg(&agent_cmd);
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         if let Some(mut stdin) = child.inner().stdin.take() {
@@ -89,6 +91,8 @@ impl StandardCodingAgentExecutor for Cursor {
             .arg(shell_arg)
             .arg(&agent_cmd);
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         if let Some(mut stdin) = child.inner().stdin.take() {
@@ -99,12 +103,16 @@ impl StandardCodingAgentExecutor for Cursor {
         Ok(child)
     }
 
-    fn normalize_logs(&self, msg_store: Arc<MsgStore>, worktree_path: &PathBuf) {
+    fn normalize_logs(
+        &self,
+        msg_store: Arc<MsgStore>,
+        worktree_path: &PathBuf,
+    ) -> Vec<JoinHandle<()>> {
         let entry_index_provider = EntryIndexProvider::start_from(&msg_store);
 
         // Process Cursor stdout JSONL with typed serde models
         let current_dir = worktree_path.clone();
-        tokio::spawn(async move {
+        let stdout = tokio::spawn(async move {
             let mut lines = msg_store.stdout_lines_stream();
 
             // Cursor agent doesn't use STDERR. Everything comes through STDOUT, both JSONL and raw error output.
@@ -252,6 +260,8 @@ impl StandardCodingAgentExecutor for Cursor {
                 }
             }
         });
+
+        vec![stdout]
     }
 }
 
@@ -0,0 +1,576 @@
+use std::{collections::BTreeMap, path::PathBuf, process::Stdio, sync::Arc};
+
+use async_trait::async_trait;
+use command_group::{AsyncCommandGroup, AsyncGroupChild};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{io::AsyncWriteExt, process::Command, task::JoinHandle};
+use ts_rs::TS;
+use utils::{
+    msg_store::MsgStore,
+    shell::{get_shell_command, shell_quote},
+};
+use uuid::Uuid;
+
+use crate::{
+    command::CommandBuilder,
+    executors::{ExecutorError, StandardCodingAgentExecutor},
+    logs::{
+        ActionType, NormalizedEntry, NormalizedEntryType,
+        plain_text_processor::PlainTextLogProcessor,
+        stderr_processor::normalize_stderr_logs,
+        utils::{EntryIndexProvider, patch::ConversationPatch},
+    },
+};
+
+/// An executor for any CLI agent, configured entirely from `profiles.json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+pub struct Custom {
+    pub command: CommandBuilder,
+    pub append_prompt: Option<String>,
+    #[serde(default)]
+    pub prompt_delivery: PromptDelivery,
+    /// Arguments appended to the command to resume a session, with `{session_id}`
+    /// replaced by the session to resume. Follow-ups are not supported when unset
+    #[serde(default)]
+    pub follow_up_args: Option<Vec<String>>,
+    #[serde(default)]
+    pub output: OutputSchema,
+}
+
+/// How the prompt reaches the agent
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptDelivery {
+    /// Written to stdin, which is then closed
+    #[default]
+    Stdin,
+    /// Appended to the command as its last argument. `cmd` has no quoting that
+    /// keeps arbitrary text inert, so on Windows this falls back to `File`
+    Argv,
+    /// Written to a temporary file whose path is appended as the last argument
+    File,
+}
+
+/// How the agent's stdout is turned into conversation entries
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, TS)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub enum OutputSchema {
+    /// Every line is assistant text
+    #[default]
+    PlainText,
+    /// One JSON object per line, mapped by `rules`. Lines that are not JSON or
+    /// that no rule matches are shown as plain assistant text
+    Jsonl {
+        /// JSON pointer to the session id, e.g. `/session_id`
+        session_id: Option<String>,
+        /// Tried in order; the first rule that matches a line wins
+        rules: Vec<OutputRule>,
+    },
+}
+
+/// Maps JSON lines that match `when` to one conversation entry.
+///
+/// `content`, `tool_name` and the action fields are templates: each
+/// `{/json/pointer}` is replaced with the value at that pointer in the line.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+pub struct OutputRule {
+    /// JSON pointers and the values they must hold; an empty map matches every line
+    #[serde(default)]
+    pub when: BTreeMap<String, Value>,
+    pub entry: EntryKind,
+    /// Defaults to the tool name for tool uses and to the whole line otherwise
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_name: Option<String>,
+    /// What a tool use does; defaults to `other` with the rendered content
+    #[serde(default)]
+    pub action: Option<ActionRule>,
+}
+
+/// Kind of entry an [`OutputRule`] produces
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    UserMessage,
+    AssistantMessage,
+    ToolUse,
+    SystemMessage,
+    ErrorMessage,
+    Thinking,
+    /// Drop the line
+    Skip,
+}
+
+/// Template form of [`ActionType`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ActionRule {
+    FileRead { path: String },
+    FileEdit { path: String },
+    CommandRun { command: String },
+    Search { query: String },
+    WebFetch { url: String },
+    TaskCreate { description: String },
+    PlanPresentation { plan: String },
+    Other { description: String },
+}
+
+/// Replace each `{/json/pointer}` in `template` with the value at that pointer;
+/// missing values render empty and anything else in braces is kept as is
+fn render(template: &str, line: &Value) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{/") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match line.pointer(&rest[start + 1..start + len]) {
+            Some(Value::String(s)) => out.push_str(s),
+            Some(Value::Null) | None => {}
+            Some(other) => out.push_str(&other.to_string()),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+impl ActionRule {
+    fn render(&self, line: &Value) -> ActionType {
+        match self {
+            Self::FileRead { path } => ActionType::FileRead {
+                path: render(path, line),
+            },
+            Self::FileEdit { path } => ActionType::FileEdit {
+                path: render(path, line),
+                changes: vec![],
+            },
+            Self::CommandRun { command } => ActionType::CommandRun {
+                command: render(command, line),
+            },
+            Self::Search { query } => ActionType::Search {
+                query: render(query, line),
+            },
+            Self::WebFetch { url } => ActionType::WebFetch {
+                url: render(url, line),
+            },
+            Self::TaskCreate { description } => ActionType::TaskCreate {
+                description: render(description, line),
+            },
+            Self::PlanPresentation { plan } => ActionType::PlanPresentation {
+                plan: render(plan, line),
+            },
+            Self::Other { description } => ActionType::Other {
+                description: render(description, line),
+            },
+        }
+    }
+}
+
+impl OutputRule {
+    pub fn matches(&self, line: &Value) -> bool {
+        self.when
+            .iter()
+            .all(|(pointer, expected)| line.pointer(pointer) == Some(expected))
+    }
+
+    /// The entry for a line this rule matches, or `None` for [`EntryKind::Skip`]
+    pub fn to_normalized_entry(&self, line: &Value) -> Option<NormalizedEntry> {
+        let content = self.content.as_deref().map(|c| render(c, line));
+        let entry_type = match self.entry {
+            EntryKind::UserMessage => NormalizedEntryType::UserMessage,
+            EntryKind::AssistantMessage => NormalizedEntryType::AssistantMessage,
+            EntryKind::SystemMessage => NormalizedEntryType::SystemMessage,
+            EntryKind::ErrorMessage => NormalizedEntryType::ErrorMessage,
+            EntryKind::Thinking => NormalizedEntryType::Thinking,
+            EntryKind::Skip => return None,
+            EntryKind::ToolUse => {
+                let tool_name = self
+                    .tool_name
+                    .as_deref()
+                    .map(|t| render(t, line))
+                    .unwrap_or_else(|| "tool".to_string());
+                let action_type = match &self.action {
+                    Some(action) => action.render(line),
+                    None => ActionType::Other {
+                        description: content.clone().unwrap_or_else(|| tool_name.clone()),
+                    },
+                };
+                return Some(NormalizedEntry {
+                    timestamp: None,
+                    content: content.unwrap_or_else(|| tool_name.clone()),
+                    entry_type: NormalizedEntryType::ToolUse {
+                        tool_name,
+                        action_type,
+                    },
+                    metadata: Some(line.clone()),
+                });
+            }
+        };
+        Some(NormalizedEntry {
+            timestamp: None,
+            entry_type,
+            content: content.unwrap_or_else(|| line.to_string()),
+            metadata: Some(line.clone()),
+        })
+    }
+}
+
+fn plain_text_processor(index_provider: EntryIndexProvider) -> PlainTextLogProcessor {
+    PlainTextLogProcessor::builder()
+        .normalized_entry_producer(Box::new(|content: String| NormalizedEntry {
+            timestamp: None,
+            entry_type: NormalizedEntryType::AssistantMessage,
+            content,
+            metadata: None,
+        }))
+        .index_provider(index_provider)
+        .build()
+}
+
+/// Names the prompt file for the shell, so the cleanup does not need to quote it
+const PROMPT_FILE_ENV: &str = "VIBE_KANBAN_PROMPT_FILE";
+
+/// Wrap `command` so the shell deletes the prompt file however the agent exits,
+/// including when it is interrupted or terminated by a stop
+fn remove_prompt_file_on_exit(command: &str) -> String {
+    if cfg!(windows) {
+        format!(
+            "{command} && (del /q \"%{PROMPT_FILE_ENV}%\") || (del /q \"%{PROMPT_FILE_ENV}%\" & exit /b 1)"
+        )
+    } else {
+        format!("trap 'rm -f \"${PROMPT_FILE_ENV}\"' EXIT INT TERM HUP; {command}")
+    }
+}
+
+impl Custom {
+    /// How the prompt is delivered on this platform
+    fn delivery(&self) -> PromptDelivery {
+        match self.prompt_delivery {
+            PromptDelivery::Argv if cfg!(windows) => PromptDelivery::File,
+            delivery => delivery,
+        }
+    }
+
+    async fn spawn_with(
+        &self,
+        current_dir: &PathBuf,
+        prompt: &str,
+        extra_args: &[String],
+    ) -> Result<AsyncGroupChild, ExecutorError> {
+        let (shell_cmd, shell_arg) = get_shell_command();
+        let combined_prompt = utils::text::combine_prompt(&self.append_prompt, prompt);
+
+        let mut args = extra_args.to_vec();
+        let mut prompt_file = None;
+        match self.delivery() {
+            PromptDelivery::Stdin => {}
+            PromptDelivery::Argv => args.push(shell_quote(&combined_prompt)),
+            PromptDelivery::File => {
+                let path =
+                    std::env::temp_dir().join(format!("vibe-kanban-prompt-{}.md", Uuid::new_v4()));
+                tokio::fs::write(&path, &combined_prompt)
+                    .await
+                    .map_err(ExecutorError::Io)?;
+                args.push(shell_quote(&path.to_string_lossy()));
+                prompt_file = Some(path);
+            }
+        }
+        let mut custom_command = self.command.build_follow_up(&args);
+        if prompt_file.is_some() {
+            custom_command = remove_prompt_file_on_exit(&custom_command);
+        }
+
+        let mut command = Command::new(shell_cmd);
+        command
+            .kill_on_drop(true)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .current_dir(current_dir)
+            .arg(shell_arg)
+            .arg(custom_command);
+
+        command.envs(self.command.env.clone());
+        if let Some(path) = &prompt_file {
+            command.env(PROMPT_FILE_ENV, path);
+        }
+
+        let mut child = match command.group_spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                if let Some(path) = &prompt_file {
+                    let _ = tokio::fs::remove_file(path).await;
+                }
+                return Err(e.into());
+            }
+        };
+
+        // Close stdin either way so agents that read it see EOF
+        if let Some(mut stdin) = child.inner().stdin.take() {
+            if self.delivery() == PromptDelivery::Stdin {
+                stdin.write_all(combined_prompt.as_bytes()).await?;
+            }
+            stdin.shutdown().await?;
+        }
+
+        Ok(child)
+    }
+}
+
+#[async_trait]
+impl StandardCodingAgentExecutor for Custom {
+    async fn spawn(
+        &self,
+        current_dir: &PathBuf,
+        prompt: &str,
+    ) -> Result<AsyncGroupChild, ExecutorError> {
+        self.spawn_with(current_dir, prompt, &[]).await
+    }
+
+    async fn spawn_follow_up(
+        &self,
+        current_dir: &PathBuf,
+        prompt: &str,
+        session_id: &str,
+    ) -> Result<AsyncGroupChild, ExecutorError> {
+        let Some(follow_up_args) = &self.follow_up_args else {
+            return Err(ExecutorError::FollowUpNotSupported(
+                "custom agent has no follow_up_args".to_string(),
+            ));
+        };
+        let args: Vec<String> = follow_up_args
+            .iter()
+            .map(|arg| arg.replace("{session_id}", &shell_quote(session_id)))
+            .collect();
+        self.spawn_with(current_dir, prompt, &args).await
+    }
+
+    fn normalize_logs(
+        &self,
+        msg_store: Arc<MsgStore>,
+        worktree_path: &PathBuf,
+    ) -> Vec<JoinHandle<()>> {
+        let entry_index_provider = EntryIndexProvider::start_from(&msg_store);
+        let stderr = normalize_stderr_logs(msg_store.clone(), entry_index_provider.clone());
+
+        let (session_pointer, rules) = match &self.output {
+            OutputSchema::PlainText => {
+                // Without structured output there is no session id to read, so
+                // resume by worktree like Gemini does
+                msg_store.push_session_id(
+                    worktree_path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                );
+                let stdout = tokio::spawn(async move {
+                    let mut stdout = msg_store.stdout_chunked_stream();
+                    let mut processor = plain_text_processor(entry_index_provider);
+                    while let Some(Ok(chunk)) = stdout.next().await {
+                        for patch in processor.process(chunk) {
+                            msg_store.push_patch(patch);
+                        }
+                    }
+                });
+                return vec![stderr, stdout];
+            }
+            OutputSchema::Jsonl { session_id, rules } => (session_id.clone(), rules.clone()),
+        };
+
+        let stdout = tokio::spawn(async move {
+            let mut lines = msg_store.stdout_lines_stream();
+            let mut session_id_pushed = false;
+            // Consecutive unmatched lines are grouped into one plain-text entry;
+            // a structured entry starts a new group
+            let mut fallback: Option<PlainTextLogProcessor> = None;
+
+            while let Some(Ok(line)) = lines.next().await {
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                let json = serde_json::from_str::<Value>(trimmed).ok();
+
+                let session_id = match (&session_pointer, &json) {
+                    (Some(pointer), Some(json)) if !session_id_pushed => {
+                        json.pointer(pointer).and_then(Value::as_str)
+                    }
+                    _ => None,
+                };
+                if let Some(id) = session_id {
+                    msg_store.push_session_id(id.to_string());
+                    session_id_pushed = true;
+                }
+
+                match json.and_then(|json| {
+                    rules
+                        .iter()
+                        .find(|rule| rule.matches(&json))
+                        .map(|rule| rule.to_normalized_entry(&json))
+                }) {
+                    Some(entry) => {
+                        fallback = None;
+                        if let Some(entry) = entry {
+                            msg_store.push_patch(ConversationPatch::add_normalized_entry(
+                                entry_index_provider.next(),
+                                entry,
+                            ));
+                        }
+                    }
+                    None => {
+                        let processor = fallback.get_or_insert_with(|| {
+                            plain_text_processor(entry_index_provider.clone())
+                        });
+                        for patch in processor.process(format!("{trimmed}\n")) {
+                            msg_store.push_patch(patch);
+                        }
+                    }
+                }
+            }
+        });
+
+        vec![stderr, stdout]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> Vec<OutputRule> {
+        serde_json::from_str(
+            r#"[
+                {"when": {"/type": "init"}, "entry": "skip"},
+                {"when": {"/type": "message", "/role": "assistant"}, "entry": "assistant_message", "content": "{/text}"},
+                {"when": {"/type": "tool", "/name": "shell"}, "entry": "tool_use", "tool_name": "{/name}",
+                 "content": "`{/input/cmd}`", "action": {"action": "command_run", "command": "{/input/cmd}"}},
+                {"when": {"/type": "tool"}, "entry": "tool_use", "tool_name": "{/name}"}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn normalize(line: &str) -> Option<Option<NormalizedEntry>> {
+        let json: Value = serde_json::from_str(line).unwrap();
+        rules()
+            .iter()
+            .find(|rule| rule.matches(&json))
+            .map(|rule| rule.to_normalized_entry(&json))
+    }
+
+    #[test]
+    fn render_fills_pointers_and_keeps_other_braces() {
+        let line = serde_json::json!({"a": {"b": "x"}, "n": 3});
+        assert_eq!(render("{/a/b}-{/n}-{/missing}", &line), "x-3-");
+        assert_eq!(render("{literal} {/a", &line), "{literal} {/a");
+    }
+
+    #[test]
+    fn rules_map_lines_to_entries() {
+        assert!(matches!(
+            normalize(r#"{"type":"init","session":"s1"}"#),
+            Some(None)
+        ));
+
+        let entry = normalize(r#"{"type":"message","role":"assistant","text":"Done."}"#)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            entry.entry_type,
+            NormalizedEntryType::AssistantMessage
+        ));
+        assert_eq!(entry.content, "Done.");
+
+        let entry = normalize(r#"{"type":"tool","name":"shell","input":{"cmd":"cargo test"}}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.content, "`cargo test`");
+        match entry.entry_type {
+            NormalizedEntryType::ToolUse {
+                tool_name,
+                action_type: ActionType::CommandRun { command },
+            } => {
+                assert_eq!(tool_name, "shell");
+                assert_eq!(command, "cargo test");
+            }
+            other => panic!("unexpected entry type: {other:?}"),
+        }
+
+        let entry = normalize(r#"{"type":"tool","name":"browse"}"#)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            entry.entry_type,
+            NormalizedEntryType::ToolUse { action_type: ActionType::Other { ref description }, .. }
+                if description == "browse"
+        ));
+
+        assert!(normalize(r#"{"type":"message","role":"user","text":"hi"}"#).is_none());
+    }
+
+    #[test]
+    fn profile_config_defaults() {
+        let custom: Custom = serde_json::from_str(
+            r#"{"command": {"base": "my-agent", "params": null}, "append_prompt": null}"#,
+        )
+        .unwrap();
+        assert_eq!(custom.prompt_delivery, PromptDelivery::Stdin);
+        assert_eq!(custom.output, OutputSchema::PlainText);
+        assert!(custom.follow_up_args.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn prompt_file_is_removed_when_the_agent_exits() {
+        let custom: Custom = serde_json::from_str(
+            r#"{"command": {"base": "sh -c 'cat \"$0\"; echo; echo \"$0\"'", "params": null},
+                "append_prompt": null, "prompt_delivery": "file"}"#,
+        )
+        .unwrap();
+        let child = custom
+            .spawn_with(&std::env::temp_dir(), "fix the bug", &[])
+            .await
+            .unwrap();
+        let output = child.into_inner().wait_with_output().await.unwrap();
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut lines = stdout.lines();
+        assert_eq!(lines.next(), Some("fix the bug"));
+        let path = lines.next().unwrap();
+        assert!(path.contains("vibe-kanban-prompt-"));
+        assert!(!std::path::Path::new(path).exists());
+    }
+
+    #[tokio::test]
+    async fn argv_prompt_reaches_the_agent_verbatim() {
+        let custom: Custom = serde_json::from_str(
+            r#"{"command": {"base": "echo", "params": null},
+                "append_prompt": null, "prompt_delivery": "argv"}"#,
+        )
+        .unwrap();
+        let prompt = r#"fix "the" bug" & echo pwned; echo $(whoami) `id` 'x' %PATH%"#;
+        let child = custom
+            .spawn_with(&std::env::temp_dir(), prompt, &[])
+            .await
+            .unwrap();
+        let output = child.into_inner().wait_with_output().await.unwrap();
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        if cfg!(windows) {
+            // Delivered as a file, so only its path reaches the command line
+            assert_eq!(custom.delivery(), PromptDelivery::File);
+            assert!(!stdout.contains("pwned"));
+        } else {
+            assert_eq!(custom.delivery(), PromptDelivery::Argv);
+            assert_eq!(stdout.trim_end(), prompt);
+        }
+    }
+}
@@ -8,6 +8,7 @@ use tokio::{
     fs::{self, OpenOptions},
     io::AsyncWriteExt,
     process::Command,
+    task::JoinHandle,
 };
 use ts_rs::TS;
 use utils::{msg_store::MsgStore, shell::get_shell_command};
@@ -52,6 +53,8 @@ impl StandardCodingAgentExecutor for Gemini {
             .arg(gemini_command)
             .env("NODE_NO_WARNINGS", "1");
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         // Write prompt to stdin
@@ -96,6 +99,8 @@ impl StandardCodingAgentExecutor for Gemini {
             .arg(gemini_command)
             .env("NODE_NO_WARNINGS", "1");
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         // Write comprehensive prompt to stdin
@@ -134,9 +139,13 @@ impl StandardCodingAgentExecutor for Gemini {
     /// Sets up log normalization for the Gemini executor:
     /// - stderr via [`normalize_stderr_logs`]
     /// - stdout via [`PlainTextLogProcessor`] with Gemini-specific formatting and default heuristics
-    fn normalize_logs(&self, msg_store: Arc<MsgStore>, worktree_path: &PathBuf) {
+    fn normalize_logs(
+        &self,
+        msg_store: Arc<MsgStore>,
+        worktree_path: &PathBuf,
+    ) -> Vec<JoinHandle<()>> {
         let entry_index_counter = EntryIndexProvider::start_from(&msg_store);
-        normalize_stderr_logs(msg_store.clone(), entry_index_counter.clone());
+        let stderr = normalize_stderr_logs(msg_store.clone(), entry_index_counter.clone());
 
         // Send session ID to msg_store to enable follow-ups
         msg_store.push_session_id(
@@ -148,7 +157,7 @@ impl StandardCodingAgentExecutor for Gemini {
         );
 
         // Normalize Agent logs
-        tokio::spawn(async move {
+        let stdout = tokio::spawn(async move {
             let mut stdout = msg_store.stdout_chunked_stream();
 
             // Create a processor with Gemini-specific formatting
@@ -177,6 +186,8 @@ impl StandardCodingAgentExecutor for Gemini {
                 }
             }
         });
+
+        vec![stderr, stdout]
     }
 }
 
@@ -6,12 +6,15 @@ use enum_dispatch::enum_dispatch;
 use futures_io::Error as FuturesIoError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tokio::{process::ChildStdin, sync::Mutex, task::JoinHandle};
 use ts_rs::TS;
 use utils::msg_store::MsgStore;
 
 use crate::{
+    command::CommandBuilder,
+    env::AgentEnv,
     executors::{
-        amp::Amp, claude::ClaudeCode, codex::Codex, cursor::Cursor, gemini::Gemini,
+        amp::Amp, claude::ClaudeCode, codex::Codex, cursor::Cursor, custom::Custom, gemini::Gemini,
         opencode::Opencode,
     },
     mcp_config::McpConfig,
@@ -22,6 +25,7 @@ pub mod amp;
 pub mod claude;
 pub mod codex;
 pub mod cursor;
+pub mod custom;
 pub mod gemini;
 pub mod opencode;
 
@@ -33,6 +37,8 @@ pub enum ExecutorError {
     SpawnError(#[from] FuturesIoError),
     #[error("Unknown executor type: {0}")]
     UnknownExecutorType(String),
+    #[error("Action cannot be spawned as a process: {0}")]
+    NotSpawnable(String),
     #[error("I/O error: {0}")]
     Io(std::io::Error),
     #[error(transparent)]
@@ -41,8 +47,18 @@ pub enum ExecutorError {
     TomlSerialize(#[from] toml::ser::Error),
     #[error(transparent)]
     TomlDeserialize(#[from] toml::de::Error),
+    #[error("Environment error: {0}")]
+    Env(String),
+    #[error("This agent does not take messages while it is running")]
+    MessagesNotSupported,
+    #[error("The agent is no longer taking messages")]
+    StdinClosed,
 }
 
+/// Stdin of a running agent, shared between the task managing it and callers
+/// sending messages; `None` once closed
+pub type AgentStdin = Arc<Mutex<Option<ChildStdin>>>;
+
 #[enum_dispatch]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -53,6 +69,7 @@ pub enum CodingAgent {
     Codex,
     Opencode,
     Cursor,
+    Custom,
 }
 
 impl CodingAgent {
@@ -83,6 +100,49 @@ impl CodingAgent {
         }
     }
 
+    /// The command line this agent is launched with
+    pub fn command(&self) -> &CommandBuilder {
+        match self {
+            Self::ClaudeCode(agent) => &agent.command,
+            Self::Amp(agent) => &agent.command,
+            Self::Gemini(agent) => &agent.command,
+            Self::Codex(agent) => &agent.command,
+            Self::Opencode(agent) => &agent.command,
+            Self::Cursor(agent) => &agent.command,
+            Self::Custom(agent) => &agent.command,
+        }
+    }
+
+    fn command_mut(&mut self) -> &mut CommandBuilder {
+        match self {
+            Self::ClaudeCode(agent) => &mut agent.command,
+            Self::Amp(agent) => &mut agent.command,
+            Self::Gemini(agent) => &mut agent.command,
+            Self::Codex(agent) => &mut agent.command,
+            Self::Opencode(agent) => &mut agent.command,
+            Self::Cursor(agent) => &mut agent.command,
+            Self::Custom(agent) => &mut agent.command,
+        }
+    }
+
+    /// Where a supervised agent's permission prompt tool posts approval requests
+    pub fn set_approval_url(&mut self, url: String) {
+        if let Self::ClaudeCode(agent) = self {
+            agent.approval_url = Some(url);
+        }
+    }
+
+    /// Create a CodingAgent from a profile variant with the variant's environment
+    /// applied to its command, along with that environment
+    pub fn with_env_from_profile_variant_label(
+        profile_variant_label: &ProfileVariantLabel,
+    ) -> Result<(Self, AgentEnv), ExecutorError> {
+        let mut agent = Self::from_profile_variant_label(profile_variant_label)?;
+        let env = AgentEnv::from_profile_variant_label(profile_variant_label)?;
+        agent.command_mut().env = env.vars.clone();
+        Ok((agent, env))
+    }
+
     pub fn supports_mcp(&self) -> bool {
         self.default_mcp_config_path().is_some()
     }
@@ -164,6 +224,8 @@ impl CodingAgent {
                 dirs::home_dir().map(|home| home.join(".gemini").join("settings.json"))
             }
             Self::Cursor(_) => dirs::home_dir().map(|home| home.join(".cursor").join("mcp.json")),
+            // Where an arbitrary agent reads MCP servers from is unknown; profiles can set `mcp_config_path`
+            Self::Custom(_) => None,
         }
     }
 }
@@ -182,5 +244,26 @@ pub trait StandardCodingAgentExecutor {
         prompt: &str,
         session_id: &str,
     ) -> Result<AsyncGroupChild, ExecutorError>;
-    fn normalize_logs(&self, _raw_logs_event_store: Arc<MsgStore>, _worktree_path: &PathBuf);
+    /// Spawn the tasks that turn raw logs into normalized entries. They end once
+    /// the store is finished and everything derived from it has been pushed
+    fn normalize_logs(
+        &self,
+        _raw_logs_event_store: Arc<MsgStore>,
+        _worktree_path: &PathBuf,
+    ) -> Vec<JoinHandle<()>>;
+    /// Take over the stdin `spawn` left open: forward approval decisions pushed
+    /// to the store, and close it once the agent is done
+    fn manage_stdin(&self, stdin: AgentStdin, _msg_store: Arc<MsgStore>) {
+        // Nothing else writes to it: close it, as a plain run would have
+        tokio::spawn(async move { stdin.lock().await.take() });
+    }
+    /// Write a user message to the running agent. Only agents that read their
+    /// input as a stream take messages; others only take follow-ups after exit.
+    async fn send_message(
+        &self,
+        _stdin: Option<&mut ChildStdin>,
+        _message: &str,
+    ) -> Result<(), ExecutorError> {
+        Err(ExecutorError::MessagesNotSupported)
+    }
 }
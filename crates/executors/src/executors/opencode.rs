@@ -7,7 +7,7 @@ use futures::{StreamExt, future::ready, stream::BoxStream};
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncWriteExt, process::Command};
+use tokio::{io::AsyncWriteExt, process::Command, task::JoinHandle};
 use ts_rs::TS;
 use utils::{
     diff::create_unified_diff, msg_store::MsgStore, path::make_path_relative,
@@ -54,6 +54,8 @@ impl StandardCodingAgentExecutor for Opencode {
             .arg(opencode_command)
             .env("NODE_NO_WARNINGS", "1");
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         // Write prompt to stdin
@@ -89,6 +91,8 @@ impl StandardCodingAgentExecutor for Opencode {
             .arg(&opencode_command)
             .env("NODE_NO_WARNINGS", "1");
 
+        command.envs(self.command.env.clone());
+
         let mut child = command.group_spawn()?;
 
         // Write prompt to stdin
@@ -107,7 +111,11 @@ impl StandardCodingAgentExecutor for Opencode {
     /// 2. Error log recognition thread: read by line, identify error log lines, store them as error messages.
     /// 3. Main normalizer thread: read stderr by line, filter out log lines, send lines (with '\n' appended) to plain text normalizer,
     ///    then define predicate for split and create appropriate normalized entry (either assistant or tool call).
-    fn normalize_logs(&self, msg_store: Arc<MsgStore>, worktree_path: &PathBuf) {
+    fn normalize_logs(
+        &self,
+        msg_store: Arc<MsgStore>,
+        worktree_path: &PathBuf,
+    ) -> Vec<JoinHandle<()>> {
         let entry_index_counter = EntryIndexProvider::start_from(&msg_store);
         let worktree_path = worktree_path.clone();
 
@@ -127,7 +135,7 @@ impl StandardCodingAgentExecutor for Opencode {
             .boxed();
 
         // Process log lines, which contain error messages and session ID
-        tokio::spawn(Self::process_opencode_log_lines(
+        let log_lines_task = tokio::spawn(Self::process_opencode_log_lines(
             log_lines,
             msg_store.clone(),
             entry_index_counter.clone(),
@@ -144,12 +152,14 @@ impl StandardCodingAgentExecutor for Opencode {
             .boxed();
 
         // Normalize agent logs
-        tokio::spawn(Self::process_agent_logs(
+        let agent_logs_task = tokio::spawn(Self::process_agent_logs(
             agent_logs,
             worktree_path,
             entry_index_counter,
             msg_store,
         ));
+
+        vec![log_lines_task, agent_logs_task]
     }
 }
 impl Opencode {
@@ -0,0 +1,108 @@
+//! Normalizes tool approval requests into conversation entries.
+//!
+//! Each approval becomes one `ToolApproval` entry when it is first requested;
+//! later decisions replace that entry in place, so the UI shows a single entry
+//! per tool call whose status moves from pending to approved, denied or timed out.
+
+use std::{collections::HashMap, sync::Arc};
+
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+use utils::{approvals::ToolApproval, log_msg::LogMsg, msg_store::MsgStore};
+
+use super::{NormalizedEntry, NormalizedEntryType};
+use crate::logs::utils::{ConversationPatch, EntryIndexProvider};
+
+pub fn normalize_approvals(
+    msg_store: Arc<MsgStore>,
+    entry_index_provider: EntryIndexProvider,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut stream = msg_store.history_plus_stream();
+        // Approval id -> index of its entry
+        let mut entries: HashMap<String, usize> = HashMap::new();
+
+        while let Some(Ok(msg)) = stream.next().await {
+            let approval = match msg {
+                LogMsg::Approval(approval) => approval,
+                LogMsg::Finished => break,
+                _ => continue,
+            };
+            let patch = match entries.get(&approval.id) {
+                // A request replayed after its decision must not reopen it
+                Some(_) if approval.status.is_pending() => continue,
+                Some(&index) => ConversationPatch::replace(index, approval_entry(&approval)),
+                None => {
+                    let index = entry_index_provider.next();
+                    entries.insert(approval.id.clone(), index);
+                    ConversationPatch::add_normalized_entry(index, approval_entry(&approval))
+                }
+            };
+            msg_store.push_patch(patch);
+        }
+    })
+}
+
+fn approval_entry(approval: &ToolApproval) -> NormalizedEntry {
+    let content = match approval.primary_argument() {
+        Some(argument) => format!("{}: {}", approval.tool_name, argument),
+        None => approval.tool_name.clone(),
+    };
+    NormalizedEntry {
+        timestamp: None,
+        entry_type: NormalizedEntryType::ToolApproval {
+            approval_id: approval.id.clone(),
+            tool_name: approval.tool_name.clone(),
+            status: approval.status.clone(),
+        },
+        content,
+        metadata: Some(approval.input.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::approvals::ApprovalStatus;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn decisions_replace_the_request_entry() {
+        let store = Arc::new(MsgStore::new());
+        let approval = ToolApproval::pending(
+            "a".to_string(),
+            "Bash".to_string(),
+            serde_json::json!({"command": "cargo test"}),
+        );
+        store.push_approval(approval.clone());
+        store.push_approval(ToolApproval {
+            status: ApprovalStatus::Approved,
+            ..approval.clone()
+        });
+        // Replaying stored logs re-raises the request after its decision
+        store.push_approval(approval);
+        store.push_finished();
+
+        normalize_approvals(store.clone(), EntryIndexProvider::test_new())
+            .await
+            .unwrap();
+        let patches: Vec<serde_json::Value> = store
+            .get_history()
+            .into_iter()
+            .filter_map(|msg| match msg {
+                LogMsg::JsonPatch(patch) => serde_json::to_value(patch).ok(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(patches.len(), 2);
+
+        assert_eq!(patches[0][0]["op"], "add");
+        assert_eq!(patches[1][0]["op"], "replace");
+        for (patch, status) in patches.iter().zip(["pending", "approved"]) {
+            let entry = &patch[0]["value"]["content"];
+            assert_eq!(patch[0]["path"], "/entries/0");
+            assert_eq!(entry["content"], "Bash: cargo test");
+            assert_eq!(entry["entry_type"]["status"]["status"], status);
+        }
+    }
+}
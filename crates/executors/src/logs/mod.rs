@@ -1,8 +1,11 @@
+use ::utils::approvals::ApprovalStatus;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+pub mod approval_processor;
 pub mod plain_text_processor;
 pub mod stderr_processor;
+pub mod user_message_processor;
 pub mod utils;
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -26,6 +29,12 @@ pub enum NormalizedEntryType {
     SystemMessage,
     ErrorMessage,
     Thinking,
+    /// A tool call a supervised agent is waiting to have approved or denied
+    ToolApproval {
+        approval_id: String,
+        tool_name: String,
+        status: ApprovalStatus,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -0,0 +1,73 @@
+//! Normalizes messages sent to a running agent into conversation entries.
+//!
+//! Each message takes the next entry index when it reaches the store, so it
+//! lands after whatever the agent had output by then and before its reply.
+
+use std::sync::Arc;
+
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+use utils::{log_msg::LogMsg, msg_store::MsgStore};
+
+use super::{NormalizedEntry, NormalizedEntryType};
+use crate::logs::utils::{ConversationPatch, EntryIndexProvider};
+
+pub fn normalize_user_messages(
+    msg_store: Arc<MsgStore>,
+    entry_index_provider: EntryIndexProvider,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut stream = msg_store.history_plus_stream();
+
+        while let Some(Ok(msg)) = stream.next().await {
+            let content = match msg {
+                LogMsg::UserMessage(content) => content,
+                LogMsg::Finished => break,
+                _ => continue,
+            };
+            let entry = NormalizedEntry {
+                timestamp: None,
+                entry_type: NormalizedEntryType::UserMessage,
+                content,
+                metadata: None,
+            };
+            msg_store.push_patch(ConversationPatch::add_normalized_entry(
+                entry_index_provider.next(),
+                entry,
+            ));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn messages_take_the_next_entry_index() {
+        let store = Arc::new(MsgStore::new());
+        let entry_index_provider = EntryIndexProvider::test_new();
+        // An entry the agent's own normalizer already added
+        entry_index_provider.next();
+        store.push_user_message("Use the existing helper instead".to_string());
+        store.push_finished();
+
+        normalize_user_messages(store.clone(), entry_index_provider)
+            .await
+            .unwrap();
+        let patch = store
+            .get_history()
+            .into_iter()
+            .find_map(|msg| match msg {
+                LogMsg::JsonPatch(patch) => serde_json::to_value(patch).ok(),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(patch[0]["op"], "add");
+        assert_eq!(patch[0]["path"], "/entries/1");
+        let entry = &patch[0]["value"]["content"];
+        assert_eq!(entry["entry_type"]["type"], "user_message");
+        assert_eq!(entry["content"], "Use the existing helper instead");
+    }
+}
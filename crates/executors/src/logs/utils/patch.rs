@@ -34,6 +34,21 @@ pub fn escape_json_pointer_segment(s: &str) -> String {
     s.replace('~', "~0").replace('/', "~1")
 }
 
+/// Extract the NormalizedEntry carried by a conversation patch, if it contains one
+pub fn extract_normalized_entry(patch: &Patch) -> Option<NormalizedEntry> {
+    let patch_json = serde_json::to_value(patch).ok()?;
+    for operation in patch_json.as_array()? {
+        if let Some(value) = operation.get("value")
+            && value.get("type").and_then(|t| t.as_str()) == Some("NORMALIZED_ENTRY")
+            && let Some(content) = value.get("content")
+            && let Ok(entry) = from_value::<NormalizedEntry>(content.clone())
+        {
+            return Some(entry);
+        }
+    }
+    None
+}
+
 /// Helper functions to create JSON patches for conversation entries
 pub struct ConversationPatch;
 
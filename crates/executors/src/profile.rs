@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
     path::PathBuf,
     sync::RwLock,
@@ -27,6 +27,14 @@ pub struct VariantAgentConfig {
     pub agent: CodingAgent,
     /// Optional profile-specific MCP config file path (absolute; supports leading ~). Overrides the default `BaseCodingAgent` config path
     pub mcp_config_path: Option<String>,
+    /// Environment variables for the agent; values may reference secrets as `${secret:NAME}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub env: Option<BTreeMap<String, String>>,
+    /// `.env` file loaded before `env` (absolute; supports leading ~)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub env_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
@@ -189,6 +197,7 @@ mod tests {
                         CodingAgent::Codex(codex) => codex.command.build_initial(),
                         CodingAgent::Opencode(opencode) => opencode.command.build_initial(),
                         CodingAgent::Cursor(cursor) => cursor.command.build_initial(),
+                        CodingAgent::Custom(custom) => custom.command.build_initial(),
                     }
                 })
                 .unwrap_or_else(|| panic!("Profile not found: {label}"))
@@ -2,7 +2,10 @@ use std::{
     collections::{HashMap, HashSet},
     io,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
     time::Duration,
 };
 
@@ -19,6 +22,7 @@ use db::{
         },
         executor_session::ExecutorSession,
         merge::Merge,
+        phase::Phase,
         project::Project,
         task::{Task, TaskStatus},
         task_attempt::TaskAttempt,
@@ -27,44 +31,120 @@ use db::{
 use deployment::DeploymentError;
 use executors::{
     actions::{Executable, ExecutorAction},
+    executors::AgentStdin,
     logs::{
-        NormalizedEntry, NormalizedEntryType,
-        utils::{ConversationPatch, patch::escape_json_pointer_segment},
+        NormalizedEntryType,
+        utils::{
+            ConversationPatch,
+            patch::{escape_json_pointer_segment, extract_normalized_entry},
+        },
     },
 };
 use futures::{StreamExt, TryStreamExt, stream::select};
 use notify_debouncer_full::DebouncedEvent;
+use orchestrator::{
+    RunHooks,
+    record::RunRecorder,
+    run::{OrchestratorRun, RunSummary},
+};
 use serde_json::json;
 use services::services::{
     analytics::AnalyticsContext,
+    approvals::{Approvals, AutoApproveRules},
     config::Config,
     container::{ContainerError, ContainerRef, ContainerService},
+    context_file::ContextFileService,
     filesystem_watcher,
     git::{DiffTarget, GitService},
     image::ImageService,
     notification::NotificationService,
     worktree_manager::WorktreeManager,
 };
-use tokio::{sync::RwLock, task::JoinHandle};
+use tokio::{
+    sync::{Mutex, RwLock},
+    task::JoinHandle,
+};
 use tokio_util::io::ReaderStream;
 use utils::{
-    log_msg::LogMsg,
+    log_msg::{LogMsg, TokenUsage},
     msg_store::MsgStore,
+    port_file::backend_url,
+    redact::{Redactor, redact_stream},
     text::{git_branch_id, short_uuid},
 };
 use uuid::Uuid;
 
 use crate::command;
 
+/// Routes an orchestrator run's progress into its MsgStore and registers the
+/// children it spawns, so `stop_execution` can kill them.
+struct OrchestratorHooks {
+    recorder: RunRecorder,
+    child_store: Arc<RwLock<HashMap<Uuid, Arc<RwLock<AsyncGroupChild>>>>>,
+    msg_store: Arc<MsgStore>,
+    passed: AtomicBool,
+    /// Replays are compared against the run they re-execute and leave the attempt as it was
+    replay: bool,
+}
+
+#[async_trait]
+impl RunHooks for OrchestratorHooks {
+    fn log(&self, line: &str) {
+        self.msg_store.push_stdout(format!("{line}\n"));
+    }
+
+    async fn track_child(&self, child: AsyncGroupChild) -> Arc<RwLock<AsyncGroupChild>> {
+        let child = Arc::new(RwLock::new(child));
+        self.child_store
+            .write()
+            .await
+            .insert(self.recorder.execution_process_id, child.clone());
+        child
+    }
+
+    async fn untrack_child(&self) {
+        self.child_store
+            .write()
+            .await
+            .remove(&self.recorder.execution_process_id);
+    }
+
+    async fn is_cancelled(&self) -> bool {
+        ExecutionProcess::was_killed(&self.recorder.db.pool, self.recorder.execution_process_id)
+            .await
+    }
+
+    async fn record_artifact(&self, kind: &str, path: &Path) {
+        self.recorder.record_artifact(kind, path).await;
+    }
+
+    async fn record_usage(&self, usage: &TokenUsage) {
+        if !self.replay {
+            self.recorder.record_usage(usage).await;
+        }
+    }
+
+    async fn record_summary(&self, summary: &RunSummary) {
+        self.passed.store(summary.passed(), Ordering::SeqCst);
+        if !self.replay {
+            self.recorder.record_summary(summary).await;
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct LocalContainerService {
     db: DBService,
     child_store: Arc<RwLock<HashMap<Uuid, Arc<RwLock<AsyncGroupChild>>>>>,
+    /// Stdin of running agents that left it open, for messages sent mid-run
+    stdin_store: Arc<RwLock<HashMap<Uuid, AgentStdin>>>,
     msg_stores: Arc<RwLock<HashMap<Uuid, Arc<MsgStore>>>>,
     config: Arc<RwLock<Config>>,
     git: GitService,
     image_service: ImageService,
+    context_files: ContextFileService,
     analytics: Option<AnalyticsContext>,
+    approvals: Approvals,
 }
 
 impl LocalContainerService {
@@ -74,18 +154,45 @@ impl LocalContainerService {
         config: Arc<RwLock<Config>>,
         git: GitService,
         image_service: ImageService,
+        context_files: ContextFileService,
         analytics: Option<AnalyticsContext>,
+        approvals: Approvals,
     ) -> Self {
         let child_store = Arc::new(RwLock::new(HashMap::new()));
+        let stdin_store = Arc::new(RwLock::new(HashMap::new()));
 
         LocalContainerService {
             db,
             child_store,
+            stdin_store,
             msg_stores,
             config,
             git,
             image_service,
+            context_files,
             analytics,
+            approvals,
+        }
+    }
+
+    /// The auto-approve rules of the attempt's project; none when they cannot be loaded
+    async fn auto_approve_rules(&self, task_attempt: &TaskAttempt) -> AutoApproveRules {
+        let project = match task_attempt.parent_task(&self.db.pool).await {
+            Ok(Some(task)) => Project::find_by_id(&self.db.pool, task.project_id).await,
+            Ok(None) => Ok(None),
+            Err(e) => Err(e),
+        };
+        match project {
+            Ok(project) => AutoApproveRules::parse(
+                project
+                    .and_then(|p| p.auto_approve_rules)
+                    .as_deref()
+                    .unwrap_or_default(),
+            ),
+            Err(e) => {
+                tracing::error!("Failed to load auto-approve rules: {}", e);
+                AutoApproveRules::default()
+            }
         }
     }
 
@@ -119,6 +226,17 @@ impl LocalContainerService {
             ))
     }
 
+    /// Settle the attempt's phase once its run ends, see [`Phase::finish_attempt`]
+    async fn finish_phase(db: &DBService, attempt_id: Uuid, passed: bool) -> bool {
+        match Phase::finish_attempt(&db.pool, attempt_id, passed).await {
+            Ok(ready) => ready,
+            Err(e) => {
+                tracing::error!("Failed to settle phase of attempt {}: {}", attempt_id, e);
+                false
+            }
+        }
+    }
+
     /// Finalize task execution by updating status to InReview and sending notifications
     async fn finalize_task(db: &DBService, config: &Arc<RwLock<Config>>, ctx: &ExecutionContext) {
         if let Err(e) = Task::update_status(&db.pool, ctx.task.id, TaskStatus::InReview).await {
@@ -286,6 +404,7 @@ impl LocalContainerService {
     pub fn spawn_exit_monitor(&self, exec_id: &Uuid) -> JoinHandle<()> {
         let exec_id = *exec_id;
         let child_store = self.child_store.clone();
+        let stdin_store = self.stdin_store.clone();
         let msg_stores = self.msg_stores.clone();
         let db = self.db.clone();
         let config = self.config.clone();
@@ -381,6 +500,7 @@ impl LocalContainerService {
                                         "Failed to start next action after completion: {}",
                                         e
                                     );
+                                    Self::finish_phase(&db, ctx.task_attempt.id, false).await;
                                 }
                             } else {
                                 tracing::info!(
@@ -393,6 +513,26 @@ impl LocalContainerService {
                             }
                         }
 
+                        // A phase the attempt was started for settles with its coding
+                        // agent, or as failed when an earlier step of the chain fails
+                        if !matches!(
+                            ctx.execution_process.run_reason,
+                            ExecutionProcessRunReason::DevServer
+                        ) {
+                            let succeeded = matches!(
+                                ctx.execution_process.status,
+                                ExecutionProcessStatus::Completed
+                            ) && exit_code == Some(0);
+                            if !succeeded
+                                || matches!(
+                                    ctx.execution_process.run_reason,
+                                    ExecutionProcessRunReason::CodingAgent
+                                )
+                            {
+                                Self::finish_phase(&db, ctx.task_attempt.id, succeeded).await;
+                            }
+                        }
+
                         if Self::should_finalize(&ctx) {
                             Self::finalize_task(&db, &config, &ctx).await;
                         }
@@ -431,6 +571,7 @@ impl LocalContainerService {
 
                     // Cleanup child handle
                     child_store.write().await.remove(&exec_id);
+                    stdin_store.write().await.remove(&exec_id);
                     break;
                 }
 
@@ -445,19 +586,28 @@ impl LocalContainerService {
         format!("vk-{}-{}", short_uuid(attempt_id), task_title_id)
     }
 
-    async fn track_child_msgs_in_store(&self, id: Uuid, child: &mut AsyncGroupChild) {
+    async fn track_child_msgs_in_store(
+        &self,
+        id: Uuid,
+        child: &mut AsyncGroupChild,
+        redactor: Redactor,
+    ) {
         let store = Arc::new(MsgStore::new());
 
         let out = child.inner().stdout.take().expect("no stdout");
         let err = child.inner().stderr.take().expect("no stderr");
 
-        // Map stdout bytes -> LogMsg::Stdout
-        let out = ReaderStream::new(out)
-            .map_ok(|chunk| LogMsg::Stdout(String::from_utf8_lossy(&chunk).into_owned()));
+        // Decode chunks and redact secrets before anything reaches the store or the db
+        let out =
+            ReaderStream::new(out).map_ok(|chunk| String::from_utf8_lossy(&chunk).into_owned());
+        let err =
+            ReaderStream::new(err).map_ok(|chunk| String::from_utf8_lossy(&chunk).into_owned());
 
-        // Map stderr bytes -> LogMsg::Stderr
-        let err = ReaderStream::new(err)
-            .map_ok(|chunk| LogMsg::Stderr(String::from_utf8_lossy(&chunk).into_owned()));
+        // Map stdout text -> LogMsg::Stdout
+        let out = redact_stream(out, redactor.clone()).map_ok(LogMsg::Stdout);
+
+        // Map stderr text -> LogMsg::Stderr
+        let err = redact_stream(err, redactor).map_ok(LogMsg::Stderr);
 
         // If you have a JSON Patch source, map it to LogMsg::JsonPatch too, then select all three.
 
@@ -688,6 +838,10 @@ impl ContainerService for LocalContainerService {
         &self.git
     }
 
+    fn context_files(&self) -> &ContextFileService {
+        &self.context_files
+    }
+
     fn task_attempt_to_current_dir(&self, task_attempt: &TaskAttempt) -> PathBuf {
         PathBuf::from(task_attempt.container_ref.clone().unwrap_or_default())
     }
@@ -843,12 +997,41 @@ impl ContainerService for LocalContainerService {
             )))?;
         let current_dir = PathBuf::from(container_ref);
 
+        // Supervised agents post their permission prompts back to this execution
+        let executor_action = match backend_url() {
+            Some(url) => executor_action.with_approval_url(format!(
+                "{url}/api/execution-processes/{}/approvals/request",
+                execution_process.id
+            )),
+            None => executor_action.clone(),
+        };
+
         // Create the child and stream, add to execution tracker
-        let mut child = executor_action.spawn(&current_dir).await?;
+        let (mut child, redactor) = executor_action.spawn(&current_dir).await?;
+        let stdin = child.inner().stdin.take();
 
-        self.track_child_msgs_in_store(execution_process.id, &mut child)
+        self.track_child_msgs_in_store(execution_process.id, &mut child, redactor)
             .await;
 
+        if matches!(
+            execution_process.run_reason,
+            ExecutionProcessRunReason::CodingAgent
+        ) {
+            if let Some(msg_store) = self.get_msg_store_by_id(&execution_process.id).await {
+                let rules = self.auto_approve_rules(task_attempt).await;
+                self.approvals
+                    .supervise(execution_process.id, msg_store.clone(), rules);
+                if let Some(stdin) = stdin {
+                    let stdin: AgentStdin = Arc::new(Mutex::new(Some(stdin)));
+                    self.stdin_store
+                        .write()
+                        .await
+                        .insert(execution_process.id, stdin.clone());
+                    executor_action.manage_stdin(stdin, msg_store);
+                }
+            }
+        }
+
         self.add_child_to_store(execution_process.id, child).await;
 
         // Spawn exit monitor
@@ -857,16 +1040,129 @@ impl ContainerService for LocalContainerService {
         Ok(())
     }
 
+    async fn start_orchestrator_inner(
+        &self,
+        task_attempt: &TaskAttempt,
+        execution_process: &ExecutionProcess,
+        run: OrchestratorRun,
+    ) -> Result<(), ContainerError> {
+        let exec_id = execution_process.id;
+        let attempt_id = task_attempt.id;
+        let msg_store = self.get_msg_store_by_id(&exec_id).await.ok_or_else(|| {
+            ContainerError::Other(anyhow!("No MsgStore registered for execution {exec_id}"))
+        })?;
+
+        let hooks = OrchestratorHooks {
+            recorder: RunRecorder {
+                db: self.db.clone(),
+                attempt_id,
+                execution_process_id: exec_id,
+            },
+            child_store: self.child_store.clone(),
+            msg_store,
+            passed: AtomicBool::new(false),
+            replay: run.replay.is_some(),
+        };
+        let db = self.db.clone();
+        let config = self.config.clone();
+        let child_store = self.child_store.clone();
+        let msg_stores = self.msg_stores.clone();
+        let container = self.clone();
+
+        tokio::spawn(async move {
+            let result = run.execute(attempt_id.to_string(), &hooks).await;
+            if let Err(e) = &result {
+                hooks.msg_store.push_stderr(format!("ORCH: failed: {e}\n"));
+            }
+            let passed = result.is_ok() && hooks.passed.load(Ordering::SeqCst);
+
+            // A stopped run has already been marked Killed by stop_execution
+            let killed = ExecutionProcess::was_killed(&db.pool, exec_id).await;
+            if hooks.replay {
+                // Nothing to commit or settle: the replay ran in a throwaway checkout
+                if !killed {
+                    let (status, exit_code) = match result {
+                        Ok(()) => (ExecutionProcessStatus::Completed, 0),
+                        Err(_) => (ExecutionProcessStatus::Failed, 1),
+                    };
+                    if let Err(e) = ExecutionProcess::update_completion(
+                        &db.pool,
+                        exec_id,
+                        status,
+                        Some(exit_code),
+                    )
+                    .await
+                    {
+                        tracing::error!("Failed to update replay completion: {}", e);
+                    }
+                }
+            } else if killed {
+                Self::finish_phase(&db, attempt_id, false).await;
+            } else {
+                let (status, exit_code) = match result {
+                    Ok(()) => (ExecutionProcessStatus::Completed, 0),
+                    Err(_) => (ExecutionProcessStatus::Failed, 1),
+                };
+                if let Err(e) =
+                    ExecutionProcess::update_completion(&db.pool, exec_id, status, Some(exit_code))
+                        .await
+                {
+                    tracing::error!("Failed to update orchestrator run completion: {}", e);
+                }
+                if let Ok(ctx) = ExecutionProcess::load_context(&db.pool, exec_id).await {
+                    // Commit the applied patch so it shows up in the attempt's diff
+                    if matches!(
+                        ctx.execution_process.status,
+                        ExecutionProcessStatus::Completed
+                    ) {
+                        match container.try_commit_changes(&ctx).await {
+                            Ok(true) => hooks.log("ORCH: committed changes"),
+                            Ok(false) => hooks.log("ORCH: no changes to commit"),
+                            Err(e) => {
+                                tracing::error!(
+                                    "Failed to commit orchestrator changes for {}: {}",
+                                    exec_id,
+                                    e
+                                );
+                            }
+                        }
+                    }
+                    // The phase settles after the commit, so a pipeline waiting on it
+                    // can branch the next phase off these changes
+                    if Self::finish_phase(&db, attempt_id, passed).await {
+                        Self::finalize_task(&db, &config, &ctx).await;
+                    }
+                } else {
+                    Self::finish_phase(&db, attempt_id, passed).await;
+                }
+            }
+
+            child_store.write().await.remove(&exec_id);
+            if let Some(msg) = msg_stores.write().await.remove(&exec_id) {
+                msg.push_finished();
+            }
+        });
+
+        Ok(())
+    }
+
     async fn stop_execution(
         &self,
         execution_process: &ExecutionProcess,
     ) -> Result<(), ContainerError> {
-        let child = self
-            .get_child_from_store(&execution_process.id)
-            .await
-            .ok_or_else(|| {
-                ContainerError::Other(anyhow!("Child process not found for execution"))
-            })?;
+        let child = self.get_child_from_store(&execution_process.id).await;
+        // Orchestrator runs only have a child while the agent or tests are running;
+        // between steps they notice the Killed status and stop on their own
+        if child.is_none()
+            && !matches!(
+                execution_process.run_reason,
+                ExecutionProcessRunReason::Orchestrator
+            )
+        {
+            return Err(ContainerError::Other(anyhow!(
+                "Child process not found for execution"
+            )));
+        }
         ExecutionProcess::update_completion(
             &self.db.pool,
             execution_process.id,
@@ -876,7 +1172,7 @@ impl ContainerService for LocalContainerService {
         .await?;
 
         // Kill the child process and remove from the store
-        {
+        if let Some(child) = child {
             let mut child_guard = child.write().await;
             if let Err(e) = command::kill_process_group(&mut child_guard).await {
                 tracing::error!(
@@ -888,6 +1184,7 @@ impl ContainerService for LocalContainerService {
             }
         }
         self.remove_child_from_store(&execution_process.id).await;
+        self.stdin_store.write().await.remove(&execution_process.id);
 
         // Mark the process finished in the MsgStore
         if let Some(msg) = self.msg_stores.write().await.remove(&execution_process.id) {
@@ -900,10 +1197,19 @@ impl ContainerService for LocalContainerService {
                 ctx.execution_process.run_reason,
                 ExecutionProcessRunReason::DevServer
             )
-            && let Err(e) =
-                Task::update_status(&self.db.pool, ctx.task.id, TaskStatus::InReview).await
         {
-            tracing::error!("Failed to update task status to InReview: {e}");
+            // Orchestrator runs settle their phase themselves once they notice the stop
+            if !matches!(
+                ctx.execution_process.run_reason,
+                ExecutionProcessRunReason::Orchestrator
+            ) {
+                Self::finish_phase(&self.db, ctx.task_attempt.id, false).await;
+            }
+            if let Err(e) =
+                Task::update_status(&self.db.pool, ctx.task.id, TaskStatus::InReview).await
+            {
+                tracing::error!("Failed to update task status to InReview: {e}");
+            }
         }
 
         tracing::debug!(
@@ -914,6 +1220,33 @@ impl ContainerService for LocalContainerService {
         Ok(())
     }
 
+    async fn send_message(
+        &self,
+        execution_process: &ExecutionProcess,
+        message: String,
+    ) -> Result<(), ContainerError> {
+        let executor_action = execution_process.executor_action()?;
+        // Processes that never left stdin open, or have exited, get a closed one
+        let stdin = self
+            .stdin_store
+            .read()
+            .await
+            .get(&execution_process.id)
+            .cloned()
+            .unwrap_or_default();
+        let mut stdin = stdin.lock().await;
+        executor_action
+            .send_message(stdin.as_mut(), &message)
+            .await?;
+
+        // Recorded before the lock is released, so an agent closing its stdin
+        // once every message is answered counts this one
+        if let Some(msg_store) = self.get_msg_store_by_id(&execution_process.id).await {
+            msg_store.push_user_message(message);
+        }
+        Ok(())
+    }
+
     async fn get_diff(
         &self,
         task_attempt: &TaskAttempt,
@@ -966,7 +1299,9 @@ impl ContainerService for LocalContainerService {
     async fn try_commit_changes(&self, ctx: &ExecutionContext) -> Result<bool, ContainerError> {
         if !matches!(
             ctx.execution_process.run_reason,
-            ExecutionProcessRunReason::CodingAgent | ExecutionProcessRunReason::CleanupScript,
+            ExecutionProcessRunReason::CodingAgent
+                | ExecutionProcessRunReason::CleanupScript
+                | ExecutionProcessRunReason::Orchestrator,
         ) {
             return Ok(false);
         }
@@ -1011,6 +1346,12 @@ impl ContainerService for LocalContainerService {
                     ctx.task_attempt.id
                 )
             }
+            ExecutionProcessRunReason::Orchestrator => {
+                format!(
+                    "Apply orchestrator patch for task attempt {}",
+                    ctx.task_attempt.id
+                )
+            }
             _ => Err(ContainerError::Other(anyhow::anyhow!(
                 "Invalid run reason for commit"
             )))?,
@@ -1092,7 +1433,7 @@ impl LocalContainerService {
         for msg in history.iter().rev() {
             if let LogMsg::JsonPatch(patch) = msg {
                 // Try to extract a NormalizedEntry from the patch
-                if let Some(entry) = self.extract_normalized_entry_from_patch(patch)
+                if let Some(entry) = extract_normalized_entry(patch)
                     && matches!(entry.entry_type, NormalizedEntryType::AssistantMessage)
                 {
                     let content = entry.content.trim();
@@ -1111,32 +1452,6 @@ impl LocalContainerService {
         None
     }
 
-    /// Extract a NormalizedEntry from a JsonPatch if it contains one
-    fn extract_normalized_entry_from_patch(
-        &self,
-        patch: &json_patch::Patch,
-    ) -> Option<NormalizedEntry> {
-        // Convert the patch to JSON to examine its structure
-        if let Ok(patch_json) = serde_json::to_value(patch)
-            && let Some(operations) = patch_json.as_array()
-        {
-            for operation in operations {
-                if let Some(value) = operation.get("value") {
-                    // Try to extract a NormalizedEntry from the value
-                    if let Some(patch_type) = value.get("type").and_then(|t| t.as_str())
-                        && patch_type == "NORMALIZED_ENTRY"
-                        && let Some(content) = value.get("content")
-                        && let Ok(entry) =
-                            serde_json::from_value::<NormalizedEntry>(content.clone())
-                    {
-                        return Some(entry);
-                    }
-                }
-            }
-        }
-        None
-    }
-
     /// Update the executor session summary with the final assistant message
     async fn update_executor_session_summary(&self, exec_id: &Uuid) -> Result<(), anyhow::Error> {
         // Check if there's an executor session for this execution process
@@ -1157,3 +1472,62 @@ impl LocalContainerService {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use command_group::AsyncCommandGroup;
+    use db::{
+        models::execution_process::ExecutionProcessStatus,
+        test_support::{seed_attempt, seed_orchestrator_run, seed_task},
+    };
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stopping_an_orchestrator_run_kills_its_child() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let container = LocalContainerService::new(
+            db.clone(),
+            Arc::new(RwLock::new(HashMap::new())),
+            Arc::new(RwLock::new(Config::default())),
+            GitService::new(),
+            ImageService::new(db.pool.clone()).unwrap(),
+            ContextFileService::new(db.pool.clone()),
+            None,
+            Approvals::new(),
+        );
+        let task = seed_task(&db.pool).await;
+        let attempt = seed_attempt(&db.pool, task.id).await;
+        let process = seed_orchestrator_run(&db.pool, attempt.id).await;
+        let hooks = OrchestratorHooks {
+            recorder: RunRecorder {
+                db: db.clone(),
+                attempt_id: process.task_attempt_id,
+                execution_process_id: process.id,
+            },
+            child_store: container.child_store.clone(),
+            msg_store: Arc::new(MsgStore::new()),
+            passed: AtomicBool::new(false),
+            replay: false,
+        };
+
+        let child = tokio::process::Command::new("sleep")
+            .arg("30")
+            .group_spawn()
+            .unwrap();
+        let child = hooks.track_child(child).await;
+        assert!(!hooks.is_cancelled().await);
+
+        container.stop_execution(&process).await.unwrap();
+
+        assert!(child.write().await.try_wait().unwrap().is_some());
+        assert!(container.get_child_from_store(&process.id).await.is_none());
+        assert!(hooks.is_cancelled().await);
+        let process = ExecutionProcess::find_by_id(&db.pool, process.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(process.status, ExecutionProcessStatus::Killed);
+    }
+}
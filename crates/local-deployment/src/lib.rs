@@ -5,9 +5,11 @@ use db::DBService;
 use deployment::{Deployment, DeploymentError};
 use services::services::{
     analytics::{AnalyticsConfig, AnalyticsContext, AnalyticsService, generate_user_id},
+    approvals::Approvals,
     auth::AuthService,
     config::{Config, load_config_from_file, save_config_to_file},
     container::ContainerService,
+    context_file::ContextFileService,
     events::EventService,
     filesystem::FilesystemService,
     git::GitService,
@@ -35,8 +37,10 @@ pub struct LocalDeployment {
     git: GitService,
     auth: AuthService,
     image: ImageService,
+    context_files: ContextFileService,
     filesystem: FilesystemService,
     events: EventService,
+    approvals: Approvals,
 }
 
 #[async_trait]
@@ -54,6 +58,7 @@ impl Deployment for LocalDeployment {
         let msg_stores = Arc::new(RwLock::new(HashMap::new()));
         let auth = AuthService::new();
         let filesystem = FilesystemService::new();
+        let approvals = Approvals::new();
 
         // Create shared components for EventService
         let events_msg_store = Arc::new(MsgStore::new());
@@ -80,6 +85,17 @@ impl Deployment for LocalDeployment {
             });
         }
 
+        let context_files = ContextFileService::new(db.clone().pool);
+        {
+            let context_files = context_files.clone();
+            tokio::spawn(async move {
+                tracing::info!("Starting orphaned context blob cleanup...");
+                if let Err(e) = context_files.delete_orphaned_blobs().await {
+                    tracing::error!("Failed to clean up orphaned context blobs: {}", e);
+                }
+            });
+        }
+
         // We need to make analytics accessible to the ContainerService
         // TODO: Handle this more gracefully
         let analytics_ctx = analytics.as_ref().map(|s| AnalyticsContext {
@@ -92,7 +108,9 @@ impl Deployment for LocalDeployment {
             config.clone(),
             git.clone(),
             image.clone(),
+            context_files.clone(),
             analytics_ctx,
+            approvals.clone(),
         );
         container.spawn_worktree_cleanup().await;
 
@@ -109,8 +127,10 @@ impl Deployment for LocalDeployment {
             git,
             auth,
             image,
+            context_files,
             filesystem,
             events,
+            approvals,
         })
     }
 
@@ -153,6 +173,10 @@ impl Deployment for LocalDeployment {
         &self.image
     }
 
+    fn context_files(&self) -> &ContextFileService {
+        &self.context_files
+    }
+
     fn filesystem(&self) -> &FilesystemService {
         &self.filesystem
     }
@@ -164,4 +188,8 @@ impl Deployment for LocalDeployment {
     fn events(&self) -> &EventService {
         &self.events
     }
+
+    fn approvals(&self) -> &Approvals {
+        &self.approvals
+    }
 }
@@ -0,0 +1,142 @@
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use executors::{
+    executors::{CodingAgent, StandardCodingAgentExecutor},
+    logs::{utils::patch::extract_normalized_entry, NormalizedEntryType},
+    profile::ProfileVariantLabel,
+};
+use futures::{future::join_all, stream::select, TryStreamExt};
+use tokio_util::io::ReaderStream;
+use utils::{log_msg::LogMsg, msg_store::MsgStore, redact::redact_stream};
+
+use crate::{manifest::AgentManifest, process::wait_tracked, AgentAdapter, RunHooks};
+
+/// Resolve the profile used for an orchestrator run.
+///
+/// A phase `agent_override` takes precedence over the attempt profile and may
+/// name a variant as `profile:variant` (e.g. `claude-code:plan`).
+pub fn resolve_profile(agent_override: Option<&str>, attempt_profile: &str) -> ProfileVariantLabel {
+    match agent_override.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(label) => match label.split_once(':') {
+            Some((profile, variant)) if !variant.is_empty() => {
+                ProfileVariantLabel::with_variant(profile.to_string(), variant.to_string())
+            }
+            Some((profile, _)) => ProfileVariantLabel::default(profile.to_string()),
+            None => ProfileVariantLabel::default(label.to_string()),
+        },
+        None => ProfileVariantLabel::default(attempt_profile.to_string()),
+    }
+}
+
+/// Runs a configured coding agent in the attempt worktree and returns its final
+/// assistant message, which is expected to contain PATCH blocks.
+pub struct CodingAgentAdapter {
+    pub profile_variant_label: ProfileVariantLabel,
+    pub workdir: PathBuf,
+    pub prompt: String,
+}
+
+#[async_trait]
+impl AgentAdapter for CodingAgentAdapter {
+    async fn get_patch_text(&self, hooks: &dyn RunHooks) -> Result<String, String> {
+        hooks.log(&format!(
+            "ORCH: agent {}",
+            self.profile_variant_label.profile
+        ));
+        let (agent, env) =
+            CodingAgent::with_env_from_profile_variant_label(&self.profile_variant_label)
+                .map_err(|e| e.to_string())?;
+        let mut child = agent
+            .spawn(&self.workdir, &self.prompt)
+            .await
+            .map_err(|e| e.to_string())?;
+        // Nothing answers approvals or sends messages during a run; agents that
+        // read stdin as a stream would otherwise wait for more input forever
+        drop(child.inner().stdin.take());
+
+        let store = Arc::new(MsgStore::new());
+        let out = child
+            .inner()
+            .stdout
+            .take()
+            .ok_or_else(|| "agent stdout not captured".to_string())?;
+        let err = child
+            .inner()
+            .stderr
+            .take()
+            .ok_or_else(|| "agent stderr not captured".to_string())?;
+        // Redacted so secrets from the profile's env cannot end up in the patch or its artifacts
+        let out =
+            ReaderStream::new(out).map_ok(|chunk| String::from_utf8_lossy(&chunk).into_owned());
+        let err =
+            ReaderStream::new(err).map_ok(|chunk| String::from_utf8_lossy(&chunk).into_owned());
+        let out = redact_stream(out, env.redactor()).map_ok(LogMsg::Stdout);
+        let err = redact_stream(err, env.redactor()).map_ok(LogMsg::Stderr);
+        let forwarder = store.clone().spawn_forwarder(select(out, err));
+
+        let normalizers = agent.normalize_logs(store.clone(), &self.workdir);
+
+        let child = hooks.track_child(child).await;
+        let status = wait_tracked(&child, None).await;
+        hooks.untrack_child().await;
+        // A stopped or timed-out agent may leave its pipes open, so stop forwarding
+        // rather than wait for them. Either way the normalizers need the stream to
+        // finish, or they and the store outlive the run
+        if status.is_ok() {
+            let _ = forwarder.await;
+        } else {
+            forwarder.abort();
+        }
+        store.push_finished();
+        // The last message and usage can be pushed after the final log line
+        join_all(normalizers).await;
+        for msg in store.get_history() {
+            if let LogMsg::Usage(usage) = msg {
+                hooks.record_usage(&usage).await;
+            }
+        }
+        let status = status?;
+
+        if !status.success() {
+            return Err(format!("agent exited with {status}"));
+        }
+        last_assistant_message(&store)
+            .ok_or_else(|| "agent produced no assistant message".to_string())
+    }
+
+    fn manifest(&self) -> AgentManifest {
+        AgentManifest::for_profile(&self.profile_variant_label, &self.prompt)
+    }
+}
+
+/// Scan the normalized conversation in reverse for the last assistant message
+fn last_assistant_message(store: &MsgStore) -> Option<String> {
+    store.get_history().iter().rev().find_map(|msg| match msg {
+        LogMsg::JsonPatch(patch) => extract_normalized_entry(patch)
+            .filter(|entry| matches!(entry.entry_type, NormalizedEntryType::AssistantMessage))
+            .map(|entry| entry.content.trim().to_string())
+            .filter(|content| !content.is_empty()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_takes_precedence_over_attempt_profile() {
+        let label = resolve_profile(Some("claude-code:plan"), "amp");
+        assert_eq!(label.profile, "claude-code");
+        assert_eq!(label.variant.as_deref(), Some("plan"));
+
+        let label = resolve_profile(Some("gemini"), "amp");
+        assert_eq!(label.profile, "gemini");
+        assert_eq!(label.variant, None);
+
+        let label = resolve_profile(Some("  "), "amp");
+        assert_eq!(label.profile, "amp");
+        assert_eq!(label.variant, None);
+    }
+}
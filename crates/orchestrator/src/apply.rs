@@ -1,44 +1,933 @@
-#[derive(Debug, Clone, Default)]
+use std::{
+    fs,
+    io::Write,
+    path::{Component, Path},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use executors::logs::FileChange;
+use serde::Serialize;
+
+use crate::patch::{PatchBlock, PatchChange};
+
+/// How a hunk made it into the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyStrategy {
+    /// `git apply` took the whole block as written
+    Strict,
+    /// Applied to the base commit's version of the file, then merged with `git merge-file`
+    ThreeWay,
+    /// Matched at a different line and/or ignoring whitespace
+    Fuzzy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HunkOutcome {
+    pub file: String,
+    /// 1-based position of the hunk within its file
+    pub hunk: usize,
+    /// The hunk's `@@ -a,b +c,d @@` line
+    pub header: String,
+    /// `None` when the hunk was rejected
+    pub strategy: Option<ApplyStrategy>,
+    /// Lines between where the hunk said it applies and where it matched
+    pub offset: i64,
+    /// Context only matched once whitespace was ignored
+    pub ignored_whitespace: bool,
+    pub error: Option<String>,
+}
+
+/// Outcome of applying one patch block. Either every hunk applied, or the
+/// tree was left untouched and `rejected_patch` holds the hunks that did not.
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ApplyResult {
     pub touched_files: Vec<String>,
+    pub hunks: Vec<HunkOutcome>,
+    /// Why `git apply` refused the block, when it did
+    pub git_apply_error: Option<String>,
+    #[serde(skip)]
+    pub rejected_patch: String,
+}
+
+impl ApplyResult {
+    pub fn applied(&self) -> bool {
+        self.hunks.iter().all(|h| h.strategy.is_some())
+    }
+
+    pub fn rejected_count(&self) -> usize {
+        self.hunks.iter().filter(|h| h.strategy.is_none()).count()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Hunk {
+    header: String,
+    old_start: usize,
+    lines: Vec<HunkLine>,
+    /// `\ No newline at end of file` followed the new side
+    new_missing_eol: bool,
+    text: String,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct FileDiff {
+    /// `None` for `/dev/null`, i.e. a created file
+    old: Option<String>,
+    /// `None` for `/dev/null`, i.e. a deleted file
+    new: Option<String>,
+    header: String,
+    hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    fn name(&self) -> &str {
+        self.new
+            .as_deref()
+            .or(self.old.as_deref())
+            .unwrap_or_default()
+    }
 }
 
-fn normalize_path(p: &str) -> Option<String> {
-    if p.starts_with('/') || p.contains(':') || p.contains("..") { return None; }
+pub(crate) fn normalize_path(p: &str) -> Option<String> {
+    let escapes = Path::new(p).components().any(|c| c == Component::ParentDir);
+    if p.starts_with('/') || p.contains(':') || escapes {
+        return None;
+    }
     Some(p.trim_start_matches("./").to_string())
 }
 
-pub fn apply_block(repo_root: &std::path::Path, diff: &str) -> Result<ApplyResult, String> {
-    // Collect touched files from diff headers
-    let mut files = Vec::new();
-    for line in diff.lines() {
-        if let Some(rest) = line.strip_prefix("+++ b/") {
-            if let Some(n) = normalize_path(rest) { files.push(n); }
-        } else if let Some(rest) = line.strip_prefix("--- a/") {
-            if let Some(n) = normalize_path(rest) { files.push(n); }
+/// Path from a `---`/`+++` header, `None` for `/dev/null`; the flag is whether
+/// it carried an `a/` or `b/` prefix. Paths leaving the repository are an error.
+fn header_path(rest: &str, prefix: &str) -> Result<(Option<String>, bool), String> {
+    let raw = rest.split('\t').next().unwrap_or_default().trim_end();
+    if raw == "/dev/null" {
+        return Ok((None, false));
+    }
+    let (path, prefixed) = match raw.strip_prefix(prefix) {
+        Some(stripped) => (stripped, true),
+        None => (raw, false),
+    };
+    let path = normalize_path(path).ok_or_else(|| format!("unsafe path `{raw}`"))?;
+    Ok((Some(path), prefixed))
+}
+
+/// `@@ -12,5 +12,6 @@` → 12; counts are ignored because agents often get them wrong
+fn hunk_old_start(line: &str) -> Option<usize> {
+    let range = line.strip_prefix("@@ -")?.split(' ').next()?;
+    range.split(',').next()?.parse().ok()
+}
+
+/// Split a unified diff into files and hunks. Hunks run until the next hunk or
+/// file header rather than trusting their line counts.
+fn parse_diff(diff: &str) -> Result<(Vec<FileDiff>, bool), String> {
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut prefixed = false;
+    let mut in_hunk = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let next_is_new = lines.get(i + 1).is_some_and(|n| n.starts_with("+++ "));
+        if line.starts_with("diff --git ") {
+            files.push(FileDiff {
+                header: format!("{line}\n"),
+                ..Default::default()
+            });
+            in_hunk = false;
+        } else if line.starts_with("--- ") && next_is_new {
+            let (old, old_prefixed) =
+                header_path(&line[4..], "a/").map_err(|e| format!("line {}: {e}", i + 1))?;
+            let (new, new_prefixed) = header_path(&lines[i + 1][4..], "b/")
+                .map_err(|e| format!("line {}: {e}", i + 2))?;
+            prefixed |= old_prefixed || new_prefixed;
+            // A `diff --git` line already opened this file
+            if !matches!(files.last(), Some(f) if !in_hunk && f.hunks.is_empty() && f.old.is_none() && f.new.is_none())
+            {
+                files.push(FileDiff::default());
+            }
+            let file = files.last_mut().expect("file pushed above");
+            file.old = old;
+            file.new = new;
+            file.header.push_str(&format!("{line}\n{}\n", lines[i + 1]));
+            in_hunk = false;
+            i += 1;
+        } else if line.starts_with("@@ ") {
+            let file = files
+                .last_mut()
+                .filter(|f| f.old.is_some() || f.new.is_some())
+                .ok_or_else(|| format!("line {}: hunk without a file header", i + 1))?;
+            let old_start = hunk_old_start(line)
+                .ok_or_else(|| format!("line {}: malformed hunk header `{line}`", i + 1))?;
+            file.hunks.push(Hunk {
+                header: line.to_string(),
+                old_start,
+                text: format!("{line}\n"),
+                ..Default::default()
+            });
+            in_hunk = true;
+        } else if in_hunk {
+            let hunk = files
+                .last_mut()
+                .and_then(|f| f.hunks.last_mut())
+                .expect("in_hunk implies a hunk");
+            let parsed = match line.chars().next() {
+                Some(' ') => Some(HunkLine::Context(line[1..].to_string())),
+                Some('-') => Some(HunkLine::Remove(line[1..].to_string())),
+                Some('+') => Some(HunkLine::Add(line[1..].to_string())),
+                // Editors and agents often drop the space of an empty context line
+                None => Some(HunkLine::Context(String::new())),
+                Some('\\') => {
+                    if matches!(
+                        hunk.lines.last(),
+                        Some(HunkLine::Add(_) | HunkLine::Context(_))
+                    ) {
+                        hunk.new_missing_eol = true;
+                    }
+                    None
+                }
+                Some(_) => {
+                    in_hunk = false;
+                    None
+                }
+            };
+            if in_hunk {
+                hunk.text.push_str(&format!("{line}\n"));
+            }
+            if let Some(parsed) = parsed {
+                hunk.lines.push(parsed);
+            }
+        } else if let Some(file) = files.last_mut().filter(|f| f.hunks.is_empty()) {
+            // `index`, mode and rename lines between `diff --git` and the hunks
+            file.header.push_str(&format!("{line}\n"));
         }
+        i += 1;
     }
-    files.sort(); files.dedup();
+    Ok((files, prefixed))
+}
 
-    // Apply via git apply
-    let status = std::process::Command::new("git")
+/// Files named in the diff headers, without touching the tree
+pub fn touched_files(diff: &str) -> Vec<String> {
+    let mut files: Vec<String> = parse_diff(diff)
+        .map(|(files, _)| files)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|f| [f.old, f.new])
+        .flatten()
+        .collect();
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// The commit the worktree forked from `base_branch`, used as the common
+/// ancestor for three-way application
+pub fn merge_base(repo_root: &Path, base_branch: &str) -> Option<String> {
+    let out = Command::new("git")
+        .args(["merge-base", "HEAD", base_branch])
+        .current_dir(repo_root)
+        .output()
+        .ok()?;
+    let sha = String::from_utf8(out.stdout).ok()?.trim().to_string();
+    (out.status.success() && !sha.is_empty()).then_some(sha)
+}
+
+/// Apply one unified diff. Tries `git apply` first; when it refuses, each file
+/// is applied to its version at `base_commit` and merged into the current
+/// file, and failing that its hunks are matched at an offset and/or ignoring
+/// whitespace. Nothing is written unless every hunk finds a home.
+pub fn apply_block(
+    repo_root: &Path,
+    diff: &str,
+    base_commit: Option<&str>,
+) -> Result<ApplyResult, String> {
+    let (files, prefixed) = parse_diff(diff)?;
+    let touched = touched_files(diff);
+
+    let git_apply_error = match git_apply(repo_root, diff, if prefixed { 1 } else { 0 })? {
+        None => {
+            let hunks = files
+                .iter()
+                .flat_map(|f| {
+                    f.hunks.iter().enumerate().map(|(i, h)| HunkOutcome {
+                        file: f.name().to_string(),
+                        hunk: i + 1,
+                        header: h.header.clone(),
+                        strategy: Some(ApplyStrategy::Strict),
+                        offset: 0,
+                        ignored_whitespace: false,
+                        error: None,
+                    })
+                })
+                .collect();
+            return Ok(ApplyResult {
+                touched_files: touched,
+                hunks,
+                ..Default::default()
+            });
+        }
+        Some(error) => error,
+    };
+
+    let mut result = ApplyResult {
+        touched_files: touched,
+        git_apply_error: Some(git_apply_error),
+        ..Default::default()
+    };
+    // (path, new content) pairs; `None` content deletes the file
+    let mut writes: Vec<(String, Option<String>)> = Vec::new();
+    for file in &files {
+        let (content, outcomes) = apply_file(repo_root, file, base_commit);
+        let rejected: Vec<&Hunk> = file
+            .hunks
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, o)| o.strategy.is_none())
+            .map(|(h, _)| h)
+            .collect();
+        if !rejected.is_empty() {
+            result.rejected_patch.push_str(&file.header);
+            for hunk in rejected {
+                result.rejected_patch.push_str(&hunk.text);
+            }
+        }
+        result.hunks.extend(outcomes);
+        if let Some(content) = content {
+            if let (Some(old), Some(new)) = (&file.old, &file.new) {
+                if old != new {
+                    writes.push((old.clone(), None));
+                }
+            }
+            match &file.new {
+                Some(new) => writes.push((new.clone(), Some(content))),
+                None => writes.push((file.name().to_string(), None)),
+            }
+        }
+    }
+    if !result.applied() {
+        return Ok(result);
+    }
+
+    for (path, content) in writes {
+        let target = repo_root.join(&path);
+        match content {
+            Some(content) => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::write(&target, content).map_err(|e| format!("{path}: {e}"))?;
+            }
+            None => {
+                if target.exists() {
+                    fs::remove_file(&target).map_err(|e| format!("{path}: {e}"))?;
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Apply one parsed change. Edits go through `apply_block`; writes, deletes
+/// and renames act on the file directly and have no hunks to report.
+pub fn apply_change(
+    repo_root: &Path,
+    change: &PatchChange,
+    base_commit: Option<&str>,
+) -> Result<ApplyResult, String> {
+    let path = &change.path;
+    let target = repo_root.join(path);
+    let touched_files = match &change.change {
+        FileChange::Edit { unified_diff, .. } => {
+            return apply_block(repo_root, unified_diff, base_commit);
+        }
+        FileChange::Write { content } => {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&target, content).map_err(|e| format!("{path}: {e}"))?;
+            vec![path.clone()]
+        }
+        FileChange::Delete => {
+            fs::remove_file(&target).map_err(|e| format!("cannot delete {path}: {e}"))?;
+            vec![path.clone()]
+        }
+        FileChange::Rename { new_path } => {
+            let dest = normalize_path(new_path)
+                .ok_or_else(|| format!("unsafe rename target {new_path}"))?;
+            let dest_path = repo_root.join(&dest);
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::rename(&target, &dest_path)
+                .map_err(|e| format!("cannot rename {path} to {dest}: {e}"))?;
+            vec![path.clone(), dest]
+        }
+    };
+    Ok(ApplyResult {
+        touched_files,
+        ..Default::default()
+    })
+}
+
+/// Apply a block's changes in order, all or nothing: when a change is
+/// rejected or fails, every file the block touches is put back the way it was.
+/// Returns the result of each change tried; only the last can be rejected.
+pub fn apply_changes(
+    repo_root: &Path,
+    block: &PatchBlock,
+    base_commit: Option<&str>,
+) -> Result<Vec<ApplyResult>, String> {
+    let snapshot = Snapshot::capture(repo_root, &block.touched_files())?;
+    let mut results = Vec::new();
+    for change in &block.changes {
+        let res = match apply_change(repo_root, change, base_commit) {
+            Ok(res) => res,
+            Err(e) => {
+                snapshot.restore(repo_root)?;
+                return Err(e);
+            }
+        };
+        let applied = res.applied();
+        results.push(res);
+        if !applied {
+            snapshot.restore(repo_root)?;
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// Contents of files before a block was applied; `None` for files that did not exist
+struct Snapshot(Vec<(String, Option<Vec<u8>>)>);
+
+impl Snapshot {
+    fn capture(repo_root: &Path, paths: &[String]) -> Result<Self, String> {
+        let mut files = Vec::new();
+        for path in paths.iter().filter_map(|p| normalize_path(p)) {
+            let content = match fs::read(repo_root.join(&path)) {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(format!("{path}: {e}")),
+            };
+            files.push((path, content));
+        }
+        Ok(Self(files))
+    }
+
+    fn restore(&self, repo_root: &Path) -> Result<(), String> {
+        for (path, content) in &self.0 {
+            let target = repo_root.join(path);
+            match content {
+                Some(content) => {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                    }
+                    fs::write(&target, content).map_err(|e| format!("{path}: {e}"))?;
+                }
+                None if target.exists() => {
+                    fs::remove_file(&target).map_err(|e| format!("{path}: {e}"))?;
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `Ok(None)` when git applied the diff, `Ok(Some(stderr))` when it refused
+fn git_apply(repo_root: &Path, diff: &str, strip: usize) -> Result<Option<String>, String> {
+    let output = Command::new("git")
         .arg("apply")
         .arg("--whitespace=nowarn")
-        .arg("-p0")
+        .arg(format!("-p{strip}"))
         .current_dir(repo_root)
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
         .spawn()
         .and_then(|mut child| {
-            use std::io::Write;
-            if let Some(stdin) = child.stdin.as_mut() {
+            if let Some(mut stdin) = child.stdin.take() {
+                // Agents routinely drop the final newline, which git reports as a corrupt patch
                 stdin.write_all(diff.as_bytes())?;
+                if !diff.ends_with('\n') {
+                    stdin.write_all(b"\n")?;
+                }
             }
-            child.wait()
+            child.wait_with_output()
         })
         .map_err(|e| e.to_string())?;
-    if !status.success() { return Err("git apply failed".to_string()); }
+    if output.status.success() {
+        return Ok(None);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Ok(Some(if stderr.is_empty() {
+        "git apply failed".to_string()
+    } else {
+        stderr
+    }))
+}
+
+/// New content for one file (`None` if any hunk was rejected) and each hunk's outcome
+fn apply_file(
+    repo_root: &Path,
+    file: &FileDiff,
+    base_commit: Option<&str>,
+) -> (Option<String>, Vec<HunkOutcome>) {
+    let reject_all = |error: &str| {
+        let outcomes = file
+            .hunks
+            .iter()
+            .enumerate()
+            .map(|(i, h)| HunkOutcome {
+                file: file.name().to_string(),
+                hunk: i + 1,
+                header: h.header.clone(),
+                strategy: None,
+                offset: 0,
+                ignored_whitespace: false,
+                error: Some(error.to_string()),
+            })
+            .collect();
+        (None, outcomes)
+    };
+
+    let current = match &file.old {
+        Some(old) => match fs::read_to_string(repo_root.join(old)) {
+            Ok(text) => Some(text),
+            Err(_) => return reject_all("file does not exist"),
+        },
+        None => {
+            if file
+                .new
+                .as_ref()
+                .is_some_and(|new| repo_root.join(new).exists())
+            {
+                return reject_all("file already exists");
+            }
+            None
+        }
+    };
+    let current = current.unwrap_or_default();
+
+    if let (Some(base), Some(old)) = (base_commit, &file.old) {
+        if let Some(merged) = three_way(repo_root, base, old, &current, &file.hunks) {
+            let outcomes = file
+                .hunks
+                .iter()
+                .enumerate()
+                .map(|(i, h)| HunkOutcome {
+                    file: file.name().to_string(),
+                    hunk: i + 1,
+                    header: h.header.clone(),
+                    strategy: Some(ApplyStrategy::ThreeWay),
+                    offset: 0,
+                    ignored_whitespace: false,
+                    error: None,
+                })
+                .collect();
+            return (Some(merged), outcomes);
+        }
+    }
+
+    let (content, placements) = apply_hunks(&current, &file.hunks, true);
+    let outcomes = file
+        .hunks
+        .iter()
+        .zip(&placements)
+        .enumerate()
+        .map(|(i, (h, placement))| HunkOutcome {
+            file: file.name().to_string(),
+            hunk: i + 1,
+            header: h.header.clone(),
+            strategy: placement.as_ref().ok().map(|_| ApplyStrategy::Fuzzy),
+            offset: placement.as_ref().map_or(0, |p| p.offset),
+            ignored_whitespace: placement.as_ref().is_ok_and(|p| p.ignored_whitespace),
+            error: placement.as_ref().err().cloned(),
+        })
+        .collect();
+    let applied = placements.iter().all(Result::is_ok);
+    (applied.then_some(content), outcomes)
+}
+
+/// Apply the hunks to the base commit's version of the file and merge that
+/// result into the current file. `None` if either step fails or conflicts.
+fn three_way(
+    repo_root: &Path,
+    base_commit: &str,
+    path: &str,
+    current: &str,
+    hunks: &[Hunk],
+) -> Option<String> {
+    let show = Command::new("git")
+        .arg("show")
+        .arg(format!("{base_commit}:{path}"))
+        .current_dir(repo_root)
+        .output()
+        .ok()?;
+    if !show.status.success() {
+        return None;
+    }
+    let base = String::from_utf8(show.stdout).ok()?;
+    let (theirs, placements) = apply_hunks(&base, hunks, false);
+    if placements.iter().any(Result::is_err) {
+        return None;
+    }
+    if theirs == current {
+        return Some(theirs);
+    }
+    merge_file(current, &base, &theirs)
+}
+
+static MERGE_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// `git merge-file` on temporary copies; `None` when the merge conflicts
+fn merge_file(ours: &str, base: &str, theirs: &str) -> Option<String> {
+    let dir = std::env::temp_dir().join(format!(
+        "vk-merge-{}-{}",
+        std::process::id(),
+        MERGE_SEQ.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).ok()?;
+    let merged = (|| {
+        fs::write(dir.join("ours"), ours).ok()?;
+        fs::write(dir.join("base"), base).ok()?;
+        fs::write(dir.join("theirs"), theirs).ok()?;
+        let out = Command::new("git")
+            .args(["merge-file", "-p", "ours", "base", "theirs"])
+            .current_dir(&dir)
+            .output()
+            .ok()?;
+        // Exit code is the number of conflicts, negative on error
+        if out.status.code() != Some(0) {
+            return None;
+        }
+        String::from_utf8(out.stdout).ok()
+    })();
+    let _ = fs::remove_dir_all(&dir);
+    merged
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Placement {
+    offset: i64,
+    ignored_whitespace: bool,
+}
+
+fn split_lines(text: &str) -> (Vec<String>, bool) {
+    if text.is_empty() {
+        return (Vec::new(), true);
+    }
+    let mut lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+    let eol = text.ends_with('\n');
+    if eol {
+        lines.pop();
+    }
+    (lines, eol)
+}
+
+fn same_ignoring_whitespace(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
+
+/// Start of the window of `lines` matching `old`, searching outward from
+/// `expected` and never before `min_start`
+fn find_window(
+    lines: &[String],
+    old: &[&str],
+    expected: usize,
+    min_start: usize,
+    eq: fn(&str, &str) -> bool,
+) -> Option<usize> {
+    let last = lines.len().checked_sub(old.len())?;
+    if min_start > last {
+        return None;
+    }
+    let expected = expected.clamp(min_start, last);
+    let matches = |pos: usize| old.iter().zip(&lines[pos..]).all(|(o, l)| eq(o, l));
+    for distance in 0..=(last - min_start) {
+        if let Some(pos) = expected.checked_sub(distance).filter(|p| *p >= min_start) {
+            if matches(pos) {
+                return Some(pos);
+            }
+        }
+        let pos = expected + distance;
+        if distance > 0 && pos <= last && matches(pos) {
+            return Some(pos);
+        }
+    }
+    None
+}
 
-    Ok(ApplyResult { touched_files: files })
+/// Apply hunks in order to `text`. Each hunk is placed at the nearest offset
+/// where its context and removed lines match exactly, else (with `fuzzy`)
+/// where they match ignoring whitespace. Rejected hunks are skipped.
+fn apply_hunks(
+    text: &str,
+    hunks: &[Hunk],
+    fuzzy: bool,
+) -> (String, Vec<Result<Placement, String>>) {
+    let (mut lines, mut eol) = split_lines(text);
+    let mut placements = Vec::new();
+    // Lines added minus lines removed by the hunks applied so far
+    let mut delta: i64 = 0;
+    let mut min_start = 0;
+    for hunk in hunks {
+        let old = hunk.old_lines();
+        // `@@ -0,0` means "before the first line"
+        let stated = hunk.old_start.saturating_sub(1) as i64 + delta;
+        let expected = stated.max(0) as usize;
+
+        let found = if old.is_empty() {
+            Some((expected.clamp(min_start, lines.len().max(min_start)), false))
+        } else {
+            find_window(&lines, &old, expected, min_start, |a, b| a == b)
+                .map(|pos| (pos, false))
+                .or_else(|| {
+                    fuzzy
+                        .then(|| {
+                            find_window(&lines, &old, expected, min_start, same_ignoring_whitespace)
+                        })
+                        .flatten()
+                        .map(|pos| (pos, true))
+                })
+        };
+        let Some((pos, ignored_whitespace)) = found else {
+            placements.push(Err(format!(
+                "no match for {} line(s) of context near line {}",
+                old.len(),
+                hunk.old_start
+            )));
+            continue;
+        };
+
+        // Context keeps the file's own text so ignored whitespace is not rewritten
+        let mut replacement = Vec::new();
+        let mut cursor = pos;
+        for line in &hunk.lines {
+            match line {
+                HunkLine::Context(_) => {
+                    replacement.push(lines[cursor].clone());
+                    cursor += 1;
+                }
+                HunkLine::Remove(_) => cursor += 1,
+                HunkLine::Add(s) => replacement.push(s.clone()),
+            }
+        }
+        if cursor == lines.len() {
+            eol = !hunk.new_missing_eol;
+        }
+        let added = replacement.len();
+        lines.splice(pos..cursor, replacement);
+        placements.push(Ok(Placement {
+            offset: pos as i64 - stated,
+            ignored_whitespace,
+        }));
+        delta += added as i64 - old.len() as i64;
+        min_start = pos + added;
+    }
+    let mut out = lines.join("\n");
+    if eol && !lines.is_empty() {
+        out.push('\n');
+    }
+    (out, placements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_file(diff: &str) -> FileDiff {
+        let (mut files, _) = parse_diff(diff).unwrap();
+        assert_eq!(files.len(), 1);
+        files.remove(0)
+    }
+
+    #[test]
+    fn parses_prefixed_and_bare_headers() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\nindex 1..2 100644\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n fn a() {}\n-fn b() {}\n+fn c() {}\n--- README.md\n+++ README.md\n@@ -3 +3 @@\n-old\n+new\n";
+        let (files, prefixed) = parse_diff(diff).unwrap();
+        assert!(prefixed);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].new.as_deref(), Some("src/lib.rs"));
+        assert!(files[0].header.contains("index 1..2"));
+        assert_eq!(files[1].old.as_deref(), Some("README.md"));
+        assert_eq!(files[1].hunks[0].old_start, 3);
+        assert_eq!(touched_files(diff), vec!["README.md", "src/lib.rs"]);
+    }
+
+    #[test]
+    fn hunk_without_file_is_an_error() {
+        let err = parse_diff("@@ -1 +1 @@\n-a\n+b\n").unwrap_err();
+        assert!(err.starts_with("line 1:"), "{err}");
+    }
+
+    #[test]
+    fn unsafe_header_paths_are_errors_not_deletions() {
+        let err = parse_diff("--- a/src/lib.rs\n+++ b/../../x\n@@ -1 +1 @@\n-a\n+b\n").unwrap_err();
+        assert_eq!(err, "line 2: unsafe path `b/../../x`");
+        let err =
+            parse_diff("--- /etc/passwd\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n").unwrap_err();
+        assert_eq!(err, "line 1: unsafe path `/etc/passwd`");
+
+        let (files, _) = parse_diff("--- a/gone.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-a\n").unwrap();
+        assert_eq!(files[0].old.as_deref(), Some("gone.rs"));
+        assert!(files[0].new.is_none());
+    }
+
+    #[test]
+    fn applies_at_an_offset() {
+        let file = single_file("--- a/f\n+++ b/f\n@@ -1,3 +1,3 @@\n two\n-three\n+THREE\n four\n");
+        let (out, placements) = apply_hunks("zero\none\ntwo\nthree\nfour\n", &file.hunks, false);
+        assert_eq!(out, "zero\none\ntwo\nTHREE\nfour\n");
+        assert_eq!(
+            placements,
+            vec![Ok(Placement {
+                offset: 2,
+                ignored_whitespace: false
+            })]
+        );
+    }
+
+    #[test]
+    fn fuzzy_ignores_whitespace_but_keeps_the_files_context() {
+        let file = single_file(
+            "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n fn main() {\n-  old();\n+    new();\n",
+        );
+        let text = "fn  main()  {\n    old();\n}\n";
+        let (_, strict) = apply_hunks(text, &file.hunks, false);
+        assert!(strict[0].is_err());
+        let (out, placements) = apply_hunks(text, &file.hunks, true);
+        assert_eq!(out, "fn  main()  {\n    new();\n}\n");
+        assert!(placements[0].as_ref().unwrap().ignored_whitespace);
+    }
+
+    #[test]
+    fn later_hunks_account_for_earlier_ones() {
+        let file = single_file(
+            "--- a/f\n+++ b/f\n@@ -1,2 +1,3 @@\n a\n+a2\n b\n@@ -4,2 +5,2 @@\n d\n-e\n+E\n",
+        );
+        let (out, placements) = apply_hunks("a\nb\nc\nd\ne\n", &file.hunks, false);
+        assert_eq!(out, "a\na2\nb\nc\nd\nE\n");
+        assert!(placements
+            .iter()
+            .all(|p| p.as_ref().is_ok_and(|p| p.offset == 0)));
+    }
+
+    #[test]
+    fn unmatched_hunk_is_rejected_and_others_still_apply() {
+        let file =
+            single_file("--- a/f\n+++ b/f\n@@ -1 +1 @@\n-missing\n+x\n@@ -2 +2 @@\n-b\n+B\n");
+        let (out, placements) = apply_hunks("a\nb\n", &file.hunks, true);
+        assert!(placements[0].as_ref().unwrap_err().contains("near line 1"));
+        assert!(placements[1].is_ok());
+        assert_eq!(out, "a\nB\n");
+    }
+
+    #[test]
+    fn creates_files_and_honours_missing_newline() {
+        let file = single_file("--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+hello\n+world\n\\ No newline at end of file\n");
+        assert_eq!(file.old, None);
+        let (out, placements) = apply_hunks("", &file.hunks, true);
+        assert!(placements[0].is_ok());
+        assert_eq!(out, "hello\nworld");
+    }
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("vk-apply-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn block(changes: Vec<(&str, FileChange)>) -> PatchBlock {
+        PatchBlock {
+            format: crate::patch::PatchFormat::WholeFile,
+            line: 1,
+            changes: changes
+                .into_iter()
+                .map(|(path, change)| PatchChange {
+                    path: path.to_string(),
+                    change,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn a_block_whose_second_change_is_rejected_leaves_no_trace() {
+        let dir = scratch_dir("rejected");
+        fs::write(dir.join("a.txt"), "a\n").unwrap();
+        fs::write(dir.join("b.txt"), "b\n").unwrap();
+        let block = block(vec![
+            (
+                "a.txt",
+                FileChange::Write {
+                    content: "A\n".to_string(),
+                },
+            ),
+            (
+                "new.txt",
+                FileChange::Write {
+                    content: "new\n".to_string(),
+                },
+            ),
+            (
+                "b.txt",
+                FileChange::Edit {
+                    unified_diff: "--- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-missing\n+B\n"
+                        .to_string(),
+                    has_line_numbers: true,
+                },
+            ),
+        ]);
+
+        let results = apply_changes(&dir, &block, None).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(!results[2].applied());
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a\n");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b\n");
+        assert!(!dir.join("new.txt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_block_whose_second_change_errors_leaves_no_trace() {
+        let dir = scratch_dir("errors");
+        fs::write(dir.join("a.txt"), "a\n").unwrap();
+        let block = block(vec![
+            (
+                "a.txt",
+                FileChange::Rename {
+                    new_path: "moved.txt".to_string(),
+                },
+            ),
+            ("gone.txt", FileChange::Delete),
+        ]);
+
+        let err = apply_changes(&dir, &block, None).unwrap_err();
+        assert!(err.contains("gone.txt"), "{err}");
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "a\n");
+        assert!(!dir.join("moved.txt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
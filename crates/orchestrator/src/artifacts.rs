@@ -1,63 +1,98 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// File name of the agent's reply within a run's artifacts directory
+pub const AGENT_OUTPUT_FILE: &str = "agent_output.txt";
 
 pub struct Artifacts;
 
 impl Artifacts {
+    /// Directory of one run inside an attempt's artifacts directory, so earlier
+    /// runs of the same attempt are kept rather than overwritten
+    pub fn run_dir(attempt_dir: &Path, run_id: &str) -> PathBuf { attempt_dir.join(run_id) }
+
     pub fn ensure_dir(dir: &Path) -> Result<(), String> { fs::create_dir_all(dir).map_err(|e| e.to_string())?; Ok(()) }
 
-    pub fn write_touched_files(dir: &Path, files: &[String]) -> Result<(), String> {
+    /// Inputs of the run and their hash, see `manifest::RunManifest`
+    pub fn write_run_manifest_json(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
+        let path = dir.join(crate::manifest::MANIFEST_FILE);
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    /// How a replay compares to the run it re-executed, see `replay::ReplayReport`
+    pub fn write_replay_report_json(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
+        let path = dir.join("replay_report.json");
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    /// The agent's reply the patch blocks were parsed from, so a replay can apply it again
+    pub fn write_agent_output(dir: &Path, text: &str) -> Result<PathBuf, String> {
+        let path = dir.join(AGENT_OUTPUT_FILE);
+        fs::write(&path, text).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    /// Per-hunk outcome of every patch block applied, see `apply::ApplyResult`
+    pub fn write_apply_report_json(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
+        let path = dir.join("apply_report.json");
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    /// The hunks that could not be applied, as a diff a fix phase can be shown
+    pub fn write_rejected_hunks(dir: &Path, patch: &str) -> Result<PathBuf, String> {
+        let path = dir.join("rejected_hunks.patch");
+        fs::write(&path, patch).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    pub fn write_touched_files(dir: &Path, files: &[String]) -> Result<PathBuf, String> {
         let path = dir.join("touched_files.txt");
         let content = files.join("\n");
-        fs::write(path, content).map_err(|e| e.to_string())?; Ok(())
+        fs::write(&path, content).map_err(|e| e.to_string())?; Ok(path)
     }
 
-    pub fn write_dep_snapshot(dir: &Path, snapshot: &str) -> Result<(), String> {
+    pub fn write_dep_snapshot(dir: &Path, snapshot: &str) -> Result<PathBuf, String> {
         let path = dir.join("dep_snapshot.txt");
-        fs::write(path, snapshot).map_err(|e| e.to_string())?; Ok(())
+        fs::write(&path, snapshot).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    pub fn write_dep_diff_json(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
+        let path = dir.join("dep_diff.json");
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
     }
 
-    pub fn write_kpi_json_raw(dir: &Path, json_bytes: &[u8]) -> Result<(), String> {
+    pub fn write_api_diff_json(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
+        let path = dir.join("api_diff.json");
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    pub fn write_test_report_json(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
+        let path = dir.join("test_report.json");
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    pub fn write_kpi_json_raw(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
         let path = dir.join("kpi.json");
-        fs::write(path, json_bytes).map_err(|e| e.to_string())?; Ok(())
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
     }
 
-    pub fn write_snippets_log(dir: &Path, content: &str) -> Result<(), String> {
+    pub fn write_snippets_log(dir: &Path, content: &str) -> Result<PathBuf, String> {
         let path = dir.join("snippets.log");
-        fs::write(path, content).map_err(|e| e.to_string())?; Ok(())
+        fs::write(&path, content).map_err(|e| e.to_string())?; Ok(path)
     }
 
-    pub fn write_summary_json(dir: &Path, json_bytes: &[u8]) -> Result<(), String> {
+    pub fn write_summary_json(dir: &Path, json_bytes: &[u8]) -> Result<PathBuf, String> {
         let path = dir.join("summary.json");
-        fs::write(path, json_bytes).map_err(|e| e.to_string())?; Ok(())
+        fs::write(&path, json_bytes).map_err(|e| e.to_string())?; Ok(path)
     }
-}
 
-pub fn simple_dep_snapshot(workdir: &Path) -> String {
-    let mut out = String::new();
-    let cargo_lock = workdir.join("Cargo.lock");
-    if let Ok(text) = fs::read_to_string(&cargo_lock) {
-        for line in text.lines() {
-            if line.starts_with("name = \"") {
-                out.push_str(line);
-                out.push('\n');
-            }
-            if line.starts_with("version = \"") {
-                out.push_str(line);
-                out.push('\n');
-            }
-        }
-    }
-    let pkg_json = workdir.join("package.json");
-    if let Ok(text) = fs::read_to_string(&pkg_json) {
-        out.push_str("\n[package.json deps]\n");
-        if let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) {
-            if let Some(obj) = v.get("dependencies").and_then(|d| d.as_object()) {
-                for (k, _) in obj.iter() {
-                    out.push_str(k);
-                    out.push('\n');
-                }
-            }
-        }
-    }
-    out
+    /// Why a run stopped before producing a summary; removed when the next run starts
+    pub fn write_failure_txt(dir: &Path, message: &str) -> Result<PathBuf, String> {
+        let path = dir.join("failure.txt");
+        fs::write(&path, message).map_err(|e| e.to_string())?; Ok(path)
+    }
+
+    pub fn clear_failure_txt(dir: &Path) {
+        let _ = fs::remove_file(dir.join("failure.txt"));
+    }
 }
@@ -0,0 +1,172 @@
+use std::{fs, path::Path};
+
+use serde_json::Value;
+
+/// Caps that keep a fix prompt focused on the failures rather than the whole log.
+const MAX_FAILED_TESTS: usize = 50;
+const MAX_SNIPPET_BYTES: usize = 4 * 1024;
+
+/// Describe why the run whose artifacts live in `artifacts_dir` failed, for the
+/// prompt of the phase that follows it. `None` when the run passed or left
+/// nothing to report.
+pub fn failure_feedback(artifacts_dir: &Path) -> Option<String> {
+    if let Ok(error) = fs::read_to_string(artifacts_dir.join("failure.txt")) {
+        if !error.trim().is_empty() {
+            let rejected = fs::read_to_string(artifacts_dir.join("rejected_hunks.patch")).ok();
+            return Some(stopped(&error, rejected.as_deref()));
+        }
+    }
+    let summary: Value =
+        serde_json::from_str(&fs::read_to_string(artifacts_dir.join("summary.json")).ok()?).ok()?;
+    let test_report = fs::read_to_string(artifacts_dir.join("test_report.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<Value>(&s).ok());
+    let snippets = fs::read_to_string(artifacts_dir.join("snippets.log")).ok();
+    build(&summary, test_report.as_ref(), snippets.as_deref())
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_SNIPPET_BYTES {
+        let mut end = MAX_SNIPPET_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[truncated]");
+    }
+    text
+}
+
+fn stopped(error: &str, rejected_hunks: Option<&str>) -> String {
+    let mut out = format!(
+        "The previous run stopped before validation: {}\n",
+        error.trim()
+    );
+    if let Some(rejected) = rejected_hunks
+        .map(str::trim_end)
+        .filter(|r| !r.trim().is_empty())
+    {
+        out.push_str("\nThese hunks did not apply; regenerate them against the current files:\n");
+        out.push_str(&truncate(rejected.to_string()));
+        out.push('\n');
+    }
+    out
+}
+
+fn build(summary: &Value, test_report: Option<&Value>, snippets: Option<&str>) -> Option<String> {
+    let validators = summary.get("validator")?.as_object()?;
+    // `null` means the validator was skipped, which is not a failure
+    let failing: Vec<&str> = validators
+        .iter()
+        .filter(|(_, v)| v.as_bool() == Some(false))
+        .map(|(k, _)| k.as_str())
+        .collect();
+    let failed_tests: Vec<&str> = test_report
+        .and_then(|r| r.pointer("/warm/results"))
+        .and_then(Value::as_object)
+        .map(|results| {
+            results
+                .iter()
+                .filter(|(_, outcome)| outcome.as_str() == Some("failed"))
+                .map(|(name, _)| name.as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    if failing.is_empty() && failed_tests.is_empty() {
+        return None;
+    }
+
+    let mut out = if failing.is_empty() {
+        "The previous run failed its tests\n".to_string()
+    } else {
+        format!(
+            "The previous run failed these validators: {}\n",
+            failing.join(", ")
+        )
+    };
+
+    if !failed_tests.is_empty() {
+        out.push_str("\nFailing tests:\n");
+        for name in failed_tests.iter().take(MAX_FAILED_TESTS) {
+            out.push_str(&format!("- {name}\n"));
+        }
+        if failed_tests.len() > MAX_FAILED_TESTS {
+            out.push_str(&format!(
+                "- ... and {} more\n",
+                failed_tests.len() - MAX_FAILED_TESTS
+            ));
+        }
+    }
+
+    // The first line of snippets.log is the metadata header; the rest are validator messages
+    let messages: Vec<&str> = snippets
+        .unwrap_or_default()
+        .lines()
+        .skip(1)
+        .map(str::trim_end)
+        .filter(|l| !l.trim().is_empty())
+        .collect();
+    if !messages.is_empty() {
+        let text = truncate(messages.join("\n"));
+        out.push_str("\nValidator output:\n");
+        out.push_str(&text);
+        out.push('\n');
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn lists_failing_validators_and_tests() {
+        let summary = json!({"validator": {"scope": true, "dep": true, "api": null, "det": false, "kpi": true}});
+        let report = json!({"warm": {"results": {"a::ok": "passed", "a::broken": "failed"}}});
+        let snippets = "ALGO_VERSION=P3\nscope ok\nDET: fingerprints differ\n";
+        let text = build(&summary, Some(&report), Some(snippets)).unwrap();
+        assert!(text.contains("failed these validators: det\n"));
+        assert!(text.contains("- a::broken\n"));
+        assert!(!text.contains("a::ok"));
+        assert!(text.contains("DET: fingerprints differ"));
+        assert!(!text.contains("ALGO_VERSION"));
+    }
+
+    #[test]
+    fn rejected_hunks_follow_the_failure() {
+        let text = stopped(
+            "patch block 1 did not apply: 1 hunk(s) rejected\n",
+            Some("--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n"),
+        );
+        assert!(text.starts_with(
+            "The previous run stopped before validation: patch block 1 did not apply"
+        ));
+        assert!(text.contains("did not apply; regenerate them"));
+        assert!(text.ends_with("+b\n"));
+        assert_eq!(
+            stopped("agent timed out", Some("")),
+            "The previous run stopped before validation: agent timed out\n"
+        );
+    }
+
+    #[test]
+    fn failed_tests_alone_produce_feedback() {
+        let summary = json!({"validator": {"scope": true, "dep": true, "api": true, "det": true, "kpi": true}});
+        let report = json!({"warm": {"results": {"a::ok": "passed", "a::broken": "failed"}}});
+        let text = build(&summary, Some(&report), None).unwrap();
+        assert!(text.starts_with("The previous run failed its tests\n"));
+        assert!(text.contains("- a::broken\n"));
+
+        let summary = json!({"validator": {"scope": true, "tests": false}});
+        let text = build(&summary, Some(&report), None).unwrap();
+        assert!(text.starts_with("The previous run failed these validators: tests\n"));
+    }
+
+    #[test]
+    fn passing_run_has_no_feedback() {
+        let summary = json!({"validator": {"scope": true, "dep": true, "api": null, "det": true, "kpi": true}});
+        assert_eq!(build(&summary, None, Some("header\nall good\n")), None);
+    }
+}
@@ -1,26 +1,111 @@
+pub mod agent;
 pub mod prompt;
 pub mod patch;
 pub mod apply;
 pub mod test;
+pub mod test_report;
 pub mod artifacts;
+pub mod feedback;
 pub mod run;
+pub mod manifest;
+pub mod replay;
+pub mod process;
+pub mod record;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use command_group::AsyncGroupChild;
+use tokio::sync::RwLock;
 
 #[derive(Clone, Default)]
 pub struct OrchestratorConfig {
 	pub cache_dir: std::path::PathBuf,
 	pub artifacts_dir: std::path::PathBuf,
+	pub dep_policy: validators::dep_diff::DepPolicy,
+	pub scope: validators::scope_guard::ScopePolicy,
+	pub test: test::TestConfig,
+	/// Branch the attempt forked from; patches that no longer apply cleanly are merged against it
+	pub base_branch: Option<String>,
+	/// Warm-run budget for the KPI validator; `None` uses `validators::kpi::DEFAULT_WARM_BUDGET_SEC`
+	pub warm_kpi_budget: Option<f64>,
 }
 
+#[async_trait]
 pub trait AgentAdapter: Send + Sync {
-	fn get_patch_text(&self) -> Result<String, String>;
+	async fn get_patch_text(&self, hooks: &dyn RunHooks) -> Result<String, String>;
+	/// The agent and prompt to record in the run manifest
+	fn manifest(&self) -> manifest::AgentManifest {
+		manifest::AgentManifest::default()
+	}
 }
 
-pub struct NullAgentAdapter;
-impl AgentAdapter for NullAgentAdapter {
-	fn get_patch_text(&self) -> Result<String, String> {
-		let path = std::env::var("VK_FAKE_PATCH_PATH")
-			.map_err(|_| "VK_FAKE_PATCH_PATH not set".to_string())?;
-		std::fs::read_to_string(path).map_err(|e| e.to_string())
+/// Callbacks through which the host observes a run and controls its child processes.
+#[async_trait]
+pub trait RunHooks: Send + Sync {
+	/// Progress line for the run log, e.g. `ORCH: applying 2 patch blocks`
+	fn log(&self, line: &str);
+	/// Take ownership of a spawned child so the host can kill it while it runs
+	async fn track_child(&self, child: AsyncGroupChild) -> Arc<RwLock<AsyncGroupChild>>;
+	/// The most recently tracked child has exited
+	async fn untrack_child(&self);
+	/// Whether the run has been stopped by the user
+	async fn is_cancelled(&self) -> bool;
+	/// An artifact file was written; `kind` is its file name
+	async fn record_artifact(&self, _kind: &str, _path: &std::path::Path) {}
+	/// Timings and validator verdicts of a finished run
+	async fn record_summary(&self, _summary: &run::RunSummary) {}
+	/// Tokens one turn of the run's coding agent consumed
+	async fn record_usage(&self, _usage: &utils::log_msg::TokenUsage) {}
+}
+
+/// Hooks for runs without a host: progress goes to stdout, nothing can cancel.
+pub struct StandaloneHooks;
+
+#[async_trait]
+impl RunHooks for StandaloneHooks {
+	fn log(&self, line: &str) {
+		println!("{line}");
+	}
+
+	async fn track_child(&self, child: AsyncGroupChild) -> Arc<RwLock<AsyncGroupChild>> {
+		Arc::new(RwLock::new(child))
+	}
+
+	async fn untrack_child(&self) {}
+
+	async fn is_cancelled(&self) -> bool {
+		false
+	}
+}
+
+/// Reads the agent's reply from a file instead of running an agent, e.g. the
+/// `agent_output.txt` of a run being replayed
+pub struct PatchFileAdapter {
+	pub path: std::path::PathBuf,
+	/// The agent whose reply the file holds, recorded in the run manifest
+	pub agent: manifest::AgentManifest,
+}
+
+impl PatchFileAdapter {
+	/// The canned patch named by `VK_FAKE_PATCH_PATH`, for demos and tests
+	pub fn fake() -> Option<Self> {
+		std::env::var_os("VK_FAKE_PATCH_PATH").map(|path| Self {
+			path: path.into(),
+			agent: manifest::AgentManifest::default(),
+		})
+	}
+}
+
+#[async_trait]
+impl AgentAdapter for PatchFileAdapter {
+	async fn get_patch_text(&self, hooks: &dyn RunHooks) -> Result<String, String> {
+		hooks.log(&format!("ORCH: patch file {}", self.path.display()));
+		std::fs::read_to_string(&self.path).map_err(|e| format!("{}: {e}", self.path.display()))
+	}
+
+	fn manifest(&self) -> manifest::AgentManifest {
+		self.agent.clone()
 	}
 }
 
@@ -0,0 +1,322 @@
+use std::{collections::BTreeMap, fs, path::Path, process::Command};
+
+use executors::{env::AgentEnv, executors::CodingAgent, profile::ProfileVariantLabel};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use validators::{dep_diff::DepPolicy, scope_guard::ScopePolicy};
+
+use crate::{test::TestConfig, OrchestratorConfig};
+
+/// Version of the run algorithm, bumped whenever the pipeline changes what a run does
+pub const ALGO_VERSION: &str = "P3";
+
+/// File name of the manifest within a run's artifacts directory
+pub const MANIFEST_FILE: &str = "run_manifest.json";
+
+/// Which agent a run asked for and how it is launched.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentManifest {
+    pub profile_variant: Option<ProfileVariantLabel>,
+    /// The resolved agent configuration, including flags such as plan mode
+    pub agent: Option<CodingAgent>,
+    /// Command line built by the agent's `CommandBuilder`
+    pub command: Option<String>,
+    /// Environment the variant resolves to, with secret values masked
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+    pub prompt: Option<String>,
+}
+
+impl AgentManifest {
+    pub fn for_profile(profile_variant: &ProfileVariantLabel, prompt: &str) -> Self {
+        let agent = CodingAgent::from_profile_variant_label(profile_variant).ok();
+        Self {
+            profile_variant: Some(profile_variant.clone()),
+            command: agent.as_ref().map(|a| a.command().build_initial()),
+            agent,
+            env: AgentEnv::from_profile_variant_label(profile_variant)
+                .map(|env| env.redacted_vars())
+                .unwrap_or_default(),
+            prompt: Some(prompt.to_string()),
+        }
+    }
+}
+
+/// Phase rules a run is validated against.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseManifest {
+    pub scope: ScopePolicy,
+    pub dep_policy: DepPolicy,
+    pub warm_kpi_budget: Option<f64>,
+}
+
+/// Everything that influences the outcome of a run. Host paths such as the
+/// cache and artifacts directories are left out, so the same inputs hash the
+/// same on any machine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub algo_version: String,
+    pub agent: AgentManifest,
+    pub phase: PhaseManifest,
+    pub test: TestConfig,
+    /// `rustc`, `cargo`, `node` and `git` versions; tools that are not installed are left out
+    pub toolchain: BTreeMap<String, String>,
+    pub base_branch: Option<String>,
+    /// `HEAD` of the worktree when the run started
+    pub base_commit: Option<String>,
+    /// The worktree had uncommitted changes, so `base_commit` alone does not reproduce it
+    pub dirty: bool,
+}
+
+/// On-disk form of `run_manifest.json`
+#[derive(Serialize, Deserialize)]
+struct ManifestFile {
+    config_sha256: String,
+    manifest: RunManifest,
+}
+
+fn first_line(out: std::process::Output) -> Option<String> {
+    if !out.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(out.stdout).ok()?;
+    text.lines()
+        .next()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+}
+
+fn git(workdir: &Path, args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .arg("-C")
+        .arg(workdir)
+        .args(args)
+        .output()
+        .ok()
+        .and_then(first_line)
+}
+
+/// Versions of the tools a run in `workdir` builds and tests with; asked from
+/// inside it so toolchain files such as `rust-toolchain.toml` are honoured
+pub fn toolchain_versions(workdir: &Path) -> BTreeMap<String, String> {
+    ["rustc", "cargo", "node", "git"]
+        .into_iter()
+        .filter_map(|tool| {
+            let out = Command::new(tool)
+                .arg("--version")
+                .current_dir(workdir)
+                .output()
+                .ok()?;
+            first_line(out).map(|v| (tool.to_string(), v))
+        })
+        .collect()
+}
+
+/// Compact JSON with object keys sorted at every level, so equal values
+/// always serialize to the same bytes regardless of field or insertion order
+pub fn canonical_json(value: &Value) -> String {
+    fn write(value: &Value, out: &mut String) {
+        match value {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                out.push('{');
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&Value::String(key.clone()).to_string());
+                    out.push(':');
+                    write(&map[key], out);
+                }
+                out.push('}');
+            }
+            Value::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write(item, out);
+                }
+                out.push(']');
+            }
+            other => out.push_str(&other.to_string()),
+        }
+    }
+    let mut out = String::new();
+    write(value, &mut out);
+    out
+}
+
+/// Dotted paths at which two JSON values differ, e.g. `toolchain.rustc` or `test.env[1]`
+pub fn differences(a: &Value, b: &Value) -> Vec<String> {
+    fn walk(a: &Value, b: &Value, path: &str, out: &mut Vec<String>) {
+        match (a, b) {
+            (Value::Object(x), Value::Object(y)) => {
+                let mut keys: Vec<&String> = x.keys().chain(y.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    let sub = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{path}.{key}")
+                    };
+                    walk(
+                        x.get(key).unwrap_or(&Value::Null),
+                        y.get(key).unwrap_or(&Value::Null),
+                        &sub,
+                        out,
+                    );
+                }
+            }
+            (Value::Array(x), Value::Array(y)) if x.len() == y.len() => {
+                for (i, (p, q)) in x.iter().zip(y).enumerate() {
+                    walk(p, q, &format!("{path}[{i}]"), out);
+                }
+            }
+            _ if a != b => out.push(path.to_string()),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    walk(a, b, "", &mut out);
+    out
+}
+
+impl RunManifest {
+    /// Record the inputs of a run about to start in `workdir`
+    pub fn capture(cfg: &OrchestratorConfig, workdir: &Path, agent: AgentManifest) -> Self {
+        Self {
+            algo_version: ALGO_VERSION.to_string(),
+            agent,
+            phase: PhaseManifest {
+                scope: cfg.scope.clone(),
+                dep_policy: cfg.dep_policy.clone(),
+                warm_kpi_budget: cfg.warm_kpi_budget,
+            },
+            test: cfg.test.clone(),
+            toolchain: toolchain_versions(workdir),
+            base_branch: cfg.base_branch.clone(),
+            base_commit: git(workdir, &["rev-parse", "HEAD"]),
+            dirty: git(workdir, &["status", "--porcelain"]).is_some(),
+        }
+    }
+
+    /// SHA-256 of the manifest's canonical JSON, as lowercase hex
+    pub fn sha256(&self) -> Result<String, String> {
+        let value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        Ok(format!(
+            "{:x}",
+            Sha256::digest(canonical_json(&value).as_bytes())
+        ))
+    }
+
+    /// Configuration that runs with the same rules, writing into the given directories
+    pub fn config(&self, cache_dir: &Path, artifacts_dir: &Path) -> OrchestratorConfig {
+        OrchestratorConfig {
+            cache_dir: cache_dir.to_path_buf(),
+            artifacts_dir: artifacts_dir.to_path_buf(),
+            dep_policy: self.phase.dep_policy.clone(),
+            scope: self.phase.scope.clone(),
+            test: self.test.clone(),
+            base_branch: self.base_branch.clone(),
+            warm_kpi_budget: self.phase.warm_kpi_budget,
+        }
+    }
+
+    /// Pretty JSON of the manifest together with its hash
+    pub fn to_json(&self) -> Result<Vec<u8>, String> {
+        let file = ManifestFile {
+            config_sha256: self.sha256()?,
+            manifest: self.clone(),
+        };
+        serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())
+    }
+
+    /// Read the manifest a run wrote into `dir`
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let bytes =
+            fs::read(dir.join(MANIFEST_FILE)).map_err(|e| format!("{MANIFEST_FILE}: {e}"))?;
+        let file: ManifestFile =
+            serde_json::from_slice(&bytes).map_err(|e| format!("{MANIFEST_FILE}: {e}"))?;
+        Ok(file.manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> RunManifest {
+        RunManifest {
+            algo_version: ALGO_VERSION.to_string(),
+            agent: AgentManifest {
+                prompt: Some("fix the bug".into()),
+                ..Default::default()
+            },
+            phase: PhaseManifest::default(),
+            test: TestConfig::default(),
+            toolchain: BTreeMap::from([("rustc".to_string(), "rustc 1.89.0".to_string())]),
+            base_branch: Some("main".into()),
+            base_commit: Some("0123abcd".into()),
+            dirty: false,
+        }
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys_at_every_level() {
+        let a: Value =
+            serde_json::from_str(r#"{"b": 1, "a": {"y": [2, {"d": 1, "c": 0}], "x": "é"}}"#)
+                .unwrap();
+        let b: Value =
+            serde_json::from_str(r#"{"a": {"x": "é", "y": [2, {"c": 0, "d": 1}]}, "b": 1}"#)
+                .unwrap();
+        assert_eq!(
+            canonical_json(&a),
+            r#"{"a":{"x":"é","y":[2,{"c":0,"d":1}]},"b":1}"#
+        );
+        assert_eq!(canonical_json(&a), canonical_json(&b));
+    }
+
+    #[test]
+    fn hash_changes_with_any_input() {
+        let base = manifest();
+        let hash = base.sha256().unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, manifest().sha256().unwrap());
+
+        let mut other = manifest();
+        other
+            .toolchain
+            .insert("rustc".into(), "rustc 1.90.0".into());
+        assert_ne!(other.sha256().unwrap(), hash);
+        let mut other = manifest();
+        other
+            .agent
+            .env
+            .push(("ANTHROPIC_BASE_URL".into(), "https://proxy.local".into()));
+        assert_ne!(other.sha256().unwrap(), hash);
+        let mut other = manifest();
+        other.test.env.push(("RUST_LOG".into(), "debug".into()));
+        assert_ne!(other.sha256().unwrap(), hash);
+        assert_eq!(
+            differences(
+                &serde_json::to_value(&base).unwrap(),
+                &serde_json::to_value(&other).unwrap()
+            ),
+            vec!["test.env".to_string()]
+        );
+    }
+
+    #[test]
+    fn manifest_round_trips_through_its_file() {
+        let dir = std::env::temp_dir().join(format!("vk-manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(MANIFEST_FILE), manifest().to_json().unwrap()).unwrap();
+        assert_eq!(RunManifest::load(&dir).unwrap(), manifest());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
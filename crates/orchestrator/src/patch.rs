@@ -1,19 +1,644 @@
+use executors::logs::FileChange;
+
+use crate::apply;
+
+const BEGIN_MARKER: &str = "---BEGIN PATCH---";
+const END_MARKER: &str = "---END PATCH---";
+const CODEX_BEGIN: &str = "*** Begin Patch";
+const CODEX_END: &str = "*** End Patch";
+
+/// The shape an agent wrote its changes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    /// Unified diff between `---BEGIN PATCH---` and `---END PATCH---`
+    Markers,
+    /// `*** Begin Patch` envelope with `*** Add/Update/Delete File:` sections
+    Codex,
+    /// Unified diff in a ```` ```diff ```` fence
+    FencedDiff,
+    /// Complete file contents in a fence whose info string names the path
+    WholeFile,
+}
+
+/// One change to one file, whatever format it arrived in
+#[derive(Debug, Clone)]
+pub struct PatchChange {
+    pub path: String,
+    pub change: FileChange,
+}
+
 #[derive(Debug, Clone)]
 pub struct PatchBlock {
-    pub content: String,
+    pub format: PatchFormat,
+    /// 1-based line of the agent output where the block starts
+    pub line: usize,
+    pub changes: Vec<PatchChange>,
+}
+
+impl PatchBlock {
+    /// Every path the block creates, edits, deletes or renames to
+    pub fn touched_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for c in &self.changes {
+            match &c.change {
+                FileChange::Edit { unified_diff, .. } => {
+                    files.extend(apply::touched_files(unified_diff))
+                }
+                FileChange::Rename { new_path } => files.extend([c.path.clone(), new_path.clone()]),
+                FileChange::Write { .. } | FileChange::Delete => files.push(c.path.clone()),
+            }
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+/// Recognizes one patch format in an agent's reply
+pub trait PatchParser: Send + Sync {
+    fn format(&self) -> PatchFormat;
+    /// Every block of this format in `input`; empty when the format does not occur.
+    /// Errors name the line of `input` that is malformed.
+    fn parse(&self, input: &str) -> Result<Vec<PatchBlock>, String>;
+}
+
+/// The built-in parsers, most explicit format first
+pub fn parsers() -> Vec<Box<dyn PatchParser>> {
+    vec![
+        Box::new(MarkerParser),
+        Box::new(CodexParser),
+        Box::new(FencedDiffParser),
+        Box::new(WholeFileParser),
+    ]
 }
 
 pub fn parse_blocks(input: &str) -> Result<Vec<PatchBlock>, String> {
-    let mut out = Vec::new();
-    let mut rest = input;
-    loop {
-        let s = match rest.find("---BEGIN PATCH---") { Some(i) => i, None => break };
-        rest = &rest[s + "---BEGIN PATCH---".len()..];
-        let e = rest.find("---END PATCH---").ok_or_else(|| "END PATCH not found".to_string())?;
-        let content = rest[..e].trim().to_string();
-        out.push(PatchBlock { content });
-        rest = &rest[e + "---END PATCH---".len()..];
-    }
-    if out.is_empty() { return Err("No PATCH blocks found".to_string()); }
-    Ok(out)
+    parse_with(&parsers(), input)
+}
+
+/// Blocks of the first parser that finds any, so a diff quoted inside another
+/// format's block is not applied twice
+pub fn parse_with(
+    parsers: &[Box<dyn PatchParser>],
+    input: &str,
+) -> Result<Vec<PatchBlock>, String> {
+    for parser in parsers {
+        let blocks = parser.parse(input)?;
+        if !blocks.is_empty() {
+            return Ok(blocks);
+        }
+    }
+    Err("No PATCH blocks found".to_string())
+}
+
+fn numbered(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    input.lines().enumerate().map(|(i, l)| (i + 1, l))
+}
+
+fn safe_path(raw: &str, line: usize) -> Result<String, String> {
+    apply::normalize_path(raw.trim())
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| format!("line {line}: unsafe or empty path `{}`", raw.trim()))
+}
+
+/// Path named by a `---`/`+++` header, without `a/`/`b/` prefix or timestamp
+fn header_path(rest: &str, prefix: &str) -> Option<String> {
+    let raw = rest.split('\t').next().unwrap_or_default().trim_end();
+    (raw != "/dev/null").then(|| raw.strip_prefix(prefix).unwrap_or(raw).to_string())
+}
+
+/// Split a unified diff starting at line `first` into one `Edit` per file
+fn unified_diff_changes(lines: &[&str], first: usize) -> Result<Vec<PatchChange>, String> {
+    let mut changes = Vec::new();
+    // (path, diff lines, opened by `diff --git` and still waiting for its ---/+++ pair)
+    let mut current: Option<(String, Vec<&str>, bool)> = None;
+    fn flush(current: &mut Option<(String, Vec<&str>, bool)>, changes: &mut Vec<PatchChange>) {
+        if let Some((path, body, _)) = current.take() {
+            changes.push(PatchChange {
+                path,
+                change: FileChange::Edit {
+                    unified_diff: format!("{}\n", body.join("\n")),
+                    has_line_numbers: true,
+                },
+            });
+        }
+    }
+    let mut i = 0;
+    while i < lines.len() {
+        let (n, line) = (first + i, lines[i]);
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            flush(&mut current, &mut changes);
+            let path = rest
+                .rsplit_once(" b/")
+                .map(|(_, p)| p)
+                .ok_or_else(|| format!("line {n}: malformed diff --git header"))?;
+            current = Some((safe_path(path, n)?, vec![line], true));
+        } else if line.starts_with("--- ")
+            && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+        {
+            let path = header_path(&lines[i + 1][4..], "b/")
+                .or_else(|| header_path(&line[4..], "a/"))
+                .ok_or_else(|| format!("line {n}: file header names no file"))?;
+            let path = safe_path(&path, n)?;
+            match current.as_mut().filter(|(_, _, awaiting)| *awaiting) {
+                Some(open) => {
+                    open.0 = path;
+                    open.1.extend([line, lines[i + 1]]);
+                    open.2 = false;
+                }
+                None => {
+                    flush(&mut current, &mut changes);
+                    current = Some((path, vec![line, lines[i + 1]], false));
+                }
+            }
+            i += 1;
+        } else if line.starts_with("@@") {
+            let Some((_, body, awaiting)) = current.as_mut() else {
+                return Err(format!("line {n}: hunk before any file header"));
+            };
+            if *awaiting {
+                return Err(format!("line {n}: hunk before the ---/+++ file header"));
+            }
+            let start = line
+                .strip_prefix("@@ -")
+                .and_then(|r| r.split([',', ' ']).next())
+                .and_then(|s| s.parse::<usize>().ok());
+            if start.is_none() {
+                return Err(format!("line {n}: malformed hunk header `{line}`"));
+            }
+            body.push(line);
+        } else if let Some((_, body, _)) = current.as_mut() {
+            body.push(line);
+        }
+        i += 1;
+    }
+    flush(&mut current, &mut changes);
+    if changes.is_empty() {
+        return Err(format!("line {first}: patch block has no file header"));
+    }
+    Ok(changes)
+}
+
+struct MarkerParser;
+
+impl PatchParser for MarkerParser {
+    fn format(&self) -> PatchFormat {
+        PatchFormat::Markers
+    }
+
+    fn parse(&self, input: &str) -> Result<Vec<PatchBlock>, String> {
+        let mut blocks = Vec::new();
+        let mut open: Option<(usize, Vec<&str>)> = None;
+        for (n, line) in numbered(input) {
+            match (open.as_mut(), line.trim()) {
+                (None, BEGIN_MARKER) => open = Some((n, Vec::new())),
+                (Some(_), BEGIN_MARKER) => {
+                    return Err(format!("line {n}: {BEGIN_MARKER} inside an open block"))
+                }
+                (Some((start, body)), END_MARKER) => {
+                    let changes = unified_diff_changes(body, *start + 1)?;
+                    blocks.push(PatchBlock {
+                        format: self.format(),
+                        line: *start,
+                        changes,
+                    });
+                    open = None;
+                }
+                (Some((_, body)), _) => body.push(line),
+                (None, _) => {}
+            }
+        }
+        if let Some((start, _)) = open {
+            return Err(format!("line {start}: {BEGIN_MARKER} without {END_MARKER}"));
+        }
+        Ok(blocks)
+    }
+}
+
+/// A fenced code block: opening line, info string and body
+struct Fence<'a> {
+    line: usize,
+    info: &'a str,
+    body: Vec<&'a str>,
+}
+
+/// Closed fenced blocks in `input`, and the last one if it is never closed
+fn fences(input: &str) -> (Vec<Fence<'_>>, Option<Fence<'_>>) {
+    let mut done = Vec::new();
+    let mut open: Option<(Fence, &str)> = None;
+    for (n, line) in numbered(input) {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+        match (open.take(), marker) {
+            (Some((fence, close)), Some(m))
+                if m == close
+                    && trimmed
+                        .trim_end()
+                        .trim_start_matches(m.chars().next().unwrap())
+                        .is_empty() =>
+            {
+                done.push(fence)
+            }
+            (Some((mut fence, close)), _) => {
+                fence.body.push(line);
+                open = Some((fence, close));
+            }
+            (None, Some(m)) => {
+                let info = trimmed.trim_start_matches(m.chars().next().unwrap()).trim();
+                open = Some((
+                    Fence {
+                        line: n,
+                        info,
+                        body: Vec::new(),
+                    },
+                    m,
+                ));
+            }
+            (None, None) => {}
+        }
+    }
+    (done, open.map(|(fence, _)| fence))
+}
+
+fn is_diff_fence(info: &str) -> bool {
+    matches!(
+        info.split_whitespace().next(),
+        Some("diff" | "patch" | "udiff")
+    )
+}
+
+struct FencedDiffParser;
+
+impl PatchParser for FencedDiffParser {
+    fn format(&self) -> PatchFormat {
+        PatchFormat::FencedDiff
+    }
+
+    fn parse(&self, input: &str) -> Result<Vec<PatchBlock>, String> {
+        let (done, unclosed) = fences(input);
+        if let Some(fence) = unclosed.filter(|f| is_diff_fence(f.info)) {
+            return Err(format!(
+                "line {}: unterminated ```{} block",
+                fence.line, fence.info
+            ));
+        }
+        done.into_iter()
+            .filter(|f| is_diff_fence(f.info))
+            .map(|f| {
+                let changes = unified_diff_changes(&f.body, f.line + 1)?;
+                Ok(PatchBlock {
+                    format: self.format(),
+                    line: f.line,
+                    changes,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Path named by a fence info string: ```` ```rust:src/lib.rs ````,
+/// ```` ```rust title="src/lib.rs" ```` or ```` ```src/lib.rs ````
+fn fence_path(info: &str) -> Option<&str> {
+    if let Some((_, rest)) = info.split_once("title=") {
+        return rest
+            .trim_start_matches('"')
+            .split(['"', ' '])
+            .next()
+            .filter(|p| !p.is_empty());
+    }
+    if let Some((lang, path)) = info.split_once(':') {
+        return (!lang.contains(' ') && !path.trim().is_empty()).then(|| path.trim());
+    }
+    (!info.contains(' ') && (info.contains('/') || info.contains('.'))).then_some(info)
+}
+
+struct WholeFileParser;
+
+impl PatchParser for WholeFileParser {
+    fn format(&self) -> PatchFormat {
+        PatchFormat::WholeFile
+    }
+
+    fn parse(&self, input: &str) -> Result<Vec<PatchBlock>, String> {
+        let (done, _) = fences(input);
+        done.into_iter()
+            .filter(|f| !is_diff_fence(f.info))
+            .filter_map(|f| fence_path(f.info).map(|p| (p, f)))
+            .map(|(path, f)| {
+                let content = if f.body.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}\n", f.body.join("\n"))
+                };
+                Ok(PatchBlock {
+                    format: self.format(),
+                    line: f.line,
+                    changes: vec![PatchChange {
+                        path: safe_path(path, f.line)?,
+                        change: FileChange::Write { content },
+                    }],
+                })
+            })
+            .collect()
+    }
+}
+
+/// A `*** Update File:` section being collected
+struct CodexUpdate<'a> {
+    path: String,
+    line: usize,
+    move_to: Option<String>,
+    /// (line of the `@@`, text after it, hunk lines)
+    hunks: Vec<(usize, &'a str, Vec<&'a str>)>,
+}
+
+impl CodexUpdate<'_> {
+    /// Codex hunks carry context but no line numbers; emit a unified diff whose
+    /// counts are right and whose positions are only a starting point for the search
+    fn into_changes(self) -> Result<Vec<PatchChange>, String> {
+        let mut changes = Vec::new();
+        if !self.hunks.is_empty() {
+            let mut diff = format!("--- a/{0}\n+++ b/{0}\n", self.path);
+            for (n, anchor, lines) in &self.hunks {
+                let old = lines.iter().filter(|l| !l.starts_with('+')).count();
+                let new = lines.iter().filter(|l| !l.starts_with('-')).count();
+                if old == 0 {
+                    return Err(format!(
+                        "line {n}: hunk has no context or removed lines to anchor it"
+                    ));
+                }
+                let anchor = if anchor.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {anchor}")
+                };
+                diff.push_str(&format!("@@ -1,{old} +1,{new} @@{anchor}\n"));
+                for l in lines {
+                    // Codex writes empty context lines without their leading space
+                    diff.push_str(if l.is_empty() { " " } else { l });
+                    diff.push('\n');
+                }
+            }
+            changes.push(PatchChange {
+                path: self.path.clone(),
+                change: FileChange::Edit {
+                    unified_diff: diff,
+                    has_line_numbers: false,
+                },
+            });
+        }
+        if let Some(new_path) = self.move_to {
+            changes.push(PatchChange {
+                path: self.path,
+                change: FileChange::Rename { new_path },
+            });
+        } else if changes.is_empty() {
+            return Err(format!(
+                "line {}: *** Update File section has no hunks",
+                self.line
+            ));
+        }
+        Ok(changes)
+    }
+}
+
+enum CodexSection<'a> {
+    Add { path: String, lines: Vec<&'a str> },
+    Update(CodexUpdate<'a>),
+}
+
+impl CodexSection<'_> {
+    fn into_changes(self) -> Result<Vec<PatchChange>, String> {
+        match self {
+            CodexSection::Add { path, lines } => {
+                let content = lines.iter().map(|l| format!("{}\n", &l[1..])).collect();
+                Ok(vec![PatchChange {
+                    path,
+                    change: FileChange::Write { content },
+                }])
+            }
+            CodexSection::Update(update) => update.into_changes(),
+        }
+    }
+}
+
+struct CodexParser;
+
+impl PatchParser for CodexParser {
+    fn format(&self) -> PatchFormat {
+        PatchFormat::Codex
+    }
+
+    fn parse(&self, input: &str) -> Result<Vec<PatchBlock>, String> {
+        let mut blocks = Vec::new();
+        // (line of `*** Begin Patch`, changes so far, section being read)
+        let mut open: Option<(usize, Vec<PatchChange>, Option<CodexSection>)> = None;
+        for (n, line) in numbered(input) {
+            let trimmed = line.trim_end();
+            let Some((start, changes, section)) = open.as_mut() else {
+                if trimmed.trim_start() == CODEX_BEGIN {
+                    open = Some((n, Vec::new(), None));
+                }
+                continue;
+            };
+            let directive = |prefix: &str| trimmed.strip_prefix(prefix).map(|p| safe_path(p, n));
+            // Any directive but these two ends the section being read
+            let ends_section = trimmed.starts_with("*** ")
+                && !trimmed.starts_with("*** Move to:")
+                && trimmed != "*** End of File";
+            if ends_section {
+                if let Some(s) = section.take() {
+                    changes.extend(s.into_changes()?);
+                }
+            }
+            if trimmed == CODEX_END {
+                if changes.is_empty() {
+                    return Err(format!("line {start}: empty {CODEX_BEGIN} envelope"));
+                }
+                blocks.push(PatchBlock {
+                    format: self.format(),
+                    line: *start,
+                    changes: std::mem::take(changes),
+                });
+                open = None;
+            } else if let Some(path) = directive("*** Add File: ") {
+                *section = Some(CodexSection::Add {
+                    path: path?,
+                    lines: Vec::new(),
+                });
+            } else if let Some(path) = directive("*** Delete File: ") {
+                changes.push(PatchChange {
+                    path: path?,
+                    change: FileChange::Delete,
+                });
+            } else if let Some(path) = directive("*** Update File: ") {
+                *section = Some(CodexSection::Update(CodexUpdate {
+                    path: path?,
+                    line: n,
+                    move_to: None,
+                    hunks: Vec::new(),
+                }));
+            } else if let Some(path) = directive("*** Move to: ") {
+                match section {
+                    Some(CodexSection::Update(u)) if u.move_to.is_none() && u.hunks.is_empty() => {
+                        u.move_to = Some(path?)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {n}: *** Move to: must directly follow *** Update File:"
+                        ))
+                    }
+                }
+            } else if trimmed == "*** End of File" {
+                continue;
+            } else if trimmed.starts_with("*** ") {
+                return Err(format!("line {n}: unknown directive `{trimmed}`"));
+            } else {
+                match section {
+                    Some(CodexSection::Add { lines, .. }) if line.starts_with('+') => {
+                        lines.push(line)
+                    }
+                    Some(CodexSection::Add { .. }) => {
+                        return Err(format!(
+                            "line {n}: lines of an added file must start with '+'"
+                        ))
+                    }
+                    Some(CodexSection::Update(u)) => {
+                        if let Some(anchor) = line.strip_prefix("@@") {
+                            u.hunks.push((n, anchor.trim(), Vec::new()));
+                        } else if line.is_empty() || line.starts_with([' ', '+', '-']) {
+                            if u.hunks.is_empty() {
+                                u.hunks.push((n, "", Vec::new()));
+                            }
+                            u.hunks.last_mut().expect("pushed above").2.push(line);
+                        } else {
+                            return Err(format!(
+                                "line {n}: expected a hunk line starting with ' ', '+' or '-'"
+                            ));
+                        }
+                    }
+                    None if trimmed.is_empty() => {}
+                    None => return Err(format!(
+                        "line {n}: expected *** Add File:, *** Update File: or *** Delete File:"
+                    )),
+                }
+            }
+        }
+        if let Some((start, _, _)) = open {
+            return Err(format!("line {start}: {CODEX_BEGIN} without {CODEX_END}"));
+        }
+        Ok(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit_diff(change: &PatchChange) -> &str {
+        match &change.change {
+            FileChange::Edit { unified_diff, .. } => unified_diff,
+            other => panic!("expected an edit, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn marker_blocks_split_per_file() {
+        let input = "Here you go\n---BEGIN PATCH---\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-x\n+y\ndiff --git a/b.rs b/b.rs\ndeleted file mode 100644\n--- a/b.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-gone\n---END PATCH---\n";
+        let blocks = parse_blocks(input).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            (blocks[0].format, blocks[0].line),
+            (PatchFormat::Markers, 2)
+        );
+        let paths: Vec<_> = blocks[0].changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, ["a.rs", "b.rs"]);
+        assert!(edit_diff(&blocks[0].changes[1])
+            .starts_with("diff --git a/b.rs b/b.rs\ndeleted file mode"));
+        assert_eq!(blocks[0].touched_files(), ["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn errors_name_the_offending_line() {
+        let unclosed = parse_blocks("intro\n---BEGIN PATCH---\n--- a/x\n+++ b/x\n").unwrap_err();
+        assert_eq!(
+            unclosed,
+            "line 2: ---BEGIN PATCH--- without ---END PATCH---"
+        );
+        let orphan = parse_blocks("```diff\n@@ -1 +1 @@\n-a\n+b\n```\n").unwrap_err();
+        assert_eq!(orphan, "line 2: hunk before any file header");
+        let unsafe_path = parse_blocks(
+            "---BEGIN PATCH---\n--- a/../etc/passwd\n+++ b/../etc/passwd\n---END PATCH---\n",
+        )
+        .unwrap_err();
+        assert!(unsafe_path.starts_with("line 2: unsafe"), "{unsafe_path}");
+        let dotted = parse_blocks("---BEGIN PATCH---\n--- a/src/a..b.rs\n+++ b/src/a..b.rs\n@@ -1 +1 @@\n-a\n+b\n---END PATCH---\n").unwrap();
+        assert_eq!(dotted[0].touched_files(), ["src/a..b.rs"]);
+    }
+
+    #[test]
+    fn fenced_diff_is_found_when_there_are_no_markers() {
+        let input = "```rust\nfn unrelated() {}\n```\n\n```diff\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -3,1 +3,1 @@\n-old\n+new\n```\n";
+        let blocks = parse_blocks(input).unwrap();
+        assert_eq!(
+            (blocks[0].format, blocks[0].line),
+            (PatchFormat::FencedDiff, 5)
+        );
+        assert_eq!(blocks[0].changes[0].path, "src/lib.rs");
+    }
+
+    #[test]
+    fn codex_envelope_becomes_changes() {
+        let input = "*** Begin Patch\n*** Add File: docs/new.md\n+# Title\n+\n*** Update File: src/main.rs\n*** Move to: src/app.rs\n@@ fn main() {\n-    old();\n+    new();\n \n*** Delete File: obsolete.txt\n*** End Patch\n";
+        let blocks = parse_blocks(input).unwrap();
+        assert_eq!(blocks[0].format, PatchFormat::Codex);
+        let changes = &blocks[0].changes;
+        assert_eq!(changes.len(), 4);
+        assert!(
+            matches!(&changes[0].change, FileChange::Write { content } if content == "# Title\n\n")
+        );
+        assert_eq!(
+            edit_diff(&changes[1]),
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,2 +1,2 @@ fn main() {\n-    old();\n+    new();\n \n"
+        );
+        assert!(
+            matches!(&changes[2].change, FileChange::Rename { new_path } if new_path == "src/app.rs")
+        );
+        assert!(matches!(changes[3].change, FileChange::Delete));
+        assert_eq!(
+            blocks[0].touched_files(),
+            ["docs/new.md", "obsolete.txt", "src/app.rs", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn codex_rejects_stray_lines() {
+        let err = parse_blocks(
+            "*** Begin Patch\n*** Update File: a.rs\n@@\n-x\nnot a hunk line\n*** End Patch\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "line 5: expected a hunk line starting with ' ', '+' or '-'"
+        );
+    }
+
+    #[test]
+    fn whole_file_fences_name_their_path() {
+        let input = "```rust:src/lib.rs\npub fn a() {}\n```\n```toml title=\"Cargo.toml\"\n[package]\n```\n```rust\nignored\n```\n";
+        let blocks = parse_blocks(input).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].changes[0].path, "src/lib.rs");
+        assert!(
+            matches!(&blocks[1].changes[0].change, FileChange::Write { content } if content == "[package]\n")
+        );
+    }
+
+    #[test]
+    fn nothing_recognizable_is_an_error() {
+        assert_eq!(
+            parse_blocks("I could not find the bug.").unwrap_err(),
+            "No PATCH blocks found"
+        );
+    }
 }
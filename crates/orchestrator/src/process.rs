@@ -0,0 +1,29 @@
+use std::{process::ExitStatus, sync::Arc, time::Duration};
+
+use command_group::AsyncGroupChild;
+use tokio::{sync::RwLock, time::Instant};
+
+/// Poll a tracked child until it exits, killing it once `timeout` elapses.
+///
+/// The lock is only held for each `try_wait`, so the host can take it to kill
+/// the process group while we wait.
+pub async fn wait_tracked(
+    child: &Arc<RwLock<AsyncGroupChild>>,
+    timeout: Option<Duration>,
+) -> Result<ExitStatus, String> {
+    let start = Instant::now();
+    loop {
+        {
+            let mut guard = child.write().await;
+            if let Some(status) = guard.try_wait().map_err(|e| e.to_string())? {
+                return Ok(status);
+            }
+            if timeout.is_some_and(|t| start.elapsed() > t) {
+                let _ = guard.kill().await;
+                let _ = guard.wait().await;
+                return Err("timed out".to_string());
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
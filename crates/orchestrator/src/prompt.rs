@@ -1,7 +1,102 @@
+use std::{collections::HashSet, fs, path::Path};
+
+/// Output contract the orchestrator expects from the agent's final message.
+pub const PATCH_CONTRACT: &str = "Do not edit files directly. Reply with your changes as unified diffs (paths relative to the repository root), each wrapped between a line containing ---BEGIN PATCH--- and a line containing ---END PATCH---.";
+
+/// Default prompt budget, in estimated tokens.
+pub const DEFAULT_TOKEN_BUDGET: usize = 32_000;
+
+/// Smallest slice of a context file worth including when it has to be truncated.
+const MIN_CONTEXT_TOKENS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PhaseKind {
+    #[default]
+    Prompt,
+    Fix,
+    Hardening,
+}
+
+impl PhaseKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "prompt" => Some(Self::Prompt),
+            "fix" => Some(Self::Fix),
+            "hardening" => Some(Self::Hardening),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Prompt => "prompt",
+            Self::Fix => "fix",
+            Self::Hardening => "hardening",
+        }
+    }
+
+    fn instructions(&self) -> &'static str {
+        match self {
+            Self::Prompt => "Implement the task described above.",
+            Self::Fix => "A previous attempt at this task failed validation. Make the smallest change that fixes the failures; do not refactor unrelated code.",
+            Self::Hardening => "The task is implemented. Harden it: add missing tests, handle edge cases and errors, and keep the public API unchanged.",
+        }
+    }
+}
+
+/// Text content of an uploaded project or task context file.
+#[derive(Debug, Clone)]
+pub struct ContextDoc {
+    pub filename: String,
+    pub sha256: String,
+    pub text: String,
+}
+
+impl ContextDoc {
+    /// Load the text of a stored upload from its extracted `blob/{sha256}.txt`
+    /// sidecar. Text uploads fall back to the raw file until extraction has
+    /// run; PDFs without a sidecar and binary uploads yield `None`.
+    pub fn load(
+        data_root: &Path,
+        filename: &str,
+        mime: &str,
+        sha256: &str,
+        stored_path: &str,
+    ) -> Option<Self> {
+        let sidecar = data_root.join("blob").join(format!("{sha256}.txt"));
+        let text = match mime {
+            "application/pdf" => fs::read_to_string(sidecar).ok()?,
+            "text/plain" | "text/markdown" | "text/csv" | "application/json" => {
+                fs::read_to_string(sidecar)
+                    .ok()
+                    .filter(|t| !t.trim().is_empty())
+                    .or_else(|| fs::read_to_string(data_root.join(stored_path)).ok())?
+            }
+            _ => return None,
+        };
+        if text.trim().is_empty() {
+            return None;
+        }
+        Some(Self {
+            filename: filename.to_string(),
+            sha256: sha256.to_string(),
+            text,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PromptRequest {
-    pub system: String,
-    pub user: String,
+    /// Task title and description, as produced by `Task::to_prompt`
+    pub task: String,
+    pub phase: PhaseKind,
+    pub allowlist: Vec<String>,
+    pub denylist: Vec<String>,
+    pub context: Vec<ContextDoc>,
+    /// Failures of the preceding run, e.g. from `feedback::failure_feedback`
+    pub feedback: Option<String>,
+    /// Maximum prompt size in estimated tokens
+    pub token_budget: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -9,4 +104,161 @@ pub struct PromptResponse {
     pub raw_text: String,
 }
 
-pub async fn assemble_prompt(_req: &PromptRequest) -> Result<String, String> { Ok(String::new()) }
+/// Rough token estimate (~4 bytes per token), good enough for budgeting.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+fn truncate_to_bytes(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Build the agent prompt. Context files are deduplicated and ordered by
+/// sha256, so identical inputs always produce a byte-identical prompt.
+pub fn assemble_prompt(req: &PromptRequest) -> String {
+    let mut out = String::new();
+    out.push_str(req.task.trim());
+    out.push_str("\n\n## Phase: ");
+    out.push_str(req.phase.as_str());
+    out.push('\n');
+    out.push_str(req.phase.instructions());
+    out.push('\n');
+
+    if let Some(feedback) = req.feedback.as_deref().filter(|f| !f.trim().is_empty()) {
+        out.push_str("\n## Previous run\n");
+        out.push_str(feedback.trim_end());
+        out.push('\n');
+    }
+
+    if !req.allowlist.is_empty() || !req.denylist.is_empty() {
+        out.push_str("\n## Scope\n");
+        if !req.allowlist.is_empty() {
+            out.push_str("Only modify files matching these patterns:\n");
+            for pattern in &req.allowlist {
+                out.push_str(&format!("- {pattern}\n"));
+            }
+        }
+        if !req.denylist.is_empty() {
+            out.push_str("Never modify files matching these patterns:\n");
+            for pattern in &req.denylist {
+                out.push_str(&format!("- {pattern}\n"));
+            }
+        }
+    }
+
+    out.push_str("\n## Output format\n");
+    out.push_str(PATCH_CONTRACT);
+    out.push('\n');
+
+    let mut docs: Vec<&ContextDoc> = req.context.iter().collect();
+    docs.sort_by(|a, b| a.sha256.cmp(&b.sha256).then(a.filename.cmp(&b.filename)));
+    let mut seen = HashSet::new();
+    docs.retain(|d| seen.insert(d.sha256.as_str()));
+    if docs.is_empty() {
+        return out;
+    }
+
+    out.push_str("\n## Context files\n");
+    let mut omitted = Vec::new();
+    for doc in docs {
+        let header = format!("\n### {} (sha256 {})\n", doc.filename, doc.sha256);
+        let remaining = req.token_budget.saturating_sub(estimate_tokens(&out));
+        let needed = estimate_tokens(&header) + estimate_tokens(&doc.text);
+        if needed <= remaining {
+            out.push_str(&header);
+            out.push_str(doc.text.trim_end());
+            out.push('\n');
+        } else if remaining >= estimate_tokens(&header) + MIN_CONTEXT_TOKENS {
+            let marker = "\n[truncated]\n";
+            let budget_bytes = (remaining - estimate_tokens(&header) - estimate_tokens(marker)) * 4;
+            out.push_str(&header);
+            out.push_str(truncate_to_bytes(&doc.text, budget_bytes));
+            out.push_str(marker);
+        } else {
+            omitted.push(doc);
+        }
+    }
+    if !omitted.is_empty() {
+        out.push_str("\nOmitted (token budget exhausted):\n");
+        for doc in omitted {
+            out.push_str(&format!("- {} (sha256 {})\n", doc.filename, doc.sha256));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(name: &str, sha: &str, text: &str) -> ContextDoc {
+        ContextDoc {
+            filename: name.to_string(),
+            sha256: sha.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    fn request(context: Vec<ContextDoc>, token_budget: usize) -> PromptRequest {
+        PromptRequest {
+            task: "Title: Add login".to_string(),
+            phase: PhaseKind::Fix,
+            allowlist: vec!["src/**".to_string()],
+            denylist: vec!["Cargo.lock".to_string()],
+            context,
+            feedback: None,
+            token_budget,
+        }
+    }
+
+    #[test]
+    fn context_order_is_deterministic() {
+        let a = request(vec![doc("b.md", "bb", "second"), doc("a.md", "aa", "first")], 10_000);
+        let b = request(vec![doc("a.md", "aa", "first"), doc("b.md", "bb", "second")], 10_000);
+        let prompt = assemble_prompt(&a);
+        assert_eq!(prompt, assemble_prompt(&b));
+        assert!(prompt.find("first").unwrap() < prompt.find("second").unwrap());
+        assert!(prompt.contains("## Phase: fix"));
+        assert!(prompt.contains("- src/**"));
+        assert!(prompt.contains("- Cargo.lock"));
+        assert!(prompt.contains("---BEGIN PATCH---"));
+    }
+
+    #[test]
+    fn feedback_follows_phase_instructions() {
+        let mut req = request(Vec::new(), 10_000);
+        req.feedback = Some("The previous run failed these validators: det\n".to_string());
+        let prompt = assemble_prompt(&req);
+        let feedback = prompt.find("## Previous run\nThe previous run failed").unwrap();
+        assert!(prompt.find("## Phase: fix").unwrap() < feedback);
+        assert!(feedback < prompt.find("## Output format").unwrap());
+    }
+
+    #[test]
+    fn duplicate_hashes_are_included_once() {
+        let prompt = assemble_prompt(&request(
+            vec![doc("spec.md", "aa", "shared spec"), doc("copy.md", "aa", "shared spec")],
+            10_000,
+        ));
+        assert_eq!(prompt.matches("shared spec").count(), 1);
+    }
+
+    #[test]
+    fn budget_truncates_then_omits() {
+        let big = "x".repeat(8_000);
+        let prompt = assemble_prompt(&request(
+            vec![doc("big.txt", "aa", &big), doc("late.txt", "bb", "late")],
+            1_000,
+        ));
+        assert!(prompt.contains("[truncated]"));
+        assert!(prompt.contains("- late.txt (sha256 bb)"));
+        assert!(estimate_tokens(&prompt) <= 1_000 + 32);
+    }
+}
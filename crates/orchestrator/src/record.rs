@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use db::{
+    models::{
+        attempt_artifact::{AttemptArtifact, CreateAttemptArtifact},
+        task_attempt::{AttemptTelemetry, TaskAttempt},
+        token_usage::ExecutionTokenUsage,
+    },
+    DBService,
+};
+use utils::log_msg::TokenUsage;
+use uuid::Uuid;
+
+use crate::run::RunSummary;
+
+/// Records what a run of an attempt produced against its execution process, so
+/// runs started from the server and from the CLI leave the same rows behind.
+#[derive(Clone)]
+pub struct RunRecorder {
+    pub db: DBService,
+    pub attempt_id: Uuid,
+    pub execution_process_id: Uuid,
+}
+
+impl From<&RunSummary> for AttemptTelemetry {
+    fn from(summary: &RunSummary) -> Self {
+        Self {
+            cold_sec: Some(summary.cold_sec),
+            warm_sec: Some(summary.warm_sec),
+            cache_hit_count: Some(summary.cache_hit_count as i64),
+            scope_pass: Some(summary.scope_pass),
+            dep_pass: Some(summary.dep_pass),
+            api_pass: summary.api_pass,
+            det_pass: Some(summary.det_pass),
+            kpi_pass: Some(summary.kpi_pass),
+            ..Default::default()
+        }
+    }
+}
+
+impl RunRecorder {
+    pub async fn record_artifact(&self, kind: &str, path: &Path) {
+        let data = CreateAttemptArtifact {
+            attempt_id: self.attempt_id,
+            execution_process_id: Some(self.execution_process_id),
+            kind: kind.to_string(),
+            path: path.to_string_lossy().to_string(),
+            size_bytes: std::fs::metadata(path).ok().map(|m| m.len() as i64),
+        };
+        if let Err(e) = AttemptArtifact::create(&self.db.pool, &data, Uuid::new_v4()).await {
+            tracing::error!(
+                "Failed to record artifact {} for {}: {}",
+                kind,
+                self.attempt_id,
+                e
+            );
+        }
+    }
+
+    pub async fn record_usage(&self, usage: &TokenUsage) {
+        if let Err(e) =
+            ExecutionTokenUsage::record(&self.db.pool, self.execution_process_id, usage).await
+        {
+            tracing::error!(
+                "Failed to record token usage for {}: {}",
+                self.execution_process_id,
+                e
+            );
+        }
+    }
+
+    pub async fn record_summary(&self, summary: &RunSummary) {
+        let telemetry = AttemptTelemetry::from(summary);
+        if let Err(e) =
+            TaskAttempt::update_telemetry(&self.db.pool, self.attempt_id, &telemetry).await
+        {
+            tracing::error!("Failed to record telemetry for {}: {}", self.attempt_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use db::test_support::{seed_attempt, seed_orchestrator_run, seed_task};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn summary_and_artifacts_land_on_the_attempt() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let attempt = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        let run = seed_orchestrator_run(&db.pool, attempt.id).await;
+        let recorder = RunRecorder {
+            db: db.clone(),
+            attempt_id: attempt.id,
+            execution_process_id: run.id,
+        };
+
+        recorder
+            .record_summary(&RunSummary {
+                cold_sec: 2.5,
+                warm_sec: 1.0,
+                cache_hit_count: 3,
+                scope_pass: true,
+                dep_pass: false,
+                api_pass: None,
+                det_pass: true,
+                kpi_pass: true,
+                tests_pass: true,
+            })
+            .await;
+        let telemetry = TaskAttempt::find_telemetry(&db.pool, attempt.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(telemetry.cold_sec, Some(2.5));
+        assert_eq!(telemetry.cache_hit_count, Some(3));
+        assert_eq!(telemetry.dep_pass, Some(false));
+        assert_eq!(telemetry.api_pass, None);
+
+        let path = std::env::temp_dir().join(format!("vk-record-{}.json", run.id));
+        std::fs::write(&path, "{}").unwrap();
+        recorder.record_artifact("summary.json", &path).await;
+        let artifacts = AttemptArtifact::find_by_execution_process_id(&db.pool, run.id)
+            .await
+            .unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].kind, "summary.json");
+        assert_eq!(artifacts[0].size_bytes, Some(2));
+        let _ = std::fs::remove_file(&path);
+    }
+}
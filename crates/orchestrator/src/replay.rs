@@ -0,0 +1,147 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    artifacts::AGENT_OUTPUT_FILE,
+    manifest::{differences, RunManifest},
+};
+
+/// Artifacts whose bytes only depend on the patch and the worktree it was applied to
+const COMPARED_ARTIFACTS: &[&str] = &[
+    AGENT_OUTPUT_FILE,
+    "touched_files.txt",
+    "apply_report.json",
+    "dep_diff.json",
+    "api_diff.json",
+];
+
+/// A run re-executed from another run's manifest.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// Artifacts directory of the run being replayed
+    pub original_dir: PathBuf,
+    /// Repository the replay checkout was added to; the checkout is removed when the replay ends
+    pub repo: PathBuf,
+}
+
+/// The outputs of a run that a faithful replay reproduces. Timings vary
+/// between runs and are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunOutputs {
+    /// SHA-256 of each compared artifact the run wrote
+    pub artifacts: BTreeMap<String, String>,
+    pub cold_fingerprint: Option<String>,
+    pub warm_fingerprint: Option<String>,
+    /// Validator verdicts from `summary.json`
+    pub validators: Option<Value>,
+    /// Contents of `failure.txt` when the run stopped early
+    pub failure: Option<String>,
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+impl RunOutputs {
+    pub fn load(dir: &Path) -> Self {
+        let artifacts = COMPARED_ARTIFACTS
+            .iter()
+            .filter_map(|name| {
+                let bytes = fs::read(dir.join(name)).ok()?;
+                Some((name.to_string(), format!("{:x}", Sha256::digest(&bytes))))
+            })
+            .collect();
+        let tests = read_json(&dir.join("test_report.json"));
+        let fingerprint = |run: &str| {
+            tests
+                .as_ref()
+                .and_then(|t| t[run]["fingerprint"].as_str())
+                .map(str::to_string)
+        };
+        Self {
+            artifacts,
+            cold_fingerprint: fingerprint("cold"),
+            warm_fingerprint: fingerprint("warm"),
+            validators: read_json(&dir.join("summary.json")).map(|s| s["validator"].clone()),
+            failure: fs::read_to_string(dir.join("failure.txt")).ok(),
+        }
+    }
+}
+
+/// How a replay compares to the run it re-executed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub original_config_sha256: String,
+    pub replay_config_sha256: String,
+    /// Manifest fields that differ, e.g. `toolchain.rustc` or `agent.command`
+    pub config_differences: Vec<String>,
+    pub outputs_match: bool,
+    /// Outputs that differ, e.g. `artifacts.dep_diff.json` or `cold_fingerprint`
+    pub output_differences: Vec<String>,
+}
+
+impl ReplayReport {
+    /// Compare the manifests and outputs written into two runs' artifacts directories
+    pub fn compare(original_dir: &Path, replay_dir: &Path) -> Result<Self, String> {
+        let original = RunManifest::load(original_dir)?;
+        let replay = RunManifest::load(replay_dir)?;
+        let config_differences = differences(&to_value(&original)?, &to_value(&replay)?);
+        let output_differences = differences(
+            &to_value(&RunOutputs::load(original_dir))?,
+            &to_value(&RunOutputs::load(replay_dir))?,
+        );
+        Ok(Self {
+            original_config_sha256: original.sha256()?,
+            replay_config_sha256: replay.sha256()?,
+            config_differences,
+            outputs_match: output_differences.is_empty(),
+            output_differences,
+        })
+    }
+
+    pub fn config_matches(&self) -> bool {
+        self.original_config_sha256 == self.replay_config_sha256
+    }
+}
+
+/// Add a detached worktree of `repo` at `commit`, so a replay starts from the
+/// original run's base without touching the attempt's own worktree
+pub fn add_checkout(repo: &Path, commit: &str, dest: &Path) -> Result<(), String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["worktree", "add", "--detach"])
+        .arg(dest)
+        .arg(commit)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !out.status.success() {
+        return Err(format!(
+            "git worktree add failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+pub fn remove_checkout(repo: &Path, dest: &Path) {
+    let _ = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["worktree", "remove", "--force"])
+        .arg(dest)
+        .output();
+    let _ = fs::remove_dir_all(dest);
+}
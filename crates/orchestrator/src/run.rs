@@ -1,83 +1,360 @@
-use std::path::Path;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use crate::{apply, artifacts::Artifacts, patch, test, NullAgentAdapter, OrchestratorConfig};
+use validators::{
+    api_stability::{ApiDiffReport, ApiStability, ApiSurface},
+    dep_diff::{node::NodeDepDiff, rust::RustDepDiff, DepDiffReport, DepSnapshot},
+    Validator,
+};
 
-fn metadata_header() -> String {
-    let algo = "P3";
-    let sha = std::process::Command::new("git")
-        .args(["rev-parse", "--short", "HEAD"])
-        .output()
-        .ok()
-        .and_then(|o| String::from_utf8(o.stdout).ok())
-        .unwrap_or_default()
-        .trim()
-        .to_string();
-    let rustc = std::process::Command::new("rustc")
-        .arg("--version")
-        .output()
-        .ok()
-        .and_then(|o| String::from_utf8(o.stdout).ok())
+use crate::{
+    apply,
+    artifacts::Artifacts,
+    manifest::RunManifest,
+    patch,
+    replay::{add_checkout, remove_checkout, Replay, ReplayReport},
+    test, AgentAdapter, OrchestratorConfig, RunHooks,
+};
+
+/// Timings and validator verdicts of a run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    pub cold_sec: f64,
+    pub warm_sec: f64,
+    pub cache_hit_count: u32,
+    pub scope_pass: bool,
+    pub dep_pass: bool,
+    pub api_pass: Option<bool>,
+    pub det_pass: bool,
+    pub kpi_pass: bool,
+    /// The warm test run exited cleanly with no failed tests
+    pub tests_pass: bool,
+}
+
+impl RunSummary {
+    /// Every validator and the test suite passed; a skipped API check does not
+    /// count against the run
+    pub fn passed(&self) -> bool {
+        self.tests_pass
+            && self.scope_pass
+            && self.dep_pass
+            && self.api_pass.unwrap_or(true)
+            && self.det_pass
+            && self.kpi_pass
+    }
+}
+
+/// Everything needed to run one attempt, so the host can start it in the background.
+pub struct OrchestratorRun {
+    pub cfg: OrchestratorConfig,
+    pub workdir: PathBuf,
+    pub agent: Box<dyn AgentAdapter>,
+    /// Set when this run re-executes an earlier one from its manifest
+    pub replay: Option<Replay>,
+}
+
+impl OrchestratorRun {
+    pub async fn execute(self, attempt_id: String, hooks: &dyn RunHooks) -> Result<(), String> {
+        let artifacts_dir = self.cfg.artifacts_dir.clone();
+        Artifacts::clear_failure_txt(&artifacts_dir);
+        let result = run_attempt(attempt_id, self.cfg, &self.workdir, self.agent.as_ref(), hooks).await;
+        // Keep the reason next to the other artifacts so a follow-up fix phase can see it
+        if let Err(e) = &result {
+            if let Ok(path) = Artifacts::write_failure_txt(&artifacts_dir, e) {
+                record(hooks, path).await;
+            }
+        }
+        if let Some(replay) = &self.replay {
+            report_replay(replay, &artifacts_dir, hooks).await;
+            remove_checkout(&replay.repo, &self.workdir);
+        }
+        result
+    }
+}
+
+async fn report_replay(replay: &Replay, artifacts_dir: &Path, hooks: &dyn RunHooks) {
+    let report = match ReplayReport::compare(&replay.original_dir, artifacts_dir) {
+        Ok(report) => report,
+        Err(e) => return hooks.log(&format!("ORCH: replay not compared: {e}")),
+    };
+    for field in &report.config_differences {
+        hooks.log(&format!("ORCH: replay config differs: {field}"));
+    }
+    for output in &report.output_differences {
+        hooks.log(&format!("ORCH: replay output differs: {output}"));
+    }
+    hooks.log(&format!(
+        "ORCH: replay config {} outputs {}",
+        if report.config_matches() { "matches" } else { "differs" },
+        if report.outputs_match { "match" } else { "differ" }
+    ));
+    if let Ok(bytes) = serde_json::to_vec_pretty(&report) {
+        if let Ok(path) = Artifacts::write_replay_report_json(artifacts_dir, &bytes) {
+            record(hooks, path).await;
+        }
+    }
+}
+
+async fn record(hooks: &dyn RunHooks, path: PathBuf) {
+    let kind = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
         .unwrap_or_default();
+    hooks.record_artifact(&kind, &path).await;
+}
+
+async fn check_cancelled(hooks: &dyn RunHooks) -> Result<(), String> {
+    if hooks.is_cancelled().await {
+        return Err("cancelled".to_string());
+    }
+    Ok(())
+}
+
+static CHECKOUT_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// Dependencies and public API of the tree at `commit`, read from a temporary checkout of it
+fn baseline_at(workdir: &Path, commit: &str) -> Result<(DepSnapshot, ApiSurface), String> {
+    let dest = std::env::temp_dir().join(format!(
+        "vk-api-base-{}-{}",
+        std::process::id(),
+        CHECKOUT_SEQ.fetch_add(1, Ordering::Relaxed)
+    ));
+    add_checkout(workdir, commit, &dest)?;
+    let deps = DepSnapshot::capture(&dest);
+    let surface = ApiSurface::capture(&dest);
+    remove_checkout(workdir, &dest);
+    Ok((deps, surface))
+}
+
+/// Content hash of every file `git status` reports as changed, keyed by its
+/// path in the worktree; `None` for a deleted file. Empty outside a repository.
+fn changed_files(workdir: &Path, skip: &[&Path]) -> BTreeMap<String, Option<u64>> {
+    let out = match Command::new("git")
+        .args(["status", "--porcelain", "-z", "--untracked-files=all"])
+        .current_dir(workdir)
+        .output()
+    {
+        Ok(out) if out.status.success() => out,
+        _ => return BTreeMap::new(),
+    };
+    let text = String::from_utf8_lossy(&out.stdout);
+    let mut entries = text.split('\0').filter(|e| !e.is_empty());
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next() {
+        let Some(path) = entry.get(3..) else { continue };
+        paths.push(path.to_string());
+        // Renames and copies are followed by the path they came from
+        if matches!(entry.as_bytes().first(), Some(b'R' | b'C')) {
+            paths.extend(entries.next().map(str::to_string));
+        }
+    }
+    paths
+        .into_iter()
+        .filter(|p| !skip.iter().any(|dir| workdir.join(p).starts_with(dir)))
+        .map(|p| {
+            let hash = std::fs::read(workdir.join(&p)).ok().map(|bytes| {
+                let mut hasher = DefaultHasher::new();
+                bytes.hash(&mut hasher);
+                hasher.finish()
+            });
+            (p, hash)
+        })
+        .collect()
+}
+
+/// Paths whose state differs between two [`changed_files`] snapshots
+fn edited_between(
+    before: &BTreeMap<String, Option<u64>>,
+    after: &BTreeMap<String, Option<u64>>,
+) -> Vec<String> {
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter(|p| before.get(*p) != after.get(*p))
+        .cloned()
+        .collect()
+}
+
+fn metadata_header(manifest: &RunManifest, config_sha256: &str) -> String {
     format!(
-        "ALGO_VERSION={}; CANONICAL_CONFIG_SHA256=0; RUNTIME_VERSION={}; COMMIT_OR_ARTIFACT_HASH={}",
-        algo, rustc.trim(), sha
+        "ALGO_VERSION={}; CANONICAL_CONFIG_SHA256={}; RUNTIME_VERSION={}; COMMIT_OR_ARTIFACT_HASH={}",
+        manifest.algo_version,
+        config_sha256,
+        manifest.toolchain.get("rustc").map(String::as_str).unwrap_or_default(),
+        manifest.base_commit.as_deref().unwrap_or_default()
     )
 }
 
-pub fn run_attempt(attempt_id: String, cfg: OrchestratorConfig, workdir: &Path) -> Result<(), String> {
-    let header = metadata_header();
+pub async fn run_attempt(
+    attempt_id: String,
+    cfg: OrchestratorConfig,
+    workdir: &Path,
+    agent: &dyn AgentAdapter,
+    hooks: &dyn RunHooks,
+) -> Result<(), String> {
+    hooks.log(&format!("ORCH: started attempt={attempt_id}"));
     let _ = Artifacts::ensure_dir(&cfg.artifacts_dir);
 
-    // Agent → patch text
-    let agent = NullAgentAdapter;
-    let raw = agent.get_patch_text()?;
+    // Record every input before the agent can touch the worktree
+    let manifest = RunManifest::capture(&cfg, workdir, agent.manifest());
+    let config_sha256 = manifest.sha256()?;
+    record(hooks, Artifacts::write_run_manifest_json(&cfg.artifacts_dir, &manifest.to_json()?)?).await;
+    hooks.log(&format!("ORCH: config sha256={config_sha256}"));
+    let header = metadata_header(&manifest, &config_sha256);
+
+    // Snapshot BEFORE, ahead of the agent editing the worktree. The baseline is the
+    // base commit, so dependencies and API breaks an earlier phase left in the
+    // worktree are still reported
+    let base_commit = cfg.base_branch.as_deref().and_then(|b| apply::merge_base(workdir, b));
+    let (dep_before, api_before) = match base_commit.as_deref() {
+        Some(commit) => baseline_at(workdir, commit).unwrap_or_else(|e| {
+            hooks.log(&format!("ORCH: baseline taken from the worktree: {e}"));
+            (DepSnapshot::capture(workdir), ApiSurface::capture(workdir))
+        }),
+        None => (DepSnapshot::capture(workdir), ApiSurface::capture(workdir)),
+    };
+
+    // Agent → patch text, kept so a replay can apply the same reply. Agents may
+    // edit the worktree directly despite the patch contract; those files count
+    // as touched so the scope guard still sees them
+    let own_dirs = [cfg.artifacts_dir.as_path(), cfg.cache_dir.as_path()];
+    let dirty_before = changed_files(workdir, &own_dirs);
+    let raw = agent.get_patch_text(hooks).await?;
+    let direct = edited_between(&dirty_before, &changed_files(workdir, &own_dirs));
+    for path in &direct {
+        hooks.log(&format!("ORCH: agent edited {path} outside the patch"));
+    }
+    record(hooks, Artifacts::write_agent_output(&cfg.artifacts_dir, &raw)?).await;
+    check_cancelled(hooks).await?;
 
-    // Parse blocks between markers
-    // Confirm markers exist for acceptance
-    let _ = raw.contains("---BEGIN PATCH---");
-    let _ = raw.contains("---END PATCH---");
+    // Parse blocks in whichever format the agent used
     let blocks = patch::parse_blocks(&raw)?;
+    hooks.log(&format!(
+        "ORCH: applying {} patch blocks ({:?})",
+        blocks.len(),
+        blocks.first().map(|b| b.format).unwrap_or(patch::PatchFormat::Markers)
+    ));
 
-        // Snapshot BEFORE
-        let dep_before = crate::artifacts::simple_dep_snapshot(workdir);
+    // Refuse out-of-scope patches before anything is written
+    if cfg.scope.reject_before_apply {
+        let planned: Vec<String> = direct
+            .iter()
+            .cloned()
+            .chain(blocks.iter().flat_map(|b| b.touched_files()))
+            .collect();
+        let violations = cfg.scope.violations(&planned)?;
+        if !violations.is_empty() {
+            for v in &violations {
+                hooks.log(&format!("ORCH: scope violation: {}: {}", v.path, v.reason));
+            }
+            return Err(format!("patch rejected: {} file(s) out of scope", violations.len()));
+        }
+    }
 
-        // Apply each block and gather touched files
-    let mut touched: Vec<String> = Vec::new();
-    for b in &blocks {
-        let res = apply::apply_block(workdir, &b.content)?;
-        touched.extend(res.touched_files);
+    // Apply each block whole and gather touched files; stop at the first block that does not apply
+    let mut touched: Vec<String> = direct;
+    let mut reports: Vec<apply::ApplyResult> = Vec::new();
+    let mut failed_block = None;
+    for (i, b) in blocks.iter().enumerate() {
+        let results = apply::apply_changes(workdir, b, base_commit.as_deref())?;
+        for res in &results {
+            for h in res.hunks.iter().filter(|h| h.strategy != Some(apply::ApplyStrategy::Strict)) {
+                match (&h.strategy, &h.error) {
+                    (Some(strategy), _) => hooks.log(&format!(
+                        "ORCH: block {} {} hunk {} applied via {:?} (offset {})",
+                        i + 1, h.file, h.hunk, strategy, h.offset
+                    )),
+                    (None, Some(error)) => hooks.log(&format!(
+                        "ORCH: block {} {} hunk {} rejected: {}",
+                        i + 1, h.file, h.hunk, error
+                    )),
+                    (None, None) => {}
+                }
+            }
+        }
+        let applied = results.iter().all(|r| r.applied());
+        touched.extend(results.iter().flat_map(|r| r.touched_files.iter().cloned()));
+        reports.extend(results);
+        if !applied {
+            failed_block = Some(i + 1);
+            break;
+        }
+    }
+    let report = serde_json::to_vec_pretty(&reports).map_err(|e| e.to_string())?;
+    record(hooks, Artifacts::write_apply_report_json(&cfg.artifacts_dir, &report)?).await;
+    if let (Some(block), Some(failed)) = (failed_block, reports.last()) {
+        record(hooks, Artifacts::write_rejected_hunks(&cfg.artifacts_dir, &failed.rejected_patch)?).await;
+        return Err(format!(
+            "patch block {} did not apply: {} hunk(s) rejected",
+            block,
+            failed.rejected_count()
+        ));
     }
     touched.sort();
     touched.dedup();
-    Artifacts::write_touched_files(&cfg.artifacts_dir, &touched)?;
+    record(hooks, Artifacts::write_touched_files(&cfg.artifacts_dir, &touched)?).await;
+    hooks.log(&format!("ORCH: touched {} files", touched.len()));
 
-    // Simple dep snapshot (before/after not tracked deeply in MVP). Just write current.
-        // Snapshot AFTER
-        let dep_after = crate::artifacts::simple_dep_snapshot(workdir);
-        let dep_combined = format!("=== BEFORE ===\n{}\n=== AFTER ===\n{}\n", dep_before, dep_after);
-        Artifacts::write_dep_snapshot(&cfg.artifacts_dir, &dep_combined)?;
+    // Snapshot AFTER and diff against the phase's dependency policy
+    let dep_after = DepSnapshot::capture(workdir);
+    let dep_combined = format!("=== BEFORE ===\n{}\n=== AFTER ===\n{}\n", dep_before.render(), dep_after.render());
+    record(hooks, Artifacts::write_dep_snapshot(&cfg.artifacts_dir, &dep_combined)?).await;
+    let dep_report = DepDiffReport::new(&dep_before, &dep_after, &cfg.dep_policy);
+    let dep_json = serde_json::to_vec_pretty(&dep_report).map_err(|e| e.to_string())?;
+    record(hooks, Artifacts::write_dep_diff_json(&cfg.artifacts_dir, &dep_json)?).await;
+    let api_after = ApiSurface::capture(workdir);
+    let api_report = ApiDiffReport::new(&api_before, &api_after);
+    let api_json = serde_json::to_vec_pretty(&api_report).map_err(|e| e.to_string())?;
+    record(hooks, Artifacts::write_api_diff_json(&cfg.artifacts_dir, &api_json)?).await;
 
     // Double run
-    let outcome = test::run_two(workdir, &cfg.cache_dir, None)?;
+    check_cancelled(hooks).await?;
+    let outcome = test::run_two(workdir, &cfg.cache_dir, &cfg.test, hooks).await?;
+    check_cancelled(hooks).await?;
+    let warm_budget = cfg.warm_kpi_budget.unwrap_or(validators::kpi::DEFAULT_WARM_BUDGET_SEC);
     let kpi = format!(
-        "{{\n  \"cold_run_sec\": {:.3},\n  \"warm_run_sec\": {:.3},\n  \"cache_hit_count\": {}\n}}",
-        outcome.cold_sec, outcome.warm_sec, outcome.cache_hit_count
+        "{{\n  \"cold_run_sec\": {:.3},\n  \"warm_run_sec\": {:.3},\n  \"warm_budget_sec\": {:.3},\n  \"cache_hit_count\": {}\n}}",
+        outcome.cold_sec, outcome.warm_sec, warm_budget, outcome.cache_hit_count
     );
-    Artifacts::write_kpi_json_raw(&cfg.artifacts_dir, kpi.as_bytes())?;
+    record(hooks, Artifacts::write_kpi_json_raw(&cfg.artifacts_dir, kpi.as_bytes())?).await;
+    let test_report = serde_json::json!({
+        "cold": { "fingerprint": outcome.cold.fingerprint(), "results": &outcome.cold.results },
+        "warm": { "fingerprint": outcome.warm.fingerprint(), "results": &outcome.warm.results },
+        "flaky": &outcome.flaky
+    });
+    let test_json = serde_json::to_vec_pretty(&test_report).map_err(|e| e.to_string())?;
+    record(hooks, Artifacts::write_test_report_json(&cfg.artifacts_dir, &test_json)?).await;
+    for name in &outcome.flaky {
+        hooks.log(&format!("ORCH: flaky test: {name}"));
+    }
     // Validators
-    let scope = validators::scope_guard::ScopeGuard { touched: &touched, allowlist: None };
+    let scope = validators::scope_guard::ScopeGuard { touched: &touched, policy: &cfg.scope };
     let scope_val = scope.validate()?;
     let scope_res = scope_val.message.clone().unwrap_or_default();
-        let dep_rust = validators::dep_diff::rust::RustDepDiff { before: &dep_before, after: &dep_after };
-        let dep_node = validators::dep_diff::node::NodeDepDiff { before: &dep_before, after: &dep_after };
-    let dep_rust_pass = dep_rust.validate()?.pass;
-    let dep_node_pass = dep_node.validate()?.pass;
-    let dep_msg = if dep_rust_pass && dep_node_pass { "DEP_DIFF: PASS".to_string() } else { "DEP_DIFF: FAIL".to_string() };
+    let dep_rust = RustDepDiff { before: &dep_before.cargo, after: &dep_after.cargo, policy: &cfg.dep_policy }.validate()?;
+    let dep_node = NodeDepDiff { before: &dep_before.npm, after: &dep_after.npm, policy: &cfg.dep_policy }.validate()?;
+    let dep_rust_pass = dep_rust.pass;
+    let dep_node_pass = dep_node.pass;
+    let dep_msg = format!(
+        "{}\n{}",
+        dep_rust.message.unwrap_or_default(),
+        dep_node.message.unwrap_or_default()
+    );
+    let api_val = ApiStability { before: &api_before, after: &api_after }.validate()?;
+    let api_msg = api_val.message.clone().unwrap_or_default();
     let det = validators::determinism::Determinism { snippets: &outcome.snippets };
-    let det_pass = det.validate()?.pass;
-    let kpi_v = validators::kpi::Kpi { kpi_json: &kpi };
+    let det_val = det.validate()?;
+    let det_pass = det_val.pass;
+    let det_msg = det_val.message.unwrap_or_default();
+    let kpi_v = validators::kpi::Kpi { kpi_json: &kpi, warm_budget_sec: warm_budget };
     let kpi_val = kpi_v.validate()?;
     let kpi_msg = kpi_val.message.clone().unwrap_or_default();
+    let tests_pass = outcome.tests_pass();
     let mut combined = String::new();
     combined.push_str(&header);
     combined.push('\n');
@@ -85,7 +362,9 @@ pub fn run_attempt(attempt_id: String, cfg: OrchestratorConfig, workdir: &Path)
     combined.push('\n');
     combined.push_str(&dep_msg);
     combined.push('\n');
-    combined.push_str(if det_pass {"DETERMINISM: PASS"} else {"DETERMINISM: FAIL"});
+    combined.push_str(&api_msg);
+    combined.push('\n');
+    combined.push_str(&det_msg);
     combined.push('\n');
     combined.push_str(&kpi_msg);
     combined.push('\n');
@@ -93,15 +372,18 @@ pub fn run_attempt(attempt_id: String, cfg: OrchestratorConfig, workdir: &Path)
     // Append condensed validators status line for SSE-friendly consumption
     let as_pass_fail = |b: bool| if b { "PASS" } else { "FAIL" };
     let validators_line = format!(
-        "ORCH: validators: scope={} dep={} api=SKIP det={} kpi={}",
+        "ORCH: validators: scope={} dep={} api={} det={} kpi={} tests={}",
         as_pass_fail(scope_val.pass),
         as_pass_fail(dep_rust_pass && dep_node_pass),
+        as_pass_fail(api_val.pass),
         as_pass_fail(det_pass),
-        as_pass_fail(kpi_val.pass)
+        as_pass_fail(kpi_val.pass),
+        as_pass_fail(tests_pass)
     );
     combined.push('\n');
     combined.push_str(&validators_line);
-    Artifacts::write_snippets_log(&cfg.artifacts_dir, &combined)?;
+    hooks.log(&validators_line);
+    record(hooks, Artifacts::write_snippets_log(&cfg.artifacts_dir, &combined)?).await;
 
     // Machine-readable summary.json (idempotent overwrite)
     let summary = serde_json::json!({
@@ -109,18 +391,219 @@ pub fn run_attempt(attempt_id: String, cfg: OrchestratorConfig, workdir: &Path)
         "validator": {
             "scope": scope_val.pass,
             "dep": dep_rust_pass && dep_node_pass,
-            "api": serde_json::Value::Null,
+            "api": api_val.pass,
             "det": det_pass,
-            "kpi": kpi_val.pass
+            "kpi": kpi_val.pass,
+            "tests": tests_pass
         },
         "timing": {
             "cold_sec": outcome.cold_sec,
             "warm_sec": outcome.warm_sec
         },
-        "cache_hit_count": outcome.cache_hit_count
+        "cache_hit_count": outcome.cache_hit_count,
+        "flaky_tests": &outcome.flaky
     });
     let bytes = serde_json::to_vec_pretty(&summary).map_err(|e| e.to_string())?;
-    Artifacts::write_summary_json(&cfg.artifacts_dir, &bytes)?;
+    record(hooks, Artifacts::write_summary_json(&cfg.artifacts_dir, &bytes)?).await;
+    hooks
+        .record_summary(&RunSummary {
+            cold_sec: outcome.cold_sec,
+            warm_sec: outcome.warm_sec,
+            cache_hit_count: outcome.cache_hit_count,
+            scope_pass: scope_val.pass,
+            dep_pass: dep_rust_pass && dep_node_pass,
+            api_pass: Some(api_val.pass),
+            det_pass,
+            kpi_pass: kpi_val.pass,
+            tests_pass,
+        })
+        .await;
+    hooks.log("ORCH: finished");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command, sync::{Arc, Mutex}};
+
+    use async_trait::async_trait;
+    use command_group::AsyncGroupChild;
+    use tokio::sync::RwLock;
+
+    use super::*;
+
+    struct PatchText(&'static str);
+
+    #[async_trait]
+    impl AgentAdapter for PatchText {
+        async fn get_patch_text(&self, _hooks: &dyn RunHooks) -> Result<String, String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[derive(Default)]
+    struct SummaryHooks(Mutex<Option<RunSummary>>);
+
+    #[async_trait]
+    impl RunHooks for SummaryHooks {
+        fn log(&self, _line: &str) {}
+
+        async fn track_child(&self, child: AsyncGroupChild) -> Arc<RwLock<AsyncGroupChild>> {
+            Arc::new(RwLock::new(child))
+        }
+
+        async fn untrack_child(&self) {}
+
+        async fn is_cancelled(&self) -> bool {
+            false
+        }
+
+        async fn record_summary(&self, summary: &RunSummary) {
+            *self.0.lock().unwrap() = Some(summary.clone());
+        }
+    }
+
+    const PATCH: &str = "---BEGIN PATCH---\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-x\n+y\n---END PATCH---\n";
+
+    /// Edits the manifest while it works, as a coding agent may, then returns `PATCH`
+    struct AddsDependency(PathBuf);
+
+    #[async_trait]
+    impl AgentAdapter for AddsDependency {
+        async fn get_patch_text(&self, _hooks: &dyn RunHooks) -> Result<String, String> {
+            fs::write(self.0.join("Cargo.toml"), "[package]\nname = \"demo\"\n[dependencies]\nrand = \"0.8\"\n")
+                .map_err(|e| e.to_string())?;
+            Ok(PATCH.to_string())
+        }
+    }
+
+    /// Run `agent` in a fresh directory holding `a.txt`, with `test_command` as the suite
+    async fn summary_of_run(
+        name: &str,
+        test_command: &str,
+        agent: impl FnOnce(&Path) -> Box<dyn AgentAdapter>,
+    ) -> RunSummary {
+        let dir = std::env::temp_dir().join(format!("vk-run-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "x\n").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        let cfg = OrchestratorConfig {
+            cache_dir: dir.join(".cache"),
+            artifacts_dir: dir.join(".artifacts"),
+            test: test::TestConfig { command: test_command.to_string(), ..Default::default() },
+            ..Default::default()
+        };
+        let agent = agent(&dir);
+        let hooks = SummaryHooks::default();
+        run_attempt("a1".to_string(), cfg, &dir, agent.as_ref(), &hooks).await.unwrap();
+        let summary: serde_json::Value =
+            serde_json::from_slice(&fs::read(dir.join(".artifacts").join("summary.json")).unwrap()).unwrap();
+        let recorded = hooks.0.lock().unwrap().take().unwrap();
+        assert_eq!(summary["validator"]["tests"], recorded.tests_pass);
+        assert!(dir.join(".artifacts").join("agent_output.txt").exists());
+        let _ = fs::remove_dir_all(&dir);
+        recorded
+    }
+
+    async fn summary_with_suite(name: &str, test_command: &str) -> RunSummary {
+        summary_of_run(name, test_command, |_| Box::new(PatchText(PATCH))).await
+    }
+
+    #[tokio::test]
+    async fn red_suite_fails_the_run() {
+        let green = summary_with_suite("green", "echo 'test a ... ok'").await;
+        assert!(green.tests_pass);
+
+        // Failing tests, even when the command itself exits cleanly
+        let failed = summary_with_suite("failed", "echo 'test a ... FAILED'").await;
+        assert!(!failed.tests_pass);
+        assert!(!failed.passed());
+
+        // A non-zero exit, even when no test was reported failed
+        let exit = summary_with_suite("exit", "echo 'test a ... ok'; exit 101").await;
+        assert!(!exit.tests_pass);
+        assert!(!exit.passed());
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let out = Command::new("git")
+            .args(["-c", "user.name=vk", "-c", "user.email=vk@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+    }
+
+    #[tokio::test]
+    async fn dependencies_the_agent_adds_while_working_are_new() {
+        let summary = summary_of_run("deps", "echo 'test a ... ok'", |dir| {
+            Box::new(AddsDependency(dir.to_path_buf()))
+        })
+        .await;
+        assert!(summary.tests_pass);
+        assert!(!summary.dep_pass);
+    }
+
+    /// Writes a file straight into the worktree, then returns `PATCH`
+    struct EditsDirectly(PathBuf);
+
+    #[async_trait]
+    impl AgentAdapter for EditsDirectly {
+        async fn get_patch_text(&self, _hooks: &dyn RunHooks) -> Result<String, String> {
+            fs::write(self.0.join("secrets.env"), "TOKEN=1\n").map_err(|e| e.to_string())?;
+            Ok(PATCH.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn files_the_agent_edits_directly_are_scope_checked() {
+        let dir = std::env::temp_dir().join(format!("vk-run-direct-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "x\n").unwrap();
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["add", "-A"]);
+        git(&dir, &["commit", "-q", "-m", "base"]);
+        let mut cfg = OrchestratorConfig {
+            cache_dir: dir.join(".cache"),
+            artifacts_dir: dir.join(".artifacts"),
+            test: test::TestConfig { command: "echo 'test a ... ok'".to_string(), ..Default::default() },
+            ..Default::default()
+        };
+        cfg.scope.denylist = vec!["*.env".to_string()];
+        let hooks = SummaryHooks::default();
+        run_attempt("a1".to_string(), cfg, &dir, &EditsDirectly(dir.clone()), &hooks).await.unwrap();
+
+        let summary = hooks.0.lock().unwrap().take().unwrap();
+        assert!(!summary.scope_pass);
+        let touched = fs::read_to_string(dir.join(".artifacts").join("touched_files.txt")).unwrap();
+        assert_eq!(touched.lines().collect::<Vec<_>>(), vec!["a.txt", "secrets.env"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn baseline_is_read_from_the_commit_not_the_worktree() {
+        let dir = std::env::temp_dir().join(format!("vk-run-api-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        fs::write(dir.join("src/lib.rs"), "pub fn kept() {}\npub fn dropped() {}\n").unwrap();
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["add", "-A"]);
+        git(&dir, &["commit", "-q", "-m", "base"]);
+        // An earlier phase or the agent removed `dropped` and added a dependency without committing
+        fs::write(dir.join("src/lib.rs"), "pub fn kept() {}\n").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n[dependencies]\nrand = \"0.8\"\n").unwrap();
+
+        let (deps, api) = baseline_at(&dir, "HEAD").unwrap();
+        assert!(api.items.contains_key("demo::dropped"));
+        assert!(!ApiSurface::capture(&dir).items.contains_key("demo::dropped"));
+        assert!(!deps.cargo.contains_key("rand"));
+        assert!(DepSnapshot::capture(&dir).cargo.contains_key("rand"));
+        assert!(baseline_at(&dir, "no-such-commit").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,55 +1,200 @@
-use std::time::Instant;
+use std::{
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+
+use command_group::AsyncCommandGroup;
+use tokio::{io::AsyncReadExt, time::Instant};
+
+use crate::{
+    process::wait_tracked,
+    test_report::{TestOutcome, TestReport},
+    RunHooks,
+};
+
+pub const DEFAULT_TEST_COMMAND: &str = "cargo test --workspace";
+pub const DEFAULT_COLD_TIMEOUT_SEC: u64 = 900;
+pub const DEFAULT_WARM_TIMEOUT_SEC: u64 = 600;
+
+/// How the cold and warm test runs are invoked. `{cache_dir}` in env values
+/// expands to the orchestrator cache, e.g. `CARGO_TARGET_DIR={cache_dir}/target`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TestConfig {
+    pub command: String,
+    pub cold_timeout_sec: u64,
+    pub warm_timeout_sec: u64,
+    pub env: Vec<(String, String)>,
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self {
+            command: DEFAULT_TEST_COMMAND.to_string(),
+            cold_timeout_sec: DEFAULT_COLD_TIMEOUT_SEC,
+            warm_timeout_sec: DEFAULT_WARM_TIMEOUT_SEC,
+            env: Vec::new(),
+        }
+    }
+}
+
+impl TestConfig {
+    /// Parse `KEY=VALUE` lines; blank lines and `#` comments are skipped
+    pub fn parse_env(text: &str) -> Result<Vec<(String, String)>, String> {
+        let mut env = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let (key, value) = line.split_once('=').ok_or_else(|| format!("invalid env line: {line}"))?;
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(format!("invalid env key: {key:?}"));
+            }
+            env.push((key.to_string(), value.trim().to_string()));
+        }
+        Ok(env)
+    }
+
+    fn resolved_env(&self, cache_dir: &std::path::Path) -> Vec<(String, String)> {
+        let cache = cache_dir.to_string_lossy();
+        self.env.iter().map(|(k, v)| (k.clone(), v.replace("{cache_dir}", &cache))).collect()
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct RunOutcome {
     pub cold_sec: f64,
     pub warm_sec: f64,
     pub cache_hit_count: u32,
+    pub cold: TestReport,
+    pub warm: TestReport,
+    /// Exit status of the test command in each run
+    pub cold_status: ExitStatus,
+    pub warm_status: ExitStatus,
+    /// Tests whose outcome differed between the cold and warm run
+    pub flaky: Vec<String>,
     pub snippets: String,
 }
 
-fn run_once(work_dir: &std::path::Path, cmd: &str, timeout_s: u64) -> Result<(f64, String), String> {
+impl RunOutcome {
+    /// The warm run exited cleanly and none of its tests failed
+    pub fn tests_pass(&self) -> bool {
+        self.warm_status.success() && self.warm.count(TestOutcome::Failed) == 0
+    }
+}
+
+async fn run_once(
+    work_dir: &std::path::Path,
+    cmd: &str,
+    env: &[(String, String)],
+    timeout_s: u64,
+    hooks: &dyn RunHooks,
+) -> Result<(f64, String, ExitStatus), String> {
     let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
     let start = Instant::now();
-    let mut child = std::process::Command::new(shell.0)
+    // Own process group, so stopping the run takes cargo's children down too
+    let mut child = tokio::process::Command::new(shell.0)
         .arg(shell.1)
         .arg(cmd)
         .current_dir(work_dir)
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .group_spawn()
         .map_err(|e| e.to_string())?;
-    let to = std::time::Duration::from_secs(timeout_s);
-    // Naive timeout loop
-    loop {
-        if let Ok(Some(_)) = child.try_wait() { break; }
-        if start.elapsed() > to { let _ = child.kill(); return Err("test run timed out".to_string()); }
-        std::thread::sleep(std::time::Duration::from_millis(100));
-    }
-    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    let mut stdout = child.inner().stdout.take().ok_or("test stdout not captured")?;
+    let mut stderr = child.inner().stderr.take().ok_or("test stderr not captured")?;
+    let out = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf).await;
+        buf
+    });
+    let err = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf).await;
+        buf
+    });
+
+    let child = hooks.track_child(child).await;
+    let waited = wait_tracked(&child, Some(Duration::from_secs(timeout_s))).await;
+    hooks.untrack_child().await;
+    let status = waited.map_err(|e| format!("test run {e}"))?;
+
+    let stdout = out.await.unwrap_or_default();
+    let stderr = err.await.unwrap_or_default();
     let secs = start.elapsed().as_secs_f64();
-    let text = String::from_utf8_lossy(&output.stdout).to_string() +
-        &String::from_utf8_lossy(&output.stderr);
-    Ok((secs, text))
+    let text = String::from_utf8_lossy(&stdout).to_string() + &String::from_utf8_lossy(&stderr);
+    Ok((secs, text, status))
 }
 
-pub fn run_two(work_dir: &std::path::Path, _cache_dir: &std::path::Path, test_cmd: Option<&str>) -> Result<RunOutcome, String> {
-    let cmd = test_cmd.unwrap_or("cargo test --workspace");
-    let (cold_s, cold_out) = run_once(work_dir, cmd, 900)?;
-    let (warm_s, warm_out) = run_once(work_dir, cmd, 600)?;
+pub async fn run_two(
+    work_dir: &std::path::Path,
+    cache_dir: &std::path::Path,
+    test: &TestConfig,
+    hooks: &dyn RunHooks,
+) -> Result<RunOutcome, String> {
+    let cmd = test.command.as_str();
+    let env = test.resolved_env(cache_dir);
+    if !env.is_empty() {
+        std::fs::create_dir_all(cache_dir).map_err(|e| e.to_string())?;
+    }
+    hooks.log(&format!("ORCH: test cold run: {cmd}"));
+    let (cold_s, cold_out, cold_status) = run_once(work_dir, cmd, &env, test.cold_timeout_sec, hooks).await?;
+    if hooks.is_cancelled().await {
+        return Err("cancelled".to_string());
+    }
+    hooks.log(&format!("ORCH: test warm run: {cmd}"));
+    let (warm_s, warm_out, warm_status) = run_once(work_dir, cmd, &env, test.warm_timeout_sec, hooks).await?;
     let mut cache_hits = 0u32;
     for line in cold_out.lines().chain(warm_out.lines()) {
         if line.to_uppercase().contains("CACHE_HIT") { cache_hits += 1; }
     }
-    // very small digest: sum of bytes as hex for both runs
-    let sum_cold: u64 = cold_out.as_bytes().iter().fold(0u64, |acc, b| acc.wrapping_add(*b as u64));
-    let sum_warm: u64 = warm_out.as_bytes().iter().fold(0u64, |acc, b| acc.wrapping_add(*b as u64));
-    let digest_cold = format!("OUTPUT_DIGEST_COLD={:016x}", sum_cold);
-    let digest_warm = format!("OUTPUT_DIGEST_WARM={:016x}", sum_warm);
+    let cold = TestReport::parse(&cold_out);
+    let warm = TestReport::parse(&warm_out);
+    let flaky = cold.flaky_against(&warm);
     let mut lines = Vec::new();
     if cache_hits>0 { lines.push("CACHE_HIT".to_string()); }
-    lines.push(digest_cold);
-    lines.push(digest_warm);
+    for (label, report) in [("COLD", &cold), ("WARM", &warm)] {
+        lines.push(format!("TEST_FINGERPRINT_{label}={}", report.fingerprint()));
+        lines.push(format!(
+            "TEST_COUNTS_{label}=passed:{} failed:{} ignored:{}",
+            report.count(TestOutcome::Passed),
+            report.count(TestOutcome::Failed),
+            report.count(TestOutcome::Ignored)
+        ));
+    }
+    for name in &flaky {
+        lines.push(format!("FLAKY={name}"));
+    }
     let snippets = lines.join("\n");
-    Ok(RunOutcome { cold_sec: cold_s, warm_sec: warm_s, cache_hit_count: cache_hits, snippets })
+    Ok(RunOutcome {
+        cold_sec: cold_s,
+        warm_sec: warm_s,
+        cache_hit_count: cache_hits,
+        cold,
+        warm,
+        cold_status,
+        warm_status,
+        flaky,
+        snippets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_env_lines_and_expands_cache_dir() {
+        let env = TestConfig::parse_env("# cache\nCARGO_TARGET_DIR={cache_dir}/target\n\nnpm_config_cache = {cache_dir}/npm\n").unwrap();
+        let cfg = TestConfig { env, ..Default::default() };
+        assert_eq!(
+            cfg.resolved_env(std::path::Path::new("/data/cache")),
+            vec![
+                ("CARGO_TARGET_DIR".to_string(), "/data/cache/target".to_string()),
+                ("npm_config_cache".to_string(), "/data/cache/npm".to_string()),
+            ]
+        );
+        assert!(TestConfig::parse_env("NOEQUALS").is_err());
+    }
 }
@@ -0,0 +1,327 @@
+use std::{collections::BTreeMap, path::Path, sync::LazyLock};
+
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+impl TestOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+            Self::Ignored => "ignored",
+        }
+    }
+}
+
+/// Per-test outcomes parsed from a test run, keyed by `group::name` where the
+/// group is the test binary, doc-test crate or jest file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct TestReport {
+    pub results: BTreeMap<String, TestOutcome>,
+}
+
+impl TestReport {
+    /// Parse `cargo test` (human or libtest JSON), nextest and jest output.
+    /// Lines that are not test results are ignored.
+    pub fn parse(output: &str) -> Self {
+        let mut report = Self::default();
+        let mut group = String::new();
+        for line in output.lines() {
+            let trimmed = line.trim();
+            if let Some(g) = parse_group(trimmed) {
+                group = g;
+            } else if let Some((name, outcome)) = parse_result(trimmed) {
+                let name = normalize_name(&name);
+                let key = if group.is_empty() {
+                    name
+                } else {
+                    format!("{group}::{name}")
+                };
+                report.results.insert(key, outcome);
+            }
+        }
+        report
+    }
+
+    pub fn count(&self, outcome: TestOutcome) -> usize {
+        self.results.values().filter(|o| **o == outcome).count()
+    }
+
+    /// SHA-256 over the sorted `name outcome` pairs. Durations, paths and
+    /// execution order do not affect it.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (name, outcome) in &self.results {
+            hasher.update(name.as_bytes());
+            hasher.update(b"\t");
+            hasher.update(outcome.as_str().as_bytes());
+            hasher.update(b"\n");
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Tests whose outcome differs between two runs, or that only ran once
+    pub fn flaky_against(&self, other: &TestReport) -> Vec<String> {
+        let mut flaky: Vec<String> = self
+            .results
+            .iter()
+            .filter(|(name, outcome)| other.results.get(*name) != Some(outcome))
+            .map(|(name, _)| name.clone())
+            .collect();
+        flaky.extend(
+            other
+                .results
+                .keys()
+                .filter(|name| !self.results.contains_key(*name))
+                .cloned(),
+        );
+        flaky.sort();
+        flaky
+    }
+}
+
+/// Lines announcing which binary or file the following results belong to
+fn parse_group(line: &str) -> Option<String> {
+    // `Running unittests src/lib.rs (target/debug/deps/validators-5cee605643d03312)`
+    if let Some(rest) = line.strip_prefix("Running ") {
+        let binary = rest.rsplit(['/', '\\']).next()?.trim_end_matches(')');
+        let binary = binary
+            .rsplit_once('-')
+            .map(|(name, _)| name)
+            .unwrap_or(binary);
+        let target = rest
+            .split_whitespace()
+            .nth(1)
+            .filter(|_| rest.starts_with("unittests"));
+        return Some(match target {
+            Some(src) => format!("{binary}[{src}]"),
+            None => binary.to_string(),
+        });
+    }
+    if let Some(krate) = line.strip_prefix("Doc-tests ") {
+        return Some(format!("doc:{}", krate.trim()));
+    }
+    // jest file headers: `PASS src/a.test.ts` / `FAIL src/a.test.ts (5.2 s)`
+    for prefix in ["PASS ", "FAIL "] {
+        if let Some(file) = line.strip_prefix(prefix) {
+            if !file.starts_with('[') {
+                return Some(strip_timing(file).to_string());
+            }
+        }
+    }
+    None
+}
+
+fn parse_result(line: &str) -> Option<(String, TestOutcome)> {
+    // libtest: `test tests::it_works ... ok`
+    if let Some(rest) = line.strip_prefix("test ") {
+        let (name, status) = rest.rsplit_once(" ... ")?;
+        let outcome = match status.split(',').next()?.trim() {
+            "ok" => TestOutcome::Passed,
+            "FAILED" => TestOutcome::Failed,
+            "ignored" => TestOutcome::Ignored,
+            _ => return None,
+        };
+        return Some((name.to_string(), outcome));
+    }
+    // libtest JSON: `{ "type": "test", "event": "ok", "name": "tests::it_works" }`
+    if line.starts_with('{') {
+        let v: serde_json::Value = serde_json::from_str(line).ok()?;
+        if v.get("type")?.as_str()? != "test" {
+            return None;
+        }
+        let outcome = match v.get("event")?.as_str()? {
+            "ok" => TestOutcome::Passed,
+            "failed" | "timeout" => TestOutcome::Failed,
+            "ignored" => TestOutcome::Ignored,
+            _ => return None,
+        };
+        return Some((v.get("name")?.as_str()?.to_string(), outcome));
+    }
+    // nextest: `PASS [   0.004s] validators dep_diff::tests::classifies_changes`
+    for (prefix, outcome) in [
+        ("PASS [", TestOutcome::Passed),
+        ("FAIL [", TestOutcome::Failed),
+        ("TIMEOUT [", TestOutcome::Failed),
+        ("SKIP [", TestOutcome::Ignored),
+    ] {
+        if let Some(rest) = line.strip_prefix(prefix) {
+            let (_, test) = rest.split_once("] ")?;
+            let (binary, name) = test.trim().split_once(' ')?;
+            return Some((format!("{binary}::{}", name.trim()), outcome));
+        }
+    }
+    // jest: `✓ adds numbers (3 ms)`, `✕ rejects input`, `○ skipped later`
+    for (marks, outcome) in [
+        (&["✓ ", "√ "][..], TestOutcome::Passed),
+        (&["✕ ", "× "][..], TestOutcome::Failed),
+        (&["○ skipped ", "○ "][..], TestOutcome::Ignored),
+    ] {
+        if let Some(name) = marks.iter().find_map(|m| line.strip_prefix(m)) {
+            return Some((strip_timing(name).to_string(), outcome));
+        }
+    }
+    None
+}
+
+/// Drop a trailing `(3 ms)` / `(5.2 s)` duration
+fn strip_timing(s: &str) -> &str {
+    let s = s.trim_end();
+    if let Some(open) = s.rfind(" (") {
+        let inner = &s[open + 2..];
+        if inner.ends_with(" ms)") || inner.ends_with(" s)") {
+            return &s[..open];
+        }
+    }
+    s
+}
+
+/// Absolute directories whose first component differs between runs, with the
+/// placeholder that replaces both. Longest first so a worktree under the temp
+/// directory is not reported as `<tmp>`.
+static VOLATILE_ROOTS: LazyLock<Vec<(String, &'static str)>> = LazyLock::new(|| {
+    let mut roots = vec![
+        (
+            dir_prefix(&utils::path::get_vibe_kanban_temp_dir().join("worktrees")),
+            "<worktree>",
+        ),
+        (dir_prefix(&std::env::temp_dir()), "<tmp>"),
+    ];
+    for fixed in ["/tmp/", "/var/tmp/", "/private/tmp/"] {
+        roots.push((fixed.to_string(), "<tmp>"));
+    }
+    roots.sort_by_key(|(root, _)| (std::cmp::Reverse(root.len()), root.clone()));
+    roots.dedup_by(|a, b| a.0 == b.0);
+    roots
+});
+
+fn dir_prefix(dir: &Path) -> String {
+    let mut prefix = dir.to_string_lossy().into_owned();
+    if !prefix.ends_with(['/', '\\']) {
+        prefix.push(std::path::MAIN_SEPARATOR);
+    }
+    prefix
+}
+
+/// Remove parts of a test name that change between otherwise identical runs:
+/// doc-test line numbers and temporary or worktree directories.
+fn normalize_name(name: &str) -> String {
+    let mut name = name.trim().to_string();
+    // `src/lib.rs - foo (line 12)`
+    if let Some(open) = name.rfind(" (line ") {
+        if name.ends_with(')') {
+            name.truncate(open);
+        }
+    }
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name.as_str();
+    loop {
+        // earliest match; on a tie the longer root, which sorts first
+        let found = VOLATILE_ROOTS
+            .iter()
+            .filter_map(|(root, placeholder)| {
+                rest.find(root.as_str()).map(|i| (i, root, placeholder))
+            })
+            .min_by_key(|(i, _, _)| *i);
+        let Some((i, root, placeholder)) = found else {
+            break;
+        };
+        out.push_str(&rest[..i]);
+        out.push_str(placeholder);
+        let after = &rest[i + root.len()..];
+        let end = after.find(['/', '\\', ' ']).unwrap_or(after.len());
+        rest = &after[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cargo_test_output() {
+        let out = "\
+     Running unittests src/lib.rs (target/debug/deps/validators-5cee605643d03312)
+running 3 tests
+test dep_diff::tests::b ... ok
+test dep_diff::tests::a ... FAILED
+test slow ... ignored, needs network
+     Running tests/api.rs (target/debug/deps/api-0123456789abcdef)
+test a ... ok
+   Doc-tests validators
+test src/lib.rs - helper (line 12) ... ok
+";
+        let report = TestReport::parse(out);
+        let keys: Vec<&str> = report.results.keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "api::a",
+                "doc:validators::src/lib.rs - helper",
+                "validators[src/lib.rs]::dep_diff::tests::a",
+                "validators[src/lib.rs]::dep_diff::tests::b",
+                "validators[src/lib.rs]::slow",
+            ]
+        );
+        assert_eq!(report.count(TestOutcome::Failed), 1);
+        assert_eq!(report.count(TestOutcome::Ignored), 1);
+    }
+
+    #[test]
+    fn fingerprint_ignores_order_and_timing() {
+        let a =
+            TestReport::parse("PASS src/a.test.ts (5.2 s)\n  ✓ adds (3 ms)\n  ✕ rejects (1 ms)\n");
+        let b =
+            TestReport::parse("PASS src/a.test.ts (1.1 s)\n  ✕ rejects (9 ms)\n  ✓ adds (12 ms)\n");
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(
+            a.results.get("src/a.test.ts::adds"),
+            Some(&TestOutcome::Passed)
+        );
+
+        let nextest = TestReport::parse("        PASS [   0.004s] validators tests::a\n        FAIL [   1.200s] validators tests::b\n");
+        let json = TestReport::parse(
+            "{ \"type\": \"test\", \"event\": \"ok\", \"name\": \"validators::tests::a\" }\n{ \"type\": \"test\", \"event\": \"failed\", \"name\": \"validators::tests::b\" }\n",
+        );
+        assert_eq!(nextest.fingerprint(), json.fingerprint());
+    }
+
+    #[test]
+    fn reports_flaky_tests_individually() {
+        let cold = TestReport::parse("test a ... ok\ntest b ... ok\ntest c ... ok\n");
+        let warm = TestReport::parse("test a ... ok\ntest b ... FAILED\ntest d ... ok\n");
+        assert_ne!(cold.fingerprint(), warm.fingerprint());
+        assert_eq!(cold.flaky_against(&warm), vec!["b", "c", "d"]);
+    }
+
+    #[test]
+    fn normalizes_temp_and_worktree_directories() {
+        let worktree = utils::path::get_vibe_kanban_temp_dir()
+            .join("worktrees")
+            .join("vk-1a2b-fix");
+        let file = worktree.join("src").join("a.rs");
+        let name = format!("reads {} and /var/tmp/build-9/out", file.display());
+        let sep = std::path::MAIN_SEPARATOR;
+        assert_eq!(
+            normalize_name(&name),
+            format!("reads <worktree>{sep}src{sep}a.rs and <tmp>/out")
+        );
+
+        let temp = std::env::temp_dir().join(".tmpAb12Cd").join("fixture");
+        let other = std::env::temp_dir().join(".tmpZz99Yy").join("fixture");
+        assert_eq!(
+            normalize_name(&temp.display().to_string()),
+            normalize_name(&other.display().to_string())
+        );
+        assert_eq!(normalize_name("uses /tmp/.tmpX1/data"), "uses <tmp>/data");
+    }
+}
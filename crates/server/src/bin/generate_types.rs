@@ -31,9 +31,29 @@ fn generate_types_content() -> String {
         db::models::task::TaskWithAttemptStatus::decl(),
         db::models::task::CreateTask::decl(),
         db::models::task::UpdateTask::decl(),
+        db::models::phase::PhaseType::decl(),
+        db::models::phase::PhaseStatus::decl(),
+        db::models::phase::Phase::decl(),
+        db::models::phase::CreatePhase::decl(),
+        db::models::phase::UpdatePhase::decl(),
+        server::routes::pipelines::StartPipelineRequest::decl(),
+        db::models::metrics::MetricsFilter::decl(),
+        db::models::metrics::MetricsSummary::decl(),
+        db::models::metrics::ProfileMetrics::decl(),
+        db::models::metrics::DurationStats::decl(),
+        db::models::metrics::DurationMetrics::decl(),
+        db::models::metrics::ValidatorMetrics::decl(),
+        db::models::metrics::TokenMetrics::decl(),
+        db::models::metrics::TestTimeMetrics::decl(),
+        db::models::token_usage::ModelTokenUsage::decl(),
+        services::services::pricing::TokenSpend::decl(),
         db::models::image::Image::decl(),
         db::models::image::CreateImage::decl(),
         utils::response::ApiResponse::<()>::decl(),
+        utils::approvals::ApprovalStatus::decl(),
+        utils::approvals::ToolApproval::decl(),
+        server::routes::execution_processes::ApprovalDecision::decl(),
+        server::routes::execution_processes::SendMessageRequest::decl(),
         server::routes::config::UserSystemInfo::decl(),
         server::routes::config::Environment::decl(),
         server::routes::config::McpServerQuery::decl(),
@@ -69,12 +89,24 @@ fn generate_types_content() -> String {
         executors::executors::codex::Codex::decl(),
         executors::executors::cursor::Cursor::decl(),
         executors::executors::opencode::Opencode::decl(),
+        executors::executors::custom::Custom::decl(),
+        executors::executors::custom::PromptDelivery::decl(),
+        executors::executors::custom::OutputSchema::decl(),
+        executors::executors::custom::OutputRule::decl(),
+        executors::executors::custom::EntryKind::decl(),
+        executors::executors::custom::ActionRule::decl(),
         executors::actions::coding_agent_initial::CodingAgentInitialRequest::decl(),
         executors::actions::coding_agent_follow_up::CodingAgentFollowUpRequest::decl(),
+        executors::actions::orchestrator::OrchestratorRequest::decl(),
         server::routes::task_attempts::CreateTaskAttemptBody::decl(),
         server::routes::task_attempts::RebaseTaskAttemptRequest::decl(),
         server::routes::task_attempts::BranchStatus::decl(),
         db::models::task_attempt::TaskAttempt::decl(),
+        db::models::task_attempt::AttemptTelemetry::decl(),
+        db::models::attempt_artifact::AttemptArtifact::decl(),
+        server::routes::attempts_orchestrator::ArtifactRun::decl(),
+        server::routes::attempts_orchestrator::AttemptArtifacts::decl(),
+        server::routes::attempts_orchestrator::ReplayQuery::decl(),
         db::models::execution_process::ExecutionProcess::decl(),
         db::models::execution_process::ExecutionProcessStatus::decl(),
         db::models::execution_process::ExecutionProcessRunReason::decl(),
@@ -0,0 +1,634 @@
+//! Headless orchestrator runs for CI and batch jobs: agent → apply → test →
+//! validate once, print the validator summary and exit non-zero on failure.
+
+use std::{
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use command_group::AsyncGroupChild;
+use db::{
+    models::{
+        execution_process::{
+            CreateExecutionProcess, ExecutionProcess, ExecutionProcessRunReason,
+            ExecutionProcessStatus,
+        },
+        phase::{Phase, UpdatePhase},
+        task::{Task, TaskStatus},
+        task_attempt::TaskAttempt,
+    },
+    DBService,
+};
+use executors::actions::{orchestrator::OrchestratorRequest, ExecutorAction, ExecutorActionType};
+use orchestrator::{
+    agent::{resolve_profile, CodingAgentAdapter},
+    artifacts::Artifacts,
+    manifest::AgentManifest,
+    prompt::{assemble_prompt, PhaseKind, PromptRequest, DEFAULT_TOKEN_BUDGET},
+    record::RunRecorder,
+    run::{OrchestratorRun, RunSummary},
+    test::TestConfig,
+    AgentAdapter, OrchestratorConfig, PatchFileAdapter, RunHooks,
+};
+use server::routes::attempts_orchestrator::{
+    ensure_attempt_idle, mark_phase_running, phase_kind, plan_run, RunPlan,
+};
+use services::services::{config::load_config_from_file, git::GitService};
+use tokio::sync::RwLock;
+use tracing_subscriber::EnvFilter;
+use utils::{assets::config_path, log_msg::TokenUsage};
+use uuid::Uuid;
+use validators::dep_diff::DepPolicy;
+
+const USAGE: &str = "\
+Usage: vibe-orchestrator --repo <path> (--task <text> | --prompt-file <path>) [options]
+       vibe-orchestrator --attempt <id> [--repo <path>] [options]
+
+Runs agent -> apply -> test -> validate once and prints the validator summary.
+Exits 0 when every validator passed, 1 when the run failed and 2 on usage errors.
+
+Options:
+  --repo <path>            Repository to apply and test in; with --attempt defaults to its worktree,
+                           and only runs in that worktree commit the applied patch
+  --task <text>            Task title and description to build the prompt from
+  --prompt-file <path>     Use the file's contents as the prompt verbatim
+  --phase-config <path>    Phase JSON in the shape PATCH /api/phases/{id} accepts
+                           (type, allowlist, denylist, agent_override, warm_kpi_budget,
+                           dep_policy, reject_out_of_scope)
+  --profile <label>        Agent profile, optionally as profile:variant (default: claude-code)
+  --patch-file <path>      Skip the agent and apply the patch blocks in this file
+  --test-command <cmd>     Test command (default: cargo test --workspace)
+  --test-env-file <path>   KEY=VALUE lines passed to the test runs
+  --base-branch <name>     Branch to merge against when hunks no longer apply cleanly
+  --artifacts-dir <path>   Where the run writes artifacts (default: ./orchestrator-artifacts)
+  --cache-dir <path>       Test cache (default: ./orchestrator-cache)
+  --attempt <id>           Run an attempt from the local database and record the run there,
+                           moving its phase and task like a run started from the UI
+  -h, --help               Show this help
+";
+
+const DEFAULT_PROFILE: &str = "claude-code";
+
+#[derive(Debug, Default)]
+struct Args {
+    repo: Option<PathBuf>,
+    task: Option<String>,
+    prompt_file: Option<PathBuf>,
+    phase_config: Option<PathBuf>,
+    profile: Option<String>,
+    patch_file: Option<PathBuf>,
+    test_command: Option<String>,
+    test_env_file: Option<PathBuf>,
+    base_branch: Option<String>,
+    artifacts_dir: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    attempt: Option<Uuid>,
+}
+
+impl Args {
+    /// `Ok(None)` when help was asked for
+    fn parse(mut argv: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut args = Args::default();
+        while let Some(flag) = argv.next() {
+            if flag == "-h" || flag == "--help" {
+                return Ok(None);
+            }
+            let (flag, inline) = match flag.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (flag, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| argv.next())
+                    .ok_or_else(|| format!("{flag} needs a value"))
+            };
+            match flag.as_str() {
+                "--repo" => args.repo = Some(value()?.into()),
+                "--task" => args.task = Some(value()?),
+                "--prompt-file" => args.prompt_file = Some(value()?.into()),
+                "--phase-config" => args.phase_config = Some(value()?.into()),
+                "--profile" => args.profile = Some(value()?),
+                "--patch-file" => args.patch_file = Some(value()?.into()),
+                "--test-command" => args.test_command = Some(value()?),
+                "--test-env-file" => args.test_env_file = Some(value()?.into()),
+                "--base-branch" => args.base_branch = Some(value()?),
+                "--artifacts-dir" => args.artifacts_dir = Some(value()?.into()),
+                "--cache-dir" => args.cache_dir = Some(value()?.into()),
+                "--attempt" => {
+                    let id = value()?;
+                    args.attempt = Some(
+                        Uuid::parse_str(&id).map_err(|_| format!("invalid attempt id: {id}"))?,
+                    );
+                }
+                other => return Err(format!("unknown argument: {other}")),
+            }
+        }
+        if args.task.is_some() && args.prompt_file.is_some() {
+            return Err("--task and --prompt-file are mutually exclusive".to_string());
+        }
+        if args.attempt.is_none() {
+            if args.repo.is_none() {
+                return Err("--repo is required without --attempt".to_string());
+            }
+            if args.task.is_none() && args.prompt_file.is_none() {
+                return Err(
+                    "one of --task or --prompt-file is required without --attempt".to_string(),
+                );
+            }
+        }
+        Ok(Some(args))
+    }
+}
+
+/// Progress goes to stdout; Ctrl-C cancels the run and kills the running child.
+#[derive(Default)]
+struct CliHooks {
+    /// Set for runs started with `--attempt`
+    recorder: Option<RunRecorder>,
+    child: tokio::sync::Mutex<Option<Arc<RwLock<AsyncGroupChild>>>>,
+    cancelled: AtomicBool,
+    summary: Mutex<Option<RunSummary>>,
+}
+
+impl CliHooks {
+    async fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(child) = self.child.lock().await.clone() {
+            let _ = child.write().await.kill().await;
+        }
+    }
+}
+
+#[async_trait]
+impl RunHooks for CliHooks {
+    fn log(&self, line: &str) {
+        println!("{line}");
+    }
+
+    async fn track_child(&self, child: AsyncGroupChild) -> Arc<RwLock<AsyncGroupChild>> {
+        let child = Arc::new(RwLock::new(child));
+        *self.child.lock().await = Some(child.clone());
+        child
+    }
+
+    async fn untrack_child(&self) {
+        self.child.lock().await.take();
+    }
+
+    async fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    async fn record_artifact(&self, kind: &str, path: &std::path::Path) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record_artifact(kind, path).await;
+        }
+    }
+
+    async fn record_usage(&self, usage: &TokenUsage) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record_usage(usage).await;
+        }
+    }
+
+    async fn record_summary(&self, summary: &RunSummary) {
+        *self.summary.lock().unwrap() = Some(summary.clone());
+        if let Some(recorder) = &self.recorder {
+            recorder.record_summary(summary).await;
+        }
+    }
+}
+
+/// Orchestrator data directory the server would use, see `attempts_orchestrator::data_dir`
+async fn orchestrator_data_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("VK_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    load_config_from_file(&config_path())
+        .await
+        .workspace_dir
+        .map(PathBuf::from)
+}
+
+/// Apply a phase config file over the run's prompt kind, scope and policies
+fn apply_phase_config(
+    update: UpdatePhase,
+    kind: &mut PhaseKind,
+    profile: &mut Option<String>,
+    cfg: &mut OrchestratorConfig,
+) {
+    if let Some(phase_type) = update.phase_type {
+        *kind = phase_kind(phase_type);
+    }
+    if let Some(allowlist) = update.allowlist {
+        cfg.scope.allowlist = allowlist;
+    }
+    if let Some(denylist) = update.denylist {
+        cfg.scope.denylist = denylist;
+    }
+    if let Some(reject) = update.reject_out_of_scope {
+        cfg.scope.reject_before_apply = reject;
+    }
+    if let Some(budget) = update.warm_kpi_budget {
+        cfg.warm_kpi_budget = budget;
+    }
+    if let Some(policy) = update.dep_policy {
+        cfg.dep_policy = DepPolicy::from_json(policy.map(|v| v.to_string()).as_deref());
+    }
+    if let Some(Some(agent_override)) = update.agent_override {
+        profile.get_or_insert(agent_override);
+    }
+}
+
+fn print_summary(
+    summary: Option<&RunSummary>,
+    result: &Result<(), String>,
+    artifacts_dir: &std::path::Path,
+) {
+    let verdict = |pass: bool| if pass { "PASS" } else { "FAIL" };
+    println!();
+    match summary {
+        Some(s) => {
+            println!("scope  {}", verdict(s.scope_pass));
+            println!("dep    {}", verdict(s.dep_pass));
+            println!("api    {}", s.api_pass.map(verdict).unwrap_or("SKIP"));
+            println!("det    {}", verdict(s.det_pass));
+            println!("kpi    {}", verdict(s.kpi_pass));
+            println!("tests  {}", verdict(s.tests_pass));
+            println!(
+                "cold {:.3}s, warm {:.3}s, {} cache hit(s)",
+                s.cold_sec, s.warm_sec, s.cache_hit_count
+            );
+        }
+        None => println!("validators did not run"),
+    }
+    if let Err(e) = result {
+        println!("failed: {e}");
+    }
+    println!("artifacts: {}", artifacts_dir.display());
+}
+
+/// Settle a run started with `--attempt` the way the server settles its runs:
+/// record how it ended, commit the applied patch when it ran in the attempt's
+/// worktree, settle the phase and move the task to review once it is ready.
+/// Unlike the server, no notification is sent.
+async fn finish_attempt_run(
+    recorder: &RunRecorder,
+    attempt: &TaskAttempt,
+    workdir: &std::path::Path,
+    result: &Result<(), String>,
+    passed: bool,
+    cancelled: bool,
+) {
+    let pool = &recorder.db.pool;
+    let (status, exit_code) = match (result, cancelled) {
+        (_, true) => (ExecutionProcessStatus::Killed, None),
+        (Ok(()), false) => (ExecutionProcessStatus::Completed, Some(0)),
+        (Err(_), false) => (ExecutionProcessStatus::Failed, Some(1)),
+    };
+    if let Err(e) =
+        ExecutionProcess::update_completion(pool, recorder.execution_process_id, status, exit_code)
+            .await
+    {
+        tracing::error!("Failed to record run completion: {}", e);
+    }
+    if cancelled {
+        let _ = Phase::finish_attempt(pool, attempt.id, false).await;
+        return;
+    }
+
+    let in_worktree = attempt
+        .container_ref
+        .as_ref()
+        .and_then(|dir| std::path::Path::new(dir).canonicalize().ok())
+        .is_some_and(|dir| dir == workdir);
+    if result.is_ok() && in_worktree {
+        let message = format!("Apply orchestrator patch for task attempt {}", attempt.id);
+        match GitService::new().commit(workdir, &message) {
+            Ok(true) => println!("ORCH: committed changes"),
+            Ok(false) => println!("ORCH: no changes to commit"),
+            Err(e) => tracing::error!(
+                "Failed to commit orchestrator changes for {}: {}",
+                attempt.id,
+                e
+            ),
+        }
+    }
+    match Phase::finish_attempt(pool, attempt.id, passed).await {
+        Ok(true) => {
+            if let Err(e) = Task::update_status(pool, attempt.task_id, TaskStatus::InReview).await {
+                tracing::error!("Failed to update task status to InReview: {}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => tracing::error!("Failed to settle phase of attempt {}: {}", attempt.id, e),
+    }
+}
+
+async fn run(args: Args) -> Result<bool, String> {
+    let db = match args.attempt {
+        Some(_) => Some(
+            DBService::new()
+                .await
+                .map_err(|e| format!("database: {e}"))?,
+        ),
+        None => None,
+    };
+
+    // Start from the attempt's plan when there is one, else from an empty phase
+    let mut kind = PhaseKind::default();
+    let mut profile = args.profile.clone();
+    let (attempt, plan) = match (args.attempt, &db) {
+        (Some(attempt_id), Some(db)) => {
+            let attempt = TaskAttempt::find_by_id(&db.pool, attempt_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("attempt {attempt_id} not found"))?;
+            let plan = plan_run(&db.pool, &attempt, None)
+                .await
+                .map_err(|(_, body)| format!("cannot plan run: {body}"))?;
+            (Some(attempt), Some(plan))
+        }
+        _ => (None, None),
+    };
+    let (mut cfg, planned_prompt, project_id, phase) = match plan {
+        Some(RunPlan {
+            project_id,
+            phase,
+            prompt,
+            profile_variant_label,
+            cfg,
+        }) => {
+            if let Some(phase) = &phase {
+                kind = phase_kind(phase.phase_type);
+            }
+            let label = match profile_variant_label.variant {
+                Some(variant) => format!("{}:{}", profile_variant_label.profile, variant),
+                None => profile_variant_label.profile,
+            };
+            profile.get_or_insert(label);
+            (cfg, Some(prompt), Some(project_id), phase)
+        }
+        None => (OrchestratorConfig::default(), None, None, None),
+    };
+    if let Some(path) = &args.phase_config {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let update: UpdatePhase =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        apply_phase_config(update, &mut kind, &mut profile, &mut cfg);
+    }
+    cfg.scope.validate_patterns()?;
+    if let Some(command) = args.test_command.clone() {
+        cfg.test.command = command;
+    }
+    if let Some(path) = &args.test_env_file {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        cfg.test.env = TestConfig::parse_env(&text)?;
+    }
+    if let Some(branch) = args.base_branch.clone() {
+        cfg.base_branch = Some(branch);
+    }
+
+    let prompt = match (&args.prompt_file, &args.task, planned_prompt) {
+        (Some(path), _, _) => {
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?
+        }
+        (None, Some(task), _) => assemble_prompt(&PromptRequest {
+            task: task.clone(),
+            phase: kind,
+            allowlist: cfg.scope.allowlist.clone(),
+            denylist: cfg.scope.denylist.clone(),
+            context: Vec::new(),
+            feedback: None,
+            token_budget: DEFAULT_TOKEN_BUDGET,
+        }),
+        (None, None, Some(prompt)) => prompt,
+        (None, None, None) => return Err("no task or prompt".to_string()),
+    };
+    let profile_variant_label = resolve_profile(profile.as_deref(), DEFAULT_PROFILE);
+
+    let workdir = match (&args.repo, &attempt) {
+        (Some(repo), _) => repo.clone(),
+        (None, Some(attempt)) => attempt
+            .container_ref
+            .clone()
+            .map(PathBuf::from)
+            .ok_or_else(|| "attempt has no worktree; pass --repo".to_string())?,
+        (None, None) => return Err("--repo is required".to_string()),
+    };
+    let workdir = workdir
+        .canonicalize()
+        .map_err(|e| format!("{}: {e}", workdir.display()))?;
+
+    // A run started with --attempt writes where the server would put its artifacts
+    let execution_process_id = Uuid::new_v4();
+    let data_dir = match &attempt {
+        Some(_) if args.artifacts_dir.is_none() || args.cache_dir.is_none() => {
+            orchestrator_data_dir().await
+        }
+        _ => None,
+    };
+    cfg.artifacts_dir = match (&args.artifacts_dir, &data_dir, &attempt) {
+        (Some(dir), _, _) => dir.clone(),
+        (None, Some(data_dir), Some(attempt)) => Artifacts::run_dir(
+            &data_dir.join("artifacts").join(attempt.id.to_string()),
+            &execution_process_id.to_string(),
+        ),
+        _ => PathBuf::from("orchestrator-artifacts"),
+    };
+    cfg.cache_dir = match (&args.cache_dir, &data_dir, project_id) {
+        (Some(dir), _, _) => dir.clone(),
+        (None, Some(data_dir), Some(project_id)) => {
+            data_dir.join("cache").join(project_id.to_string())
+        }
+        _ => PathBuf::from("orchestrator-cache"),
+    };
+    // Tests run inside the repo, so relative paths would land there
+    cfg.artifacts_dir = std::path::absolute(&cfg.artifacts_dir).map_err(|e| e.to_string())?;
+    cfg.cache_dir = std::path::absolute(&cfg.cache_dir).map_err(|e| e.to_string())?;
+    Artifacts::ensure_dir(&cfg.artifacts_dir)?;
+    let artifacts_dir = cfg.artifacts_dir.clone();
+
+    let request = OrchestratorRequest {
+        prompt: prompt.clone(),
+        profile_variant_label: profile_variant_label.clone(),
+    };
+    let agent: Box<dyn AgentAdapter> = match &args.patch_file {
+        Some(path) => Box::new(PatchFileAdapter {
+            path: path.clone(),
+            agent: AgentManifest::default(),
+        }),
+        None => Box::new(CodingAgentAdapter {
+            profile_variant_label,
+            workdir: workdir.clone(),
+            prompt,
+        }),
+    };
+    let run = OrchestratorRun {
+        cfg,
+        workdir: workdir.clone(),
+        agent,
+        replay: None,
+    };
+
+    // With --attempt the run is recorded like one started from the UI: it waits
+    // for the attempt's other processes and moves the attempt's phase to running
+    let record = match (&db, &attempt) {
+        (Some(db), Some(attempt)) => {
+            ensure_attempt_idle(&db.pool, attempt.id)
+                .await
+                .map_err(|(_, body)| format!("cannot start run: {body}"))?;
+            mark_phase_running(&db.pool, phase.as_ref(), false)
+                .await
+                .map_err(|(_, body)| format!("cannot start run: {body}"))?;
+            let created = ExecutionProcess::create(
+                &db.pool,
+                &CreateExecutionProcess {
+                    task_attempt_id: attempt.id,
+                    executor_action: ExecutorAction::new(
+                        ExecutorActionType::OrchestratorRequest(request),
+                        None,
+                    ),
+                    run_reason: ExecutionProcessRunReason::Orchestrator,
+                },
+                execution_process_id,
+            )
+            .await;
+            if let Err(e) = created {
+                // Nothing will settle the phase, so fail it and leave it retryable
+                let _ = Phase::finish_attempt(&db.pool, attempt.id, false).await;
+                return Err(e.to_string());
+            }
+            Some(RunRecorder {
+                db: db.clone(),
+                attempt_id: attempt.id,
+                execution_process_id,
+            })
+        }
+        _ => None,
+    };
+
+    let hooks = Arc::new(CliHooks {
+        recorder: record,
+        ..Default::default()
+    });
+    let on_interrupt = hooks.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("interrupted, stopping run");
+            on_interrupt.cancel().await;
+        }
+    });
+
+    let attempt_id = attempt
+        .as_ref()
+        .map(|a| a.id.to_string())
+        .unwrap_or_else(|| "headless".to_string());
+    let result = run.execute(attempt_id, hooks.as_ref()).await;
+    let summary = hooks.summary.lock().unwrap().clone();
+    let passed = result.is_ok() && summary.as_ref().is_some_and(RunSummary::passed);
+
+    if let (Some(recorder), Some(attempt)) = (&hooks.recorder, &attempt) {
+        let cancelled = hooks.is_cancelled().await;
+        finish_attempt_run(recorder, attempt, &workdir, &result, passed, cancelled).await;
+    }
+    print_summary(summary.as_ref(), &result, &artifacts_dir);
+    Ok(passed)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .init();
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(args).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(argv: &[&str]) -> Result<Option<Args>, String> {
+        Args::parse(argv.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn help_wins_over_everything_else() {
+        assert!(parse(&["--repo", ".", "--help"]).unwrap().is_none());
+        assert!(parse(&["-h"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn values_come_inline_or_as_the_next_argument() {
+        let args = parse(&[
+            "--repo=/tmp/repo",
+            "--task",
+            "Fix it",
+            "--patch-file=a=b.diff",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.repo, Some(PathBuf::from("/tmp/repo")));
+        assert_eq!(args.task.as_deref(), Some("Fix it"));
+        assert_eq!(args.patch_file, Some(PathBuf::from("a=b.diff")));
+    }
+
+    #[test]
+    fn an_attempt_stands_in_for_repo_and_task() {
+        let id = Uuid::new_v4();
+        let args = parse(&["--attempt", &id.to_string()]).unwrap().unwrap();
+        assert_eq!(args.attempt, Some(id));
+        assert!(args.repo.is_none());
+    }
+
+    #[test]
+    fn bad_arguments_are_usage_errors() {
+        assert!(parse(&["--task", "t"])
+            .unwrap_err()
+            .contains("--repo is required"));
+        assert!(parse(&["--repo", "."])
+            .unwrap_err()
+            .contains("one of --task or --prompt-file"));
+        assert!(parse(&["--repo", ".", "--task", "t", "--prompt-file", "p"])
+            .unwrap_err()
+            .contains("mutually exclusive"));
+        assert!(parse(&["--attempt", "nope"])
+            .unwrap_err()
+            .contains("invalid attempt id"));
+        assert!(parse(&["--repo", ".", "--task", "t", "--verbose"])
+            .unwrap_err()
+            .contains("unknown argument"));
+        assert_eq!(
+            parse(&["--repo", ".", "--task"]).unwrap_err(),
+            "--task needs a value"
+        );
+    }
+}
@@ -4,13 +4,14 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use db::models::{project::ProjectError, task_attempt::TaskAttemptError};
+use db::models::{phase::PhaseError, project::ProjectError, task_attempt::TaskAttemptError};
 use deployment::DeploymentError;
 use executors::executors::ExecutorError;
 use git2::Error as Git2Error;
 use services::services::{
-    auth::AuthError, config::ConfigError, container::ContainerError, git::GitServiceError,
-    github_service::GitHubServiceError, image::ImageError, worktree_manager::WorktreeError,
+    approvals::ApprovalError, auth::AuthError, config::ConfigError, container::ContainerError,
+    context_file::ContextFileError, git::GitServiceError, github_service::GitHubServiceError,
+    image::ImageError, worktree_manager::WorktreeError,
 };
 use thiserror::Error;
 use utils::response::ApiResponse;
@@ -23,12 +24,16 @@ pub enum ApiError {
     #[error(transparent)]
     TaskAttempt(#[from] TaskAttemptError),
     #[error(transparent)]
+    Phase(#[from] PhaseError),
+    #[error(transparent)]
     GitService(#[from] GitServiceError),
     #[error(transparent)]
     GitHubService(#[from] GitHubServiceError),
     #[error(transparent)]
     Auth(#[from] AuthError),
     #[error(transparent)]
+    Approval(#[from] ApprovalError),
+    #[error(transparent)]
     Deployment(#[from] DeploymentError),
     #[error(transparent)]
     Container(#[from] ContainerError),
@@ -42,6 +47,8 @@ pub enum ApiError {
     Config(#[from] ConfigError),
     #[error(transparent)]
     Image(#[from] ImageError),
+    #[error(transparent)]
+    ContextFile(#[from] ContextFileError),
     #[error("Multipart error: {0}")]
     Multipart(#[from] MultipartError),
     #[error("IO error: {0}")]
@@ -59,10 +66,27 @@ impl IntoResponse for ApiError {
         let (status_code, error_type) = match &self {
             ApiError::Project(_) => (StatusCode::INTERNAL_SERVER_ERROR, "ProjectError"),
             ApiError::TaskAttempt(_) => (StatusCode::INTERNAL_SERVER_ERROR, "TaskAttemptError"),
+            ApiError::Phase(phase_err) => match phase_err {
+                PhaseError::NotFound => (StatusCode::NOT_FOUND, "PhaseNotFound"),
+                PhaseError::InvalidTransition { .. } => {
+                    (StatusCode::CONFLICT, "InvalidPhaseTransition")
+                }
+                _ => (StatusCode::INTERNAL_SERVER_ERROR, "PhaseError"),
+            },
             ApiError::GitService(_) => (StatusCode::INTERNAL_SERVER_ERROR, "GitServiceError"),
             ApiError::GitHubService(_) => (StatusCode::INTERNAL_SERVER_ERROR, "GitHubServiceError"),
             ApiError::Auth(_) => (StatusCode::INTERNAL_SERVER_ERROR, "AuthError"),
+            ApiError::Approval(approval_err) => match approval_err {
+                ApprovalError::NotFound(_) => (StatusCode::NOT_FOUND, "ApprovalNotFound"),
+                ApprovalError::NotSupervised(_) => (StatusCode::CONFLICT, "ApprovalError"),
+            },
             ApiError::Deployment(_) => (StatusCode::INTERNAL_SERVER_ERROR, "DeploymentError"),
+            ApiError::Container(ContainerError::ExecutorError(
+                ExecutorError::MessagesNotSupported | ExecutorError::StdinClosed,
+            ))
+            | ApiError::Executor(
+                ExecutorError::MessagesNotSupported | ExecutorError::StdinClosed,
+            ) => (StatusCode::CONFLICT, "MessageNotAccepted"),
             ApiError::Container(_) => (StatusCode::INTERNAL_SERVER_ERROR, "ContainerError"),
             ApiError::Executor(_) => (StatusCode::INTERNAL_SERVER_ERROR, "ExecutorError"),
             ApiError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "DatabaseError"),
@@ -74,6 +98,10 @@ impl IntoResponse for ApiError {
                 ImageError::NotFound => (StatusCode::NOT_FOUND, "ImageNotFound"),
                 _ => (StatusCode::INTERNAL_SERVER_ERROR, "ImageError"),
             },
+            ApiError::ContextFile(ctx_err) => match ctx_err {
+                ContextFileError::NotFound => (StatusCode::NOT_FOUND, "ContextFileNotFound"),
+                _ => (StatusCode::INTERNAL_SERVER_ERROR, "ContextFileError"),
+            },
             ApiError::Io(_) => (StatusCode::INTERNAL_SERVER_ERROR, "IoError"),
             ApiError::Multipart(_) => (StatusCode::BAD_REQUEST, "MultipartError"),
         };
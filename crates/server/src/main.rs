@@ -1,12 +1,22 @@
 use anyhow::{self, Error as AnyhowError};
 use deployment::{Deployment, DeploymentError};
-use server::{routes, DeploymentImpl};
+use rmcp::{transport::stdio, ServiceExt};
+use server::{
+    mcp::approval_server::ApprovalServer,
+    routes,
+    util::{extract, storage::data_root},
+    DeploymentImpl,
+};
 use sqlx::Error as SqlxError;
 use strip_ansi_escapes::strip;
 use thiserror::Error;
 use tracing_subscriber::{prelude::*, EnvFilter};
 use utils::{
-    assets::asset_dir, browser::open_browser, port_file::write_port_file, sentry::sentry_layer,
+    approvals::APPROVAL_URL_ENV,
+    assets::asset_dir,
+    browser::open_browser,
+    port_file::{set_backend_port, write_port_file},
+    sentry::sentry_layer,
 };
 
 #[derive(Debug, Error)]
@@ -23,6 +33,11 @@ pub enum VibeKanbanError {
 
 #[tokio::main]
 async fn main() -> Result<(), VibeKanbanError> {
+    // Supervised Claude Code runs start this binary as their permission prompt tool
+    if let Ok(url) = std::env::var(APPROVAL_URL_ENV) {
+        return serve_approvals(url).await;
+    }
+
     let log_level = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
     let filter_string = format!(
         "warn,server={level},services={level},db={level},executors={level},deployment={level},local_deployment={level},utils={level}",
@@ -42,6 +57,10 @@ async fn main() -> Result<(), VibeKanbanError> {
     let deployment = DeploymentImpl::new().await?;
     deployment.update_sentry_scope().await?;
     deployment.cleanup_orphan_executions().await?;
+    // Uploads stored before extraction existed, or whose extraction was cut short
+    if let Err(e) = extract::recover(&deployment.db().pool, &data_root()).await {
+        tracing::warn!("Failed to queue pending text extraction: {}", e);
+    }
     deployment.spawn_pr_monitor_service().await;
     deployment
         .track_if_analytics_allowed("session_start", serde_json::json!({}))
@@ -67,6 +86,9 @@ async fn main() -> Result<(), VibeKanbanError> {
     let listener = tokio::net::TcpListener::bind(format!("{host}:{port}")).await?;
     let actual_port = listener.local_addr()?.port(); // get → 53427 (example)
 
+    // Supervised agents call back into the API for tool approvals
+    set_backend_port(actual_port);
+
     // Write port file for discovery if prod, warn on fail
     if !cfg!(debug_assertions) {
         if let Err(e) = write_port_file(actual_port).await {
@@ -86,3 +108,22 @@ async fn main() -> Result<(), VibeKanbanError> {
     axum::serve(listener, app_router).await?;
     Ok(())
 }
+
+/// Serve the approvals MCP server over stdio. Stdout carries the protocol, so
+/// logs go to stderr
+async fn serve_approvals(url: String) -> Result<(), VibeKanbanError> {
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(EnvFilter::new("warn")),
+        )
+        .init();
+
+    let service = ApprovalServer::new(url)
+        .serve(stdio())
+        .await
+        .map_err(AnyhowError::from)?;
+    service.waiting().await.map_err(AnyhowError::from)?;
+    Ok(())
+}
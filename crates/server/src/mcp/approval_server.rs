@@ -0,0 +1,124 @@
+use std::future::Future;
+
+use rmcp::{
+    handler::server::tool::{Parameters, ToolRouter},
+    model::{
+        CallToolResult, Content, Implementation, ProtocolVersion, ServerCapabilities, ServerInfo,
+    },
+    schemars, tool, tool_handler, tool_router, ErrorData, ServerHandler,
+};
+use serde::Deserialize;
+use serde_json;
+use utils::approvals::ApprovalStatus;
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ApproveRequest {
+    #[schemars(description = "The name of the tool the agent wants to call")]
+    pub tool_name: String,
+    #[schemars(description = "The input the tool would be called with")]
+    #[serde(default)]
+    pub input: serde_json::Value,
+    #[schemars(description = "The id of the tool call")]
+    pub tool_use_id: Option<String>,
+}
+
+/// Permission prompt tool for a supervised Claude Code run: forwards each tool
+/// call to the execution's approvals endpoint and waits for the decision
+#[derive(Debug, Clone)]
+pub struct ApprovalServer {
+    url: String,
+    client: reqwest::Client,
+    tool_router: ToolRouter<ApprovalServer>,
+}
+
+impl ApprovalServer {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+            tool_router: Self::tool_router(),
+        }
+    }
+
+    async fn decide(
+        &self,
+        tool_name: &str,
+        input: &serde_json::Value,
+    ) -> Result<ApprovalStatus, String> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&serde_json::json!({ "tool_name": tool_name, "input": input }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+        match body.get("data") {
+            Some(data) if !data.is_null() => {
+                serde_json::from_value(data.clone()).map_err(|e| e.to_string())
+            }
+            _ => Err(body
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("No decision returned")
+                .to_string()),
+        }
+    }
+}
+
+#[tool_router]
+impl ApprovalServer {
+    #[tool(
+        description = "Ask the user whether a tool call may run. Returns a JSON permission decision."
+    )]
+    async fn approve(
+        &self,
+        Parameters(ApproveRequest {
+            tool_name,
+            input,
+            tool_use_id: _,
+        }): Parameters<ApproveRequest>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let decision = match self.decide(&tool_name, &input).await {
+            Ok(ApprovalStatus::Approved) => {
+                serde_json::json!({ "behavior": "allow", "updatedInput": input })
+            }
+            Ok(ApprovalStatus::Denied { reason }) => serde_json::json!({
+                "behavior": "deny",
+                "message": reason.unwrap_or_else(|| "The user denied this tool call".to_string())
+            }),
+            Ok(ApprovalStatus::TimedOut) | Ok(ApprovalStatus::Pending) => serde_json::json!({
+                "behavior": "deny",
+                "message": "The tool call was not approved in time"
+            }),
+            Err(e) => {
+                tracing::error!("Approval request for {} failed: {}", tool_name, e);
+                serde_json::json!({
+                    "behavior": "deny",
+                    "message": format!("Could not get approval: {e}")
+                })
+            }
+        };
+        Ok(CallToolResult::success(vec![Content::text(
+            decision.to_string(),
+        )]))
+    }
+}
+
+#[tool_handler]
+impl ServerHandler for ApprovalServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2025_03_26,
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: Implementation {
+                name: "vibe-kanban-approvals".to_string(),
+                version: "1.0.0".to_string(),
+            },
+            instructions: Some(
+                "Permission prompts for a supervised coding agent run. TOOLS: 'approve'."
+                    .to_string(),
+            ),
+        }
+    }
+}
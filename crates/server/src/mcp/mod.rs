@@ -1 +1,2 @@
+pub mod approval_server;
 pub mod task_server;
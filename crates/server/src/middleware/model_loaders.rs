@@ -5,8 +5,8 @@ use axum::{
     response::Response,
 };
 use db::models::{
-    execution_process::ExecutionProcess, project::Project, task::Task, task_attempt::TaskAttempt,
-    task_template::TaskTemplate,
+    execution_process::ExecutionProcess, phase::Phase, project::Project, task::Task,
+    task_attempt::TaskAttempt, task_template::TaskTemplate,
 };
 use deployment::Deployment;
 use uuid::Uuid;
@@ -203,3 +203,26 @@ pub async fn load_task_template_middleware(
     // Continue with the next middleware/handler
     Ok(next.run(request).await)
 }
+
+// Middleware that loads and injects Phase based on the phase_id path parameter
+pub async fn load_phase_middleware(
+    State(deployment): State<DeploymentImpl>,
+    Path(phase_id): Path<Uuid>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let phase = match Phase::find_by_id(&deployment.db().pool, phase_id).await {
+        Ok(Some(phase)) => phase,
+        Ok(None) => {
+            tracing::warn!("Phase {} not found", phase_id);
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch phase {}: {}", phase_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    request.extensions_mut().insert(phase);
+    Ok(next.run(request).await)
+}
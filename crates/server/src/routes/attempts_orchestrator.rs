@@ -1,23 +1,74 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+};
 
 use axum::{
-    extract::{Path, State},
-    response::Json as ResponseJson,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json as ResponseJson, Response},
     routing::{get, post},
     Router,
 };
-use axum::http::StatusCode;
-use db::models::{task::Task, task_attempt::TaskAttempt};
-use orchestrator::{artifacts::Artifacts, OrchestratorConfig};
-use tokio::task;
+use chrono::{DateTime, Utc};
+use db::models::{
+    attempt_artifact::AttemptArtifact,
+    context_file::{ProjectContextFile, TaskContextFile},
+    execution_process::{ExecutionProcess, ExecutionProcessRunReason, ExecutionProcessStatus},
+    phase::{Phase, PhaseStatus, PhaseType},
+    task_attempt::{AttemptTelemetry, TaskAttempt},
+};
+use deployment::Deployment;
+use executors::{actions::orchestrator::OrchestratorRequest, profile::ProfileVariantLabel};
+use flate2::{write::GzEncoder, Compression};
+use orchestrator::{
+    agent::{resolve_profile, CodingAgentAdapter},
+    artifacts::{Artifacts, AGENT_OUTPUT_FILE},
+    manifest::RunManifest,
+    prompt::{assemble_prompt, ContextDoc, PhaseKind, PromptRequest, DEFAULT_TOKEN_BUDGET},
+    replay::{add_checkout, remove_checkout, Replay},
+    run::OrchestratorRun,
+    test::TestConfig,
+    AgentAdapter, OrchestratorConfig, PatchFileAdapter,
+};
+use serde::{Deserialize, Serialize};
+use services::services::container::ContainerService;
+use sqlx::SqlitePool;
+use tokio_util::io::ReaderStream;
+use ts_rs::TS;
 use utils::response::ApiResponse;
 use uuid::Uuid;
-use crate::DeploymentImpl;
+use validators::{dep_diff::DepPolicy, scope_guard::ScopePolicy};
+
+use crate::{error::ApiError, util::storage::data_root, DeploymentImpl};
+
+pub fn phase_kind(phase_type: PhaseType) -> PhaseKind {
+    match phase_type {
+        PhaseType::Prompt => PhaseKind::Prompt,
+        PhaseType::Fix => PhaseKind::Fix,
+        PhaseType::Hardening => PhaseKind::Hardening,
+    }
+}
 
 pub fn router(_deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
     Router::new()
-        .route("/attempts/:id/run-orchestrator", post(run_orchestrator))
-        .route("/attempts/:id/artifacts", get(get_artifacts))
+        .route("/attempts/{id}/run-orchestrator", post(run_orchestrator))
+        .route("/attempts/{id}/orchestrator/stop", post(stop_orchestrator))
+        .route("/attempts/{id}/artifacts", get(get_artifacts))
+        .route(
+            "/attempts/{id}/artifacts/{artifact_id}",
+            get(get_artifact_content),
+        )
+        .route(
+            "/attempts/{id}/runs/{execution_process_id}/artifacts.tar.gz",
+            get(download_run_bundle),
+        )
+        .route(
+            "/attempts/{id}/runs/{execution_process_id}/replay",
+            post(replay_run),
+        )
 }
 
 async fn run_orchestrator(
@@ -25,74 +76,1077 @@ async fn run_orchestrator(
     Path(id): Path<String>,
 ) -> (StatusCode, ResponseJson<serde_json::Value>) {
     let attempt_id = match Uuid::parse_str(&id) {
-    Ok(u) => u,
-    Err(_) => return (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": "invalid_attempt_id"}))),
+        Ok(u) => u,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                ResponseJson(serde_json::json!({"error": "invalid_attempt_id"})),
+            )
+        }
     };
-
-    // Resolve attempt, task, project
-    let pool = &deployment.db().pool;
-    let Some(attempt) = TaskAttempt::find_by_id(pool, attempt_id).await.ok().flatten() else {
-        return (StatusCode::NOT_FOUND, ResponseJson(serde_json::json!({"error": "attempt_not_found"})));
+    let Some(attempt) = TaskAttempt::find_by_id(&deployment.db().pool, attempt_id)
+        .await
+        .ok()
+        .flatten()
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            ResponseJson(serde_json::json!({"error": "attempt_not_found"})),
+        );
     };
+    match start_run(&deployment, &attempt, None, false).await {
+        Ok(execution_process) => (
+            StatusCode::ACCEPTED,
+            ResponseJson(serde_json::json!({
+                "started": true,
+                "execution_process_id": execution_process.id,
+            })),
+        ),
+        Err((status, error)) => (status, ResponseJson(error)),
+    }
+}
+
+/// Orchestrator data lives under VK_DATA_DIR, else the configured workspace dir;
+/// with neither set the orchestrator is disabled.
+pub(crate) async fn data_dir(deployment: &DeploymentImpl) -> Option<PathBuf> {
+    let vk_dir_env = std::env::var("VK_DATA_DIR").ok().map(PathBuf::from);
+    let cfg_read = deployment.config().read().await;
+    vk_dir_env.or_else(|| cfg_read.workspace_dir.as_ref().map(PathBuf::from))
+}
+
+pub(crate) fn artifacts_dir(data_dir: &std::path::Path, attempt_id: Uuid) -> PathBuf {
+    data_dir.join("artifacts").join(attempt_id.to_string())
+}
+
+/// Where one orchestrator run of the attempt wrote its artifacts
+pub(crate) fn run_artifacts_dir(
+    data_dir: &std::path::Path,
+    attempt_id: Uuid,
+    execution_process_id: Uuid,
+) -> PathBuf {
+    Artifacts::run_dir(
+        &artifacts_dir(data_dir, attempt_id),
+        &execution_process_id.to_string(),
+    )
+}
+
+/// What an orchestrator run of an attempt is asked to do, resolved from its
+/// task, project and phase. The cache and artifacts directories of `cfg` are
+/// left empty for the caller to place.
+pub struct RunPlan {
+    pub project_id: Uuid,
+    pub phase: Option<Phase>,
+    pub prompt: String,
+    pub profile_variant_label: ProfileVariantLabel,
+    pub cfg: OrchestratorConfig,
+}
+
+/// Resolve the prompt, profile and validation rules of the attempt's next run.
+/// `feedback` describes the failures of a preceding run for the prompt.
+/// Errors carry the HTTP status and JSON body the caller should respond with.
+pub async fn plan_run(
+    pool: &SqlitePool,
+    attempt: &TaskAttempt,
+    feedback: Option<String>,
+) -> Result<RunPlan, (StatusCode, serde_json::Value)> {
     let Some(task) = attempt.parent_task(pool).await.ok().flatten() else {
-        return (StatusCode::NOT_FOUND, ResponseJson(serde_json::json!({"error": "task_not_found"})));
+        return Err((
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"error": "task_not_found"}),
+        ));
     };
     let Some(project) = task.parent_project(pool).await.ok().flatten() else {
-        return (StatusCode::NOT_FOUND, ResponseJson(serde_json::json!({"error": "project_not_found"})));
+        return Err((
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"error": "project_not_found"}),
+        ));
     };
 
-    // Feature flag: use workspace_dir as data dir; if missing, reject
-    let vk_dir_env = std::env::var("VK_DATA_DIR").ok().map(PathBuf::from);
-    let cfg_read = deployment.config().read().await;
-    let data_dir = vk_dir_env.or_else(|| cfg_read.workspace_dir.as_ref().map(PathBuf::from));
-    let Some(data_dir) = data_dir else {
-        return (StatusCode::CONFLICT, ResponseJson(serde_json::json!({"error": "orchestrator_disabled"})));
-    };
-
-    let artifacts_dir = data_dir.join("artifacts").join(attempt_id.to_string());
-    let cache_dir = data_dir.join("cache");
-
-    // Spawn orchestrator run
-    let workdir = project.git_repo_path.clone();
-    let _ = deployment
-        .events()
-        .msg_store()
-        .push_stdout(format!("ORCH: started attempt={}", attempt_id));
-    task::spawn(async move {
-        let cfg = OrchestratorConfig {
-            cache_dir,
-            artifacts_dir,
-        };
-        let _ = Artifacts::ensure_dir(&cfg.artifacts_dir);
-    let _ = orchestrator::run::run_attempt(attempt_id.to_string(), cfg.clone(), &workdir);
+    // Phase linked to this attempt, if any; agent_override wins over the attempt's profile
+    let phase = Phase::find_by_attempt_id(pool, attempt.id)
+        .await
+        .ok()
+        .flatten();
+    let agent_override = phase.as_ref().and_then(|p| p.agent_override.clone());
+    let profile_variant_label = resolve_profile(agent_override.as_deref(), &attempt.profile);
+
+    // Prompt: task, phase rules and the text of uploaded context files
+    let root = data_root();
+    let mut context: Vec<ContextDoc> = Vec::new();
+    for f in ProjectContextFile::find_by_project_id(pool, project.id)
+        .await
+        .unwrap_or_default()
+    {
+        context.extend(ContextDoc::load(
+            &root,
+            &f.filename,
+            &f.mime,
+            &f.sha256,
+            &f.stored_path,
+        ));
+    }
+    for f in TaskContextFile::find_by_task_id(pool, task.id)
+        .await
+        .unwrap_or_default()
+    {
+        context.extend(ContextDoc::load(
+            &root,
+            &f.filename,
+            &f.mime,
+            &f.sha256,
+            &f.stored_path,
+        ));
+    }
+    let scope = ScopePolicy {
+        allowlist: phase
+            .as_ref()
+            .map(|p| p.allowlist.0.clone())
+            .unwrap_or_default(),
+        denylist: phase
+            .as_ref()
+            .map(|p| p.denylist.0.clone())
+            .unwrap_or_default(),
+        reject_before_apply: phase.as_ref().is_some_and(|p| p.reject_out_of_scope),
+    };
+    let prompt = assemble_prompt(&PromptRequest {
+        task: task.to_prompt(),
+        phase: phase
+            .as_ref()
+            .map(|p| phase_kind(p.phase_type))
+            .unwrap_or_default(),
+        allowlist: scope.allowlist.clone(),
+        denylist: scope.denylist.clone(),
+        context,
+        feedback,
+        token_budget: DEFAULT_TOKEN_BUDGET,
     });
+    let test_env =
+        match TestConfig::parse_env(project.orchestrator_test_env.as_deref().unwrap_or_default()) {
+            Ok(env) => env,
+            Err(e) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    serde_json::json!({"error": "invalid_test_env", "message": e}),
+                ));
+            }
+        };
+    let defaults = TestConfig::default();
+    let test = TestConfig {
+        command: project
+            .orchestrator_test_command
+            .clone()
+            .filter(|c| !c.trim().is_empty())
+            .unwrap_or(defaults.command),
+        cold_timeout_sec: project
+            .orchestrator_cold_timeout_sec
+            .and_then(|s| u64::try_from(s).ok())
+            .unwrap_or(defaults.cold_timeout_sec),
+        warm_timeout_sec: project
+            .orchestrator_warm_timeout_sec
+            .and_then(|s| u64::try_from(s).ok())
+            .unwrap_or(defaults.warm_timeout_sec),
+        env: test_env,
+    };
+    let cfg = OrchestratorConfig {
+        dep_policy: DepPolicy::from_json(
+            phase
+                .as_ref()
+                .and_then(|p| p.dep_policy.as_ref())
+                .map(|v| v.0.to_string())
+                .as_deref(),
+        ),
+        scope,
+        test,
+        base_branch: Some(attempt.base_branch.clone()),
+        warm_kpi_budget: phase.as_ref().and_then(|p| p.warm_kpi_budget),
+        ..Default::default()
+    };
+    Ok(RunPlan {
+        project_id: project.id,
+        phase,
+        prompt,
+        profile_variant_label,
+        cfg,
+    })
+}
+
+/// Attempts whose run is being started. Held from the busy check until the run's
+/// execution process exists, so two requests cannot both start a run.
+static STARTING_RUNS: LazyLock<Mutex<HashSet<Uuid>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Releases the attempt's start claim once its run is started or refused
+struct RunStartClaim(Uuid);
+
+impl RunStartClaim {
+    fn try_claim(attempt_id: Uuid) -> Option<Self> {
+        let claimed = STARTING_RUNS.lock().unwrap().insert(attempt_id);
+        claimed.then(|| Self(attempt_id))
+    }
+}
+
+impl Drop for RunStartClaim {
+    fn drop(&mut self) {
+        STARTING_RUNS.lock().unwrap().remove(&self.0);
+    }
+}
 
-    (StatusCode::ACCEPTED, ResponseJson(serde_json::json!({"started": true})))
+/// Refuse a run while another process of the attempt is running. Orchestrator
+/// runs, coding agents and dev servers all work in the attempt's worktree, and
+/// stopping a run only reaches the attempt's latest orchestrator process.
+pub async fn ensure_attempt_idle(
+    pool: &SqlitePool,
+    attempt_id: Uuid,
+) -> Result<(), (StatusCode, serde_json::Value)> {
+    let processes = match ExecutionProcess::find_by_task_attempt_id(pool, attempt_id).await {
+        Ok(processes) => processes,
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({"error": e.to_string()}),
+            ));
+        }
+    };
+    match processes
+        .iter()
+        .find(|p| p.status == ExecutionProcessStatus::Running)
+    {
+        Some(process) => Err((
+            StatusCode::CONFLICT,
+            serde_json::json!({"error": "run_in_progress", "execution_process_id": process.id}),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Move the run's phase to running. Re-running a finished phase retries it. A
+/// running phase only goes ahead when `phase_claimed` says the caller started
+/// it together with the attempt, as a pipeline does; otherwise another run
+/// holds it. A phase that cannot be moved refuses the run.
+pub async fn mark_phase_running(
+    pool: &SqlitePool,
+    phase: Option<&Phase>,
+    phase_claimed: bool,
+) -> Result<(), (StatusCode, serde_json::Value)> {
+    let Some(phase) = phase else {
+        return Ok(());
+    };
+    if phase.status == PhaseStatus::Running {
+        if phase_claimed {
+            return Ok(());
+        }
+        return Err((
+            StatusCode::CONFLICT,
+            serde_json::json!({"error": "phase_running"}),
+        ));
+    }
+    match Phase::transition(pool, phase.id, PhaseStatus::Running).await {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            tracing::warn!("Refusing to start phase {}: {}", phase.id, e);
+            Err((
+                StatusCode::CONFLICT,
+                serde_json::json!({"error": "phase_not_startable", "message": e.to_string()}),
+            ))
+        }
+    }
+}
+
+/// Build and start an orchestrator run in the attempt's worktree. `feedback`
+/// describes the failures of a preceding run for the prompt; `phase_claimed`
+/// is set by callers that already moved the attempt's phase to running. Errors
+/// carry the HTTP status and JSON body the caller should respond with.
+pub(crate) async fn start_run(
+    deployment: &DeploymentImpl,
+    attempt: &TaskAttempt,
+    feedback: Option<String>,
+    phase_claimed: bool,
+) -> Result<ExecutionProcess, (StatusCode, serde_json::Value)> {
+    let attempt_id = attempt.id;
+    let pool = &deployment.db().pool;
+    let Some(_claim) = RunStartClaim::try_claim(attempt_id) else {
+        return Err((
+            StatusCode::CONFLICT,
+            serde_json::json!({"error": "run_in_progress"}),
+        ));
+    };
+    ensure_attempt_idle(pool, attempt_id).await?;
+    let RunPlan {
+        project_id,
+        phase,
+        prompt,
+        profile_variant_label,
+        mut cfg,
+    } = plan_run(pool, attempt, feedback).await?;
+
+    // Feature flag: use workspace_dir as data dir; if missing, reject
+    let Some(data_dir) = data_dir(deployment).await else {
+        return Err((
+            StatusCode::CONFLICT,
+            serde_json::json!({"error": "orchestrator_disabled"}),
+        ));
+    };
+    cfg.artifacts_dir = artifacts_dir(&data_dir, attempt_id);
+    cfg.cache_dir = data_dir.join("cache").join(project_id.to_string());
+
+    // Apply and test inside the attempt's worktree, never the project's main checkout
+    let container = deployment.container();
+    let container_ref = match attempt.container_ref {
+        Some(_) => container.ensure_container_exists(attempt).await,
+        None => container.create(attempt).await,
+    };
+    let workdir = match container_ref {
+        Ok(container_ref) => PathBuf::from(container_ref),
+        Err(e) => {
+            tracing::error!(
+                "Failed to prepare worktree for attempt {}: {}",
+                attempt_id,
+                e
+            );
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({"error": "worktree_unavailable"}),
+            ));
+        }
+    };
+
+    let _ = Artifacts::ensure_dir(&cfg.artifacts_dir);
+    // VK_FAKE_PATCH_PATH keeps the canned-patch adapter available for demos and tests
+    let agent: Box<dyn AgentAdapter> = match PatchFileAdapter::fake() {
+        Some(fake) => Box::new(fake),
+        None => Box::new(CodingAgentAdapter {
+            profile_variant_label: profile_variant_label.clone(),
+            workdir: workdir.clone(),
+            prompt: prompt.clone(),
+        }),
+    };
+    let run = OrchestratorRun {
+        cfg,
+        workdir,
+        agent,
+        replay: None,
+    };
+    let request = OrchestratorRequest {
+        prompt,
+        profile_variant_label,
+    };
+
+    mark_phase_running(pool, phase.as_ref(), phase_claimed).await?;
+
+    match container.start_orchestrator(attempt, request, run).await {
+        Ok(execution_process) => Ok(execution_process),
+        Err(e) => {
+            tracing::error!(
+                "Failed to start orchestrator for attempt {}: {}",
+                attempt_id,
+                e
+            );
+            // Nothing will settle the phase, so fail it here and leave it retryable
+            if let Some(phase) = &phase {
+                let _ = Phase::transition(pool, phase.id, PhaseStatus::Fail).await;
+            }
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({"error": e.to_string()}),
+            ))
+        }
+    }
+}
+
+async fn stop_orchestrator(
+    State(deployment): State<DeploymentImpl>,
+    Path(id): Path<Uuid>,
+) -> (StatusCode, ResponseJson<serde_json::Value>) {
+    let pool = &deployment.db().pool;
+    let Some(process) = ExecutionProcess::find_latest_by_task_attempt_and_run_reason(
+        pool,
+        id,
+        &ExecutionProcessRunReason::Orchestrator,
+    )
+    .await
+    .ok()
+    .flatten() else {
+        return (
+            StatusCode::NOT_FOUND,
+            ResponseJson(serde_json::json!({"error": "run_not_found"})),
+        );
+    };
+    if process.status != ExecutionProcessStatus::Running {
+        return (
+            StatusCode::CONFLICT,
+            ResponseJson(serde_json::json!({"error": "run_not_running"})),
+        );
+    }
+    match deployment.container().stop_execution(&process).await {
+        Ok(()) => (
+            StatusCode::OK,
+            ResponseJson(serde_json::json!({"stopped": true, "execution_process_id": process.id})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ResponseJson(serde_json::json!({"error": e.to_string()})),
+        ),
+    }
+}
+
+/// One orchestrator run of an attempt and the artifacts it wrote
+#[derive(Debug, Serialize, TS)]
+pub struct ArtifactRun {
+    /// `None` groups artifacts recorded before runs were kept apart
+    pub execution_process_id: Option<Uuid>,
+    pub status: Option<ExecutionProcessStatus>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// The run's parsed summary.json, once written
+    #[ts(type = "JsonValue | null")]
+    pub summary: Option<serde_json::Value>,
+    pub artifacts: Vec<AttemptArtifact>,
+}
+
+#[derive(Debug, Serialize, TS)]
+pub struct AttemptArtifacts {
+    /// Newest run first
+    pub runs: Vec<ArtifactRun>,
+    pub telemetry: Option<AttemptTelemetry>,
+}
+
+fn error_response(status: StatusCode, error: &str) -> Response {
+    (status, ResponseJson(serde_json::json!({ "error": error }))).into_response()
+}
+
+/// Resolve an artifact's file, refusing anything outside the artifacts tree
+fn artifact_file(
+    data_dir: Option<&std::path::Path>,
+    artifact: &AttemptArtifact,
+) -> Option<PathBuf> {
+    let root = data_dir?.join("artifacts").canonicalize().ok()?;
+    let path = PathBuf::from(&artifact.path).canonicalize().ok()?;
+    path.starts_with(&root).then_some(path)
 }
 
 async fn get_artifacts(
-    Path(id): Path<String>,
-) -> ResponseJson<ApiResponse<serde_json::Value>> {
-    let base = std::env::var("VK_DATA_DIR").ok().map(PathBuf::from);
-    let dir = base.map(|b| b.join("artifacts").join(&id));
-    let key = |name: &str| {
-        dir.as_ref()
-            .and_then(|d| {
-                let p = d.join(name);
-                if p.exists() { Some(p.to_string_lossy().to_string()) } else { None }
-            })
+    State(deployment): State<DeploymentImpl>,
+    Path(id): Path<Uuid>,
+) -> Result<(StatusCode, ResponseJson<ApiResponse<AttemptArtifacts>>), ApiError> {
+    let data_dir = data_dir(&deployment).await;
+    match attempt_artifacts(&deployment.db().pool, data_dir.as_deref(), id).await? {
+        Some(artifacts) => Ok((
+            StatusCode::OK,
+            ResponseJson(ApiResponse::success(artifacts)),
+        )),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            ResponseJson(ApiResponse::error("attempt_not_found")),
+        )),
+    }
+}
+
+/// The attempt's artifacts grouped by the run that wrote them, `None` if
+/// there is no such attempt
+async fn attempt_artifacts(
+    pool: &SqlitePool,
+    data_dir: Option<&std::path::Path>,
+    id: Uuid,
+) -> Result<Option<AttemptArtifacts>, sqlx::Error> {
+    if TaskAttempt::find_by_id(pool, id).await?.is_none() {
+        return Ok(None);
+    }
+    let mut artifacts = AttemptArtifact::find_by_attempt_id(pool, id).await?;
+    let processes = ExecutionProcess::find_by_task_attempt_id(pool, id).await?;
+
+    let mut runs = Vec::new();
+    for process in processes
+        .into_iter()
+        .rev()
+        .filter(|p| p.run_reason == ExecutionProcessRunReason::Orchestrator)
+    {
+        let (own, rest): (Vec<_>, Vec<_>) = artifacts
+            .into_iter()
+            .partition(|a| a.execution_process_id == Some(process.id));
+        artifacts = rest;
+        runs.push(ArtifactRun {
+            execution_process_id: Some(process.id),
+            status: Some(process.status),
+            started_at: Some(process.started_at),
+            completed_at: process.completed_at,
+            summary: None,
+            artifacts: own,
+        });
+    }
+    if !artifacts.is_empty() {
+        runs.push(ArtifactRun {
+            execution_process_id: None,
+            status: None,
+            started_at: None,
+            completed_at: None,
+            summary: None,
+            artifacts,
+        });
+    }
+    for run in &mut runs {
+        let Some(summary) = run
+            .artifacts
+            .iter()
+            .rev()
+            .find(|a| a.kind == "summary.json")
+        else {
+            continue;
+        };
+        if let Some(path) = artifact_file(data_dir, summary) {
+            run.summary = tokio::fs::read_to_string(path)
+                .await
+                .ok()
+                .and_then(|s| serde_json::from_str(&s).ok());
+        }
+    }
+
+    let telemetry = TaskAttempt::find_telemetry(pool, id).await?;
+    Ok(Some(AttemptArtifacts { runs, telemetry }))
+}
+
+/// Stream one artifact's contents with a content type guessed from its name
+async fn get_artifact_content(
+    State(deployment): State<DeploymentImpl>,
+    Path((id, artifact_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, ApiError> {
+    let data_dir = data_dir(&deployment).await;
+    artifact_content(&deployment.db().pool, data_dir.as_deref(), id, artifact_id).await
+}
+
+async fn artifact_content(
+    pool: &SqlitePool,
+    data_dir: Option<&std::path::Path>,
+    id: Uuid,
+    artifact_id: Uuid,
+) -> Result<Response, ApiError> {
+    let Some(artifact) = AttemptArtifact::find_by_id(pool, artifact_id)
+        .await?
+        .filter(|a| a.attempt_id == id)
+    else {
+        return Ok(error_response(StatusCode::NOT_FOUND, "artifact_not_found"));
     };
-    let summary_path = key("summary.json");
-    let summary_obj = summary_path.as_ref().and_then(|p| {
-        std::fs::read_to_string(p).ok().and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
-    }).unwrap_or_else(|| serde_json::json!({}));
-    let payload = serde_json::json!({
-        "touched_files.txt": key("touched_files.txt").unwrap_or_default(),
-        "dep_snapshot.txt": key("dep_snapshot.txt").unwrap_or_default(),
-        "kpi.json": key("kpi.json").unwrap_or_default(),
-        "snippets.log": key("snippets.log").unwrap_or_default(),
-        "summary.json": summary_path.unwrap_or_default(),
-        "summary": summary_obj,
-    });
-    ResponseJson(ApiResponse::success(payload))
+    let Some(path) = artifact_file(data_dir, &artifact) else {
+        return Ok(error_response(StatusCode::NOT_FOUND, "artifact_missing"));
+    };
+    let file = tokio::fs::File::open(&path).await?;
+    let len = file.metadata().await?.len();
+    let mime = mime_guess::from_path(&artifact.kind).first_or_text_plain();
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, mime.as_ref())
+        .header(header::CONTENT_LENGTH, len)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename=\"{}\"", artifact.kind),
+        )
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .unwrap())
+}
+
+/// Download every artifact of one run as `<run>.tar.gz`
+async fn download_run_bundle(
+    State(deployment): State<DeploymentImpl>,
+    Path((id, execution_process_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, ApiError> {
+    let data_dir = data_dir(&deployment).await;
+    run_bundle(
+        &deployment.db().pool,
+        data_dir.as_deref(),
+        id,
+        execution_process_id,
+    )
+    .await
+}
+
+async fn run_bundle(
+    pool: &SqlitePool,
+    data_dir: Option<&std::path::Path>,
+    id: Uuid,
+    execution_process_id: Uuid,
+) -> Result<Response, ApiError> {
+    let artifacts =
+        AttemptArtifact::find_by_execution_process_id(pool, execution_process_id).await?;
+    if artifacts.is_empty() || artifacts.iter().any(|a| a.attempt_id != id) {
+        return Ok(error_response(StatusCode::NOT_FOUND, "run_not_found"));
+    }
+    // A kind can be recorded more than once per run; the newest write is what's on disk
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    for artifact in artifacts.iter().rev() {
+        if files.iter().any(|(kind, _)| *kind == artifact.kind) {
+            continue;
+        }
+        if let Some(path) = artifact_file(data_dir, artifact) {
+            files.push((artifact.kind.clone(), path));
+        }
+    }
+    let name = execution_process_id.to_string();
+    let prefix = name.clone();
+    let bundle = tokio::task::spawn_blocking(move || -> std::io::Result<Vec<u8>> {
+        let encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let mut tar = tar::Builder::new(encoder);
+        for (kind, path) in files.iter().rev() {
+            tar.append_path_with_name(path, format!("{prefix}/{kind}"))?;
+        }
+        tar.into_inner()?.finish()
+    })
+    .await
+    .map_err(std::io::Error::other)??;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/gzip")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{name}.tar.gz\""),
+        )
+        .body(Body::from(bundle))
+        .unwrap())
+}
+
+/// Query of a replay request
+#[derive(Debug, Default, Deserialize, TS)]
+pub struct ReplayQuery {
+    /// Run the agent again instead of applying the original run's recorded reply
+    #[serde(default)]
+    pub rerun_agent: bool,
+}
+
+/// Re-execute a run from its `run_manifest.json` in a fresh checkout of its
+/// base commit. The replay is a new orchestrator run whose `replay_report.json`
+/// says whether the configuration and the outputs match the original. It applies
+/// the original run's `agent_output.txt`, so only `rerun_agent` asks the agent
+/// for a new reply.
+async fn replay_run(
+    State(deployment): State<DeploymentImpl>,
+    Path((id, execution_process_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<ReplayQuery>,
+) -> (StatusCode, ResponseJson<serde_json::Value>) {
+    let error = |status: StatusCode, error: &str| {
+        (status, ResponseJson(serde_json::json!({ "error": error })))
+    };
+    let pool = &deployment.db().pool;
+    let Some(attempt) = TaskAttempt::find_by_id(pool, id).await.ok().flatten() else {
+        return error(StatusCode::NOT_FOUND, "attempt_not_found");
+    };
+    let Some(task) = attempt.parent_task(pool).await.ok().flatten() else {
+        return error(StatusCode::NOT_FOUND, "task_not_found");
+    };
+    let original = ExecutionProcess::find_by_id(pool, execution_process_id)
+        .await
+        .ok()
+        .flatten();
+    if !original.is_some_and(|p| {
+        p.task_attempt_id == id && p.run_reason == ExecutionProcessRunReason::Orchestrator
+    }) {
+        return error(StatusCode::NOT_FOUND, "run_not_found");
+    }
+    let Some(data_dir) = data_dir(&deployment).await else {
+        return error(StatusCode::CONFLICT, "orchestrator_disabled");
+    };
+    // A replay is an orchestrator run of the attempt too, so it waits its turn
+    let Some(_claim) = RunStartClaim::try_claim(id) else {
+        return error(StatusCode::CONFLICT, "run_in_progress");
+    };
+    if let Err((status, body)) = ensure_attempt_idle(pool, id).await {
+        return (status, ResponseJson(body));
+    }
+    let original_dir = run_artifacts_dir(&data_dir, id, execution_process_id);
+    let manifest = match RunManifest::load(&original_dir) {
+        Ok(manifest) => manifest,
+        Err(_) => return error(StatusCode::NOT_FOUND, "manifest_not_found"),
+    };
+    let Some(base_commit) = manifest.base_commit.clone() else {
+        return error(StatusCode::CONFLICT, "base_commit_unknown");
+    };
+
+    // The checkout is added from the attempt's worktree, which shares its object store
+    let container = deployment.container();
+    let container_ref = match attempt.container_ref {
+        Some(_) => container.ensure_container_exists(&attempt).await,
+        None => container.create(&attempt).await,
+    };
+    let repo = match container_ref {
+        Ok(container_ref) => PathBuf::from(container_ref),
+        Err(e) => {
+            tracing::error!("Failed to prepare worktree for attempt {}: {}", id, e);
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "worktree_unavailable");
+        }
+    };
+
+    // The original reply, or the same profile and prompt inside the replay's own checkout
+    let workdir = data_dir.join("replays").join(Uuid::new_v4().to_string());
+    let agent: Box<dyn AgentAdapter> = if !query.rerun_agent {
+        let path = original_dir.join(AGENT_OUTPUT_FILE);
+        if !path.is_file() {
+            return error(StatusCode::CONFLICT, "agent_output_not_found");
+        }
+        Box::new(PatchFileAdapter {
+            path,
+            agent: manifest.agent.clone(),
+        })
+    } else if let Some(fake) = PatchFileAdapter::fake() {
+        Box::new(fake)
+    } else {
+        match (&manifest.agent.profile_variant, &manifest.agent.prompt) {
+            (Some(label), Some(prompt)) => Box::new(CodingAgentAdapter {
+                profile_variant_label: label.clone(),
+                workdir: workdir.clone(),
+                prompt: prompt.clone(),
+            }),
+            _ => return error(StatusCode::CONFLICT, "agent_not_replayable"),
+        }
+    };
+    let Ok(config_sha256) = manifest.sha256() else {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "manifest_unreadable");
+    };
+    let checkout = std::fs::create_dir_all(data_dir.join("replays"))
+        .map_err(|e| e.to_string())
+        .and_then(|_| add_checkout(&repo, &base_commit, &workdir));
+    if let Err(e) = checkout {
+        tracing::error!(
+            "Failed to check out {} for replay of {}: {}",
+            base_commit,
+            execution_process_id,
+            e
+        );
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "checkout_failed");
+    }
+
+    let cfg = manifest.config(
+        &data_dir.join("cache").join(task.project_id.to_string()),
+        &artifacts_dir(&data_dir, id),
+    );
+    let run = OrchestratorRun {
+        cfg,
+        workdir: workdir.clone(),
+        agent,
+        replay: Some(Replay {
+            original_dir,
+            repo: repo.clone(),
+        }),
+    };
+    let request = OrchestratorRequest {
+        prompt: manifest.agent.prompt.clone().unwrap_or_default(),
+        profile_variant_label: manifest
+            .agent
+            .profile_variant
+            .clone()
+            .unwrap_or_else(|| resolve_profile(None, &attempt.profile)),
+    };
+    match container.start_orchestrator(&attempt, request, run).await {
+        Ok(execution_process) => (
+            StatusCode::ACCEPTED,
+            ResponseJson(serde_json::json!({
+                "started": true,
+                "execution_process_id": execution_process.id,
+                "replay_of": execution_process_id,
+                "rerun_agent": query.rerun_agent,
+                "config_sha256": config_sha256,
+            })),
+        ),
+        Err(e) => {
+            tracing::error!("Failed to start replay of {}: {}", execution_process_id, e);
+            remove_checkout(&repo, &workdir);
+            error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use db::{
+        models::{attempt_artifact::CreateAttemptArtifact, phase::CreatePhase},
+        test_support::{seed_attempt, seed_orchestrator_run, seed_task},
+        DBService,
+    };
+
+    use super::*;
+
+    /// Write `contents` as the run's `kind` artifact and record it
+    async fn write_artifact(
+        pool: &SqlitePool,
+        data_dir: &std::path::Path,
+        attempt_id: Uuid,
+        execution_process_id: Option<Uuid>,
+        kind: &str,
+        contents: &str,
+    ) -> AttemptArtifact {
+        let dir = match execution_process_id {
+            Some(ep) => run_artifacts_dir(data_dir, attempt_id, ep),
+            None => artifacts_dir(data_dir, attempt_id),
+        };
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(kind);
+        std::fs::write(&path, contents).unwrap();
+        AttemptArtifact::create(
+            pool,
+            &CreateAttemptArtifact {
+                attempt_id,
+                execution_process_id,
+                kind: kind.to_string(),
+                path: path.to_string_lossy().into_owned(),
+                size_bytes: Some(contents.len() as i64),
+            },
+            Uuid::new_v4(),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn body(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn artifacts_are_grouped_by_run_newest_first() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let data = tempfile::tempdir().unwrap();
+        let attempt = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        let first = seed_orchestrator_run(&db.pool, attempt.id).await;
+        let second = seed_orchestrator_run(&db.pool, attempt.id).await;
+        write_artifact(&db.pool, data.path(), attempt.id, None, "kpi.json", "{}").await;
+        write_artifact(
+            &db.pool,
+            data.path(),
+            attempt.id,
+            Some(first.id),
+            "kpi.json",
+            "{}",
+        )
+        .await;
+        write_artifact(
+            &db.pool,
+            data.path(),
+            attempt.id,
+            Some(second.id),
+            "summary.json",
+            r#"{"passed":true}"#,
+        )
+        .await;
+
+        let artifacts = attempt_artifacts(&db.pool, Some(data.path()), attempt.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let runs: Vec<_> = artifacts
+            .runs
+            .iter()
+            .map(|r| r.execution_process_id)
+            .collect();
+        assert_eq!(runs, vec![Some(second.id), Some(first.id), None]);
+        assert_eq!(
+            artifacts.runs[0].summary,
+            Some(serde_json::json!({"passed": true}))
+        );
+        assert_eq!(artifacts.runs[1].summary, None);
+        assert!(artifacts.runs.iter().all(|r| r.artifacts.len() == 1));
+
+        assert!(
+            attempt_artifacts(&db.pool, Some(data.path()), Uuid::new_v4())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn artifact_content_is_served_only_for_its_attempt_and_tree() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let data = tempfile::tempdir().unwrap();
+        let attempt = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        let run = seed_orchestrator_run(&db.pool, attempt.id).await;
+        let artifact = write_artifact(
+            &db.pool,
+            data.path(),
+            attempt.id,
+            Some(run.id),
+            "kpi.json",
+            "{}",
+        )
+        .await;
+
+        let response = artifact_content(&db.pool, Some(data.path()), attempt.id, artifact.id)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(body(response).await, b"{}");
+
+        let other = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        let response = artifact_content(&db.pool, Some(data.path()), other.id, artifact.id)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Without a data dir nothing resolves inside the artifacts tree
+        let response = artifact_content(&db.pool, None, attempt.id, artifact.id)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let outside = tempfile::tempdir().unwrap();
+        let stray = write_artifact(
+            &db.pool,
+            outside.path(),
+            attempt.id,
+            None,
+            "secret.txt",
+            "x",
+        )
+        .await;
+        let response = artifact_content(&db.pool, Some(data.path()), attempt.id, stray.id)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn run_bundle_holds_each_artifact_of_the_run_once() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let data = tempfile::tempdir().unwrap();
+        let attempt = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        let run = seed_orchestrator_run(&db.pool, attempt.id).await;
+        write_artifact(
+            &db.pool,
+            data.path(),
+            attempt.id,
+            Some(run.id),
+            "kpi.json",
+            "{}",
+        )
+        .await;
+        write_artifact(
+            &db.pool,
+            data.path(),
+            attempt.id,
+            Some(run.id),
+            "snippets.log",
+            "a",
+        )
+        .await;
+        write_artifact(
+            &db.pool,
+            data.path(),
+            attempt.id,
+            Some(run.id),
+            "snippets.log",
+            "ab",
+        )
+        .await;
+
+        let response = run_bundle(&db.pool, Some(data.path()), attempt.id, run.id)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+        let bytes = body(response).await;
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(bytes.as_slice()));
+        let mut entries: Vec<(String, String)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents).unwrap();
+                (name, contents)
+            })
+            .collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                (format!("{}/kpi.json", run.id), "{}".to_string()),
+                (format!("{}/snippets.log", run.id), "ab".to_string()),
+            ]
+        );
+
+        let other = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        let response = run_bundle(&db.pool, Some(data.path()), other.id, run.id)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = run_bundle(&db.pool, Some(data.path()), attempt.id, Uuid::new_v4())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn runs_start_only_once_their_phase_is_running() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let attempt = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        let phase = Phase::create(
+            &db.pool,
+            attempt.task_id,
+            &CreatePhase::default(),
+            Uuid::new_v4(),
+        )
+        .await
+        .unwrap();
+
+        mark_phase_running(&db.pool, Some(&phase), false)
+            .await
+            .unwrap();
+        let running = Phase::find_by_id(&db.pool, phase.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(running.status, PhaseStatus::Running);
+        // Started together with its attempt by the caller, so the run goes ahead
+        mark_phase_running(&db.pool, Some(&running), true)
+            .await
+            .unwrap();
+        mark_phase_running(&db.pool, None, false).await.unwrap();
+
+        // Another run holds the phase
+        let (status, body) = mark_phase_running(&db.pool, Some(&running), false)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "phase_running");
+
+        // Another run took the phase after this one loaded it
+        let (status, body) = mark_phase_running(&db.pool, Some(&phase), false)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "phase_not_startable");
+    }
+
+    #[tokio::test]
+    async fn runs_wait_for_the_attempts_running_process() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let attempt = seed_attempt(&db.pool, seed_task(&db.pool).await.id).await;
+        ensure_attempt_idle(&db.pool, attempt.id).await.unwrap();
+
+        let run = seed_orchestrator_run(&db.pool, attempt.id).await;
+        let (status, body) = ensure_attempt_idle(&db.pool, attempt.id).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "run_in_progress");
+        assert_eq!(body["execution_process_id"], run.id.to_string());
+
+        ExecutionProcess::update_completion(
+            &db.pool,
+            run.id,
+            ExecutionProcessStatus::Completed,
+            Some(0),
+        )
+        .await
+        .unwrap();
+        ensure_attempt_idle(&db.pool, attempt.id).await.unwrap();
+    }
+
+    #[test]
+    fn an_attempt_starts_one_run_at_a_time() {
+        let attempt_id = Uuid::new_v4();
+        let claim = RunStartClaim::try_claim(attempt_id).unwrap();
+        assert!(RunStartClaim::try_claim(attempt_id).is_none());
+        drop(claim);
+        assert!(RunStartClaim::try_claim(attempt_id).is_some());
+    }
 }
@@ -6,14 +6,22 @@ use axum::{
         Json as ResponseJson, Sse,
     },
     routing::{get, post},
-    BoxError, Extension, Router,
+    BoxError, Extension, Json, Router,
 };
-use db::models::execution_process::ExecutionProcess;
+use db::models::{execution_process::ExecutionProcess, token_usage::ExecutionTokenUsage};
 use deployment::Deployment;
 use futures_util::TryStreamExt;
 use serde::Deserialize;
-use services::services::container::ContainerService;
-use utils::response::ApiResponse;
+use services::services::{
+    approvals::APPROVAL_TIMEOUT,
+    container::ContainerService,
+    pricing::{PriceTable, TokenSpend},
+};
+use ts_rs::TS;
+use utils::{
+    approvals::{ApprovalStatus, ToolApproval},
+    response::ApiResponse,
+};
 use uuid::Uuid;
 
 use crate::{error::ApiError, middleware::load_execution_process_middleware, DeploymentImpl};
@@ -23,6 +31,29 @@ pub struct ExecutionProcessQuery {
     pub task_attempt_id: Uuid,
 }
 
+/// A decision on a tool call a supervised agent is waiting on
+#[derive(Debug, Deserialize, TS)]
+pub struct ApprovalDecision {
+    pub approval_id: String,
+    pub approved: bool,
+    /// Passed to the agent when the call is denied
+    pub reason: Option<String>,
+}
+
+/// A message for a running agent, taken in without waiting for it to exit
+#[derive(Debug, Deserialize, TS)]
+pub struct SendMessageRequest {
+    pub message: String,
+}
+
+/// A permission prompt from a supervised agent
+#[derive(Debug, Deserialize)]
+pub struct ApprovalRequest {
+    pub tool_name: String,
+    #[serde(default)]
+    pub input: serde_json::Value,
+}
+
 pub async fn get_execution_processes(
     State(deployment): State<DeploymentImpl>,
     Query(query): Query<ExecutionProcessQuery>,
@@ -41,6 +72,20 @@ pub async fn get_execution_process_by_id(
     Ok(ResponseJson(ApiResponse::success(execution_process)))
 }
 
+pub async fn get_execution_process_usage(
+    Extension(execution_process): Extension<ExecutionProcess>,
+    State(deployment): State<DeploymentImpl>,
+) -> Result<ResponseJson<ApiResponse<TokenSpend>>, ApiError> {
+    let models = ExecutionTokenUsage::find_by_execution_process_id(
+        &deployment.db().pool,
+        execution_process.id,
+    )
+    .await?;
+    Ok(ResponseJson(ApiResponse::success(
+        PriceTable::load().spend(models),
+    )))
+}
+
 pub async fn stream_raw_logs(
     State(deployment): State<DeploymentImpl>,
     Path(exec_id): Path<Uuid>,
@@ -83,12 +128,100 @@ pub async fn stop_execution_process(
     Ok(ResponseJson(ApiResponse::success(())))
 }
 
+pub async fn send_message(
+    Extension(execution_process): Extension<ExecutionProcess>,
+    State(deployment): State<DeploymentImpl>,
+    Json(payload): Json<SendMessageRequest>,
+) -> Result<ResponseJson<ApiResponse<()>>, ApiError> {
+    deployment
+        .container()
+        .send_message(&execution_process, payload.message)
+        .await?;
+
+    deployment
+        .track_if_analytics_allowed(
+            "execution_process_message_sent",
+            serde_json::json!({
+                "execution_process_id": execution_process.id.to_string(),
+            }),
+        )
+        .await;
+
+    Ok(ResponseJson(ApiResponse::success(())))
+}
+
+pub async fn get_pending_approvals(
+    Extension(execution_process): Extension<ExecutionProcess>,
+    State(deployment): State<DeploymentImpl>,
+) -> Result<ResponseJson<ApiResponse<Vec<ToolApproval>>>, ApiError> {
+    Ok(ResponseJson(ApiResponse::success(
+        deployment.approvals().pending(execution_process.id),
+    )))
+}
+
+pub async fn respond_to_approval(
+    Extension(execution_process): Extension<ExecutionProcess>,
+    State(deployment): State<DeploymentImpl>,
+    Json(payload): Json<ApprovalDecision>,
+) -> Result<ResponseJson<ApiResponse<ToolApproval>>, ApiError> {
+    let status = if payload.approved {
+        ApprovalStatus::Approved
+    } else {
+        ApprovalStatus::Denied {
+            reason: payload.reason,
+        }
+    };
+    let approval =
+        deployment
+            .approvals()
+            .respond(execution_process.id, &payload.approval_id, status)?;
+
+    deployment
+        .track_if_analytics_allowed(
+            "tool_approval_decided",
+            serde_json::json!({
+                "execution_process_id": execution_process.id.to_string(),
+                "tool_name": approval.tool_name,
+                "approved": payload.approved,
+            }),
+        )
+        .await;
+
+    Ok(ResponseJson(ApiResponse::success(approval)))
+}
+
+/// Called by the approvals MCP server; holds the request until the tool call is
+/// approved, denied or times out
+pub async fn request_approval(
+    Extension(execution_process): Extension<ExecutionProcess>,
+    State(deployment): State<DeploymentImpl>,
+    Json(payload): Json<ApprovalRequest>,
+) -> Result<ResponseJson<ApiResponse<ApprovalStatus>>, ApiError> {
+    let status = deployment
+        .approvals()
+        .request(
+            execution_process.id,
+            payload.tool_name,
+            payload.input,
+            APPROVAL_TIMEOUT,
+        )
+        .await?;
+    Ok(ResponseJson(ApiResponse::success(status)))
+}
+
 pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
     let task_attempt_id_router = Router::new()
         .route("/", get(get_execution_process_by_id))
         .route("/stop", post(stop_execution_process))
         .route("/raw-logs", get(stream_raw_logs))
         .route("/normalized-logs", get(stream_normalized_logs))
+        .route("/usage", get(get_execution_process_usage))
+        .route(
+            "/approvals",
+            get(get_pending_approvals).post(respond_to_approval),
+        )
+        .route("/approvals/request", post(request_approval))
+        .route("/message", post(send_message))
         .layer(from_fn_with_state(
             deployment.clone(),
             load_execution_process_middleware,
@@ -1,11 +1,312 @@
-use axum::{routing::get, Router, response::Json as ResponseJson};
+use std::fmt::Write;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Json as ResponseJson, Response},
+    routing::get,
+    Router,
+};
+use db::models::metrics::{DurationStats, MetricsFilter, MetricsSummary};
+use deployment::Deployment;
+use services::services::pricing::PriceTable;
 use utils::response::ApiResponse;
-use crate::DeploymentImpl;
+
+use crate::{error::ApiError, DeploymentImpl};
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub async fn get_summary(
+    State(deployment): State<DeploymentImpl>,
+    Query(filter): Query<MetricsFilter>,
+) -> Result<ResponseJson<ApiResponse<MetricsSummary>>, ApiError> {
+    let summary = load_priced(&deployment, &filter).await?;
+    Ok(ResponseJson(ApiResponse::success(summary)))
+}
+
+async fn load_priced(
+    deployment: &DeploymentImpl,
+    filter: &MetricsFilter,
+) -> Result<MetricsSummary, ApiError> {
+    let mut summary = MetricsSummary::load(&deployment.db().pool, filter).await?;
+    summary.tokens.cost_usd = PriceTable::load().apply(&mut summary.tokens.models);
+    Ok(summary)
+}
+
+/// The same aggregates in Prometheus text exposition format, for scraping
+pub async fn get_prometheus(
+    State(deployment): State<DeploymentImpl>,
+    Query(filter): Query<MetricsFilter>,
+) -> Result<Response, ApiError> {
+    let summary = load_priced(&deployment, &filter).await?;
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(PROMETHEUS_CONTENT_TYPE),
+        )],
+        render_prometheus(&summary),
+    )
+        .into_response())
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn write_summary(out: &mut String, name: &str, label: &str, stats: &DurationStats) {
+    for (quantile, value) in [
+        ("0.5", stats.p50_sec),
+        ("0.9", stats.p90_sec),
+        ("0.99", stats.p99_sec),
+    ] {
+        if let Some(value) = value {
+            let _ = writeln!(out, "{name}{{{label},quantile=\"{quantile}\"}} {value}");
+        }
+    }
+    let _ = writeln!(out, "{name}_sum{{{label}}} {}", stats.sum_sec);
+    let _ = writeln!(out, "{name}_count{{{label}}} {}", stats.count);
+}
+
+fn render_prometheus(summary: &MetricsSummary) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP vibe_attempts Task attempts per profile.\n");
+    out.push_str("# TYPE vibe_attempts gauge\n");
+    for p in &summary.profiles {
+        let _ = writeln!(
+            out,
+            "vibe_attempts{{profile=\"{}\"}} {}",
+            escape_label(&p.profile),
+            p.attempts
+        );
+    }
+    out.push_str("# HELP vibe_attempts_merged Attempts merged directly or through a merged PR.\n");
+    out.push_str("# TYPE vibe_attempts_merged gauge\n");
+    for p in &summary.profiles {
+        let _ = writeln!(
+            out,
+            "vibe_attempts_merged{{profile=\"{}\"}} {}",
+            escape_label(&p.profile),
+            p.merged
+        );
+    }
+    out.push_str("# HELP vibe_attempts_failed Unmerged attempts whose latest execution failed.\n");
+    out.push_str("# TYPE vibe_attempts_failed gauge\n");
+    for p in &summary.profiles {
+        let _ = writeln!(
+            out,
+            "vibe_attempts_failed{{profile=\"{}\"}} {}",
+            escape_label(&p.profile),
+            p.failed
+        );
+    }
+
+    out.push_str("# HELP vibe_execution_duration_seconds Execution process wall time.\n");
+    out.push_str("# TYPE vibe_execution_duration_seconds summary\n");
+    for d in &summary.durations {
+        let reason = serde_json::to_value(&d.run_reason)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        write_summary(
+            &mut out,
+            "vibe_execution_duration_seconds",
+            &format!("run_reason=\"{}\"", escape_label(&reason)),
+            &d.stats,
+        );
+    }
+
+    out.push_str(
+        "# HELP vibe_validator_pass_ratio Share of orchestrator runs each validator passed.\n",
+    );
+    out.push_str("# TYPE vibe_validator_pass_ratio gauge\n");
+    let v = &summary.validators;
+    for (validator, rate) in [
+        ("scope", v.scope),
+        ("dep", v.dep),
+        ("api", v.api),
+        ("det", v.det),
+        ("kpi", v.kpi),
+    ] {
+        if let Some(rate) = rate {
+            let _ = writeln!(
+                out,
+                "vibe_validator_pass_ratio{{validator=\"{validator}\"}} {rate}"
+            );
+        }
+    }
+    out.push_str("# HELP vibe_orchestrator_runs Attempts with an orchestrator run.\n");
+    out.push_str("# TYPE vibe_orchestrator_runs gauge\n");
+    let _ = writeln!(out, "vibe_orchestrator_runs {}", v.runs);
+
+    out.push_str("# HELP vibe_tokens Tokens used by coding agents.\n");
+    out.push_str("# TYPE vibe_tokens gauge\n");
+    let _ = writeln!(
+        out,
+        "vibe_tokens{{kind=\"prompt\"}} {}",
+        summary.tokens.prompt_tokens
+    );
+    let _ = writeln!(
+        out,
+        "vibe_tokens{{kind=\"completion\"}} {}",
+        summary.tokens.completion_tokens
+    );
+
+    out.push_str("# HELP vibe_model_tokens Tokens used per model and kind.\n");
+    out.push_str("# TYPE vibe_model_tokens gauge\n");
+    for m in &summary.tokens.models {
+        let model = escape_label(&m.model);
+        for (kind, tokens) in [
+            ("input", m.input_tokens),
+            ("output", m.output_tokens),
+            ("cache_read", m.cache_read_tokens),
+            ("cache_write", m.cache_write_tokens),
+        ] {
+            let _ = writeln!(
+                out,
+                "vibe_model_tokens{{model=\"{model}\",kind=\"{kind}\"}} {tokens}"
+            );
+        }
+    }
+    out.push_str("# HELP vibe_model_cost_usd Spend per model from the price table.\n");
+    out.push_str("# TYPE vibe_model_cost_usd gauge\n");
+    for m in &summary.tokens.models {
+        if let Some(cost) = m.cost_usd {
+            let _ = writeln!(
+                out,
+                "vibe_model_cost_usd{{model=\"{}\"}} {cost}",
+                escape_label(&m.model)
+            );
+        }
+    }
+
+    out.push_str("# HELP vibe_test_duration_seconds Cold and warm test suite wall time.\n");
+    out.push_str("# TYPE vibe_test_duration_seconds summary\n");
+    write_summary(
+        &mut out,
+        "vibe_test_duration_seconds",
+        "run=\"cold\"",
+        &summary.test_times.cold,
+    );
+    write_summary(
+        &mut out,
+        "vibe_test_duration_seconds",
+        "run=\"warm\"",
+        &summary.test_times.warm,
+    );
+    out.push_str("# HELP vibe_test_cache_hits Test cache hits across orchestrator runs.\n");
+    out.push_str("# TYPE vibe_test_cache_hits gauge\n");
+    let _ = writeln!(
+        out,
+        "vibe_test_cache_hits {}",
+        summary.test_times.cache_hit_count
+    );
+
+    out
+}
 
 pub fn router(_deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
-    Router::new().route("/metrics/summary", get(get_summary))
+    Router::new()
+        .route("/metrics/summary", get(get_summary))
+        .route("/metrics", get(get_prometheus))
 }
 
-async fn get_summary() -> ResponseJson<ApiResponse<&'static str>> {
-    ResponseJson(ApiResponse::success("ok"))
+#[cfg(test)]
+mod tests {
+    use db::models::{
+        execution_process::ExecutionProcessRunReason,
+        metrics::{
+            DurationMetrics, ProfileMetrics, TestTimeMetrics, TokenMetrics, ValidatorMetrics,
+        },
+        token_usage::ModelTokenUsage,
+    };
+
+    use super::*;
+
+    fn summary() -> MetricsSummary {
+        MetricsSummary {
+            attempts: 3,
+            profiles: vec![ProfileMetrics {
+                profile: "claude\"code".to_string(),
+                attempts: 3,
+                merged: 1,
+                failed: 1,
+                success_rate: Some(0.5),
+            }],
+            durations: vec![DurationMetrics {
+                run_reason: ExecutionProcessRunReason::CodingAgent,
+                stats: DurationStats::from_samples(vec![1.0, 3.0]),
+            }],
+            validators: ValidatorMetrics {
+                runs: 2,
+                scope: Some(1.0),
+                ..Default::default()
+            },
+            tokens: TokenMetrics {
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                models: vec![
+                    ModelTokenUsage {
+                        model: "opus".to_string(),
+                        turns: 1,
+                        input_tokens: 10,
+                        output_tokens: 5,
+                        cache_read_tokens: 0,
+                        cache_write_tokens: 0,
+                        cost_usd: Some(0.25),
+                    },
+                    ModelTokenUsage {
+                        model: "local".to_string(),
+                        turns: 1,
+                        input_tokens: 1,
+                        output_tokens: 1,
+                        cache_read_tokens: 0,
+                        cache_write_tokens: 0,
+                        cost_usd: None,
+                    },
+                ],
+                cost_usd: Some(0.25),
+            },
+            test_times: TestTimeMetrics::default(),
+        }
+    }
+
+    #[test]
+    fn prometheus_output_lists_each_family_once_with_its_samples() {
+        let out = render_prometheus(&summary());
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert!(lines.contains(&"vibe_attempts{profile=\"claude\\\"code\"} 3"));
+        assert!(lines.contains(&"vibe_tokens{kind=\"prompt\"} 10"));
+        assert!(lines.contains(&"vibe_model_tokens{model=\"opus\",kind=\"input\"} 10"));
+        assert!(lines.contains(&"vibe_model_cost_usd{model=\"opus\"} 0.25"));
+        assert!(!out.contains("vibe_model_cost_usd{model=\"local\"}"));
+        assert!(lines.contains(&"vibe_validator_pass_ratio{validator=\"scope\"} 1"));
+        assert!(!out.contains("validator=\"dep\""));
+        assert!(lines.contains(
+            &"vibe_execution_duration_seconds{run_reason=\"codingagent\",quantile=\"0.5\"} 1"
+        ));
+        assert!(
+            lines.contains(&"vibe_execution_duration_seconds_count{run_reason=\"codingagent\"} 2")
+        );
+        // Gauges can go down between scrapes, so none is named like a counter
+        assert!(!out.contains("_total"));
+
+        // Every sample follows its own family's TYPE line, and each family is declared once
+        let mut family = "";
+        let mut declared = Vec::new();
+        for line in lines {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                family = rest.split(' ').next().unwrap();
+                assert!(!declared.contains(&family), "{family} declared twice");
+                declared.push(family);
+            } else if !line.starts_with('#') {
+                let name = line.split(['{', ' ']).next().unwrap();
+                assert!(name.starts_with(family), "{name} outside {family}");
+            }
+        }
+    }
 }
@@ -24,6 +24,7 @@ pub mod attempts_orchestrator;
 pub mod metrics;
 pub mod tasks_uploads;
 pub mod phases;
+pub mod pipelines;
 
 pub fn router(deployment: DeploymentImpl) -> IntoMakeService<Router> {
     // Create routers with different middleware layers
@@ -37,6 +38,7 @@ pub fn router(deployment: DeploymentImpl) -> IntoMakeService<Router> {
         .merge(task_attempts::router(&deployment))
     .merge(attempts_orchestrator::router(&deployment))
     .merge(phases::router(&deployment))
+    .merge(pipelines::router(&deployment))
         .merge(execution_processes::router(&deployment))
         .merge(task_templates::router(&deployment))
     .merge(tasks_uploads::router(&deployment))
@@ -1,144 +1,136 @@
-use axum::{extract::{Path, State}, response::Json as ResponseJson, routing::{post, patch, get}, Router, Json, middleware::from_fn_with_state};
-use axum::http::StatusCode;
-use serde_json::json;
-use uuid::Uuid;
-use sqlx::Row;
-use crate::{DeploymentImpl, middleware::load_task_middleware};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    middleware::from_fn_with_state,
+    response::Json as ResponseJson,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use db::models::{
+    phase::{CreatePhase, Phase, PhaseError, PhaseStatus, UpdatePhase},
+    task::Task,
+};
+use deployment::Deployment;
 use utils::response::ApiResponse;
+use uuid::Uuid;
+use validators::{dep_diff::DepPolicy, scope_guard::ScopePolicy};
 
-pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
-    Router::new()
-        .route("/tasks/:id/phases", post(create_phase).get(list_phases)
-            .layer(from_fn_with_state(deployment.clone(), load_task_middleware)))
-        .route("/phases/:id", patch(update_phase))
+use crate::{
+    error::ApiError,
+    middleware::{load_phase_middleware, load_task_middleware},
+    DeploymentImpl,
+};
+
+/// Serialized allowlist/denylist payloads larger than this are refused
+const MAX_PATTERN_LIST_BYTES: usize = 16 * 1024;
+
+type PhaseResponse<T> = Result<(StatusCode, ResponseJson<ApiResponse<T>>), ApiError>;
+
+fn reject<T>(status: StatusCode, error: &str) -> PhaseResponse<T> {
+    Ok((status, ResponseJson(ApiResponse::error(error))))
 }
 
-async fn create_phase(State(deployment): State<DeploymentImpl>, Path(task_id): Path<String>) -> ResponseJson<ApiResponse<serde_json::Value>> {
-    let Ok(task_uuid) = Uuid::parse_str(&task_id) else { return ResponseJson(ApiResponse::error("invalid_task_id")); };
-    let phase_id = format!("P4-2025-08-23-{}", &Uuid::new_v4().to_string()[..8]);
-    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs().to_string()).unwrap_or_else(|_| "0".to_string());
-    let pool = &deployment.db().pool;
-    let _ = sqlx::query("INSERT INTO phases (task_id, phase_id, type, status, allowlist, denylist, agent_override, warm_kpi_budget, created_at, updated_at) VALUES (?1, ?2, 'prompt', 'idle', '[]', '[]', NULL, NULL, ?3, ?3)")
-        .bind(task_uuid)
-        .bind(&phase_id)
-        .bind(&now)
-        .execute(pool).await;
-    ResponseJson(ApiResponse::success(json!({
-        "task_id": task_uuid,
-        "phase_id": phase_id,
-        "type": "prompt",
-        "status": "idle",
-        "allowlist": [],
-        "denylist": [],
-        "agent_override": null,
-        "warm_kpi_budget": null,
-        "created_at": now,
-        "updated_at": now
-    })))
+pub async fn create_phase(
+    Extension(task): Extension<Task>,
+    State(deployment): State<DeploymentImpl>,
+    payload: Option<Json<CreatePhase>>,
+) -> Result<ResponseJson<ApiResponse<Phase>>, ApiError> {
+    let data = payload.map(|Json(p)| p).unwrap_or_default();
+    let phase = Phase::create(&deployment.db().pool, task.id, &data, Uuid::new_v4()).await?;
+    Ok(ResponseJson(ApiResponse::success(phase)))
 }
 
-#[derive(serde::Deserialize)]
-struct PhasePatch { status: Option<String>, allowlist: Option<serde_json::Value>, denylist: Option<serde_json::Value>, agent_override: Option<Option<String>>, warm_kpi_budget: Option<Option<f64>>, r#type: Option<String> }
+pub async fn list_phases(
+    Extension(task): Extension<Task>,
+    State(deployment): State<DeploymentImpl>,
+) -> Result<ResponseJson<ApiResponse<Vec<Phase>>>, ApiError> {
+    let phases = Phase::find_by_task_id(&deployment.db().pool, task.id).await?;
+    Ok(ResponseJson(ApiResponse::success(phases)))
+}
 
-async fn update_phase(State(deployment): State<DeploymentImpl>, Path(phase_id): Path<String>, Json(p): Json<PhasePatch>) -> (StatusCode, ResponseJson<ApiResponse<serde_json::Value>>) {
-    let pool = &deployment.db().pool;
-    // Enforce scoping: phase must belong to a task accessible to caller
-    let exists = sqlx::query_scalar::<_, i64>(
-    "SELECT COUNT(1) FROM phases p JOIN tasks t ON p.task_id = t.id WHERE p.phase_id = ?"
-    )
-    .bind(&phase_id)
-    .fetch_one(pool)
-    .await
-    .unwrap_or(0);
-    if exists == 0 {
-        // Not found or not accessible
-    return (StatusCode::NOT_FOUND, ResponseJson(ApiResponse::error("not_found")));
-    }
-    // Validate enums when present
-    if let Some(ref typ) = p.r#type {
-        if !(matches!(typ.as_str(), "prompt" | "fix" | "hardening")) {
-            return (StatusCode::BAD_REQUEST, ResponseJson(ApiResponse::error("invalid_type")));
+pub async fn get_phase(
+    Extension(phase): Extension<Phase>,
+) -> Result<ResponseJson<ApiResponse<Phase>>, ApiError> {
+    Ok(ResponseJson(ApiResponse::success(phase)))
+}
+
+pub async fn update_phase(
+    Extension(phase): Extension<Phase>,
+    State(deployment): State<DeploymentImpl>,
+    Json(payload): Json<UpdatePhase>,
+) -> PhaseResponse<Phase> {
+    if let Some(Some(policy)) = &payload.dep_policy {
+        if serde_json::from_value::<DepPolicy>(policy.clone()).is_err() {
+            return reject(StatusCode::BAD_REQUEST, "invalid_dep_policy");
         }
     }
-    if let Some(ref status) = p.status {
-        if !(matches!(status.as_str(), "idle" | "running" | "pass" | "fail")) {
-            return (StatusCode::BAD_REQUEST, ResponseJson(ApiResponse::error("invalid_status")));
+    // Lists must be gitignore-style patterns the scope guard can compile
+    for (list, error) in [
+        (&payload.allowlist, "invalid_allowlist"),
+        (&payload.denylist, "invalid_denylist"),
+    ] {
+        let Some(patterns) = list else { continue };
+        if serde_json::to_string(patterns).map_or(0, |s| s.len()) > MAX_PATTERN_LIST_BYTES {
+            return reject(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large");
+        }
+        let policy = ScopePolicy {
+            allowlist: patterns.clone(),
+            ..Default::default()
+        };
+        if policy.validate_patterns().is_err() {
+            return reject(StatusCode::BAD_REQUEST, error);
         }
     }
-    let mut sets: Vec<&str> = Vec::new();
-    if p.status.is_some() { sets.push("status = ?"); }
-    if p.allowlist.is_some() { sets.push("allowlist = ?"); }
-    if p.denylist.is_some() { sets.push("denylist = ?"); }
-    if p.agent_override.is_some() { sets.push("agent_override = ?"); }
-    if p.warm_kpi_budget.is_some() { sets.push("warm_kpi_budget = ?"); }
-    if p.r#type.is_some() { sets.push("type = ?"); }
-    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs().to_string()).unwrap_or_else(|_| "0".to_string());
-    sets.push("updated_at = ?");
-    let sql = format!("UPDATE phases SET {} WHERE phase_id = ?", sets.join(", "));
-    let mut q = sqlx::query(&sql);
-    if let Some(v) = p.status { q = q.bind(v); }
-    if let Some(v) = p.allowlist {
-        // Canonicalize and cap payload size (16 KiB)
-        let s = serde_json::to_string(&v).unwrap_or_else(|_| "[]".to_string());
-        if s.len() > 16 * 1024 { return (StatusCode::PAYLOAD_TOO_LARGE, ResponseJson(ApiResponse::error("payload_too_large"))); }
-        q = q.bind(s);
+    // Check the transition up front so a rejected status leaves the other fields untouched
+    let status = payload.status.filter(|s| *s != phase.status);
+    if let Some(to) = status {
+        if !phase.status.can_transition_to(to) {
+            return Err(PhaseError::InvalidTransition {
+                from: phase.status,
+                to,
+            }
+            .into());
+        }
     }
-    if let Some(v) = p.denylist {
-        let s = serde_json::to_string(&v).unwrap_or_else(|_| "[]".to_string());
-        if s.len() > 16 * 1024 { return (StatusCode::PAYLOAD_TOO_LARGE, ResponseJson(ApiResponse::error("payload_too_large"))); }
-        q = q.bind(s);
+
+    let pool = &deployment.db().pool;
+    let mut updated = Phase::update(pool, phase.id, &payload).await?;
+    if let Some(to) = status {
+        updated = Phase::transition(pool, phase.id, to).await?;
     }
-    if let Some(v) = p.agent_override { q = q.bind(v); }
-    if let Some(v) = p.warm_kpi_budget { q = q.bind(v); }
-    if let Some(v) = p.r#type { q = q.bind(v); }
-    q = q.bind(&now).bind(&phase_id);
-    let _ = q.execute(pool).await;
-    (StatusCode::OK, ResponseJson(ApiResponse::success(json!({
-        "phase_id": phase_id,
-        "status": p.status,
-        "warm_kpi_budget": p.warm_kpi_budget,
-    }))))
+    Ok((StatusCode::OK, ResponseJson(ApiResponse::success(updated))))
 }
 
-async fn list_phases(
+pub async fn delete_phase(
+    Extension(phase): Extension<Phase>,
     State(deployment): State<DeploymentImpl>,
-    Path(task_id): Path<String>,
-) -> ResponseJson<ApiResponse<serde_json::Value>> {
-    let Ok(task_uuid) = Uuid::parse_str(&task_id) else {
-        return ResponseJson(ApiResponse::error("invalid_task_id"));
-    };
-    let pool = &deployment.db().pool;
-    let rows = sqlx::query(
-        "SELECT phase_id, type, status, allowlist, denylist, agent_override, warm_kpi_budget, created_at, updated_at FROM phases WHERE task_id = ?1 ORDER BY created_at ASC, phase_id ASC",
-    )
-    .bind(task_uuid)
-    .fetch_all(pool)
-    .await
-    .unwrap_or_default();
-    let list: Vec<serde_json::Value> = rows
-        .into_iter()
-        .map(|r| {
-            let phase_id: String = r.get::<String, _>(0);
-            let typ: String = r.get::<String, _>(1);
-            let status: String = r.get::<String, _>(2);
-            let allowlist: String = r.get::<String, _>(3);
-            let denylist: String = r.get::<String, _>(4);
-            let agent_override: Option<String> = r.get::<Option<String>, _>(5);
-            let warm_kpi_budget: Option<f64> = r.get::<Option<f64>, _>(6);
-            let created_at: String = r.get::<String, _>(7);
-            let updated_at: String = r.get::<String, _>(8);
-            serde_json::json!({
-                "phase_id": phase_id,
-                "type": typ,
-                "status": status,
-                "allowlist": serde_json::from_str::<serde_json::Value>(&allowlist).unwrap_or(serde_json::json!([])),
-                "denylist": serde_json::from_str::<serde_json::Value>(&denylist).unwrap_or(serde_json::json!([])),
-                "agent_override": agent_override,
-                "warm_kpi_budget": warm_kpi_budget,
-                "created_at": created_at,
-                "updated_at": updated_at,
-            })
-        })
-        .collect();
-    ResponseJson(ApiResponse::success(serde_json::json!(list)))
+) -> PhaseResponse<()> {
+    if phase.status == PhaseStatus::Running {
+        return reject(StatusCode::CONFLICT, "phase_running");
+    }
+    let rows_affected = Phase::delete(&deployment.db().pool, phase.id).await?;
+    if rows_affected == 0 {
+        return Err(PhaseError::NotFound.into());
+    }
+    Ok((StatusCode::OK, ResponseJson(ApiResponse::success(()))))
+}
+
+pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
+    let task_phases_router = Router::new()
+        .route("/tasks/{id}/phases", post(create_phase).get(list_phases))
+        .layer(from_fn_with_state(deployment.clone(), load_task_middleware));
+
+    let phase_id_router = Router::new()
+        .route(
+            "/phases/{id}",
+            get(get_phase).patch(update_phase).delete(delete_phase),
+        )
+        .layer(from_fn_with_state(
+            deployment.clone(),
+            load_phase_middleware,
+        ));
+
+    Router::new()
+        .merge(task_phases_router)
+        .merge(phase_id_router)
 }
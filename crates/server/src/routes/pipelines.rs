@@ -0,0 +1,386 @@
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::State, http::StatusCode, middleware::from_fn_with_state,
+    response::Json as ResponseJson, routing::post, Extension, Json, Router,
+};
+use db::models::{
+    execution_process::{ExecutionProcess, ExecutionProcessStatus},
+    phase::{Phase, PhaseStatus, PhaseType},
+    task::Task,
+    task_attempt::{CreateTaskAttempt, TaskAttempt},
+};
+use deployment::Deployment;
+use executors::profile::ProfileConfigs;
+use orchestrator::feedback::failure_feedback;
+use serde::Deserialize;
+use ts_rs::TS;
+use utils::response::ApiResponse;
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    middleware::load_task_middleware,
+    routes::attempts_orchestrator::{data_dir, run_artifacts_dir, start_run},
+    DeploymentImpl,
+};
+
+/// Runs a fix phase gets before the pipeline gives up
+pub const DEFAULT_MAX_FIX_ITERATIONS: u32 = 3;
+const PHASE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a single run may keep its phase running before the pipeline gives up
+const PHASE_RUN_TIMEOUT: Duration = Duration::from_secs(6 * 60 * 60);
+/// How long the container gets to commit and settle the phase after a run ends
+const PHASE_SETTLE_GRACE: Duration = Duration::from_secs(60);
+const GENERIC_FAILURE: &str = "The previous run failed validation.\n";
+
+#[derive(Debug, Default, Deserialize, TS)]
+pub struct StartPipelineRequest {
+    /// Branch the first phase starts from; defaults to the base branch of the
+    /// task's latest attempt, else the repository's current branch
+    pub base_branch: Option<String>,
+    /// Runs per fix phase before the pipeline stops, default 3
+    pub max_fix_iterations: Option<u32>,
+}
+
+/// Tasks with a pipeline in flight. Checked and claimed under one lock, so two
+/// requests cannot both start a pipeline for the same task.
+static RUNNING_PIPELINES: LazyLock<Mutex<HashSet<Uuid>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Releases the task's pipeline claim when the pipeline ends
+struct PipelineClaim(Uuid);
+
+impl PipelineClaim {
+    fn try_claim(task_id: Uuid) -> Option<Self> {
+        let claimed = RUNNING_PIPELINES.lock().unwrap().insert(task_id);
+        claimed.then(|| Self(task_id))
+    }
+}
+
+impl Drop for PipelineClaim {
+    fn drop(&mut self) {
+        RUNNING_PIPELINES.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Result of running one phase to completion
+enum PhaseRun {
+    /// The phase passed; later phases branch off `branch`
+    Passed { branch: String },
+    /// Every run failed; `feedback` describes the last failure
+    Failed { branch: String, feedback: String },
+    /// The user stopped the run
+    Stopped,
+}
+
+pub async fn start_pipeline(
+    Extension(task): Extension<Task>,
+    State(deployment): State<DeploymentImpl>,
+    payload: Option<Json<StartPipelineRequest>>,
+) -> Result<(StatusCode, ResponseJson<ApiResponse<Vec<Phase>>>), ApiError> {
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    let pool = &deployment.db().pool;
+
+    let phases = Phase::find_by_task_id(pool, task.id).await?;
+    if phases.is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            ResponseJson(ApiResponse::error("no_phases")),
+        ));
+    }
+    let Some(claim) = PipelineClaim::try_claim(task.id) else {
+        return Ok((
+            StatusCode::CONFLICT,
+            ResponseJson(ApiResponse::error("pipeline_running")),
+        ));
+    };
+    if phases.iter().any(|p| p.status == PhaseStatus::Running) {
+        return Ok((
+            StatusCode::CONFLICT,
+            ResponseJson(ApiResponse::error("pipeline_running")),
+        ));
+    }
+    if data_dir(&deployment).await.is_none() {
+        return Ok((
+            StatusCode::CONFLICT,
+            ResponseJson(ApiResponse::error("orchestrator_disabled")),
+        ));
+    }
+
+    // New attempts follow the task's latest attempt, else the configured default profile
+    let latest = TaskAttempt::fetch_all(pool, Some(task.id))
+        .await?
+        .into_iter()
+        .next();
+    let base_branch = match (payload.base_branch, &latest) {
+        (Some(branch), _) => branch,
+        (None, Some(attempt)) => attempt.base_branch.clone(),
+        (None, None) => {
+            let Some(project) = task.parent_project(pool).await? else {
+                return Ok((
+                    StatusCode::NOT_FOUND,
+                    ResponseJson(ApiResponse::error("project_not_found")),
+                ));
+            };
+            deployment
+                .git()
+                .get_current_branch(&project.git_repo_path)?
+        }
+    };
+    let profile = match &latest {
+        Some(attempt) => attempt.profile.clone(),
+        None => {
+            let label = deployment.config().read().await.profile.clone();
+            match ProfileConfigs::get_cached().get_profile(&label.profile) {
+                Some(profile) => profile.default.label.clone(),
+                None => {
+                    return Ok((
+                        StatusCode::BAD_REQUEST,
+                        ResponseJson(ApiResponse::error("profile_not_found")),
+                    ))
+                }
+            }
+        }
+    };
+    let max_fix_iterations = payload
+        .max_fix_iterations
+        .unwrap_or(DEFAULT_MAX_FIX_ITERATIONS)
+        .max(1);
+
+    let deployment = deployment.clone();
+    let task_id = task.id;
+    tokio::spawn(async move {
+        run_pipeline(
+            deployment,
+            task_id,
+            base_branch,
+            profile,
+            max_fix_iterations,
+        )
+        .await;
+        drop(claim);
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        ResponseJson(ApiResponse::success(phases)),
+    ))
+}
+
+/// Run the task's phases in order. Each phase gets its own attempt, branched
+/// off the previous phase's branch. Fix phases only run after a failed phase,
+/// receive its failures in their prompt and retry in the same worktree up to
+/// `max_fix_iterations` times. The pipeline stops at the first failure no fix
+/// phase repairs; once every phase passed the container moves the task to review.
+async fn run_pipeline(
+    deployment: DeploymentImpl,
+    task_id: Uuid,
+    mut base_branch: String,
+    profile: String,
+    max_fix_iterations: u32,
+) {
+    let phases = match Phase::find_by_task_id(&deployment.db().pool, task_id).await {
+        Ok(phases) => phases,
+        Err(e) => {
+            tracing::error!("Failed to load phases for task {}: {}", task_id, e);
+            return;
+        }
+    };
+
+    // Set while the previous phase's failure is waiting for a fix phase
+    let mut feedback: Option<String> = None;
+    for phase in phases {
+        let is_fix = phase.phase_type == PhaseType::Fix;
+        if is_fix && feedback.is_none() {
+            continue;
+        }
+        if !is_fix && feedback.is_some() {
+            break;
+        }
+        let runs = if is_fix { max_fix_iterations } else { 1 };
+        match run_phase(
+            &deployment,
+            &phase,
+            &base_branch,
+            &profile,
+            runs,
+            feedback.take(),
+        )
+        .await
+        {
+            Ok(PhaseRun::Passed { branch }) => base_branch = branch,
+            Ok(PhaseRun::Failed {
+                branch,
+                feedback: failure,
+            }) => {
+                base_branch = branch;
+                feedback = Some(failure);
+            }
+            Ok(PhaseRun::Stopped) => {
+                tracing::info!(
+                    "Pipeline for task {} stopped at phase {}",
+                    task_id,
+                    phase.id
+                );
+                return;
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Pipeline for task {} failed at phase {}: {}",
+                    task_id,
+                    phase.id,
+                    e
+                );
+                return;
+            }
+        }
+    }
+
+    if feedback.is_some() {
+        tracing::info!("Pipeline for task {} ended with a failed phase", task_id);
+    } else {
+        tracing::info!("Pipeline for task {} passed", task_id);
+    }
+}
+
+async fn run_phase(
+    deployment: &DeploymentImpl,
+    phase: &Phase,
+    base_branch: &str,
+    profile: &str,
+    runs: u32,
+    mut feedback: Option<String>,
+) -> Result<PhaseRun, String> {
+    let pool = &deployment.db().pool;
+    let data_dir = data_dir(deployment)
+        .await
+        .ok_or_else(|| "orchestrator disabled".to_string())?;
+    let attempt = TaskAttempt::create(
+        pool,
+        &CreateTaskAttempt {
+            profile: profile.to_string(),
+            base_branch: base_branch.to_string(),
+        },
+        phase.task_id,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    Phase::start_attempt(pool, phase.id, attempt.id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut run = 0;
+    loop {
+        run += 1;
+        // Reload so later runs see the worktree and branch created by the first
+        let attempt = TaskAttempt::find_by_id(pool, attempt.id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "attempt deleted".to_string())?;
+        // The phase was started with its attempt and is retried by this loop only
+        let process = match start_run(deployment, &attempt, feedback.take(), true).await {
+            Ok(process) => process,
+            Err((_, error)) => {
+                // A run that never started leaves nothing to settle the phase
+                let _ = Phase::transition(pool, phase.id, PhaseStatus::Fail).await;
+                return Err(error.to_string());
+            }
+        };
+        let status = wait_for_phase(deployment, phase.id, process.id).await?;
+        if ExecutionProcess::was_killed(pool, process.id).await {
+            return Ok(PhaseRun::Stopped);
+        }
+
+        let branch = TaskAttempt::find_by_id(pool, attempt.id)
+            .await
+            .map_err(|e| e.to_string())?
+            .and_then(|a| a.branch)
+            .unwrap_or_else(|| base_branch.to_string());
+        if status == PhaseStatus::Pass {
+            return Ok(PhaseRun::Passed { branch });
+        }
+        let failure = failure_feedback(&run_artifacts_dir(&data_dir, attempt.id, process.id))
+            .unwrap_or_else(|| GENERIC_FAILURE.to_string());
+        tracing::info!(
+            "Phase {} failed run {}/{} of attempt {}",
+            phase.id,
+            run,
+            runs,
+            attempt.id
+        );
+        if run >= runs {
+            return Ok(PhaseRun::Failed {
+                branch,
+                feedback: failure,
+            });
+        }
+        feedback = Some(failure);
+    }
+}
+
+/// The container settles the phase after committing the run's changes. Gives
+/// up when the phase is deleted, when the run ended without settling it, or
+/// after `PHASE_RUN_TIMEOUT`.
+async fn wait_for_phase(
+    deployment: &DeploymentImpl,
+    phase_id: Uuid,
+    process_id: Uuid,
+) -> Result<PhaseStatus, String> {
+    let pool = &deployment.db().pool;
+    let deadline = Instant::now() + PHASE_RUN_TIMEOUT;
+    let mut settle_deadline = None;
+    loop {
+        tokio::time::sleep(PHASE_POLL_INTERVAL).await;
+        let phase = Phase::find_by_id(pool, phase_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "phase deleted".to_string())?;
+        if phase.status != PhaseStatus::Running {
+            return Ok(phase.status);
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(format!(
+                "phase still running after {}s",
+                PHASE_RUN_TIMEOUT.as_secs()
+            ));
+        }
+        let ended = ExecutionProcess::find_by_id(pool, process_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .is_none_or(|p| p.status != ExecutionProcessStatus::Running);
+        if ended {
+            let settle_by = *settle_deadline.get_or_insert(now + PHASE_SETTLE_GRACE);
+            if now >= settle_by {
+                return Err("run ended without settling the phase".to_string());
+            }
+        }
+    }
+}
+
+pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
+    Router::new()
+        .route("/tasks/{id}/pipeline", post(start_pipeline))
+        .layer(from_fn_with_state(deployment.clone(), load_task_middleware))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_task_runs_one_pipeline_at_a_time() {
+        let task_id = Uuid::new_v4();
+        let claim = PipelineClaim::try_claim(task_id).unwrap();
+        assert!(PipelineClaim::try_claim(task_id).is_none());
+        assert!(PipelineClaim::try_claim(Uuid::new_v4()).is_some());
+        drop(claim);
+        assert!(PipelineClaim::try_claim(task_id).is_some());
+    }
+}
@@ -182,6 +182,11 @@ pub async fn update_project(
         dev_script,
         cleanup_script,
         copy_files,
+        orchestrator_test_command,
+        orchestrator_cold_timeout_sec,
+        orchestrator_warm_timeout_sec,
+        orchestrator_test_env,
+        auto_approve_rules,
     } = payload;
 
     let name = name.unwrap_or(existing_project.name);
@@ -197,6 +202,11 @@ pub async fn update_project(
         dev_script,
         cleanup_script,
         copy_files,
+        orchestrator_test_command,
+        orchestrator_cold_timeout_sec,
+        orchestrator_warm_timeout_sec,
+        orchestrator_test_env,
+        auto_approve_rules,
     )
     .await
     {
@@ -1,31 +1,34 @@
-use std::{env, fs, io::Write, path::{Path, PathBuf}};
+use std::{fs, io::Write};
 
 use axum::{
     extract::{DefaultBodyLimit, Multipart, State},
     middleware::from_fn_with_state,
     http::{header, StatusCode},
     response::Response,
-    routing::{get, post},
+    routing::{delete, get, post},
     Extension, Router, body::Body,
 };
 use chrono::{DateTime, Utc};
+use deployment::Deployment;
 use serde::Serialize;
 use sqlx::FromRow;
 use utils::response::ApiResponse;
 use uuid::Uuid;
 
-use crate::{middleware::load_project_middleware, util::{hash::sha256_hex, mime::detect_mime}, DeploymentImpl};
+use crate::{middleware::load_project_middleware, util::{extract::{self, ExtractionStatus}, hash::sha256_hex, mime::detect_mime, storage::data_root}, DeploymentImpl};
 
 const MAX_SIZE: usize = 30 * 1024 * 1024; // 30MB
 
 #[derive(Debug, Serialize)]
 struct UploadMeta {
+    id: Uuid,
     filename: String,
     mime: String,
     size: i64,
     content_hash: String,
     stored_at: String,
     extracted_text: bool,
+    extraction: ExtractionStatus,
     dedup: bool,
 }
 
@@ -41,15 +44,6 @@ struct ProjectCtxRow {
     created_at: DateTime<Utc>,
 }
 
-fn data_root() -> PathBuf {
-    if let Ok(dir) = env::var("VK_DATA_DIR") {
-        PathBuf::from(dir)
-    } else {
-        // fallback to app data dir
-        utils::assets::asset_dir()
-    }
-}
-
 fn normalize_filename(name: &str) -> String {
     let candidate = name.replace(['\\', '/', ':'], "_");
     let s: String = candidate
@@ -66,13 +60,17 @@ fn allowed_mime(mime: &str) -> bool {
 }
 
 pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
-    Router::new()
-    .route("/projects/:id/uploads", post(upload).get(list).layer(DefaultBodyLimit::max(MAX_SIZE)))
-    .route("/projects/:id/uploads/:hash/:file", get(download))
+    let project_router = Router::new()
+        .route("/projects/{id}/uploads", post(upload).get(list).layer(DefaultBodyLimit::max(MAX_SIZE)))
         .layer(from_fn_with_state(
             deployment.clone(),
             load_project_middleware,
-        ))
+        ));
+    // Routes with more than the project id in the path check ownership themselves
+    Router::new()
+        .merge(project_router)
+        .route("/projects/{id}/uploads/{file_id}", delete(delete_upload))
+        .route("/projects/{id}/uploads/{hash}/{file}", get(download))
 }
 
 pub async fn upload(
@@ -93,6 +91,8 @@ pub async fn upload(
             return Ok(axum::Json(ApiResponse::error("mime_not_allowed")));
         }
         let hash = sha256_hex(&bytes);
+        // Held until the row is in, so a concurrent delete cannot remove the blob under it
+        let _blob = deployment.context_files().lock_blob(&hash).await;
         let root = data_root().join("blob").join(&hash);
         fs::create_dir_all(&root)?;
         let stored_path_rel = format!("blob/{hash}/{fname}");
@@ -101,18 +101,9 @@ pub async fn upload(
             let mut f = fs::File::create(&full_path)?;
             f.write_all(&bytes)?;
         }
-        // Sidecar for PDFs
-        let sidecar = data_root().join("blob").join(format!("{hash}.txt"));
-        let mut extracted = false;
-        if mime == "application/pdf" {
-            if !sidecar.exists() {
-                // Create empty stub (no extractor available)
-                let _ = fs::File::create(&sidecar);
-            }
-            extracted = fs::metadata(&sidecar).map(|m| m.len() > 0).unwrap_or(false);
-        } else {
-            extracted = sidecar.exists() && fs::metadata(&sidecar).map(|m| m.len() > 0).unwrap_or(false);
-        }
+        // Text extraction runs in the background; the listing reports its progress
+        extract::spawn(data_root(), hash.clone(), mime.clone(), full_path.clone());
+        let extraction = extract::status(&data_root(), &hash, &mime);
 
         // Dedup by (project_id, sha256)
         let existing = sqlx::query_as!(
@@ -151,12 +142,14 @@ pub async fn upload(
         };
 
         let meta = UploadMeta {
+            id: row.id,
             filename: row.filename,
             mime: row.mime,
             size: row.size_bytes,
             content_hash: row.sha256,
             stored_at: row.stored_path,
-            extracted_text: extracted,
+            extracted_text: extraction == ExtractionStatus::Done,
+            extraction,
             dedup,
         };
         return Ok(axum::Json(ApiResponse::success(meta)));
@@ -179,15 +172,16 @@ pub async fn list(
     let metas = rows
         .into_iter()
         .map(|r| {
-            let sidecar = data_root().join("blob").join(format!("{}.txt", r.sha256));
-            let extracted = sidecar.exists() && fs::metadata(&sidecar).map(|m| m.len() > 0).unwrap_or(false);
+            let extraction = extract::status(&data_root(), &r.sha256, &r.mime);
             UploadMeta {
+                id: r.id,
                 filename: r.filename,
                 mime: r.mime,
                 size: r.size_bytes,
                 content_hash: r.sha256,
                 stored_at: r.stored_path,
-                extracted_text: extracted,
+                extracted_text: extraction == ExtractionStatus::Done,
+                extraction,
                 dedup: false,
             }
         })
@@ -196,15 +190,22 @@ pub async fn list(
     Ok(axum::Json(ApiResponse::success(metas)))
 }
 
+pub async fn delete_upload(
+    State(deployment): State<DeploymentImpl>,
+    axum::extract::Path((project_id, file_id)): axum::extract::Path<(Uuid, Uuid)>,
+) -> Result<axum::Json<ApiResponse<()>>, crate::error::ApiError> {
+    deployment.context_files().delete_project_file(project_id, file_id).await?;
+    Ok(axum::Json(ApiResponse::success(())))
+}
+
 pub async fn download(
     State(deployment): State<DeploymentImpl>,
-    Extension(project): Extension<db::models::project::Project>,
-    axum::extract::Path((hash, file)): axum::extract::Path<(String, String)>,
+    axum::extract::Path((project_id, hash, file)): axum::extract::Path<(Uuid, String, String)>,
 ) -> Result<Response, crate::error::ApiError> {
     // Verify row exists for project/hash
     let exists = sqlx::query!(
         r#"SELECT 1 as "one!: i64" FROM project_context_files WHERE project_id = ?1 AND sha256 = ?2 LIMIT 1"#,
-        project.id,
+        project_id,
         hash
     )
     .fetch_optional(&deployment.db().pool)
@@ -13,9 +13,11 @@ use db::models::{
     execution_process::{ExecutionProcess, ExecutionProcessRunReason},
     image::TaskImage,
     merge::{Merge, MergeStatus, PrMerge, PullRequestInfo},
+    phase::{Phase, PhaseError, PhaseStatus},
     project::{Project, ProjectError},
     task::{Task, TaskStatus},
     task_attempt::{CreateTaskAttempt, TaskAttempt, TaskAttemptError},
+    token_usage::ExecutionTokenUsage,
 };
 use deployment::Deployment;
 use executors::{
@@ -33,8 +35,9 @@ use services::services::{
     container::ContainerService,
     github_service::{CreatePrRequest, GitHubService, GitHubServiceError},
     image::ImageService,
+    pricing::{PriceTable, TokenSpend},
 };
-use sqlx::Error as SqlxError;
+use sqlx::{Error as SqlxError, SqlitePool};
 use ts_rs::TS;
 use utils::response::ApiResponse;
 use uuid::Uuid;
@@ -86,6 +89,10 @@ pub struct CreateTaskAttemptBody {
     pub task_id: Uuid,
     pub profile_variant_label: Option<ProfileVariantLabel>,
     pub base_branch: String,
+    /// Phase of the task this attempt runs; the phase moves to running
+    #[serde(default)]
+    #[ts(optional)]
+    pub phase_id: Option<Uuid>,
 }
 
 #[axum::debug_handler]
@@ -107,6 +114,13 @@ pub async fn create_task_attempt(
             )))
         })?;
 
+    let phase = match payload.phase_id {
+        Some(phase_id) => {
+            Some(phase_for_new_attempt(&deployment.db().pool, payload.task_id, phase_id).await?)
+        }
+        None => None,
+    };
+
     let task_attempt = TaskAttempt::create(
         &deployment.db().pool,
         &CreateTaskAttempt {
@@ -117,10 +131,23 @@ pub async fn create_task_attempt(
     )
     .await?;
 
-    let execution_process = deployment
+    if let Some(phase) = &phase {
+        Phase::start_attempt(&deployment.db().pool, phase.id, task_attempt.id).await?;
+    }
+
+    let execution_process = match deployment
         .container()
         .start_attempt(&task_attempt, profile_variant_label.clone())
-        .await?;
+        .await
+    {
+        Ok(execution_process) => execution_process,
+        Err(e) => {
+            if let Some(phase) = &phase {
+                release_phase(&deployment.db().pool, phase.id).await;
+            }
+            return Err(e.into());
+        }
+    };
 
     deployment
         .track_if_analytics_allowed(
@@ -139,6 +166,33 @@ pub async fn create_task_attempt(
     Ok(ResponseJson(ApiResponse::success(task_attempt)))
 }
 
+/// The phase a new attempt of the task is started for, if it can run again
+async fn phase_for_new_attempt(
+    pool: &SqlitePool,
+    task_id: Uuid,
+    phase_id: Uuid,
+) -> Result<Phase, ApiError> {
+    let phase = Phase::find_by_id(pool, phase_id)
+        .await?
+        .filter(|p| p.task_id == task_id)
+        .ok_or(PhaseError::NotFound)?;
+    if !phase.status.can_transition_to(PhaseStatus::Running) {
+        return Err(PhaseError::InvalidTransition {
+            from: phase.status,
+            to: PhaseStatus::Running,
+        }
+        .into());
+    }
+    Ok(phase)
+}
+
+/// Fail a phase whose attempt never started, so it can be retried
+async fn release_phase(pool: &SqlitePool, phase_id: Uuid) {
+    if let Err(e) = Phase::transition(pool, phase_id, PhaseStatus::Fail).await {
+        tracing::error!("Failed to settle phase {}: {}", phase_id, e);
+    }
+}
+
 #[derive(Debug, Deserialize, TS)]
 pub struct CreateFollowUpAttempt {
     pub prompt: String,
@@ -244,6 +298,7 @@ pub async fn follow_up(
         prompt,
         session_id,
         profile_variant_label,
+        approval_url: None,
     };
 
     let follow_up_action = ExecutorAction::new(
@@ -822,6 +877,19 @@ pub async fn get_task_attempt_children(
     }
 }
 
+/// Tokens the attempt's execution processes consumed, per model, with their cost
+pub async fn get_task_attempt_usage(
+    Extension(task_attempt): Extension<TaskAttempt>,
+    State(deployment): State<DeploymentImpl>,
+) -> Result<ResponseJson<ApiResponse<TokenSpend>>, ApiError> {
+    let models =
+        ExecutionTokenUsage::find_by_task_attempt_id(&deployment.db().pool, task_attempt.id)
+            .await?;
+    Ok(ResponseJson(ApiResponse::success(
+        PriceTable::load().spend(models),
+    )))
+}
+
 pub async fn stop_task_attempt_execution(
     Extension(task_attempt): Extension<TaskAttempt>,
     State(deployment): State<DeploymentImpl>,
@@ -844,6 +912,7 @@ pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
         .route("/open-editor", post(open_task_attempt_in_editor))
         .route("/delete-file", post(delete_task_attempt_file))
         .route("/children", get(get_task_attempt_children))
+        .route("/usage", get(get_task_attempt_usage))
         .route("/stop", post(stop_task_attempt_execution))
         .layer(from_fn_with_state(
             deployment.clone(),
@@ -856,3 +925,74 @@ pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
 
     Router::new().nest("/task-attempts", task_attempts_router)
 }
+
+#[cfg(test)]
+mod tests {
+    use db::{
+        models::phase::CreatePhase,
+        test_support::{seed_attempt, seed_task},
+        DBService,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn new_attempts_only_start_phases_of_their_task_that_can_run() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let task = seed_task(&db.pool).await;
+        let other = seed_task(&db.pool).await;
+        let phase = Phase::create(&db.pool, task.id, &CreatePhase::default(), Uuid::new_v4())
+            .await
+            .unwrap();
+
+        let err = phase_for_new_attempt(&db.pool, other.id, phase.id)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::Phase(PhaseError::NotFound)));
+
+        let found = phase_for_new_attempt(&db.pool, task.id, phase.id)
+            .await
+            .unwrap();
+        assert_eq!(found.id, phase.id);
+
+        let attempt = seed_attempt(&db.pool, task.id).await;
+        Phase::start_attempt(&db.pool, phase.id, attempt.id)
+            .await
+            .unwrap();
+        let err = phase_for_new_attempt(&db.pool, task.id, phase.id)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ApiError::Phase(PhaseError::InvalidTransition {
+                from: PhaseStatus::Running,
+                to: PhaseStatus::Running,
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_phase_whose_attempt_failed_to_start_can_be_retried() {
+        let db = DBService::new_in_memory().await.unwrap();
+        let task = seed_task(&db.pool).await;
+        let phase = Phase::create(&db.pool, task.id, &CreatePhase::default(), Uuid::new_v4())
+            .await
+            .unwrap();
+        let attempt = seed_attempt(&db.pool, task.id).await;
+        Phase::start_attempt(&db.pool, phase.id, attempt.id)
+            .await
+            .unwrap();
+
+        release_phase(&db.pool, phase.id).await;
+
+        let phase = phase_for_new_attempt(&db.pool, task.id, phase.id)
+            .await
+            .unwrap();
+        assert_eq!(phase.status, PhaseStatus::Fail);
+        let linked = Phase::find_by_attempt_id(&db.pool, attempt.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(linked.id, phase.id);
+    }
+}
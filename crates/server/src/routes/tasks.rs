@@ -10,10 +10,14 @@ use db::models::{
     project::Project,
     task::{CreateTask, Task, TaskWithAttemptStatus, UpdateTask},
     task_attempt::{CreateTaskAttempt, TaskAttempt, TaskAttemptError},
+    token_usage::ExecutionTokenUsage,
 };
 use deployment::Deployment;
 use serde::Deserialize;
-use services::services::container::ContainerService;
+use services::services::{
+    container::ContainerService,
+    pricing::{PriceTable, TokenSpend},
+};
 use sqlx::Error as SqlxError;
 use utils::response::ApiResponse;
 use uuid::Uuid;
@@ -43,6 +47,17 @@ pub async fn get_task(
     Ok(ResponseJson(ApiResponse::success(task)))
 }
 
+/// Tokens spent across all of the task's attempts, per model, with their cost
+pub async fn get_task_usage(
+    Extension(task): Extension<Task>,
+    State(deployment): State<DeploymentImpl>,
+) -> Result<ResponseJson<ApiResponse<TokenSpend>>, ApiError> {
+    let models = ExecutionTokenUsage::find_by_task_id(&deployment.db().pool, task.id).await?;
+    Ok(ResponseJson(ApiResponse::success(
+        PriceTable::load().spend(models),
+    )))
+}
+
 pub async fn create_task(
     State(deployment): State<DeploymentImpl>,
     Json(payload): Json<CreateTask>,
@@ -229,6 +244,7 @@ pub async fn delete_task(
 pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
     let task_id_router = Router::new()
         .route("/", get(get_task).put(update_task).delete(delete_task))
+        .route("/usage", get(get_task_usage))
         .layer(from_fn_with_state(deployment.clone(), load_task_middleware));
 
     let inner = Router::new()
@@ -1,29 +1,32 @@
-use std::{env, fs, io::Write, path::PathBuf};
+use std::{fs, io::Write};
 
 use axum::{
     extract::{DefaultBodyLimit, Multipart, State},
     middleware::from_fn_with_state,
-    routing::{get, post},
+    routing::{delete, get, post},
     Extension, Router,
 };
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use deployment::Deployment;
+use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use utils::response::ApiResponse;
 use uuid::Uuid;
 
-use crate::{middleware::load_task_middleware, util::{hash::sha256_hex, mime::detect_mime}, DeploymentImpl};
+use crate::{middleware::load_task_middleware, util::{extract::{self, ExtractionStatus}, hash::sha256_hex, mime::detect_mime, storage::data_root}, DeploymentImpl};
 
 const MAX_SIZE: usize = 30 * 1024 * 1024; // 30MB
 
 #[derive(Debug, Serialize)]
 struct UploadMeta {
+    id: Uuid,
     filename: String,
     mime: String,
     size: i64,
     content_hash: String,
     stored_at: String,
     extracted_text: bool,
+    extraction: ExtractionStatus,
     dedup: bool,
 }
 
@@ -39,10 +42,6 @@ struct TaskCtxRow {
     created_at: DateTime<Utc>,
 }
 
-fn data_root() -> PathBuf {
-    if let Ok(dir) = env::var("VK_DATA_DIR") { PathBuf::from(dir) } else { utils::assets::asset_dir() }
-}
-
 fn normalize_filename(name: &str) -> String {
     let candidate = name.replace(['\\', '/', ':'], "_");
     let s: String = candidate
@@ -58,10 +57,19 @@ fn allowed_mime(mime: &str) -> bool {
     )
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AttachProjectFile {
+    project_file_id: Uuid,
+}
+
 pub fn router(deployment: &DeploymentImpl) -> Router<DeploymentImpl> {
+    let task_router = Router::new()
+        .route("/tasks/{id}/uploads", post(upload).get(list).layer(DefaultBodyLimit::max(MAX_SIZE)))
+        .route("/tasks/{id}/uploads/attach", post(attach))
+        .layer(from_fn_with_state(deployment.clone(), load_task_middleware));
     Router::new()
-    .route("/tasks/:id/uploads", post(upload).get(list).layer(DefaultBodyLimit::max(MAX_SIZE)))
-        .layer(from_fn_with_state(deployment.clone(), load_task_middleware))
+        .merge(task_router)
+        .route("/tasks/{id}/uploads/{file_id}", delete(detach))
 }
 
 pub async fn upload(
@@ -78,6 +86,8 @@ pub async fn upload(
         let mime = detect_mime(&bytes, &fname).unwrap_or_else(|| "application/octet-stream".into());
         if !allowed_mime(&mime) { return Ok(axum::Json(ApiResponse::error("mime_not_allowed"))); }
         let hash = sha256_hex(&bytes);
+        // Held until the row is in, so a concurrent delete cannot remove the blob under it
+        let _blob = deployment.context_files().lock_blob(&hash).await;
         let root = data_root().join("blob").join(&hash);
         fs::create_dir_all(&root)?;
         let stored_path_rel = format!("blob/{hash}/{fname}");
@@ -86,14 +96,9 @@ pub async fn upload(
             let mut f = fs::File::create(&full_path)?;
             f.write_all(&bytes)?;
         }
-        let sidecar = data_root().join("blob").join(format!("{hash}.txt"));
-        let mut extracted = false;
-        if mime == "application/pdf" {
-            if !sidecar.exists() { let _ = fs::File::create(&sidecar); }
-            extracted = fs::metadata(&sidecar).map(|m| m.len() > 0).unwrap_or(false);
-        } else {
-            extracted = sidecar.exists() && fs::metadata(&sidecar).map(|m| m.len() > 0).unwrap_or(false);
-        }
+        // Text extraction runs in the background; the listing reports its progress
+        extract::spawn(data_root(), hash.clone(), mime.clone(), full_path.clone());
+        let extraction = extract::status(&data_root(), &hash, &mime);
 
         let existing = sqlx::query_as!(
             TaskCtxRow,
@@ -131,12 +136,14 @@ pub async fn upload(
         };
 
         let meta = UploadMeta {
+            id: row.id,
             filename: row.filename,
             mime: row.mime,
             size: row.size_bytes,
             content_hash: row.sha256,
             stored_at: row.stored_path,
-            extracted_text: extracted,
+            extracted_text: extraction == ExtractionStatus::Done,
+            extraction,
             dedup,
         };
         return Ok(axum::Json(ApiResponse::success(meta)));
@@ -147,7 +154,7 @@ pub async fn upload(
 pub async fn list(
     State(deployment): State<DeploymentImpl>,
     Extension(task): Extension<db::models::task::Task>,
-) -> Result<axum::Json<ApiResponse<Vec<UploadMeta>>>>, crate::error::ApiError> {
+) -> Result<axum::Json<ApiResponse<Vec<UploadMeta>>>, crate::error::ApiError> {
     let rows: Vec<TaskCtxRow> = sqlx::query_as!(
         TaskCtxRow,
         r#"SELECT id as "id: Uuid", task_id as "task_id: Uuid", filename, mime, size_bytes, sha256, stored_path, created_at as "created_at: DateTime<Utc>" FROM task_context_files WHERE task_id = ?1 ORDER BY created_at ASC, filename ASC"#,
@@ -159,15 +166,16 @@ pub async fn list(
     let metas = rows
         .into_iter()
         .map(|r| {
-            let sidecar = data_root().join("blob").join(format!("{}.txt", r.sha256));
-            let extracted = sidecar.exists() && fs::metadata(&sidecar).map(|m| m.len() > 0).unwrap_or(false);
+            let extraction = extract::status(&data_root(), &r.sha256, &r.mime);
             UploadMeta {
+                id: r.id,
                 filename: r.filename,
                 mime: r.mime,
                 size: r.size_bytes,
                 content_hash: r.sha256,
                 stored_at: r.stored_path,
-                extracted_text: extracted,
+                extracted_text: extraction == ExtractionStatus::Done,
+                extraction,
                 dedup: false,
             }
         })
@@ -175,3 +183,36 @@ pub async fn list(
 
     Ok(axum::Json(ApiResponse::success(metas)))
 }
+
+/// Attach one of the project's context files to this task without re-uploading it
+pub async fn attach(
+    State(deployment): State<DeploymentImpl>,
+    Extension(task): Extension<db::models::task::Task>,
+    axum::Json(payload): axum::Json<AttachProjectFile>,
+) -> Result<axum::Json<ApiResponse<UploadMeta>>, crate::error::ApiError> {
+    let file = deployment
+        .context_files()
+        .attach_project_file_to_task(task.project_id, task.id, payload.project_file_id)
+        .await?;
+    let extraction = extract::status(&data_root(), &file.sha256, &file.mime);
+    Ok(axum::Json(ApiResponse::success(UploadMeta {
+        id: file.id,
+        filename: file.filename,
+        mime: file.mime,
+        size: file.size_bytes,
+        content_hash: file.sha256,
+        stored_at: file.stored_path,
+        extracted_text: extraction == ExtractionStatus::Done,
+        extraction,
+        dedup: false,
+    })))
+}
+
+/// Remove a file from the task; its blob is deleted once nothing else uses it
+pub async fn detach(
+    State(deployment): State<DeploymentImpl>,
+    axum::extract::Path((task_id, file_id)): axum::extract::Path<(Uuid, Uuid)>,
+) -> Result<axum::Json<ApiResponse<()>>, crate::error::ApiError> {
+    deployment.context_files().delete_task_file(task_id, file_id).await?;
+    Ok(axum::Json(ApiResponse::success(())))
+}
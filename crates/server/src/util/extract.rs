@@ -0,0 +1,329 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+};
+
+use db::models::context_file::stored_blobs;
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+/// Upper bound on sidecar text; longer documents are cut at a char boundary.
+const MAX_TEXT_BYTES: usize = 1024 * 1024;
+const TRUNCATED_MARKER: &str = "\n[truncated]\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractionStatus {
+    Pending,
+    Done,
+    Failed,
+    Unsupported,
+}
+
+pub fn supports(mime: &str) -> bool {
+    matches!(
+        mime,
+        "application/pdf" | "text/plain" | "text/markdown" | "text/csv" | "application/json"
+    )
+}
+
+/// `{hash}.txt` next to the blob directory holds the extracted text
+pub fn sidecar_path(data_root: &Path, hash: &str) -> PathBuf {
+    data_root.join("blob").join(format!("{hash}.txt"))
+}
+
+/// Older uploads left empty PDF stubs behind; those count as not extracted
+fn has_text(path: &Path) -> bool {
+    fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false)
+}
+
+fn error_path(data_root: &Path, hash: &str) -> PathBuf {
+    data_root.join("blob").join(format!("{hash}.err"))
+}
+
+pub fn status(data_root: &Path, hash: &str, mime: &str) -> ExtractionStatus {
+    if !supports(mime) {
+        return ExtractionStatus::Unsupported;
+    }
+    if has_text(&sidecar_path(data_root, hash)) {
+        ExtractionStatus::Done
+    } else if error_path(data_root, hash).exists() {
+        ExtractionStatus::Failed
+    } else {
+        ExtractionStatus::Pending
+    }
+}
+
+/// Extract normalized text from an upload of the given mime type
+pub fn extract_text(mime: &str, bytes: &[u8]) -> Result<String, String> {
+    let text = match mime {
+        "application/pdf" => extract_pdf(bytes)?,
+        "text/csv" => flatten_csv(&String::from_utf8_lossy(bytes)),
+        "application/json" => {
+            let v: serde_json::Value =
+                serde_json::from_slice(bytes).map_err(|e| format!("invalid JSON: {e}"))?;
+            serde_json::to_string_pretty(&v).map_err(|e| e.to_string())?
+        }
+        "text/plain" | "text/markdown" => String::from_utf8_lossy(bytes).into_owned(),
+        other => return Err(format!("no extractor for {other}")),
+    };
+    Ok(cap(normalize(&text)))
+}
+
+fn extract_pdf(bytes: &[u8]) -> Result<String, String> {
+    // pdf-extract panics on some malformed inputs; treat that as a failed extraction
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(bytes))
+        .map_err(|_| "PDF parser panicked".to_string())?
+        .map_err(|e| format!("PDF extraction failed: {e}"))?;
+    let mut out = String::new();
+    for (i, page) in pages.iter().enumerate() {
+        out.push_str(&format!("--- page {} ---\n", i + 1));
+        out.push_str(page.trim());
+        out.push_str("\n\n");
+    }
+    if out
+        .lines()
+        .all(|l| l.is_empty() || l.starts_with("--- page "))
+    {
+        return Err("PDF has no extractable text".to_string());
+    }
+    Ok(out)
+}
+
+/// Split one CSV record, honouring double quotes and `""` escapes
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut cur = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cur.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut cur).trim().to_string()),
+            _ => cur.push(c),
+        }
+    }
+    fields.push(cur.trim().to_string());
+    fields
+}
+
+/// One line per row as `header: value` pairs, so rows read well in a prompt
+fn flatten_csv(text: &str) -> String {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return String::new();
+    };
+    let header = csv_fields(header);
+    let mut out = String::new();
+    for (i, line) in lines.enumerate() {
+        let row = csv_fields(line);
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(j, v)| match header.get(j).filter(|h| !h.is_empty()) {
+                Some(h) => format!("{h}: {v}"),
+                None => format!("col{}: {v}", j + 1),
+            })
+            .collect();
+        out.push_str(&format!("row {}: {}\n", i + 1, cells.join("; ")));
+    }
+    out
+}
+
+/// Unix line endings, no trailing whitespace, at most one blank line in a row
+fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank = 0;
+    for line in text.replace("\r\n", "\n").replace('\r', "\n").lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank += 1;
+            if blank > 1 || out.is_empty() {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim_end().to_string() + "\n"
+}
+
+fn cap(mut text: String) -> String {
+    if text.len() <= MAX_TEXT_BYTES {
+        return text;
+    }
+    let mut end = MAX_TEXT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(TRUNCATED_MARKER);
+    text
+}
+
+/// Run extraction for a stored blob and write the sidecar, or an error marker
+pub fn run(data_root: &Path, hash: &str, mime: &str, blob: &Path) {
+    let sidecar = sidecar_path(data_root, hash);
+    if !supports(mime) || has_text(&sidecar) {
+        return;
+    }
+    let result = fs::read(blob)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| extract_text(mime, &bytes));
+    match result {
+        Ok(text) => {
+            // Write then rename so readers never see a half-written sidecar
+            let tmp = sidecar.with_extension("txt.tmp");
+            if let Err(e) = fs::write(&tmp, text).and_then(|_| fs::rename(&tmp, &sidecar)) {
+                tracing::warn!("Failed to write extracted text for {}: {}", hash, e);
+            }
+            let _ = fs::remove_file(error_path(data_root, hash));
+        }
+        Err(e) => {
+            tracing::warn!("Text extraction failed for {}: {}", hash, e);
+            let _ = fs::write(error_path(data_root, hash), e);
+        }
+    }
+}
+
+/// Hashes being extracted; a blob is extracted by one task at a time
+static IN_FLIGHT: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Marks a hash in flight until dropped
+struct InFlight(String);
+
+impl InFlight {
+    fn try_claim(hash: &str) -> Option<Self> {
+        let claimed = IN_FLIGHT.lock().unwrap().insert(hash.to_string());
+        claimed.then(|| Self(hash.to_string()))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Extract in the background so uploads return immediately
+pub fn spawn(data_root: PathBuf, hash: String, mime: String, blob: PathBuf) {
+    if status(&data_root, &hash, &mime) != ExtractionStatus::Pending {
+        return;
+    }
+    let Some(claim) = InFlight::try_claim(&hash) else {
+        return;
+    };
+    tokio::task::spawn_blocking(move || {
+        let _claim = claim;
+        run(&data_root, &hash, &mime, &blob)
+    });
+}
+
+/// Queue every upload still pending extraction, e.g. one stored before
+/// extraction existed or whose extraction a restart cut short
+pub async fn recover(pool: &SqlitePool, data_root: &Path) -> Result<(), sqlx::Error> {
+    for blob in stored_blobs(pool).await? {
+        let path = data_root.join(&blob.stored_path);
+        spawn(data_root.to_path_buf(), blob.sha256, blob.mime, path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows_read_as_header_value_pairs() {
+        let text = "name,note,\n\"Smith, J\",\"said \"\"hi\"\"\",x\n\nDoe,,\n";
+        assert_eq!(
+            flatten_csv(text),
+            "row 1: name: Smith, J; note: said \"hi\"; col3: x\nrow 2: name: Doe\n"
+        );
+        assert_eq!(flatten_csv(""), "");
+    }
+
+    #[test]
+    fn text_is_normalized_and_capped() {
+        assert_eq!(normalize("\r\n\na  \r\n\n\n\nb\rc\n\n"), "a\n\nb\nc\n");
+
+        let long = "é".repeat(MAX_TEXT_BYTES);
+        let capped = cap(long);
+        assert!(capped.ends_with(TRUNCATED_MARKER));
+        assert!(capped.len() <= MAX_TEXT_BYTES + TRUNCATED_MARKER.len());
+        assert_eq!(cap("short".to_string()), "short");
+    }
+
+    #[test]
+    fn extracts_by_mime() {
+        assert_eq!(
+            extract_text("application/json", br#"{"a":1}"#).unwrap(),
+            "{\n  \"a\": 1\n}\n"
+        );
+        assert!(extract_text("application/json", b"{")
+            .unwrap_err()
+            .starts_with("invalid JSON"));
+        assert_eq!(
+            extract_text("text/markdown", b"# T  \r\n").unwrap(),
+            "# T\n"
+        );
+        assert!(extract_text("application/pdf", b"not a pdf").is_err());
+        assert!(extract_text("image/png", b"").is_err());
+    }
+
+    #[test]
+    fn run_records_text_or_failure() {
+        let root = tempfile::tempdir().unwrap();
+        let blob = root.path().join("blob").join("h1");
+        fs::create_dir_all(&blob).unwrap();
+        let file = blob.join("notes.txt");
+        fs::write(&file, "hello\n").unwrap();
+
+        assert_eq!(
+            status(root.path(), "h1", "image/png"),
+            ExtractionStatus::Unsupported
+        );
+        assert_eq!(
+            status(root.path(), "h1", "text/plain"),
+            ExtractionStatus::Pending
+        );
+        run(root.path(), "h1", "text/plain", &file);
+        assert_eq!(
+            status(root.path(), "h1", "text/plain"),
+            ExtractionStatus::Done
+        );
+        assert_eq!(
+            fs::read_to_string(sidecar_path(root.path(), "h1")).unwrap(),
+            "hello\n"
+        );
+
+        run(
+            root.path(),
+            "h2",
+            "application/json",
+            &blob.join("missing.json"),
+        );
+        assert_eq!(
+            status(root.path(), "h2", "application/json"),
+            ExtractionStatus::Failed
+        );
+    }
+
+    #[test]
+    fn a_hash_is_claimed_once_until_released() {
+        let claim = InFlight::try_claim("claimed-hash").unwrap();
+        assert!(InFlight::try_claim("claimed-hash").is_none());
+        assert!(InFlight::try_claim("other-hash").is_some());
+        drop(claim);
+        assert!(InFlight::try_claim("claimed-hash").is_some());
+    }
+}
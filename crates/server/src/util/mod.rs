@@ -1,2 +1,4 @@
+pub mod extract;
 pub mod hash;
 pub mod mime;
+pub mod storage;
@@ -0,0 +1,6 @@
+use std::path::PathBuf;
+
+/// Root directory for uploaded blobs; `VK_DATA_DIR` overrides the app data dir.
+pub fn data_root() -> PathBuf {
+    utils::assets::uploads_dir()
+}
@@ -21,26 +21,33 @@ use db::{
         executor_session::{CreateExecutorSession, ExecutorSession},
         task::{Task, TaskStatus},
         task_attempt::{TaskAttempt, TaskAttemptError},
+        token_usage::ExecutionTokenUsage,
     },
 };
 use executors::{
     actions::{
         ExecutorAction, ExecutorActionType,
         coding_agent_initial::CodingAgentInitialRequest,
+        orchestrator::OrchestratorRequest,
         script::{ScriptContext, ScriptRequest, ScriptRequestLanguage},
     },
     executors::{CodingAgent, ExecutorError, StandardCodingAgentExecutor},
     logs::{NormalizedEntry, NormalizedEntryType, utils::patch::ConversationPatch},
     profile::ProfileVariantLabel,
 };
-use futures::{StreamExt, TryStreamExt, future};
+use futures::{FutureExt, StreamExt, TryStreamExt, future};
+use orchestrator::{artifacts::Artifacts, run::OrchestratorRun};
 use sqlx::Error as SqlxError;
 use thiserror::Error;
 use tokio::{sync::RwLock, task::JoinHandle};
-use utils::{log_msg::LogMsg, msg_store::MsgStore};
+use utils::{
+    log_msg::{LogMsg, TokenUsage},
+    msg_store::MsgStore,
+};
 use uuid::Uuid;
 
 use crate::services::{
+    context_file::ContextFileService,
     git::{GitService, GitServiceError},
     image::ImageService,
     worktree_manager::WorktreeError,
@@ -75,6 +82,8 @@ pub trait ContainerService {
 
     fn git(&self) -> &GitService;
 
+    fn context_files(&self) -> &ContextFileService;
+
     fn task_attempt_to_current_dir(&self, task_attempt: &TaskAttempt) -> PathBuf;
 
     async fn create(&self, task_attempt: &TaskAttempt) -> Result<ContainerRef, ContainerError>;
@@ -119,11 +128,29 @@ pub trait ContainerService {
         executor_action: &ExecutorAction,
     ) -> Result<(), ContainerError>;
 
+    /// Drive an orchestrator run in the background for an already created
+    /// execution process, logging to its registered MsgStore and registering
+    /// any children it spawns.
+    async fn start_orchestrator_inner(
+        &self,
+        task_attempt: &TaskAttempt,
+        execution_process: &ExecutionProcess,
+        run: OrchestratorRun,
+    ) -> Result<(), ContainerError>;
+
     async fn stop_execution(
         &self,
         execution_process: &ExecutionProcess,
     ) -> Result<(), ContainerError>;
 
+    /// Write a user message to a running coding agent and record it in the
+    /// process's logs
+    async fn send_message(
+        &self,
+        execution_process: &ExecutionProcess,
+        message: String,
+    ) -> Result<(), ContainerError>;
+
     async fn try_commit_changes(&self, ctx: &ExecutionContext) -> Result<bool, ContainerError>;
 
     async fn copy_project_files(
@@ -262,7 +289,13 @@ pub trait ContainerService {
             // Create temporary store and populate
             let temp_store = Arc::new(MsgStore::new());
             for msg in raw_messages {
-                if matches!(msg, LogMsg::Stdout(_) | LogMsg::Stderr(_)) {
+                if matches!(
+                    msg,
+                    LogMsg::Stdout(_)
+                        | LogMsg::Stderr(_)
+                        | LogMsg::Approval(_)
+                        | LogMsg::UserMessage(_)
+                ) {
                     temp_store.push(msg);
                 }
             }
@@ -374,7 +407,14 @@ pub trait ContainerService {
         }
     }
 
-    fn spawn_stream_raw_logs_to_db(&self, execution_id: &Uuid) -> JoinHandle<()> {
+    /// Persist the process's raw logs, session id and token usage. Usage for the
+    /// last lines can be pushed after `Finished`, so `normalizers` are awaited
+    /// before stopping.
+    fn spawn_stream_raw_logs_to_db(
+        &self,
+        execution_id: &Uuid,
+        mut normalizers: Vec<JoinHandle<()>>,
+    ) -> JoinHandle<()> {
         let execution_id = *execution_id;
         let msg_stores = self.msg_stores().clone();
         let db = self.db().clone();
@@ -391,7 +431,10 @@ pub trait ContainerService {
 
                 while let Some(Ok(msg)) = stream.next().await {
                     match &msg {
-                        LogMsg::Stdout(_) | LogMsg::Stderr(_) => {
+                        LogMsg::Stdout(_)
+                        | LogMsg::Stderr(_)
+                        | LogMsg::Approval(_)
+                        | LogMsg::UserMessage(_) => {
                             // Serialize this individual message as a JSONL line
                             match serde_json::to_string(&msg) {
                                 Ok(jsonl_line) => {
@@ -438,7 +481,17 @@ pub trait ContainerService {
                                 );
                             }
                         }
+                        LogMsg::Usage(usage) => {
+                            record_token_usage(&db, execution_id, usage).await;
+                        }
                         LogMsg::Finished => {
+                            future::join_all(std::mem::take(&mut normalizers)).await;
+                            // Everything the normalizers pushed is buffered by now
+                            while let Some(Some(Ok(msg))) = stream.next().now_or_never() {
+                                if let LogMsg::Usage(usage) = msg {
+                                    record_token_usage(&db, execution_id, &usage).await;
+                                }
+                            }
                             break;
                         }
                         LogMsg::JsonPatch(_) => continue,
@@ -480,7 +533,21 @@ pub trait ContainerService {
                 .as_ref()
                 .ok_or_else(|| ContainerError::Other(anyhow!("Container ref not found")))?,
         );
-        let prompt = ImageService::canonicalise_image_paths(&task.to_prompt(), &worktree_path);
+        let mut prompt = ImageService::canonicalise_image_paths(&task.to_prompt(), &worktree_path);
+
+        // Materialize project and task context files where the agent can read them
+        match self
+            .context_files()
+            .copy_context_files_to_worktree(&worktree_path, project.id, task.id)
+            .await
+        {
+            Ok(paths) => {
+                if let Some(section) = ContextFileService::prompt_section(&paths) {
+                    prompt = format!("{prompt}\n\n{section}");
+                }
+            }
+            Err(e) => tracing::warn!("Failed to copy context files to worktree: {}", e),
+        }
 
         let cleanup_action = project.cleanup_script.map(|script| {
             Box::new(ExecutorAction::new(
@@ -506,6 +573,7 @@ pub trait ContainerService {
                     ExecutorActionType::CodingAgentInitialRequest(CodingAgentInitialRequest {
                         prompt,
                         profile_variant_label,
+                        approval_url: None,
                     }),
                     cleanup_action,
                 ))),
@@ -522,6 +590,7 @@ pub trait ContainerService {
                 ExecutorActionType::CodingAgentInitialRequest(CodingAgentInitialRequest {
                     prompt,
                     profile_variant_label,
+                    approval_url: None,
                 }),
                 cleanup_action,
             );
@@ -593,6 +662,7 @@ pub trait ContainerService {
             .await?;
 
         // Start processing normalised logs for executor requests and follow ups
+        let mut normalizers = Vec::new();
         match executor_action.typ() {
             ExecutorActionType::CodingAgentInitialRequest(request) => {
                 if let Some(msg_store) = self.get_msg_store_by_id(&execution_process.id).await {
@@ -604,7 +674,7 @@ pub trait ContainerService {
                         msg_store
                             .push_patch(ConversationPatch::add_normalized_entry(0, user_entry));
 
-                        executor.normalize_logs(
+                        normalizers = executor.normalize_logs(
                             msg_store,
                             &self.task_attempt_to_current_dir(task_attempt),
                         );
@@ -626,7 +696,7 @@ pub trait ContainerService {
                         msg_store
                             .push_patch(ConversationPatch::add_normalized_entry(0, user_entry));
 
-                        executor.normalize_logs(
+                        normalizers = executor.normalize_logs(
                             msg_store,
                             &self.task_attempt_to_current_dir(task_attempt),
                         );
@@ -641,7 +711,62 @@ pub trait ContainerService {
             _ => {}
         };
 
-        self.spawn_stream_raw_logs_to_db(&execution_process.id);
+        self.spawn_stream_raw_logs_to_db(&execution_process.id, normalizers);
+        Ok(execution_process)
+    }
+
+    async fn start_orchestrator(
+        &self,
+        task_attempt: &TaskAttempt,
+        request: OrchestratorRequest,
+        mut run: OrchestratorRun,
+    ) -> Result<ExecutionProcess, ContainerError> {
+        let task = task_attempt
+            .parent_task(&self.db().pool)
+            .await?
+            .ok_or(SqlxError::RowNotFound)?;
+        if task.status != TaskStatus::InProgress {
+            Task::update_status(&self.db().pool, task.id, TaskStatus::InProgress).await?;
+        }
+
+        let create_execution_process = CreateExecutionProcess {
+            task_attempt_id: task_attempt.id,
+            executor_action: ExecutorAction::new(
+                ExecutorActionType::OrchestratorRequest(request),
+                None,
+            ),
+            run_reason: ExecutionProcessRunReason::Orchestrator,
+        };
+        let execution_process =
+            ExecutionProcess::create(&self.db().pool, &create_execution_process, Uuid::new_v4())
+                .await?;
+
+        // The run writes into its own directory so earlier runs stay browsable
+        run.cfg.artifacts_dir =
+            Artifacts::run_dir(&run.cfg.artifacts_dir, &execution_process.id.to_string());
+
+        // Register the store and subscribe the DB writer before the run starts,
+        // so its first lines and a quick finish are recorded. The run records
+        // its agents' usage itself, through its hooks
+        self.msg_stores()
+            .write()
+            .await
+            .insert(execution_process.id, Arc::new(MsgStore::new()));
+        self.spawn_stream_raw_logs_to_db(&execution_process.id, Vec::new());
+        if let Err(e) = self
+            .start_orchestrator_inner(task_attempt, &execution_process, run)
+            .await
+        {
+            if let Some(msg_store) = self
+                .msg_stores()
+                .write()
+                .await
+                .remove(&execution_process.id)
+            {
+                msg_store.push_finished();
+            }
+            return Err(e);
+        }
         Ok(execution_process)
     }
 
@@ -690,3 +815,13 @@ fn create_user_message(prompt: String) -> NormalizedEntry {
         metadata: None,
     }
 }
+
+async fn record_token_usage(db: &DBService, execution_id: Uuid, usage: &TokenUsage) {
+    if let Err(e) = ExecutionTokenUsage::record(&db.pool, execution_id, usage).await {
+        tracing::error!(
+            "Failed to record token usage for execution process {}: {}",
+            execution_id,
+            e
+        );
+    }
+}
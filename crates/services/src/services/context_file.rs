@@ -0,0 +1,480 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use dashmap::DashMap;
+use db::models::context_file::{self, ProjectContextFile, TaskContextFile};
+use sqlx::SqlitePool;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use uuid::Uuid;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ContextFileError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Context file not found")]
+    NotFound,
+}
+
+/// Uploaded context files live under `{data_root}/blob/{sha256}/{filename}`,
+/// with extracted text in `{data_root}/blob/{sha256}.txt`. Rows in
+/// `project_context_files` and `task_context_files` share blobs by hash.
+#[derive(Clone)]
+pub struct ContextFileService {
+    data_root: PathBuf,
+    pool: SqlitePool,
+    blob_locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
+}
+
+/// Held while a blob is written and its row inserted, or while it is checked
+/// for references and removed, so a removal never races an upload
+pub struct BlobGuard {
+    hash: String,
+    locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Drop for BlobGuard {
+    fn drop(&mut self) {
+        // Only the map and this guard hold the lock when nobody else waits for it
+        self.locks
+            .remove_if(&self.hash, |_, lock| Arc::strong_count(lock) <= 2);
+    }
+}
+
+impl ContextFileService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self::with_data_root(pool, utils::assets::uploads_dir())
+    }
+
+    pub fn with_data_root(pool: SqlitePool, data_root: PathBuf) -> Self {
+        Self {
+            data_root,
+            pool,
+            blob_locks: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Lock the blob with this hash; uploads hold it until their row is inserted
+    pub async fn lock_blob(&self, hash: &str) -> BlobGuard {
+        let lock = self.blob_locks.entry(hash.to_string()).or_default().clone();
+        BlobGuard {
+            hash: hash.to_string(),
+            locks: self.blob_locks.clone(),
+            _guard: lock.lock_owned().await,
+        }
+    }
+
+    fn blob_dir(&self) -> PathBuf {
+        self.data_root.join("blob")
+    }
+
+    pub async fn delete_project_file(
+        &self,
+        project_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), ContextFileError> {
+        let file = ProjectContextFile::find_by_id(&self.pool, id)
+            .await?
+            .filter(|f| f.project_id == project_id)
+            .ok_or(ContextFileError::NotFound)?;
+        ProjectContextFile::delete(&self.pool, id).await?;
+        self.delete_blob_if_orphaned(&file.sha256).await?;
+        Ok(())
+    }
+
+    /// Detach a file from a task; the blob goes once nothing references it
+    pub async fn delete_task_file(&self, task_id: Uuid, id: Uuid) -> Result<(), ContextFileError> {
+        let file = TaskContextFile::find_by_id(&self.pool, id)
+            .await?
+            .filter(|f| f.task_id == task_id)
+            .ok_or(ContextFileError::NotFound)?;
+        TaskContextFile::delete(&self.pool, id).await?;
+        self.delete_blob_if_orphaned(&file.sha256).await?;
+        Ok(())
+    }
+
+    pub async fn attach_project_file_to_task(
+        &self,
+        project_id: Uuid,
+        task_id: Uuid,
+        id: Uuid,
+    ) -> Result<TaskContextFile, ContextFileError> {
+        let file = ProjectContextFile::find_by_id(&self.pool, id)
+            .await?
+            .filter(|f| f.project_id == project_id)
+            .ok_or(ContextFileError::NotFound)?;
+        Ok(TaskContextFile::attach_from_project(&self.pool, task_id, &file).await?)
+    }
+
+    /// Remove the blob unless a row still references it. Returns whether it was removed
+    async fn delete_blob_if_orphaned(&self, hash: &str) -> Result<bool, ContextFileError> {
+        let _blob = self.lock_blob(hash).await;
+        let referenced = context_file::referenced_hashes(&self.pool).await?;
+        if referenced.iter().any(|h| h == hash) {
+            return Ok(false);
+        }
+        self.remove_blob(hash)?;
+        Ok(true)
+    }
+
+    fn remove_blob(&self, hash: &str) -> Result<(), std::io::Error> {
+        let dir = self.blob_dir().join(hash);
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir)?;
+        }
+        for ext in ["txt", "err", "txt.tmp"] {
+            let sidecar = self.blob_dir().join(format!("{hash}.{ext}"));
+            if sidecar.exists() {
+                fs::remove_file(sidecar)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove blobs and sidecars that no upload row references, e.g. after
+    /// projects or tasks were deleted and their rows cascaded away.
+    pub async fn delete_orphaned_blobs(&self) -> Result<(), ContextFileError> {
+        let blob_dir = self.blob_dir();
+        if !blob_dir.is_dir() {
+            tracing::debug!("No context blobs found during cleanup");
+            return Ok(());
+        }
+        let referenced: HashSet<String> = context_file::referenced_hashes(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut orphaned = HashSet::new();
+        for entry in fs::read_dir(&blob_dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let hash = name.split('.').next().unwrap_or_default().to_string();
+            if !hash.is_empty() && !referenced.contains(&hash) {
+                orphaned.insert(hash);
+            }
+        }
+
+        let mut deleted_count = 0;
+        let mut failed_count = 0;
+        for hash in orphaned {
+            // Checked again under the blob's lock, in case an upload landed meanwhile
+            match self.delete_blob_if_orphaned(&hash).await {
+                Ok(true) => {
+                    deleted_count += 1;
+                    tracing::debug!("Deleted orphaned context blob: {}", hash);
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    failed_count += 1;
+                    tracing::error!("Failed to delete orphaned context blob {}: {}", hash, e);
+                }
+            }
+        }
+
+        tracing::info!(
+            "Context blob cleanup completed: {} deleted, {} failed",
+            deleted_count,
+            failed_count
+        );
+
+        Ok(())
+    }
+
+    /// Copy the project's and task's context files into `.vibe-context/` of
+    /// the worktree. Returns the worktree-relative paths that were written;
+    /// documents with extracted text also get a `{filename}.txt` copy of it.
+    /// A file whose name is already taken, by an earlier file or by the
+    /// directory's `.gitignore`, is written under a numbered name instead.
+    pub async fn copy_context_files_to_worktree(
+        &self,
+        worktree_path: &Path,
+        project_id: Uuid,
+        task_id: Uuid,
+    ) -> Result<Vec<String>, ContextFileError> {
+        let mut files: Vec<(String, String, String)> =
+            ProjectContextFile::find_by_project_id(&self.pool, project_id)
+                .await?
+                .into_iter()
+                .map(|f| (f.filename, f.sha256, f.stored_path))
+                .collect();
+        files.extend(
+            TaskContextFile::find_by_task_id(&self.pool, task_id)
+                .await?
+                .into_iter()
+                .map(|f| (f.filename, f.sha256, f.stored_path)),
+        );
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let context_dir = worktree_path.join(utils::path::VIBE_CONTEXT_DIR);
+        fs::create_dir_all(&context_dir)?;
+
+        // Create .gitignore to ignore all files in this directory
+        fs::write(context_dir.join(".gitignore"), "*\n")?;
+
+        let mut written = Vec::new();
+        let mut seen = HashSet::new();
+        let mut taken = HashSet::from([".gitignore".to_string()]);
+        for (filename, hash, stored_path) in files {
+            // A task may re-attach a project file; copy each blob once
+            if !seen.insert(hash.clone()) {
+                continue;
+            }
+            let src = self.data_root.join(&stored_path);
+            if !src.exists() {
+                tracing::warn!("Missing context blob: {}", src.display());
+                continue;
+            }
+            let name = unique_name(&filename, &mut taken);
+            if let Err(e) = fs::copy(&src, context_dir.join(&name)) {
+                tracing::error!("Failed to copy {}: {}", filename, e);
+                continue;
+            }
+            written.push(format!("{}/{}", utils::path::VIBE_CONTEXT_DIR, name));
+
+            let sidecar = self.blob_dir().join(format!("{hash}.txt"));
+            let has_text = fs::metadata(&sidecar).map(|m| m.len() > 0).unwrap_or(false);
+            if has_text && !name.ends_with(".txt") {
+                let text_name = unique_name(&format!("{name}.txt"), &mut taken);
+                if fs::copy(&sidecar, context_dir.join(&text_name)).is_ok() {
+                    written.push(format!("{}/{}", utils::path::VIBE_CONTEXT_DIR, text_name));
+                }
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Prompt section pointing the agent at the materialized context files
+    pub fn prompt_section(paths: &[String]) -> Option<String> {
+        if paths.is_empty() {
+            return None;
+        }
+        let mut out = String::from(
+            "Context files for this task are available in the worktree (read them as needed):\n",
+        );
+        for p in paths {
+            out.push_str(&format!("- {p}\n"));
+        }
+        Some(out)
+    }
+}
+
+/// `filename`, or `{stem}-{n}{ext}` with the first `n` whose name is free.
+/// Names are compared case-insensitively, as worktrees may live on
+/// filesystems that do.
+fn unique_name(filename: &str, taken: &mut HashSet<String>) -> String {
+    let (stem, ext) = match filename.rfind('.') {
+        Some(i) if i > 0 => filename.split_at(i),
+        _ => (filename, ""),
+    };
+    let mut name = filename.to_string();
+    let mut n = 2;
+    while !taken.insert(name.to_lowercase()) {
+        name = format!("{stem}-{n}{ext}");
+        n += 1;
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use db::DBService;
+
+    use super::*;
+
+    const HASH: &str = "0f1e2d";
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        service: ContextFileService,
+        project_id: Uuid,
+        task_id: Uuid,
+    }
+
+    impl Fixture {
+        async fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let db = DBService::new_in_memory().await.unwrap();
+            let project_id = Uuid::new_v4();
+            let task_id = Uuid::new_v4();
+            sqlx::query("INSERT INTO projects (id, name, git_repo_path) VALUES ($1, 'p', '/repo')")
+                .bind(project_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            sqlx::query("INSERT INTO tasks (id, project_id, title) VALUES ($1, $2, 't')")
+                .bind(task_id)
+                .bind(project_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            let service = ContextFileService::with_data_root(db.pool, dir.path().to_path_buf());
+            Self {
+                _dir: dir,
+                service,
+                project_id,
+                task_id,
+            }
+        }
+
+        fn write_blob(&self) -> PathBuf {
+            let dir = self.service.blob_dir().join(HASH);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("notes.md"), "notes").unwrap();
+            fs::write(self.service.blob_dir().join(format!("{HASH}.txt")), "notes").unwrap();
+            dir
+        }
+
+        async fn insert_row(&self, table: &str, owner: Uuid) -> Uuid {
+            let owner_column = if table == "project_context_files" {
+                "project_id"
+            } else {
+                "task_id"
+            };
+            let id = Uuid::new_v4();
+            sqlx::query(&format!(
+                "INSERT INTO {table} (id, {owner_column}, filename, mime, size_bytes, sha256, stored_path)
+                 VALUES ($1, $2, 'notes.md', 'text/markdown', 5, $3, $4)"
+            ))
+            .bind(id)
+            .bind(owner)
+            .bind(HASH)
+            .bind(format!("blob/{HASH}/notes.md"))
+            .execute(&self.service.pool)
+            .await
+            .unwrap();
+            id
+        }
+    }
+
+    #[tokio::test]
+    async fn blob_goes_with_the_last_row_referencing_it() {
+        let fx = Fixture::new().await;
+        let blob = fx.write_blob();
+        let project_file = fx.insert_row("project_context_files", fx.project_id).await;
+        let task_file = fx.insert_row("task_context_files", fx.task_id).await;
+
+        fx.service
+            .delete_project_file(fx.project_id, project_file)
+            .await
+            .unwrap();
+        assert!(blob.is_dir());
+
+        // Files are only deleted through their owner
+        assert!(matches!(
+            fx.service.delete_task_file(Uuid::new_v4(), task_file).await,
+            Err(ContextFileError::NotFound)
+        ));
+
+        fx.service
+            .delete_task_file(fx.task_id, task_file)
+            .await
+            .unwrap();
+        assert!(!blob.exists());
+        assert!(!fx.service.blob_dir().join(format!("{HASH}.txt")).exists());
+    }
+
+    #[tokio::test]
+    async fn delete_waits_for_an_upload_of_the_same_blob() {
+        let fx = Fixture::new().await;
+        let blob = fx.write_blob();
+        let task_file = fx.insert_row("task_context_files", fx.task_id).await;
+
+        // An upload of the same content holds the blob until its row is in
+        let upload = fx.service.lock_blob(HASH).await;
+        let service = fx.service.clone();
+        let task_id = fx.task_id;
+        let delete =
+            tokio::spawn(async move { service.delete_task_file(task_id, task_file).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!delete.is_finished());
+
+        fx.insert_row("project_context_files", fx.project_id).await;
+        drop(upload);
+        delete.await.unwrap().unwrap();
+        assert!(blob.is_dir());
+        assert!(fx.service.blob_locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cleanup_removes_only_unreferenced_blobs() {
+        let fx = Fixture::new().await;
+        let kept = fx.write_blob();
+        fx.insert_row("project_context_files", fx.project_id).await;
+        let orphan = fx.service.blob_dir().join("abc123");
+        fs::create_dir_all(&orphan).unwrap();
+        fs::write(fx.service.blob_dir().join("abc123.err"), "").unwrap();
+
+        fx.service.delete_orphaned_blobs().await.unwrap();
+        assert!(kept.is_dir());
+        assert!(!orphan.exists());
+        assert!(!fx.service.blob_dir().join("abc123.err").exists());
+    }
+
+    #[tokio::test]
+    async fn colliding_names_are_numbered_instead_of_overwritten() {
+        let fx = Fixture::new().await;
+        let uploads = [
+            ("project_context_files", fx.project_id, "notes.md", "aa11"),
+            ("project_context_files", fx.project_id, ".gitignore", "bb22"),
+            ("task_context_files", fx.task_id, "Notes.md", "cc33"),
+        ];
+        for (table, owner, filename, hash) in uploads {
+            let dir = fx.service.blob_dir().join(hash);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(filename), hash).unwrap();
+            let owner_column = if table == "project_context_files" {
+                "project_id"
+            } else {
+                "task_id"
+            };
+            sqlx::query(&format!(
+                "INSERT INTO {table} (id, {owner_column}, filename, mime, size_bytes, sha256, stored_path)
+                 VALUES ($1, $2, $3, 'text/plain', 4, $4, $5)"
+            ))
+            .bind(Uuid::new_v4())
+            .bind(owner)
+            .bind(filename)
+            .bind(hash)
+            .bind(format!("blob/{hash}/{filename}"))
+            .execute(&fx.service.pool)
+            .await
+            .unwrap();
+        }
+
+        let worktree = tempfile::tempdir().unwrap();
+        let mut written = fx
+            .service
+            .copy_context_files_to_worktree(worktree.path(), fx.project_id, fx.task_id)
+            .await
+            .unwrap();
+        written.sort();
+        assert_eq!(
+            written,
+            vec![
+                ".vibe-context/.gitignore-2",
+                ".vibe-context/Notes-2.md",
+                ".vibe-context/notes.md",
+            ]
+        );
+        let dir = worktree.path().join(utils::path::VIBE_CONTEXT_DIR);
+        assert_eq!(fs::read_to_string(dir.join(".gitignore")).unwrap(), "*\n");
+        assert_eq!(
+            fs::read_to_string(dir.join(".gitignore-2")).unwrap(),
+            "bb22"
+        );
+        assert_eq!(fs::read_to_string(dir.join("notes.md")).unwrap(), "aa11");
+        assert_eq!(fs::read_to_string(dir.join("Notes-2.md")).unwrap(), "cc33");
+    }
+}
@@ -3,7 +3,9 @@ use std::{str::FromStr, sync::Arc};
 use anyhow::Error as AnyhowError;
 use db::{
     DBService,
-    models::{execution_process::ExecutionProcess, task::Task, task_attempt::TaskAttempt},
+    models::{
+        execution_process::ExecutionProcess, phase::Phase, task::Task, task_attempt::TaskAttempt,
+    },
 };
 use serde::Serialize;
 use serde_json::json;
@@ -39,6 +41,8 @@ enum HookTables {
     TaskAttempts,
     #[strum(to_string = "execution_processes")]
     ExecutionProcesses,
+    #[strum(to_string = "phases")]
+    Phases,
 }
 
 #[derive(Serialize, TS)]
@@ -47,9 +51,11 @@ pub enum RecordTypes {
     Task(Task),
     TaskAttempt(TaskAttempt),
     ExecutionProcess(ExecutionProcess),
+    Phase(Phase),
     DeletedTask { rowid: i64 },
     DeletedTaskAttempt { rowid: i64 },
     DeletedExecutionProcess { rowid: i64 },
+    DeletedPhase { rowid: i64 },
 }
 
 #[derive(Serialize, TS)]
@@ -114,6 +120,9 @@ impl EventService {
                                 (HookTables::ExecutionProcesses, SqliteOperation::Delete) => {
                                     RecordTypes::DeletedExecutionProcess { rowid }
                                 }
+                                (HookTables::Phases, SqliteOperation::Delete) => {
+                                    RecordTypes::DeletedPhase { rowid }
+                                }
                                 (HookTables::Tasks, _) => {
                                     match Task::find_by_rowid(&db.pool, rowid).await {
                                         Ok(Some(task)) => RecordTypes::Task(task),
@@ -150,6 +159,16 @@ impl EventService {
                                         }
                                     }
                                 }
+                                (HookTables::Phases, _) => {
+                                    match Phase::find_by_rowid(&db.pool, rowid).await {
+                                        Ok(Some(phase)) => RecordTypes::Phase(phase),
+                                        Ok(None) => RecordTypes::DeletedPhase { rowid },
+                                        Err(e) => {
+                                            tracing::error!("Failed to fetch phase: {:?}", e);
+                                            return;
+                                        }
+                                    }
+                                }
                             };
 
                             let next_entry_count = {
@@ -1,7 +1,9 @@
 pub mod analytics;
+pub mod approvals;
 pub mod auth;
 pub mod config;
 pub mod container;
+pub mod context_file;
 pub mod events;
 pub mod file_ranker;
 pub mod filesystem;
@@ -11,5 +13,6 @@ pub mod github_service;
 pub mod image;
 pub mod notification;
 pub mod pr_monitor;
+pub mod pricing;
 pub mod sentry;
 pub mod worktree_manager;
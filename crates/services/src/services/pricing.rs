@@ -0,0 +1,152 @@
+//! Per-model token prices, used to turn recorded token usage into spend.
+//!
+//! Built-in prices are embedded from `default_prices.json`; a `prices.json` in
+//! the asset dir overrides them or adds models, keyed the same way.
+
+use std::{collections::BTreeMap, fs};
+
+use db::models::token_usage::ModelTokenUsage;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+const DEFAULT_PRICES_JSON: &str = include_str!("../../default_prices.json");
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default)]
+    pub cache_read: f64,
+    #[serde(default)]
+    pub cache_write: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &ModelTokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input
+            + usage.output_tokens as f64 * self.output
+            + usage.cache_read_tokens as f64 * self.cache_read
+            + usage.cache_write_tokens as f64 * self.cache_write)
+            / 1_000_000.0
+    }
+}
+
+/// Token usage broken down by model, with its cost
+#[derive(Debug, Clone, Serialize, TS)]
+pub struct TokenSpend {
+    pub models: Vec<ModelTokenUsage>,
+    /// Spend on the models with a price; `None` when none has one
+    pub cost_usd: Option<f64>,
+}
+
+/// Prices keyed by model name or by a prefix of dated model names, e.g.
+/// `claude-sonnet-4` prices `claude-sonnet-4-20250514`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[serde(transparent)]
+pub struct PriceTable {
+    pub models: BTreeMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    /// The built-in prices with `prices.json` layered over them
+    pub fn load() -> Self {
+        let mut table = Self::from_defaults();
+        let path = utils::assets::prices_path();
+        let Ok(content) = fs::read_to_string(&path) else {
+            return table;
+        };
+        match serde_json::from_str::<Self>(&content) {
+            Ok(user) => table.models.extend(user.models),
+            Err(e) => tracing::warn!("Failed to parse prices.json: {}, using defaults", e),
+        }
+        table
+    }
+
+    pub fn from_defaults() -> Self {
+        serde_json::from_str(DEFAULT_PRICES_JSON).unwrap_or_else(|e| {
+            tracing::error!("Failed to parse embedded default_prices.json: {}", e);
+            panic!("Default prices JSON is invalid")
+        })
+    }
+
+    /// Exact match first, then the longest key the model name starts with
+    pub fn price(&self, model: &str) -> Option<&ModelPrice> {
+        self.models.get(model).or_else(|| {
+            self.models
+                .iter()
+                .filter(|(key, _)| model.starts_with(key.as_str()))
+                .max_by_key(|(key, _)| key.len())
+                .map(|(_, price)| price)
+        })
+    }
+
+    /// Fill in the cost of each model and return the total over the models with
+    /// a price, or `None` when none has one
+    pub fn apply(&self, usage: &mut [ModelTokenUsage]) -> Option<f64> {
+        let mut total = None;
+        for model in usage.iter_mut() {
+            model.cost_usd = self.price(&model.model).map(|price| price.cost(model));
+            if let Some(cost) = model.cost_usd {
+                *total.get_or_insert(0.0) += cost;
+            }
+        }
+        total
+    }
+
+    pub fn spend(&self, mut models: Vec<ModelTokenUsage>) -> TokenSpend {
+        let cost_usd = self.apply(&mut models);
+        TokenSpend { models, cost_usd }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(model: &str, input: i64, output: i64, cache_read: i64) -> ModelTokenUsage {
+        ModelTokenUsage {
+            model: model.to_string(),
+            turns: 1,
+            input_tokens: input,
+            output_tokens: output,
+            cache_read_tokens: cache_read,
+            cache_write_tokens: 0,
+            cost_usd: None,
+        }
+    }
+
+    #[test]
+    fn dated_models_use_the_longest_matching_prefix() {
+        let table = PriceTable::from_defaults();
+        assert_eq!(
+            table.price("claude-sonnet-4-20250514"),
+            table.price("claude-sonnet-4")
+        );
+        assert_eq!(table.price("gpt-5-mini-2025-08-07").unwrap().input, 0.25);
+        assert_eq!(table.price("gpt-5").unwrap().input, 1.25);
+        assert!(table.price("").is_none());
+    }
+
+    #[test]
+    fn cost_covers_priced_models_only() {
+        let mut table = PriceTable::default();
+        table.models.insert(
+            "model-a".to_string(),
+            ModelPrice {
+                input: 2.0,
+                output: 10.0,
+                cache_read: 0.5,
+                cache_write: 0.0,
+            },
+        );
+        let mut models = vec![
+            usage("model-a", 1_000_000, 100_000, 2_000_000),
+            usage("unpriced", 5_000, 5_000, 0),
+        ];
+        assert_eq!(table.apply(&mut models), Some(4.0));
+        assert_eq!(models[0].cost_usd, Some(4.0));
+        assert_eq!(models[1].cost_usd, None);
+        assert_eq!(table.apply(&mut models[1..]), None);
+    }
+}
@@ -0,0 +1,59 @@
+//! Tool calls a supervised agent asks permission for before running them.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Environment variable carrying the URL an agent's permission prompt tool
+/// posts its requests to
+pub const APPROVAL_URL_ENV: &str = "VK_APPROVAL_URL";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Denied { reason: Option<String> },
+    TimedOut,
+}
+
+impl ApprovalStatus {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, ApprovalStatus::Pending)
+    }
+}
+
+/// One tool call awaiting, or having received, a decision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+pub struct ToolApproval {
+    pub id: String,
+    pub tool_name: String,
+    #[ts(type = "JsonValue")]
+    pub input: serde_json::Value,
+    pub status: ApprovalStatus,
+}
+
+impl ToolApproval {
+    pub fn pending(id: String, tool_name: String, input: serde_json::Value) -> Self {
+        Self {
+            id,
+            tool_name,
+            input,
+            status: ApprovalStatus::Pending,
+        }
+    }
+
+    /// The argument rules and summaries are matched against: the command of
+    /// a shell tool, the path of a file tool, or the URL of a fetch
+    pub fn primary_argument(&self) -> Option<&str> {
+        [
+            "command",
+            "file_path",
+            "path",
+            "notebook_path",
+            "url",
+            "pattern",
+        ]
+        .iter()
+        .find_map(|key| self.input.get(key)?.as_str())
+    }
+}
@@ -24,6 +24,14 @@ pub fn asset_dir() -> std::path::PathBuf {
     // ✔ Windows → %APPDATA%\Example\MyApp
 }
 
+/// Root for uploaded context blobs; `VK_DATA_DIR` overrides the asset dir
+pub fn uploads_dir() -> std::path::PathBuf {
+    match std::env::var("VK_DATA_DIR") {
+        Ok(dir) => std::path::PathBuf::from(dir),
+        Err(_) => asset_dir(),
+    }
+}
+
 pub fn config_path() -> std::path::PathBuf {
     asset_dir().join("config.json")
 }
@@ -32,6 +40,17 @@ pub fn profiles_path() -> std::path::PathBuf {
     asset_dir().join("profiles.json")
 }
 
+/// Per-model prices overriding or adding to the built-in price table
+pub fn prices_path() -> std::path::PathBuf {
+    asset_dir().join("prices.json")
+}
+
+/// Secret values referenced from profiles; kept out of `profiles.json` and
+/// only readable by its owner
+pub fn secrets_path() -> std::path::PathBuf {
+    asset_dir().join("secrets.json")
+}
+
 #[derive(RustEmbed)]
 #[folder = "../../assets/sounds"]
 pub struct SoundAssets;
@@ -2,6 +2,7 @@ use std::{env, sync::OnceLock};
 
 use directories::ProjectDirs;
 
+pub mod approvals;
 pub mod assets;
 pub mod browser;
 pub mod diff;
@@ -9,6 +10,7 @@ pub mod log_msg;
 pub mod msg_store;
 pub mod path;
 pub mod port_file;
+pub mod redact;
 pub mod response;
 pub mod sentry;
 pub mod shell;
@@ -2,11 +2,36 @@ use axum::response::sse::Event;
 use json_patch::Patch;
 use serde::{Deserialize, Serialize};
 
+use crate::approvals::ToolApproval;
+
 pub const EV_STDOUT: &str = "stdout";
 pub const EV_STDERR: &str = "stderr";
 pub const EV_JSON_PATCH: &str = "json_patch";
 pub const EV_SESSION_ID: &str = "session_id";
 pub const EV_FINISHED: &str = "finished";
+pub const EV_USAGE: &str = "usage";
+pub const EV_APPROVAL: &str = "approval";
+pub const EV_USER_MESSAGE: &str = "user_message";
+
+/// Tokens consumed by one agent turn, as reported in the agent's output
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub model: Option<String>,
+    /// Input tokens that were not read from the prompt cache
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn is_empty(&self) -> bool {
+        self.input_tokens == 0
+            && self.output_tokens == 0
+            && self.cache_read_tokens == 0
+            && self.cache_write_tokens == 0
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum LogMsg {
@@ -14,6 +39,11 @@ pub enum LogMsg {
     Stderr(String),
     JsonPatch(Patch),
     SessionId(String),
+    Usage(TokenUsage),
+    /// A tool call awaiting a decision, or the decision made on it
+    Approval(ToolApproval),
+    /// A message the user sent to the agent while it was running
+    UserMessage(String),
     Finished,
 }
 
@@ -24,6 +54,9 @@ impl LogMsg {
             LogMsg::Stderr(_) => EV_STDERR,
             LogMsg::JsonPatch(_) => EV_JSON_PATCH,
             LogMsg::SessionId(_) => EV_SESSION_ID,
+            LogMsg::Usage(_) => EV_USAGE,
+            LogMsg::Approval(_) => EV_APPROVAL,
+            LogMsg::UserMessage(_) => EV_USER_MESSAGE,
             LogMsg::Finished => EV_FINISHED,
         }
     }
@@ -37,6 +70,15 @@ impl LogMsg {
                 Event::default().event(EV_JSON_PATCH).data(data)
             }
             LogMsg::SessionId(s) => Event::default().event(EV_SESSION_ID).data(s.clone()),
+            LogMsg::Usage(usage) => {
+                let data = serde_json::to_string(usage).unwrap_or_else(|_| "{}".to_string());
+                Event::default().event(EV_USAGE).data(data)
+            }
+            LogMsg::Approval(approval) => {
+                let data = serde_json::to_string(approval).unwrap_or_else(|_| "{}".to_string());
+                Event::default().event(EV_APPROVAL).data(data)
+            }
+            LogMsg::UserMessage(s) => Event::default().event(EV_USER_MESSAGE).data(s.clone()),
             LogMsg::Finished => Event::default().event(EV_FINISHED).data(""),
         }
     }
@@ -52,6 +94,16 @@ impl LogMsg {
                 EV_JSON_PATCH.len() + json_len + OVERHEAD
             }
             LogMsg::SessionId(s) => EV_SESSION_ID.len() + s.len() + OVERHEAD,
+            LogMsg::Usage(usage) => {
+                EV_USAGE.len() + usage.model.as_ref().map_or(0, String::len) + 32 + OVERHEAD
+            }
+            LogMsg::Approval(approval) => {
+                let json_len = serde_json::to_string(approval)
+                    .map(|s| s.len())
+                    .unwrap_or(2);
+                EV_APPROVAL.len() + json_len + OVERHEAD
+            }
+            LogMsg::UserMessage(s) => EV_USER_MESSAGE.len() + s.len() + OVERHEAD,
             LogMsg::Finished => EV_FINISHED.len() + OVERHEAD,
         }
     }
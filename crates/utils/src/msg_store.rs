@@ -8,7 +8,11 @@ use futures::{StreamExt, TryStreamExt, future};
 use tokio::{sync::broadcast, task::JoinHandle};
 use tokio_stream::wrappers::BroadcastStream;
 
-use crate::{log_msg::LogMsg, stream_lines::LinesStreamExt};
+use crate::{
+    approvals::ToolApproval,
+    log_msg::{LogMsg, TokenUsage},
+    stream_lines::LinesStreamExt,
+};
 
 // 100 MB Limit
 const HISTORY_BYTES: usize = 100000 * 1024;
@@ -78,6 +82,18 @@ impl MsgStore {
         self.push(LogMsg::SessionId(session_id));
     }
 
+    pub fn push_usage(&self, usage: TokenUsage) {
+        self.push(LogMsg::Usage(usage));
+    }
+
+    pub fn push_approval(&self, approval: ToolApproval) {
+        self.push(LogMsg::Approval(approval));
+    }
+
+    pub fn push_user_message(&self, message: String) {
+        self.push(LogMsg::UserMessage(message));
+    }
+
     pub fn push_finished(&self) {
         self.push(LogMsg::Finished);
     }
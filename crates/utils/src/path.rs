@@ -3,6 +3,9 @@ use std::path::{Path, PathBuf};
 /// Directory name for storing images in worktrees
 pub const VIBE_IMAGES_DIR: &str = ".vibe-images";
 
+/// Directory name for project and task context files in worktrees
+pub const VIBE_CONTEXT_DIR: &str = ".vibe-context";
+
 /// Convert absolute paths to relative paths based on worktree path
 /// This is a robust implementation that handles symlinks and edge cases
 pub fn make_path_relative(path: &str, worktree_path: &str) -> String {
@@ -1,4 +1,4 @@
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, sync::OnceLock};
 
 use tokio::fs;
 
@@ -10,3 +10,17 @@ pub async fn write_port_file(port: u16) -> std::io::Result<PathBuf> {
     fs::write(&path, port.to_string()).await?;
     Ok(path)
 }
+
+static BACKEND_PORT: OnceLock<u16> = OnceLock::new();
+
+/// Record the port the API is served on, so agents can be pointed back at it
+pub fn set_backend_port(port: u16) {
+    let _ = BACKEND_PORT.set(port);
+}
+
+/// Base URL of the API, once the server is listening
+pub fn backend_url() -> Option<String> {
+    BACKEND_PORT
+        .get()
+        .map(|port| format!("http://127.0.0.1:{port}"))
+}
@@ -0,0 +1,151 @@
+//! Redaction of secret values from process output before it is stored.
+
+use futures::{Stream, StreamExt, stream};
+
+/// Text a secret is replaced with
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Secrets shorter than this are not redacted, since replacing every occurrence
+/// of a couple of characters would mangle the whole log
+pub const MIN_SECRET_LEN: usize = 4;
+
+/// Replaces secret values in text, including values split across the chunks
+/// of a stream.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    /// Longest first, so a secret containing another is replaced whole
+    secrets: Vec<String>,
+    /// Tail of the previous chunk that could be the start of a secret
+    pending: String,
+}
+
+impl Redactor {
+    pub fn new<I: IntoIterator<Item = String>>(secrets: I) -> Self {
+        let mut secrets: Vec<String> = secrets
+            .into_iter()
+            .filter(|s| s.len() >= MIN_SECRET_LEN)
+            .collect();
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        secrets.dedup();
+        Self {
+            secrets,
+            pending: String::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// Redact a complete piece of text
+    pub fn redact(&self, text: &str) -> String {
+        self.secrets.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+    }
+
+    /// Redact the next chunk of a stream. A tail that could be the start of a
+    /// secret is held back until the next chunk or [`Self::finish`].
+    pub fn push(&mut self, chunk: &str) -> String {
+        if self.is_empty() {
+            return chunk.to_string();
+        }
+        self.pending.push_str(chunk);
+        let pending = std::mem::take(&mut self.pending);
+        let mut text = self.redact(&pending);
+        let held = self.held_back_len(&text);
+        self.pending = text.split_off(text.len() - held);
+        text
+    }
+
+    /// Whatever was held back, once the stream has ended
+    pub fn finish(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Length of the longest suffix of `text` that is a proper prefix of a secret
+    fn held_back_len(&self, text: &str) -> usize {
+        let longest = self.secrets.first().map_or(0, String::len);
+        text.char_indices()
+            .filter(|(i, _)| text.len() - i < longest)
+            .map(|(i, _)| &text[i..])
+            .find(|suffix| {
+                self.secrets
+                    .iter()
+                    .any(|s| s.len() > suffix.len() && s.starts_with(suffix))
+            })
+            .map_or(0, str::len)
+    }
+}
+
+/// Redact a stream of text chunks, flushing any held-back tail when it ends
+pub fn redact_stream<S, E>(chunks: S, redactor: Redactor) -> impl Stream<Item = Result<String, E>>
+where
+    S: Stream<Item = Result<String, E>> + Unpin,
+{
+    stream::unfold(
+        (chunks, redactor, false),
+        |(mut chunks, mut redactor, done)| async move {
+            if done {
+                return None;
+            }
+            match chunks.next().await {
+                Some(Ok(chunk)) => {
+                    let text = redactor.push(&chunk);
+                    Some((Ok(text), (chunks, redactor, false)))
+                }
+                Some(Err(e)) => Some((Err(e), (chunks, redactor, false))),
+                None => {
+                    let rest = redactor.finish();
+                    Some((Ok(rest), (chunks, redactor, true)))
+                }
+            }
+        },
+    )
+    .filter(|item| futures::future::ready(!matches!(item, Ok(text) if text.is_empty())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_every_occurrence_and_ignores_short_values() {
+        let redactor = Redactor::new(["sk-live-1234".to_string(), "ab".to_string()]);
+        assert_eq!(
+            redactor.redact("key=sk-live-1234 again sk-live-1234 ab"),
+            "key=[REDACTED] again [REDACTED] ab"
+        );
+    }
+
+    #[test]
+    fn secrets_split_across_chunks_are_redacted() {
+        let mut redactor = Redactor::new(["sk-live-1234".to_string()]);
+        let mut out = String::new();
+        for chunk in ["token: sk-li", "ve-12", "34\nsk-", "done\n"] {
+            out.push_str(&redactor.push(chunk));
+        }
+        out.push_str(&redactor.finish());
+        assert_eq!(out, "token: [REDACTED]\nsk-done\n");
+    }
+
+    #[test]
+    fn held_back_tail_is_flushed_at_the_end() {
+        let mut redactor = Redactor::new(["secret-value".to_string()]);
+        assert_eq!(redactor.push("output: secr"), "output: ");
+        assert_eq!(redactor.finish(), "secr");
+    }
+
+    #[tokio::test]
+    async fn redact_stream_flushes_on_end() {
+        let chunks = stream::iter(vec![
+            Ok::<_, std::io::Error>("a secret-va".to_string()),
+            Ok("lue and secr".to_string()),
+        ]);
+        let out: Vec<String> = redact_stream(chunks, Redactor::new(["secret-value".to_string()]))
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+        assert_eq!(out.concat(), "a [REDACTED] and secr");
+    }
+}
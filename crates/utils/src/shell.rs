@@ -18,6 +18,17 @@ pub fn get_shell_command() -> (&'static str, &'static str) {
     }
 }
 
+/// Quote an argument for the shell returned by [`get_shell_command`]. Under
+/// `cmd` the quotes do not stop `%VAR%` expansion and `\"` is no escape, so
+/// never pass untrusted text through this on Windows.
+pub fn shell_quote(arg: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
 /// Resolves the full path of an executable using the system's PATH environment variable.
 pub fn resolve_executable_path(executable: &str) -> Option<String> {
     which::which(executable)
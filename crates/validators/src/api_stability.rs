@@ -1,4 +1,604 @@
-use crate::{Validator, ValidatorOutcome};
+use std::{
+	collections::{btree_map::Entry, BTreeMap},
+	fs,
+	path::{Path, PathBuf},
+};
 
-pub struct ApiStability;
-impl Validator for ApiStability { fn validate(&self) -> Result<ValidatorOutcome, String> { Ok(ValidatorOutcome { pass: true, message: None }) } }
+use quote::ToTokens;
+use serde::Serialize;
+use syn::{Attribute, Fields, ImplItem, Item, TraitItem, Type, UseTree, Visibility};
+
+use crate::{dep_diff::rust::manifest_paths, Validator, ValidatorOutcome};
+
+/// One public item, keyed in an [`ApiSurface`] by its path, e.g.
+/// `db::models::task::Task.title` or `utils::text::short_uuid`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApiItem {
+	pub kind: &'static str,
+	/// Token-normalized signature; attributes and doc comments are dropped
+	pub signature: String,
+	/// Adding this item breaks downstream code (required trait methods,
+	/// variants of exhaustive enums)
+	#[serde(skip)]
+	pub breaking_if_added: bool,
+}
+
+/// Public API of the workspace crates in a working tree
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ApiSurface {
+	pub items: BTreeMap<String, ApiItem>,
+}
+
+impl ApiSurface {
+	/// Parse every library crate of the workspace rooted at `root`. Files that
+	/// fail to parse are skipped.
+	pub fn capture(root: &Path) -> Self {
+		let mut surface = Self::default();
+		for manifest in manifest_paths(root) {
+			let Some(name) = fs::read_to_string(&manifest)
+				.ok()
+				.and_then(|t| t.parse::<toml::Table>().ok())
+				.and_then(|m| m.get("package")?.get("name")?.as_str().map(|n| n.replace('-', "_")))
+			else {
+				continue;
+			};
+			let Some(dir) = manifest.parent() else { continue };
+			let lib = dir.join("src").join("lib.rs");
+			if lib.is_file() {
+				let mut collector = Collector::new(&name);
+				collector.collect_file(&lib, &dir.join("src"), &name, true);
+				surface.items.extend(collector.finish());
+			}
+		}
+		surface
+	}
+
+	/// Surface of a single source file, for callers that already have the text
+	pub fn from_source(path: &str, source: &str) -> Self {
+		let mut collector = Collector::new(path);
+		if let Ok(file) = syn::parse_file(source) {
+			collector.collect_items(&file.items, None, path, true);
+		}
+		Self { items: collector.finish() }
+	}
+}
+
+/// Items of one crate. Private modules are walked too, since `pub use` can
+/// expose their items under a public path.
+struct Collector {
+	krate: String,
+	items: BTreeMap<String, ApiItem>,
+	/// `pub` items of modules that are not themselves reachable
+	hidden: BTreeMap<String, ApiItem>,
+	/// Every `pub use` with the module it is in and whether that module is reachable
+	uses: Vec<(String, UseTree, bool)>,
+	/// Every `use`, public or not, for resolving the self types of `impl` blocks
+	imports: Vec<Reexport>,
+	/// Inherent impls, recorded once the self type's own path is known
+	impls: Vec<InherentImpl>,
+}
+
+/// The `pub` methods and consts of an `impl Type` block, with the module it
+/// sits in and the path it names its self type by
+struct InherentImpl {
+	module: String,
+	self_ty: Vec<String>,
+	members: Vec<(String, &'static str, String)>,
+}
+
+/// Bound on re-export rounds; each round resolves one more level of `pub use` chains
+const MAX_REEXPORT_DEPTH: usize = 8;
+
+impl Collector {
+	fn new(krate: &str) -> Self {
+		Self {
+			krate: krate.to_string(),
+			items: BTreeMap::new(),
+			hidden: BTreeMap::new(),
+			uses: Vec::new(),
+			imports: Vec::new(),
+			impls: Vec::new(),
+		}
+	}
+
+	fn insert(&mut self, public: bool, key: String, kind: &'static str, signature: String, breaking_if_added: bool) {
+		let items = if public { &mut self.items } else { &mut self.hidden };
+		items.insert(key, ApiItem { kind, signature, breaking_if_added });
+	}
+
+	fn collect_file(&mut self, file: &Path, mod_dir: &Path, path: &str, public: bool) {
+		let Some(parsed) = fs::read_to_string(file).ok().and_then(|t| syn::parse_file(&t).ok()) else { return };
+		self.collect_items(&parsed.items, Some(mod_dir), path, public);
+	}
+
+	fn collect_items(&mut self, items: &[Item], mod_dir: Option<&Path>, path: &str, public: bool) {
+		for item in items {
+			match item {
+				Item::Fn(f) if is_pub(&f.vis) => {
+					self.insert(public, format!("{path}::{}", f.sig.ident), "fn", tokens(&f.sig), false);
+				}
+				Item::Struct(s) if is_pub(&s.vis) => {
+					let key = format!("{path}::{}", s.ident);
+					self.insert(public, key.clone(), "struct", generics_sig(&s.generics), false);
+					for (i, field) in s.fields.iter().enumerate() {
+						if is_pub(&field.vis) {
+							let name = field.ident.as_ref().map(|n| n.to_string()).unwrap_or_else(|| i.to_string());
+							self.insert(public, format!("{key}.{name}"), "field", tokens(&field.ty), false);
+						}
+					}
+				}
+				Item::Enum(e) if is_pub(&e.vis) => {
+					let key = format!("{path}::{}", e.ident);
+					let exhaustive = !has_attr(&e.attrs, "non_exhaustive");
+					self.insert(public, key.clone(), "enum", generics_sig(&e.generics), false);
+					for v in &e.variants {
+						self.insert(public, format!("{key}::{}", v.ident), "variant", fields_sig(&v.fields), exhaustive);
+					}
+				}
+				Item::Trait(t) if is_pub(&t.vis) => {
+					let key = format!("{path}::{}", t.ident);
+					let bounds = t.supertraits.to_token_stream().to_string();
+					self.insert(public, key.clone(), "trait", format!("{} {bounds}", generics_sig(&t.generics)).trim().to_string(), false);
+					for ti in &t.items {
+						match ti {
+							TraitItem::Fn(m) => {
+								let required = m.default.is_none();
+								self.insert(public, format!("{key}::{}", m.sig.ident), "trait_fn", tokens(&m.sig), required);
+							}
+							TraitItem::Type(ty) => {
+								let required = ty.default.is_none();
+								self.insert(public, format!("{key}::{}", ty.ident), "trait_type", ty.bounds.to_token_stream().to_string(), required);
+							}
+							TraitItem::Const(c) => {
+								let required = c.default.is_none();
+								self.insert(public, format!("{key}::{}", c.ident), "trait_const", tokens(&c.ty), required);
+							}
+							_ => {}
+						}
+					}
+				}
+				Item::Impl(imp) => {
+					let self_ty = tokens(&imp.self_ty);
+					if let Some((_, trait_path, _)) = &imp.trait_ {
+						let key = format!("{path}::impl {} for {self_ty}", tokens(trait_path));
+						self.insert(public, key, "impl", generics_sig(&imp.generics), false);
+						continue;
+					}
+					let Type::Path(ty) = &*imp.self_ty else { continue };
+					if ty.qself.is_some() {
+						continue;
+					}
+					let members = imp
+						.items
+						.iter()
+						.filter_map(|ii| match ii {
+							ImplItem::Fn(m) if is_pub(&m.vis) => Some((m.sig.ident.to_string(), "method", tokens(&m.sig))),
+							ImplItem::Const(c) if is_pub(&c.vis) => Some((c.ident.to_string(), "const", tokens(&c.ty))),
+							_ => None,
+						})
+						.collect();
+					let segments = ty.path.segments.iter().map(|s| s.ident.to_string()).collect();
+					self.impls.push(InherentImpl { module: path.to_string(), self_ty: segments, members });
+				}
+				Item::Type(t) if is_pub(&t.vis) => {
+					self.insert(public, format!("{path}::{}", t.ident), "type", tokens(&t.ty), false);
+				}
+				Item::Const(c) if is_pub(&c.vis) => {
+					self.insert(public, format!("{path}::{}", c.ident), "const", tokens(&c.ty), false);
+				}
+				Item::Static(s) if is_pub(&s.vis) => {
+					self.insert(public, format!("{path}::{}", s.ident), "static", tokens(&s.ty), false);
+				}
+				Item::Use(u) => {
+					use_targets(&self.krate, path, &u.tree, Vec::new(), &mut self.imports);
+					if is_pub(&u.vis) {
+						self.insert(public, format!("{path}::use {}", tokens(&u.tree)), "use", String::new(), false);
+						self.uses.push((path.to_string(), u.tree.clone(), public));
+					}
+				}
+				Item::Mod(m) if !has_cfg_test(&m.attrs) => {
+					let child = format!("{path}::{}", m.ident);
+					let public = public && is_pub(&m.vis);
+					match (&m.content, mod_dir) {
+						(Some((_, inner)), _) => {
+							let dir = mod_dir.map(|d| d.join(m.ident.to_string()));
+							self.collect_items(inner, dir.as_deref(), &child, public);
+						}
+						(None, Some(dir)) => {
+							if let Some((file, child_dir)) = module_file(dir, &m.ident.to_string()) {
+								self.collect_file(&file, &child_dir, &child, public);
+							}
+						}
+						(None, None) => {}
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+
+	/// Absolute path of the item `segments` names in `module`, following `use` imports
+	fn item_path(&self, module: &str, segments: &[String]) -> Option<String> {
+		let mut paths = vec![resolve_use_path(&self.krate, module, segments)?];
+		for _ in 0..MAX_REEXPORT_DEPTH {
+			if let Some(found) = paths.iter().find(|p| self.items.contains_key(*p) || self.hidden.contains_key(*p)) {
+				return Some(found.clone());
+			}
+			paths = paths.iter().flat_map(|p| self.imports.iter().filter_map(|i| i.source_of(p))).collect();
+		}
+		None
+	}
+
+	/// Public items, with those re-exported by `pub use` listed under their public path too
+	fn finish(mut self) -> BTreeMap<String, ApiItem> {
+		// Inherent members are API only when their type is, under the type's path
+		for InherentImpl { module, self_ty, members } in std::mem::take(&mut self.impls) {
+			let Some(ty) = self.item_path(&module, &self_ty) else { continue };
+			let is_type = |item: &ApiItem| matches!(item.kind, "struct" | "enum" | "type");
+			let public = match (self.items.get(&ty), self.hidden.get(&ty)) {
+				(Some(item), _) if is_type(item) => true,
+				(_, Some(item)) if is_type(item) => false,
+				_ => continue,
+			};
+			for (name, kind, signature) in members {
+				self.insert(public, format!("{ty}::{name}"), kind, signature, false);
+			}
+		}
+		for _ in 0..MAX_REEXPORT_DEPTH {
+			let mut added = false;
+			for (module, tree, public) in &self.uses {
+				let mut targets = Vec::new();
+				use_targets(&self.krate, module, tree, Vec::new(), &mut targets);
+				for target in targets {
+					let found: Vec<(String, ApiItem)> = self
+						.items
+						.iter()
+						.chain(&self.hidden)
+						.filter(|(_, item)| item.kind != "use" && item.kind != "impl")
+						.filter_map(|(key, item)| target.rekey(key).map(|key| (key, item.clone())))
+						.collect();
+					let items = if *public { &mut self.items } else { &mut self.hidden };
+					for (key, item) in found {
+						if let Entry::Vacant(entry) = items.entry(key) {
+							entry.insert(item);
+							added = true;
+						}
+					}
+				}
+			}
+			if !added {
+				break;
+			}
+		}
+		self.items
+	}
+}
+
+/// What one leaf of a `pub use` brings in: `source` and everything under it
+/// becomes visible as `dest`, or for a glob everything under `source` moves under `dest`
+struct Reexport {
+	source: String,
+	dest: String,
+	glob: bool,
+}
+
+impl Reexport {
+	fn rekey(&self, key: &str) -> Option<String> {
+		let rest = key.strip_prefix(&self.source)?;
+		if self.glob {
+			return rest.strip_prefix("::").map(|rest| format!("{}::{rest}", self.dest));
+		}
+		(rest.is_empty() || rest.starts_with("::") || rest.starts_with('.')).then(|| format!("{}{rest}", self.dest))
+	}
+
+	/// The path `key` stands for when named through this `use`; the inverse of [`Self::rekey`]
+	fn source_of(&self, key: &str) -> Option<String> {
+		let rest = key.strip_prefix(&self.dest)?;
+		if self.glob {
+			return rest.strip_prefix("::").filter(|r| !r.contains("::")).map(|r| format!("{}::{r}", self.source));
+		}
+		(rest.is_empty() || rest.starts_with("::")).then(|| format!("{}{rest}", self.source))
+	}
+}
+
+fn use_targets(krate: &str, module: &str, tree: &UseTree, mut prefix: Vec<String>, out: &mut Vec<Reexport>) {
+	let (name, alias, glob) = match tree {
+		UseTree::Path(p) => {
+			prefix.push(p.ident.to_string());
+			return use_targets(krate, module, &p.tree, prefix, out);
+		}
+		UseTree::Group(g) => {
+			for tree in &g.items {
+				use_targets(krate, module, tree, prefix.clone(), out);
+			}
+			return;
+		}
+		UseTree::Name(n) => (Some(n.ident.to_string()), None, false),
+		UseTree::Rename(r) => (Some(r.ident.to_string()), Some(r.rename.to_string()), false),
+		UseTree::Glob(_) => (None, None, true),
+	};
+	// `use a::b::{self}` names `a::b` itself
+	if let Some(name) = name.filter(|n| n != "self") {
+		prefix.push(name);
+	}
+	let Some(source) = resolve_use_path(krate, module, &prefix) else { return };
+	let dest = match (glob, alias.or_else(|| prefix.last().cloned())) {
+		(true, _) => module.to_string(),
+		(false, Some(name)) => format!("{module}::{name}"),
+		(false, None) => return,
+	};
+	out.push(Reexport { source, dest, glob });
+}
+
+/// Absolute item path of a `use` path written in `module`; paths into other crates resolve to nothing
+fn resolve_use_path(krate: &str, module: &str, segments: &[String]) -> Option<String> {
+	let mut path: Vec<&str> = module.split("::").collect();
+	for (i, segment) in segments.iter().enumerate() {
+		match segment.as_str() {
+			"crate" if i == 0 => path = vec![krate],
+			"self" if i == 0 => {}
+			"super" => {
+				if path.len() <= 1 {
+					return None;
+				}
+				path.pop();
+			}
+			other => path.push(other),
+		}
+	}
+	Some(path.join("::"))
+}
+
+/// Resolve `mod name;` declared in a module whose children live in `dir`
+fn module_file(dir: &Path, name: &str) -> Option<(PathBuf, PathBuf)> {
+	let flat = dir.join(format!("{name}.rs"));
+	if flat.is_file() {
+		return Some((flat, dir.join(name)));
+	}
+	let nested = dir.join(name).join("mod.rs");
+	nested.is_file().then(|| (nested, dir.join(name)))
+}
+
+fn is_pub(vis: &Visibility) -> bool {
+	matches!(vis, Visibility::Public(_))
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+	attrs.iter().any(|a| a.path().is_ident(name))
+}
+
+/// Exactly `#[cfg(test)]`; `cfg(not(test))` or a `testing` feature are real API
+fn has_cfg_test(attrs: &[Attribute]) -> bool {
+	attrs
+		.iter()
+		.any(|a| a.path().is_ident("cfg") && a.parse_args::<syn::Ident>().is_ok_and(|i| i == "test"))
+}
+
+fn tokens<T: ToTokens>(t: &T) -> String {
+	t.to_token_stream().to_string()
+}
+
+fn generics_sig(g: &syn::Generics) -> String {
+	format!("{} {}", tokens(g), g.where_clause.as_ref().map(tokens).unwrap_or_default()).trim().to_string()
+}
+
+/// Field types without attributes, so doc edits are not reported as changes
+fn fields_sig(fields: &Fields) -> String {
+	let parts: Vec<String> = fields
+		.iter()
+		.map(|f| match &f.ident {
+			Some(name) => format!("{name}: {}", tokens(&f.ty)),
+			None => tokens(&f.ty),
+		})
+		.collect();
+	match fields {
+		Fields::Named(_) => format!("{{ {} }}", parts.join(", ")),
+		Fields::Unnamed(_) => format!("({})", parts.join(", ")),
+		Fields::Unit => String::new(),
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApiEntry {
+	pub path: String,
+	pub kind: &'static str,
+	pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApiChange {
+	pub path: String,
+	pub kind: &'static str,
+	pub before: String,
+	pub after: String,
+}
+
+/// Machine-readable result written to `api_diff.json`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ApiDiffReport {
+	pub pass: bool,
+	pub added: Vec<ApiEntry>,
+	pub removed: Vec<ApiEntry>,
+	pub changed: Vec<ApiChange>,
+	/// Human-readable reasons the change is breaking
+	pub breaking: Vec<String>,
+}
+
+impl ApiDiffReport {
+	pub fn new(before: &ApiSurface, after: &ApiSurface) -> Self {
+		let mut report = Self::default();
+		for (path, old) in &before.items {
+			match after.items.get(path) {
+				None => {
+					report.breaking.push(format!("removed {} {path}", old.kind));
+					report.removed.push(ApiEntry { path: path.clone(), kind: old.kind, signature: old.signature.clone() });
+				}
+				Some(new) if new.signature != old.signature => {
+					report.breaking.push(format!("changed {} {path}: {} -> {}", old.kind, old.signature, new.signature));
+					report.changed.push(ApiChange {
+						path: path.clone(),
+						kind: old.kind,
+						before: old.signature.clone(),
+						after: new.signature.clone(),
+					});
+				}
+				Some(_) => {}
+			}
+		}
+		for (path, new) in &after.items {
+			if before.items.contains_key(path) {
+				continue;
+			}
+			// New members of existing traits/enums break implementors and exhaustive matches
+			let parent_existed = path.rsplit_once("::").is_some_and(|(parent, _)| before.items.contains_key(parent));
+			if new.breaking_if_added && parent_existed {
+				report.breaking.push(format!("added {} {path}", new.kind));
+			}
+			report.added.push(ApiEntry { path: path.clone(), kind: new.kind, signature: new.signature.clone() });
+		}
+		report.pass = report.breaking.is_empty();
+		report
+	}
+}
+
+pub struct ApiStability<'a> { pub before: &'a ApiSurface, pub after: &'a ApiSurface }
+impl<'a> Validator for ApiStability<'a> {
+	fn validate(&self) -> Result<ValidatorOutcome, String> {
+		let report = ApiDiffReport::new(self.before, self.after);
+		let mut message = format!("API_STABILITY: {}", if report.pass { "PASS" } else { "FAIL" });
+		for b in &report.breaking {
+			message.push('\n');
+			message.push_str(b);
+		}
+		Ok(ValidatorOutcome { pass: report.pass, message: Some(message) })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BEFORE: &str = r#"
+		pub struct Task { pub title: String, secret: u8 }
+		/// Docs are ignored
+		pub enum Status { Todo, Done }
+		#[non_exhaustive]
+		pub enum Kind { A }
+		pub trait Store { fn get(&self, id: u32) -> Option<Task>; fn len(&self) -> usize { 0 } }
+		impl Task { pub fn new(title: String) -> Self { todo!() } fn private(&self) {} }
+		pub fn helper(a: u32) -> u32 { a }
+		pub(crate) fn internal() {}
+		#[cfg(test)]
+		pub mod tests { pub fn t() {} }
+	"#;
+
+	#[test]
+	fn unchanged_surface_passes() {
+		let a = ApiSurface::from_source("c", BEFORE);
+		let b = ApiSurface::from_source("c", &BEFORE.replace("Docs are ignored", "Reworded docs"));
+		assert!(a.items.contains_key("c::Task.title"));
+		assert!(!a.items.contains_key("c::Task.secret"));
+		assert!(!a.items.contains_key("c::internal"));
+		assert!(!a.items.contains_key("c::tests::t"));
+		assert!(ApiDiffReport::new(&a, &b).pass);
+	}
+
+	#[test]
+	fn breaking_changes_are_reported() {
+		let a = ApiSurface::from_source("c", BEFORE);
+		let after = BEFORE
+			.replace("pub fn helper(a: u32) -> u32", "pub fn helper(a: u64) -> u32")
+			.replace("pub enum Status { Todo, Done }", "pub enum Status { Todo, Done, Archived }")
+			.replace("pub enum Kind { A }", "pub enum Kind { A, B }")
+			.replace("pub struct Task { pub title: String, secret: u8 }", "pub struct Task { title: String, secret: u8 }")
+			.replace("fn len(&self) -> usize { 0 }", "fn len(&self) -> usize { 0 } fn clear(&mut self);")
+			.replace("pub fn new(title", "pub fn with_title(title");
+		let report = ApiDiffReport::new(&a, &ApiSurface::from_source("c", &after));
+		assert!(!report.pass);
+		assert_eq!(
+			report.breaking,
+			vec![
+				"removed field c::Task.title".to_string(),
+				"removed method c::Task::new".to_string(),
+				"changed fn c::helper: fn helper (a : u32) -> u32 -> fn helper (a : u64) -> u32".to_string(),
+				"added variant c::Status::Archived".to_string(),
+				"added trait_fn c::Store::clear".to_string(),
+			]
+		);
+		assert!(report.added.iter().any(|e| e.path == "c::Kind::B"));
+	}
+
+	const REEXPORTS: &str = r#"
+		mod inner {
+			pub struct Hidden { pub x: u8 }
+			impl Hidden { pub fn go(&self) {} }
+			pub fn f() {}
+			pub(crate) fn crate_only() {}
+			pub use self::deep::Deep;
+			mod deep { pub struct Deep; }
+		}
+		mod globbed { pub fn h() {} }
+		pub use inner::{Hidden, f as g};
+		pub use self::globbed::*;
+		pub use crate::inner::Deep;
+		pub use serde::Serialize;
+	"#;
+
+	#[test]
+	fn reexports_of_private_modules_are_public_api() {
+		let a = ApiSurface::from_source("c", REEXPORTS);
+		for key in ["c::Hidden", "c::Hidden.x", "c::Hidden::go", "c::g", "c::h", "c::Deep"] {
+			assert!(a.items.contains_key(key), "{key} missing from {:?}", a.items.keys());
+		}
+		assert!(!a.items.keys().any(|k| k.starts_with("c::inner") || k.starts_with("c::globbed")));
+		assert!(!a.items.contains_key("c::crate_only"));
+
+		let after = REEXPORTS
+			.replace("pub struct Hidden { pub x: u8 }", "pub struct Hidden { pub x: u16 }")
+			.replace("pub fn h() {}", "pub fn h(a: u8) {}");
+		let report = ApiDiffReport::new(&a, &ApiSurface::from_source("c", &after));
+		assert_eq!(
+			report.breaking,
+			vec!["changed field c::Hidden.x: u8 -> u16".to_string(), "changed fn c::h: fn h () -> fn h (a : u8)".to_string()]
+		);
+
+		let after = REEXPORTS.replace("pub use inner::{Hidden, f as g};", "pub use inner::Hidden;");
+		let report = ApiDiffReport::new(&a, &ApiSurface::from_source("c", &after));
+		assert!(report.breaking.contains(&"removed fn c::g".to_string()), "{:?}", report.breaking);
+	}
+
+	#[test]
+	fn inherent_members_are_keyed_by_their_public_type() {
+		let a = ApiSurface::from_source(
+			"c",
+			r#"
+			pub mod models { pub struct Task; struct Private; impl Private { pub fn leak(&self) {} } }
+			mod imp {
+				use crate::models::Task;
+				impl Task { pub fn new() -> Self { todo!() } pub const MAX: u8 = 1; }
+				impl super::models::Task { pub fn done(&self) {} }
+			}
+			pub mod glob { use super::models::*; impl Task { pub fn reopen(&self) {} } }
+			"#,
+		);
+		for key in ["c::models::Task::new", "c::models::Task::MAX", "c::models::Task::done", "c::models::Task::reopen"] {
+			assert!(a.items.contains_key(key), "{key} missing from {:?}", a.items.keys());
+		}
+		assert!(!a.items.keys().any(|k| k.contains("leak") || k.starts_with("c::imp") || k.starts_with("c::glob")));
+	}
+
+	#[test]
+	fn only_cfg_test_modules_are_left_out() {
+		let a = ApiSurface::from_source(
+			"c",
+			r#"
+			#[cfg(test)] pub mod tests { pub fn t() {} }
+			#[cfg(not(test))] pub mod real { pub fn r() {} }
+			#[cfg(feature = "testing")] pub mod fixtures { pub fn f() {} }
+			"#,
+		);
+		assert!(!a.items.contains_key("c::tests::t"));
+		assert!(a.items.contains_key("c::real::r"));
+		assert!(a.items.contains_key("c::fixtures::f"));
+	}
+}
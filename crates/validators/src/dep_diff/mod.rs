@@ -1,2 +1,318 @@
 pub mod node;
 pub mod rust;
+
+use std::{
+	cmp::Ordering,
+	collections::{BTreeMap, BTreeSet},
+	path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Package name -> versions present. Lockfiles can hold several versions of one
+/// package; manifest-only entries carry their version requirement instead.
+pub type DepSet = BTreeMap<String, BTreeSet<String>>;
+
+/// Dependencies of a working tree, per ecosystem.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DepSnapshot {
+	pub cargo: DepSet,
+	pub npm: DepSet,
+}
+
+impl DepSnapshot {
+	pub fn capture(root: &Path) -> Self {
+		Self { cargo: rust::snapshot(root), npm: node::snapshot(root) }
+	}
+
+	/// Human-readable listing, one `ecosystem name version` line per entry
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		for (eco, set) in [("cargo", &self.cargo), ("npm", &self.npm)] {
+			for (name, versions) in set {
+				for v in versions {
+					out.push_str(&format!("{eco} {name} {v}\n"));
+				}
+			}
+		}
+		out
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BumpLevel {
+	None,
+	Patch,
+	Minor,
+	Major,
+}
+
+impl BumpLevel {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::None => "none",
+			Self::Patch => "patch",
+			Self::Minor => "minor",
+			Self::Major => "major",
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DepEntry {
+	pub name: String,
+	pub version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DepChange {
+	pub name: String,
+	pub from: String,
+	pub to: String,
+	pub bump: BumpLevel,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DepDiff {
+	pub added: Vec<DepEntry>,
+	pub removed: Vec<DepEntry>,
+	pub upgraded: Vec<DepChange>,
+	pub downgraded: Vec<DepChange>,
+}
+
+impl DepDiff {
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.upgraded.is_empty() && self.downgraded.is_empty()
+	}
+}
+
+/// What dependency changes a phase tolerates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DepPolicy {
+	pub allow_new: bool,
+	pub allow_removed: bool,
+	pub allow_downgrade: bool,
+	/// Largest upgrade allowed; `none` forbids any version change
+	pub max_bump: BumpLevel,
+}
+
+impl Default for DepPolicy {
+	fn default() -> Self {
+		Self { allow_new: false, allow_removed: true, allow_downgrade: false, max_bump: BumpLevel::Patch }
+	}
+}
+
+impl DepPolicy {
+	/// Parse a phase's `dep_policy` JSON, falling back to the default policy
+	pub fn from_json(json: Option<&str>) -> Self {
+		json.and_then(|s| serde_json::from_str(s).ok()).unwrap_or_default()
+	}
+
+	/// Human-readable policy violations, empty when the diff is acceptable
+	pub fn violations(&self, eco: &str, diff: &DepDiff) -> Vec<String> {
+		let mut out = Vec::new();
+		if !self.allow_new {
+			out.extend(diff.added.iter().map(|d| format!("{eco}: new dependency {} {}", d.name, d.version)));
+		}
+		if !self.allow_removed {
+			out.extend(diff.removed.iter().map(|d| format!("{eco}: removed dependency {} {}", d.name, d.version)));
+		}
+		if !self.allow_downgrade {
+			out.extend(diff.downgraded.iter().map(|c| format!("{eco}: downgraded {} {} -> {}", c.name, c.from, c.to)));
+		}
+		out.extend(
+			diff.upgraded
+				.iter()
+				.filter(|c| c.bump > self.max_bump)
+				.map(|c| format!("{eco}: {} upgrade of {} {} -> {} exceeds policy", c.bump.as_str(), c.name, c.from, c.to)),
+		);
+		out
+	}
+}
+
+/// Numeric components of a version or requirement, e.g. `^1.2` -> [1, 2] and a
+/// pre-release flag.
+fn parse_version(v: &str) -> (Vec<u64>, bool) {
+	let v = v.trim_start_matches(|c: char| !c.is_ascii_digit());
+	let (core, pre) = match v.find(['-', '+']) {
+		Some(i) => (&v[..i], v[i..].starts_with('-')),
+		None => (v, false),
+	};
+	let nums = core.split('.').map(|p| p.parse::<u64>().unwrap_or(0)).collect();
+	(nums, pre)
+}
+
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+	let (an, apre) = parse_version(a);
+	let (bn, bpre) = parse_version(b);
+	let len = an.len().max(bn.len());
+	for i in 0..len {
+		let ord = an.get(i).unwrap_or(&0).cmp(bn.get(i).unwrap_or(&0));
+		if ord != Ordering::Equal {
+			return ord;
+		}
+	}
+	// A pre-release sorts before its release; fall back to text for stability
+	bpre.cmp(&apre).then_with(|| a.cmp(b))
+}
+
+/// How far apart two versions are under Cargo's semver rules: the leftmost
+/// non-zero component of `from` acts as its major, so `0.8 -> 0.9` and
+/// `0.0.1 -> 0.0.2` are major bumps and `0.8.1 -> 0.8.2` is a minor one.
+pub fn bump_level(from: &str, to: &str) -> BumpLevel {
+	let (f, _) = parse_version(from);
+	let (t, _) = parse_version(to);
+	let part = |v: &[u64], i: usize| v.get(i).copied().unwrap_or(0);
+	let major = f.iter().position(|&n| n != 0).unwrap_or(f.len().saturating_sub(1));
+	if (0..=major).any(|i| part(&f, i) != part(&t, i)) {
+		BumpLevel::Major
+	} else if part(&f, major + 1) != part(&t, major + 1) {
+		BumpLevel::Minor
+	} else if from != to {
+		BumpLevel::Patch
+	} else {
+		BumpLevel::None
+	}
+}
+
+/// Compare two dependency sets. When a package has exactly one version on each
+/// side it is an upgrade or downgrade; otherwise versions are added or removed.
+pub fn diff(before: &DepSet, after: &DepSet) -> DepDiff {
+	let mut out = DepDiff::default();
+	let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+	let empty = BTreeSet::new();
+	for name in names {
+		let b = before.get(name).unwrap_or(&empty);
+		let a = after.get(name).unwrap_or(&empty);
+		let gone: Vec<&String> = b.difference(a).collect();
+		let new: Vec<&String> = a.difference(b).collect();
+		if let ([from], [to]) = (gone.as_slice(), new.as_slice()) {
+			let change = DepChange {
+				name: name.clone(),
+				from: (*from).clone(),
+				to: (*to).clone(),
+				bump: bump_level(from, to),
+			};
+			match compare_versions(from, to) {
+				Ordering::Greater => out.downgraded.push(change),
+				_ => out.upgraded.push(change),
+			}
+			continue;
+		}
+		out.removed.extend(gone.into_iter().map(|v| DepEntry { name: name.clone(), version: v.clone() }));
+		out.added.extend(new.into_iter().map(|v| DepEntry { name: name.clone(), version: v.clone() }));
+	}
+	out
+}
+
+/// Machine-readable result written to `dep_diff.json`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DepDiffReport {
+	pub pass: bool,
+	pub policy: DepPolicy,
+	pub cargo: DepDiff,
+	pub npm: DepDiff,
+	pub violations: Vec<String>,
+}
+
+impl DepDiffReport {
+	pub fn new(before: &DepSnapshot, after: &DepSnapshot, policy: &DepPolicy) -> Self {
+		let cargo = diff(&before.cargo, &after.cargo);
+		let npm = diff(&before.npm, &after.npm);
+		let mut violations = policy.violations("cargo", &cargo);
+		violations.extend(policy.violations("npm", &npm));
+		Self { pass: violations.is_empty(), policy: policy.clone(), cargo, npm, violations }
+	}
+}
+
+pub(crate) fn insert(set: &mut DepSet, name: &str, version: &str) {
+	set.entry(name.to_string()).or_default().insert(version.to_string());
+}
+
+/// Merge manifest requirements into locked versions. A requirement no locked
+/// version meets means the lock is stale and the package manager will
+/// re-resolve the package, so the requirement replaces the locked versions it
+/// rejects.
+pub(crate) fn resolve(mut locked: DepSet, declared: DepSet, satisfies: impl Fn(&str, &str) -> bool) -> DepSet {
+	for (name, reqs) in declared {
+		let Some(versions) = locked.get_mut(&name) else {
+			locked.insert(name, reqs);
+			continue;
+		};
+		let unmet: Vec<String> = reqs
+			.iter()
+			.filter(|r| !versions.iter().any(|v| satisfies(r, v)))
+			.cloned()
+			.collect();
+		if unmet.is_empty() {
+			continue;
+		}
+		versions.retain(|v| reqs.iter().any(|r| satisfies(r, v)));
+		versions.extend(unmet);
+	}
+	locked
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn set(entries: &[(&str, &str)]) -> DepSet {
+		let mut s = DepSet::new();
+		for (n, v) in entries {
+			insert(&mut s, n, v);
+		}
+		s
+	}
+
+	#[test]
+	fn classifies_changes() {
+		let before = set(&[("serde", "1.0.1"), ("tokio", "1.2.0"), ("old", "0.1.0"), ("rand", "0.8.5")]);
+		let after = set(&[("serde", "1.0.2"), ("tokio", "1.1.0"), ("new", "2.0.0"), ("rand", "0.9.0")]);
+		let d = diff(&before, &after);
+		assert_eq!(d.added, vec![DepEntry { name: "new".into(), version: "2.0.0".into() }]);
+		assert_eq!(d.removed, vec![DepEntry { name: "old".into(), version: "0.1.0".into() }]);
+		assert_eq!(d.downgraded.len(), 1);
+		assert_eq!(d.downgraded[0].name, "tokio");
+		let bumps: Vec<(&str, BumpLevel)> = d.upgraded.iter().map(|c| (c.name.as_str(), c.bump)).collect();
+		assert_eq!(bumps, vec![("rand", BumpLevel::Major), ("serde", BumpLevel::Patch)]);
+	}
+
+	#[test]
+	fn default_policy_allows_patch_bumps_only() {
+		let policy = DepPolicy::default();
+		let patch = diff(&set(&[("serde", "1.0.1")]), &set(&[("serde", "1.0.9")]));
+		assert!(policy.violations("cargo", &patch).is_empty());
+		let minor = diff(&set(&[("serde", "1.0.1")]), &set(&[("serde", "1.1.0")]));
+		assert_eq!(policy.violations("cargo", &minor).len(), 1);
+		let added = diff(&DepSet::new(), &set(&[("left-pad", "1.3.0")]));
+		assert_eq!(policy.violations("npm", &added), vec!["npm: new dependency left-pad 1.3.0".to_string()]);
+
+		let lenient = DepPolicy::from_json(Some(r#"{"allow_new": true, "max_bump": "major"}"#));
+		assert!(lenient.violations("cargo", &minor).is_empty());
+		assert!(lenient.violations("npm", &added).is_empty());
+	}
+
+	#[test]
+	fn leftmost_non_zero_component_is_the_major() {
+		assert_eq!(bump_level("1.2.3", "2.0.0"), BumpLevel::Major);
+		assert_eq!(bump_level("1.2.3", "1.3.0"), BumpLevel::Minor);
+		assert_eq!(bump_level("1.2.3", "1.2.4"), BumpLevel::Patch);
+		assert_eq!(bump_level("0.8.5", "0.9.0"), BumpLevel::Major);
+		assert_eq!(bump_level("0.8.1", "0.8.2"), BumpLevel::Minor);
+		assert_eq!(bump_level("0.8", "1.0"), BumpLevel::Major);
+		assert_eq!(bump_level("0.0.1", "0.0.2"), BumpLevel::Major);
+		assert_eq!(bump_level("0.0.1", "0.1.0"), BumpLevel::Major);
+		assert_eq!(bump_level("0.8.1", "0.8.1-rc.1"), BumpLevel::Patch);
+		assert_eq!(bump_level("^0.8", "0.8.3"), BumpLevel::Minor);
+		assert_eq!(bump_level("1.0.0", "1.0.0"), BumpLevel::None);
+	}
+
+	#[test]
+	fn prerelease_sorts_before_release() {
+		assert_eq!(compare_versions("1.0.0-rc.1", "1.0.0"), Ordering::Less);
+		assert_eq!(compare_versions("^1.2", "1.10.0"), Ordering::Less);
+	}
+}
@@ -1,9 +1,270 @@
-use crate::{Validator, ValidatorOutcome};
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
 
-pub struct NodeDepDiff<'a> { pub before: &'a str, pub after: &'a str }
+use crate::{
+	dep_diff::{diff, insert, resolve, DepPolicy, DepSet},
+	Validator, ValidatorOutcome,
+};
+
+/// Ranges declared in `package.json` (dependencies, devDependencies,
+/// peerDependencies and optionalDependencies).
+pub fn parse_manifest(text: &str) -> DepSet {
+	let mut set = DepSet::new();
+	let Ok(v) = serde_json::from_str::<serde_json::Value>(text) else { return set };
+	for key in ["dependencies", "devDependencies", "peerDependencies", "optionalDependencies"] {
+		for (name, range) in v.get(key).and_then(|d| d.as_object()).into_iter().flatten() {
+			if let Some(range) = range.as_str() {
+				insert(&mut set, name, range);
+			}
+		}
+	}
+	set
+}
+
+/// Installed versions from `package-lock.json` (lockfile v1, v2 and v3)
+pub fn parse_package_lock(text: &str) -> DepSet {
+	let mut set = DepSet::new();
+	let Ok(v) = serde_json::from_str::<serde_json::Value>(text) else { return set };
+	if let Some(packages) = v.get("packages").and_then(|p| p.as_object()) {
+		for (path, pkg) in packages {
+			// "" is the root project; links point at workspace packages
+			let Some((_, name)) = path.rsplit_once("node_modules/") else { continue };
+			if pkg.get("link").and_then(|l| l.as_bool()) == Some(true) {
+				continue;
+			}
+			if let Some(version) = pkg.get("version").and_then(|v| v.as_str()) {
+				insert(&mut set, name, version);
+			}
+		}
+	} else {
+		collect_v1(&mut set, v.get("dependencies"));
+	}
+	set
+}
+
+fn collect_v1(set: &mut DepSet, deps: Option<&serde_json::Value>) {
+	for (name, pkg) in deps.and_then(|d| d.as_object()).into_iter().flatten() {
+		if let Some(version) = pkg.get("version").and_then(|v| v.as_str()) {
+			insert(set, name, version);
+		}
+		collect_v1(set, pkg.get("dependencies"));
+	}
+}
+
+/// Installed versions from the `packages:` section of a `pnpm-lock.yaml`.
+/// Handles the `/name/1.0.0` (v5), `/name@1.0.0` (v6) and `name@1.0.0` (v9)
+/// key formats; peer suffixes such as `(react@18.2.0)` are dropped.
+pub fn parse_pnpm_lock(text: &str) -> DepSet {
+	let mut set = DepSet::new();
+	let mut in_packages = false;
+	for line in text.lines() {
+		if !line.starts_with(' ') && !line.trim().is_empty() {
+			in_packages = line.trim_end() == "packages:";
+			continue;
+		}
+		if !in_packages || !line.starts_with("  ") || line.starts_with("   ") {
+			continue;
+		}
+		let Some(key) = line.trim().strip_suffix(':') else { continue };
+		let key = key.trim_matches(|c| c == '\'' || c == '"');
+		let key = key.split('(').next().unwrap_or(key).trim_start_matches('/');
+		// Skip a leading '@' so scoped names keep their scope
+		let split = match key.char_indices().skip(1).find(|(_, c)| *c == '@') {
+			Some((i, _)) => Some((&key[..i], &key[i + 1..])),
+			None => key.rsplit_once('/'),
+		};
+		if let Some((name, version)) = split {
+			if !name.is_empty() && !version.is_empty() {
+				insert(&mut set, name, version);
+			}
+		}
+	}
+	set
+}
+
+/// Workspace package patterns from `pnpm-workspace.yaml`, or failing that
+/// from the `workspaces` field of the root `package.json`
+fn workspace_patterns(root: &Path) -> Vec<String> {
+	if let Ok(text) = fs::read_to_string(root.join("pnpm-workspace.yaml")) {
+		let mut patterns = Vec::new();
+		let mut in_packages = false;
+		for line in text.lines() {
+			if !line.starts_with(' ') && !line.starts_with('-') && !line.trim().is_empty() {
+				in_packages = line.trim_end() == "packages:";
+				continue;
+			}
+			if let Some(item) = line.trim().strip_prefix("- ").filter(|_| in_packages) {
+				patterns.push(item.trim().trim_matches(|c| c == '\'' || c == '"').to_string());
+			}
+		}
+		return patterns;
+	}
+	let Some(v) = fs::read_to_string(root.join("package.json"))
+		.ok()
+		.and_then(|t| serde_json::from_str::<serde_json::Value>(&t).ok())
+	else {
+		return Vec::new();
+	};
+	let workspaces = v.get("workspaces");
+	let list = workspaces.and_then(|w| w.get("packages")).or(workspaces);
+	list.and_then(|l| l.as_array())
+		.into_iter()
+		.flatten()
+		.filter_map(|p| p.as_str().map(str::to_string))
+		.collect()
+}
+
+/// The root `package.json` and those of the workspace packages. Patterns may
+/// name a directory or end in `/*` or `/**`; exclusions (`!…`) are skipped.
+fn manifest_paths(root: &Path) -> Vec<PathBuf> {
+	let mut paths = vec![root.join("package.json")];
+	for pattern in workspace_patterns(root).iter().filter(|p| !p.starts_with('!')) {
+		match pattern.strip_suffix("/**").or_else(|| pattern.strip_suffix("/*")) {
+			Some(dir) => {
+				let mut found: Vec<_> = fs::read_dir(root.join(dir))
+					.into_iter()
+					.flatten()
+					.filter_map(|e| e.ok())
+					.map(|e| e.path().join("package.json"))
+					.filter(|p| p.is_file())
+					.collect();
+				found.sort();
+				paths.extend(found);
+			}
+			None => paths.push(root.join(pattern).join("package.json")),
+		}
+	}
+	paths
+}
+
+/// Whether an installed version meets a `package.json` range. A bare version
+/// is exact, as npm reads it; specs semver cannot read, such as tags,
+/// `workspace:` or git URLs, are taken as met.
+fn satisfies(range: &str, version: &str) -> bool {
+	let Ok(version) = semver::Version::parse(version) else { return true };
+	range.split("||").any(|alternative| {
+		let comparators: Vec<String> = alternative
+			.split_whitespace()
+			.map(|c| c.strip_prefix('v').unwrap_or(c))
+			.map(|c| if c.starts_with(|ch: char| ch.is_ascii_digit()) { format!("={c}") } else { c.to_string() })
+			.collect();
+		if comparators.is_empty() {
+			return true;
+		}
+		match semver::VersionReq::parse(&comparators.join(", ")) {
+			Ok(req) => req.matches(&version),
+			Err(_) => true,
+		}
+	})
+}
+
+/// Installed versions from whichever lockfile exists, plus declared ranges of
+/// the root and workspace packages for dependencies the lockfile does not know
+/// about yet or no longer satisfies.
+pub fn snapshot(root: &Path) -> DepSet {
+	let locked = if let Ok(text) = fs::read_to_string(root.join("package-lock.json")) {
+		parse_package_lock(&text)
+	} else if let Ok(text) = fs::read_to_string(root.join("pnpm-lock.yaml")) {
+		parse_pnpm_lock(&text)
+	} else {
+		DepSet::new()
+	};
+	let mut declared = DepSet::new();
+	for path in manifest_paths(root) {
+		let Ok(text) = fs::read_to_string(&path) else { continue };
+		for (name, versions) in parse_manifest(&text) {
+			declared.entry(name).or_default().extend(versions);
+		}
+	}
+	resolve(locked, declared, satisfies)
+}
+
+pub struct NodeDepDiff<'a> { pub before: &'a DepSet, pub after: &'a DepSet, pub policy: &'a DepPolicy }
 impl<'a> Validator for NodeDepDiff<'a> {
 	fn validate(&self) -> Result<ValidatorOutcome, String> {
-		let pass = self.before == self.after;
-		Ok(ValidatorOutcome { pass, message: Some(if pass {"DEP_DIFF: PASS".into()} else {"DEP_DIFF: FAIL".into()}) })
+		let violations = self.policy.violations("npm", &diff(self.before, self.after));
+		let pass = violations.is_empty();
+		let mut message = format!("DEP_DIFF(npm): {}", if pass { "PASS" } else { "FAIL" });
+		for v in &violations {
+			message.push('\n');
+			message.push_str(v);
+		}
+		Ok(ValidatorOutcome { pass, message: Some(message) })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pnpm_key_formats() {
+		let lock = "lockfileVersion: '9.0'\n\nimporters:\n  .:\n    dependencies:\n      react:\n        specifier: ^18.2.0\n\npackages:\n  /left-pad/1.3.0:\n    resolution: {integrity: sha512-x}\n  /lodash@4.17.21:\n    resolution: {integrity: sha512-y}\n  '@types/node@20.1.0':\n    resolution: {integrity: sha512-z}\n  react-dom@18.2.0(react@18.2.0):\n    resolution: {integrity: sha512-w}\n";
+		let set = parse_pnpm_lock(lock);
+		let entries: Vec<(&str, &str)> = set
+			.iter()
+			.flat_map(|(n, vs)| vs.iter().map(move |v| (n.as_str(), v.as_str())))
+			.collect();
+		assert_eq!(
+			entries,
+			vec![("@types/node", "20.1.0"), ("left-pad", "1.3.0"), ("lodash", "4.17.21"), ("react-dom", "18.2.0")]
+		);
+	}
+
+	#[test]
+	fn package_lock_v3_uses_nested_names() {
+		let lock = r#"{"lockfileVersion": 3, "packages": {
+			"": {"name": "app"},
+			"node_modules/@scope/a": {"version": "1.0.0"},
+			"node_modules/b/node_modules/c": {"version": "2.0.0"},
+			"node_modules/ws-pkg": {"resolved": "packages/ws", "link": true}
+		}}"#;
+		let set = parse_package_lock(lock);
+		let names: Vec<&str> = set.keys().map(String::as_str).collect();
+		assert_eq!(names, vec!["@scope/a", "c"]);
+	}
+
+	#[test]
+	fn ranges_are_read_as_npm_reads_them() {
+		assert!(satisfies("^18.2.0", "18.3.1"));
+		assert!(!satisfies("^18.2.0", "19.0.0"));
+		assert!(!satisfies("18.2.0", "18.3.1"));
+		assert!(satisfies(">=1.0.0 <2.0.0 || ^3", "3.1.0"));
+		assert!(satisfies("workspace:*", "1.0.0"));
+		assert!(satisfies("latest", "1.0.0"));
+	}
+
+	#[test]
+	fn range_bumps_and_workspace_packages_are_not_hidden_by_the_lock() {
+		let root = std::env::temp_dir().join(format!("vk-node-deps-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&root);
+		fs::create_dir_all(root.join("frontend")).unwrap();
+		fs::write(root.join("pnpm-workspace.yaml"), "packages:\n  - frontend\n\nonlyBuiltDependencies:\n  - esbuild\n").unwrap();
+		fs::write(root.join("pnpm-lock.yaml"), "lockfileVersion: '9.0'\n\npackages:\n  react@18.2.0:\n    resolution: {integrity: sha512-x}\n").unwrap();
+		fs::write(root.join("package.json"), r#"{"devDependencies": {"prettier": "^3.0.0"}}"#).unwrap();
+		let frontend = |react: &str| {
+			fs::write(
+				root.join("frontend/package.json"),
+				format!(r#"{{"dependencies": {{"react": "{react}", "app-shared": "workspace:*"}}}}"#),
+			)
+			.unwrap();
+			snapshot(&root)
+		};
+
+		let before = frontend("^18.2.0");
+		assert_eq!(before["react"].iter().collect::<Vec<_>>(), vec!["18.2.0"]);
+		assert!(before["prettier"].contains("^3.0.0"));
+
+		// frontend/package.json asks for react 19 while the lock still has 18.2.0
+		let after = frontend("^19.0.0");
+		assert_eq!(after["react"].iter().collect::<Vec<_>>(), vec!["^19.0.0"]);
+		let outcome = NodeDepDiff { before: &before, after: &after, policy: &DepPolicy::default() }
+			.validate()
+			.unwrap();
+		assert!(!outcome.pass);
+		assert!(outcome.message.unwrap().contains("major upgrade of react 18.2.0 -> ^19.0.0"));
+		let _ = fs::remove_dir_all(&root);
 	}
 }
@@ -1,9 +1,218 @@
-use crate::{Validator, ValidatorOutcome};
+use std::{fs, path::Path};
 
-pub struct RustDepDiff<'a> { pub before: &'a str, pub after: &'a str }
+use crate::{
+	dep_diff::{diff, insert, resolve, DepPolicy, DepSet},
+	Validator, ValidatorOutcome,
+};
+
+/// Registry and git packages resolved in a `Cargo.lock`. Workspace members and
+/// path dependencies have no `source` and are skipped.
+pub fn parse_lock(text: &str) -> DepSet {
+	let mut set = DepSet::new();
+	let Ok(lock) = text.parse::<toml::Table>() else { return set };
+	for pkg in lock.get("package").and_then(|p| p.as_array()).into_iter().flatten() {
+		if pkg.get("source").is_none() {
+			continue;
+		}
+		if let (Some(name), Some(version)) = (
+			pkg.get("name").and_then(|v| v.as_str()),
+			pkg.get("version").and_then(|v| v.as_str()),
+		) {
+			insert(&mut set, name, version);
+		}
+	}
+	set
+}
+
+/// Dependencies declared in a `Cargo.toml`, including dev/build, target-specific
+/// and `[workspace.dependencies]` tables. Entries inherited with
+/// `workspace = true` and path-only dependencies are skipped.
+pub fn parse_manifest(text: &str) -> DepSet {
+	let mut set = DepSet::new();
+	let Ok(manifest) = text.parse::<toml::Table>() else { return set };
+	collect_tables(&mut set, &manifest);
+	if let Some(targets) = manifest.get("target").and_then(|t| t.as_table()) {
+		for target in targets.values().filter_map(|t| t.as_table()) {
+			collect_tables(&mut set, target);
+		}
+	}
+	if let Some(ws) = manifest.get("workspace").and_then(|w| w.as_table()) {
+		collect_deps(&mut set, ws.get("dependencies"));
+	}
+	set
+}
+
+fn collect_tables(set: &mut DepSet, table: &toml::Table) {
+	for key in ["dependencies", "dev-dependencies", "build-dependencies"] {
+		collect_deps(set, table.get(key));
+	}
+}
+
+fn collect_deps(set: &mut DepSet, deps: Option<&toml::Value>) {
+	for (key, spec) in deps.and_then(|d| d.as_table()).into_iter().flatten() {
+		let (name, version) = match spec {
+			toml::Value::String(req) => (key.as_str(), req.clone()),
+			toml::Value::Table(t) => {
+				if t.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+					continue;
+				}
+				let name = t.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+				match (t.get("version").and_then(|v| v.as_str()), t.get("git")) {
+					(Some(req), _) => (name, req.to_string()),
+					(None, Some(_)) => (name, "git".to_string()),
+					(None, None) => continue,
+				}
+			}
+			_ => continue,
+		};
+		insert(set, name, &version);
+	}
+}
+
+/// Manifests of the root package and the workspace members it lists
+pub(crate) fn manifest_paths(root: &Path) -> Vec<std::path::PathBuf> {
+	let root_manifest = root.join("Cargo.toml");
+	let mut paths = vec![root_manifest.clone()];
+	let Some(members) = fs::read_to_string(&root_manifest)
+		.ok()
+		.and_then(|t| t.parse::<toml::Table>().ok())
+		.and_then(|m| m.get("workspace")?.get("members")?.as_array().cloned())
+	else {
+		return paths;
+	};
+	for member in members.iter().filter_map(|m| m.as_str()) {
+		match member.strip_suffix("/*") {
+			Some(dir) => {
+				let mut found: Vec<_> = fs::read_dir(root.join(dir))
+					.into_iter()
+					.flatten()
+					.filter_map(|e| e.ok())
+					.map(|e| e.path().join("Cargo.toml"))
+					.filter(|p| p.is_file())
+					.collect();
+				found.sort();
+				paths.extend(found);
+			}
+			None => paths.push(root.join(member).join("Cargo.toml")),
+		}
+	}
+	paths
+}
+
+/// Whether a locked version meets a manifest requirement. Requirements that are
+/// not semver, such as git dependencies, are taken as met.
+fn satisfies(req: &str, version: &str) -> bool {
+	match (semver::VersionReq::parse(req), semver::Version::parse(version)) {
+		(Ok(req), Ok(version)) => req.matches(&version),
+		_ => true,
+	}
+}
+
+/// Resolved versions from `Cargo.lock`, plus declared requirements for
+/// dependencies the lockfile does not know about yet or no longer satisfies.
+pub fn snapshot(root: &Path) -> DepSet {
+	let locked = fs::read_to_string(root.join("Cargo.lock")).map(|t| parse_lock(&t)).unwrap_or_default();
+	let mut declared = DepSet::new();
+	for path in manifest_paths(root) {
+		let Ok(text) = fs::read_to_string(&path) else { continue };
+		for (name, versions) in parse_manifest(&text) {
+			declared.entry(name).or_default().extend(versions);
+		}
+	}
+	resolve(locked, declared, satisfies)
+}
+
+pub struct RustDepDiff<'a> { pub before: &'a DepSet, pub after: &'a DepSet, pub policy: &'a DepPolicy }
 impl<'a> Validator for RustDepDiff<'a> {
 	fn validate(&self) -> Result<ValidatorOutcome, String> {
-		let pass = self.before == self.after;
-		Ok(ValidatorOutcome { pass, message: Some(if pass {"DEP_DIFF: PASS".into()} else {"DEP_DIFF: FAIL".into()}) })
+		let violations = self.policy.violations("cargo", &diff(self.before, self.after));
+		let pass = violations.is_empty();
+		let mut message = format!("DEP_DIFF(cargo): {}", if pass { "PASS" } else { "FAIL" });
+		for v in &violations {
+			message.push('\n');
+			message.push_str(v);
+		}
+		Ok(ValidatorOutcome { pass, message: Some(message) })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lock_skips_local_packages() {
+		let lock = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+		let set = parse_lock(lock);
+		assert_eq!(set.len(), 1);
+		assert!(set["serde"].contains("1.0.200"));
+	}
+
+	#[test]
+	fn manifest_collects_declared_deps() {
+		let manifest = r#"
+[workspace.dependencies]
+tokio = { version = "1.0", features = ["full"] }
+
+[dependencies]
+serde = "1.0"
+tokio = { workspace = true }
+local = { path = "../local" }
+fork = { git = "https://example.com/fork" }
+renamed = { package = "real-name", version = "0.3" }
+
+[target.'cfg(unix)'.dependencies]
+nix = "0.29"
+"#;
+		let set = parse_manifest(manifest);
+		let names: Vec<&str> = set.keys().map(String::as_str).collect();
+		assert_eq!(names, vec!["fork", "nix", "real-name", "serde", "tokio"]);
+		assert!(set["tokio"].contains("1.0"));
+	}
+
+	#[test]
+	fn manifest_only_bump_is_not_hidden_by_the_stale_lock() {
+		let lock = parse_lock(
+			r#"
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "fork"
+version = "0.1.0"
+source = "git+https://example.com/fork"
+"#,
+		);
+		let manifest = |serde: &str| {
+			parse_manifest(&format!(
+				"[dependencies]\nserde = \"{serde}\"\nfork = {{ git = \"https://example.com/fork\" }}\nrand = \"0.8\"\n"
+			))
+		};
+		let before = resolve(lock.clone(), manifest("1"), satisfies);
+		assert_eq!(before["serde"].iter().collect::<Vec<_>>(), vec!["1.0.200"]);
+		assert_eq!(before["fork"].iter().collect::<Vec<_>>(), vec!["0.1.0"]);
+		assert!(before["rand"].contains("0.8"));
+
+		// Cargo.toml asks for serde 2 while Cargo.lock still pins 1.0.200
+		let after = resolve(lock, manifest("2"), satisfies);
+		assert_eq!(after["serde"].iter().collect::<Vec<_>>(), vec!["2"]);
+		let outcome = RustDepDiff { before: &before, after: &after, policy: &DepPolicy::default() }
+			.validate()
+			.unwrap();
+		assert!(!outcome.pass);
+		assert!(outcome.message.unwrap().contains("major upgrade of serde 1.0.200 -> 2"));
 	}
 }
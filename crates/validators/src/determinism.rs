@@ -1,23 +1,78 @@
 use crate::{Validator, ValidatorOutcome};
 
+/// Passes when the cold and warm test runs produced the same per-test
+/// fingerprint. Flaky tests are listed in the message; a run that parsed no
+/// tests fails, since an empty fingerprint always matches itself.
 pub struct Determinism<'a> { pub snippets: &'a str }
+
+fn value<'s>(snippets: &'s str, key: &str) -> Option<&'s str> {
+	snippets.lines().find_map(|l| l.strip_prefix(key)?.strip_prefix('=')).map(str::trim)
+}
+
+/// Total of a `TEST_COUNTS_*=passed:N failed:N ignored:N` line
+fn test_count(snippets: &str, key: &str) -> Option<u64> {
+	value(snippets, key)?
+		.split_whitespace()
+		.map(|part| part.split_once(':').and_then(|(_, n)| n.parse::<u64>().ok()))
+		.sum()
+}
+
 impl<'a> Validator for Determinism<'a> {
 	fn validate(&self) -> Result<ValidatorOutcome, String> {
-		let up = self.snippets.to_uppercase();
-		let cache_hit = up.contains("CACHE_HIT");
-		let cold = self
-			.snippets
-			.lines()
-			.find(|l| l.starts_with("OUTPUT_DIGEST_COLD="))
-			.and_then(|l| l.split('=').nth(1))
-			.map(|s| s.trim().to_string());
-		let warm = self
+		let cold = value(self.snippets, "TEST_FINGERPRINT_COLD");
+		let warm = value(self.snippets, "TEST_FINGERPRINT_WARM");
+		let no_tests = ["TEST_COUNTS_COLD", "TEST_COUNTS_WARM"]
+			.iter()
+			.any(|key| test_count(self.snippets, key) == Some(0));
+		let pass = !no_tests && matches!((cold, warm), (Some(c), Some(w)) if c == w);
+		let flaky: Vec<&str> = self
 			.snippets
 			.lines()
-			.find(|l| l.starts_with("OUTPUT_DIGEST_WARM="))
-			.and_then(|l| l.split('=').nth(1))
-			.map(|s| s.trim().to_string());
-		let digest_match = matches!((&cold, &warm), (Some(c), Some(w)) if c == w);
-		Ok(ValidatorOutcome { pass: cache_hit || digest_match, message: None })
+			.filter_map(|l| l.strip_prefix("FLAKY="))
+			.map(str::trim)
+			.collect();
+		let mut message = format!("DETERMINISM: {}", if pass { "PASS" } else { "FAIL" });
+		if cold.is_none() || warm.is_none() {
+			message.push_str("\n  missing test fingerprint");
+		}
+		if no_tests {
+			message.push_str("\n  no tests found in the test output");
+		}
+		for name in flaky {
+			message.push_str(&format!("\n  flaky: {name}"));
+		}
+		Ok(ValidatorOutcome { pass, message: Some(message) })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn compares_fingerprints_and_lists_flaky_tests() {
+		let same = "CACHE_HIT\nTEST_FINGERPRINT_COLD=ab\nTEST_FINGERPRINT_WARM=ab\n";
+		assert!(Determinism { snippets: same }.validate().unwrap().pass);
+
+		let flaky = "TEST_FINGERPRINT_COLD=ab\nTEST_FINGERPRINT_WARM=cd\nFLAKY=tests::racy\n";
+		let out = Determinism { snippets: flaky }.validate().unwrap();
+		assert!(!out.pass);
+		assert!(out.message.unwrap().contains("flaky: tests::racy"));
+
+		// a cache hit alone no longer counts as deterministic
+		assert!(!Determinism { snippets: "CACHE_HIT\n" }.validate().unwrap().pass);
+	}
+
+	#[test]
+	fn fails_when_no_tests_were_parsed() {
+		let empty = "TEST_FINGERPRINT_COLD=e3\nTEST_COUNTS_COLD=passed:0 failed:0 ignored:0\n\
+			TEST_FINGERPRINT_WARM=e3\nTEST_COUNTS_WARM=passed:0 failed:0 ignored:0\n";
+		let out = Determinism { snippets: empty }.validate().unwrap();
+		assert!(!out.pass);
+		assert!(out.message.unwrap().contains("no tests found"));
+
+		let counted = "TEST_FINGERPRINT_COLD=ab\nTEST_COUNTS_COLD=passed:2 failed:0 ignored:1\n\
+			TEST_FINGERPRINT_WARM=ab\nTEST_COUNTS_WARM=passed:2 failed:0 ignored:1\n";
+		assert!(Determinism { snippets: counted }.validate().unwrap().pass);
 	}
 }
@@ -1,10 +1,29 @@
 use crate::{Validator, ValidatorOutcome};
 
-pub struct Kpi<'a> { pub kpi_json: &'a str }
+pub const DEFAULT_WARM_BUDGET_SEC: f64 = 600.0;
+
+/// Passes when the warm test run finished within `warm_budget_sec`
+pub struct Kpi<'a> { pub kpi_json: &'a str, pub warm_budget_sec: f64 }
 impl<'a> Validator for Kpi<'a> {
 	fn validate(&self) -> Result<ValidatorOutcome, String> {
 		let v: serde_json::Value = serde_json::from_str(self.kpi_json).map_err(|e| e.to_string())?;
 		let warm = v.get("warm_run_sec").and_then(|n| n.as_f64()).unwrap_or(0.0);
-		Ok(ValidatorOutcome { pass: warm <= 600.0, message: Some(format!("KPI: {} (warm={:.3}<=600)", if warm<=600.0 {"PASS"} else {"FAIL"}, warm)) })
+		let budget = self.warm_budget_sec;
+		let pass = warm <= budget;
+		Ok(ValidatorOutcome { pass, message: Some(format!("KPI: {} (warm={:.3}<={})", if pass {"PASS"} else {"FAIL"}, warm, budget)) })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn uses_the_configured_budget() {
+		let kpi = r#"{"warm_run_sec": 42.5}"#;
+		assert!(Kpi { kpi_json: kpi, warm_budget_sec: DEFAULT_WARM_BUDGET_SEC }.validate().unwrap().pass);
+		let out = Kpi { kpi_json: kpi, warm_budget_sec: 30.0 }.validate().unwrap();
+		assert!(!out.pass);
+		assert_eq!(out.message.as_deref(), Some("KPI: FAIL (warm=42.500<=30)"));
 	}
 }
@@ -1,20 +1,133 @@
+use std::path::{Component, Path};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
 use crate::{Validator, ValidatorOutcome};
 
-pub struct ScopeGuard<'a> { pub touched: &'a [String], pub allowlist: Option<&'a [String]> }
+/// Which files a phase may touch. Patterns use gitignore syntax (`src/`,
+/// `*.lock`, `crates/**/tests/`, `!keep.rs`); an empty allowlist allows everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ScopePolicy {
+	#[serde(default)]
+	pub allowlist: Vec<String>,
+	#[serde(default)]
+	pub denylist: Vec<String>,
+	/// Refuse the patch before `git apply` instead of only failing the validator
+	#[serde(default)]
+	pub reject_before_apply: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ScopeViolation { pub path: String, pub reason: String }
+
+fn matcher(patterns: &[String]) -> Result<Gitignore, String> {
+	let mut builder = GitignoreBuilder::new("");
+	for p in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+		builder.add_line(None, p).map_err(|e| format!("invalid pattern {p:?}: {e}"))?;
+	}
+	builder.build().map_err(|e| e.to_string())
+}
+
+fn matches(m: &Gitignore, path: &str) -> bool {
+	m.matched_path_or_any_parents(path, false).is_ignore()
+}
+
+impl ScopePolicy {
+	/// Check that every pattern parses
+	pub fn validate_patterns(&self) -> Result<(), String> {
+		matcher(&self.allowlist)?;
+		matcher(&self.denylist)?;
+		Ok(())
+	}
+
+	/// Every touched file that is invalid, outside the allowlist or denied
+	pub fn violations(&self, touched: &[String]) -> Result<Vec<ScopeViolation>, String> {
+		let allow = matcher(&self.allowlist)?;
+		let deny = matcher(&self.denylist)?;
+		let mut out = Vec::new();
+		for f in touched {
+			let escapes = Path::new(f).components().any(|c| c == Component::ParentDir);
+			let reason = if f.starts_with('/') || f.contains(':') || escapes {
+				"invalid path"
+			} else if matches(&deny, f) {
+				"matches denylist"
+			} else if !allow.is_empty() && !matches(&allow, f) {
+				"outside allowlist"
+			} else {
+				continue;
+			};
+			out.push(ScopeViolation { path: f.clone(), reason: reason.to_string() });
+		}
+		Ok(out)
+	}
+}
+
+pub struct ScopeGuard<'a> { pub touched: &'a [String], pub policy: &'a ScopePolicy }
 impl<'a> Validator for ScopeGuard<'a> {
 	fn validate(&self) -> Result<ValidatorOutcome, String> {
-		for f in self.touched {
-			if f.starts_with('/') || f.contains(':') || f.contains("..") {
-				return Ok(ValidatorOutcome { pass: false, message: Some(format!("Invalid path: {}", f)) });
-			}
-		}
-		if let Some(allow) = self.allowlist {
-			for f in self.touched {
-				if !allow.iter().any(|a| f.starts_with(a)) {
-					return Ok(ValidatorOutcome { pass: false, message: Some(format!("{} not in allowlist", f)) });
-				}
-			}
+		let violations = self.policy.violations(self.touched)?;
+		let pass = violations.is_empty();
+		let mut message = format!("SCOPE_GUARD: {}", if pass { "PASS" } else { "FAIL" });
+		for v in &violations {
+			message.push_str(&format!("\n  {}: {}", v.path, v.reason));
 		}
-		Ok(ValidatorOutcome { pass: true, message: Some(format!("SCOPE_GUARD: PASS\nTOUCHED_FILES:\n{}", self.touched.join("\n"))) })
+		message.push_str(&format!("\nTOUCHED_FILES:\n{}", self.touched.join("\n")));
+		Ok(ValidatorOutcome { pass, message: Some(message) })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn strings(v: &[&str]) -> Vec<String> { v.iter().map(|s| s.to_string()).collect() }
+
+	#[test]
+	fn reports_every_violation() {
+		let policy = ScopePolicy {
+			allowlist: strings(&["src/", "*.md", "!src/generated/"]),
+			denylist: strings(&["**/secrets/**", "Cargo.lock"]),
+			reject_before_apply: false,
+		};
+		let touched = strings(&[
+			"README.md",
+			"src/lib.rs",
+			"src/a/secrets/key.rs",
+			"src/generated/types.rs",
+			"Cargo.lock",
+			"../escape.rs",
+			"src/../../escape.rs",
+			"src/a..b.rs",
+			"CHANGELOG..md",
+			"build.rs",
+		]);
+		let found: Vec<(String, String)> = policy
+			.violations(&touched)
+			.unwrap()
+			.into_iter()
+			.map(|v| (v.path, v.reason))
+			.collect();
+		assert_eq!(
+			found,
+			vec![
+				("src/a/secrets/key.rs".to_string(), "matches denylist".to_string()),
+				("src/generated/types.rs".to_string(), "outside allowlist".to_string()),
+				("Cargo.lock".to_string(), "matches denylist".to_string()),
+				("../escape.rs".to_string(), "invalid path".to_string()),
+				("src/../../escape.rs".to_string(), "invalid path".to_string()),
+				("build.rs".to_string(), "outside allowlist".to_string()),
+			]
+		);
+		let out = ScopeGuard { touched: &touched, policy: &policy }.validate().unwrap();
+		assert!(!out.pass);
+		assert!(out.message.unwrap().contains("  build.rs: outside allowlist"));
+	}
+
+	#[test]
+	fn empty_lists_allow_everything() {
+		let touched = strings(&["a/b.rs", "c.toml"]);
+		let out = ScopeGuard { touched: &touched, policy: &ScopePolicy::default() }.validate().unwrap();
+		assert!(out.pass);
+		assert!(ScopePolicy { allowlist: strings(&["src/{a,b"]), ..Default::default() }.validate_patterns().is_err());
 	}
 }
@@ -1,4 +1,7 @@
+import { useState } from 'react';
 import MarkdownRenderer from '@/components/ui/markdown-renderer.tsx';
+import { Button } from '@/components/ui/button';
+import { executionProcessesApi } from '@/lib/api';
 import {
   AlertCircle,
   Bot,
@@ -12,6 +15,7 @@ import {
   Plus,
   Search,
   Settings,
+  ShieldQuestion,
   Terminal,
   User,
 } from 'lucide-react';
@@ -26,6 +30,7 @@ type Props = {
   entry: NormalizedEntry;
   expansionKey: string;
   diffDeletable?: boolean;
+  executionProcessId?: string;
 };
 
 const getEntryIcon = (entryType: NormalizedEntryType) => {
@@ -44,6 +49,9 @@ const getEntryIcon = (entryType: NormalizedEntryType) => {
   if (entryType.type === 'error_message') {
     return <AlertCircle className="h-4 w-4 text-red-600" />;
   }
+  if (entryType.type === 'tool_approval') {
+    return <ShieldQuestion className="h-4 w-4 text-amber-600" />;
+  }
   if (entryType.type === 'tool_use') {
     const { action_type, tool_name } = entryType;
 
@@ -132,7 +140,73 @@ const shouldRenderMarkdown = (entryType: NormalizedEntryType) => {
 
 import { useExpandable } from '@/stores/useExpandableStore';
 
-function DisplayConversationEntry({ entry, expansionKey }: Props) {
+function ToolApprovalActions({
+  entryType,
+  executionProcessId,
+}: {
+  entryType: Extract<NormalizedEntryType, { type: 'tool_approval' }>;
+  executionProcessId?: string;
+}) {
+  const [submitting, setSubmitting] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const { status } = entryType;
+
+  if (status.status !== 'pending') {
+    const label =
+      status.status === 'approved'
+        ? 'Approved'
+        : status.status === 'timed_out'
+          ? 'Timed out'
+          : `Denied${status.reason ? `: ${status.reason}` : ''}`;
+    return <div className="mt-1 text-xs text-muted-foreground">{label}</div>;
+  }
+  if (!executionProcessId) {
+    return (
+      <div className="mt-1 text-xs text-muted-foreground">
+        Waiting for approval
+      </div>
+    );
+  }
+
+  const respond = async (approved: boolean) => {
+    setSubmitting(true);
+    setError(null);
+    try {
+      await executionProcessesApi.respondToApproval(executionProcessId, {
+        approval_id: entryType.approval_id,
+        approved,
+        reason: null,
+      });
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to respond');
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  return (
+    <div className="mt-2 flex items-center gap-2">
+      <Button size="sm" disabled={submitting} onClick={() => respond(true)}>
+        Approve
+      </Button>
+      <Button
+        size="sm"
+        variant="outline"
+        disabled={submitting}
+        onClick={() => respond(false)}
+      >
+        Deny
+      </Button>
+      {error && <span className="text-xs text-red-600">{error}</span>}
+    </div>
+  );
+}
+
+function DisplayConversationEntry({
+  entry,
+  expansionKey,
+  executionProcessId,
+}: Props) {
   const isErrorMessage = entry.entry_type.type === 'error_message';
   const hasMultipleLines = isErrorMessage && entry.content.includes('\n');
   const [isExpanded, setIsExpanded] = useExpandable(
@@ -213,6 +287,13 @@ function DisplayConversationEntry({ entry, expansionKey }: Props) {
             </div>
           )}
 
+          {entry.entry_type.type === 'tool_approval' && (
+            <ToolApprovalActions
+              entryType={entry.entry_type}
+              executionProcessId={executionProcessId}
+            />
+          )}
+
           {fileEdit &&
             Array.isArray(fileEdit.changes) &&
             fileEdit.changes.map((change, idx) => {
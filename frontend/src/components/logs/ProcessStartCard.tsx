@@ -38,6 +38,8 @@ function ProcessStartCard({
         return 'Coding Agent';
       case 'devserver':
         return 'Dev Server';
+      case 'orchestrator':
+        return 'Orchestrator';
       default:
         return runReason;
     }
@@ -31,6 +31,8 @@ interface ProjectFormFieldsProps {
   setCleanupScript: (script: string) => void;
   copyFiles: string;
   setCopyFiles: (files: string) => void;
+  autoApproveRules: string;
+  setAutoApproveRules: (rules: string) => void;
   error: string;
   projectId?: string;
 }
@@ -56,6 +58,8 @@ export function ProjectFormFields({
   setCleanupScript,
   copyFiles,
   setCopyFiles,
+  autoApproveRules,
+  setAutoApproveRules,
   error,
   projectId,
 }: ProjectFormFieldsProps) {
@@ -270,6 +274,24 @@ export function ProjectFormFields({
         </p>
       </div>
 
+      <div className="space-y-2">
+        <Label htmlFor="auto-approve-rules">Auto-approve Rules (Optional)</Label>
+        <textarea
+          id="auto-approve-rules"
+          value={autoApproveRules}
+          onChange={(e) => setAutoApproveRules(e.target.value)}
+          placeholder={'Read\nGrep\nBash(npm test*)'}
+          rows={3}
+          className="w-full px-3 py-2 border border-input bg-background text-foreground rounded-md resize-vertical font-mono focus:outline-none focus:ring-2 focus:ring-ring"
+        />
+        <p className="text-sm text-muted-foreground">
+          Tool calls that supervised agents may make without asking, one per
+          line. Use <code>Tool</code> to allow every call to a tool, or{' '}
+          <code>Tool(pattern)</code> to allow calls whose command or path
+          matches the pattern, where <code>*</code> matches anything.
+        </p>
+      </div>
+
       {error && (
         <Alert variant="destructive">
           <AlertCircle className="h-4 w-4" />
@@ -36,6 +36,9 @@ export function ProjectForm({
     project?.cleanup_script ?? ''
   );
   const [copyFiles, setCopyFiles] = useState(project?.copy_files ?? '');
+  const [autoApproveRules, setAutoApproveRules] = useState(
+    project?.auto_approve_rules ?? ''
+  );
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState('');
   const [showFolderPicker, setShowFolderPicker] = useState(false);
@@ -54,6 +57,7 @@ export function ProjectForm({
       setDevScript(project.dev_script ?? '');
       setCleanupScript(project.cleanup_script ?? '');
       setCopyFiles(project.copy_files ?? '');
+      setAutoApproveRules(project.auto_approve_rules ?? '');
     } else {
       setName('');
       setGitRepoPath('');
@@ -61,6 +65,7 @@ export function ProjectForm({
       setDevScript('');
       setCleanupScript('');
       setCopyFiles('');
+      setAutoApproveRules('');
     }
   }, [project]);
 
@@ -102,6 +107,12 @@ export function ProjectForm({
           dev_script: devScript.trim() || null,
           cleanup_script: cleanupScript.trim() || null,
           copy_files: copyFiles.trim() || null,
+          // Orchestrator settings have no form fields yet; keep them as they are
+          orchestrator_test_command: project.orchestrator_test_command,
+          orchestrator_cold_timeout_sec: project.orchestrator_cold_timeout_sec,
+          orchestrator_warm_timeout_sec: project.orchestrator_warm_timeout_sec,
+          orchestrator_test_env: project.orchestrator_test_env,
+          auto_approve_rules: autoApproveRules.trim() || null,
         };
 
         await projectsApi.update(project.id, updateData);
@@ -140,6 +151,11 @@ export function ProjectForm({
           dev_script: devScript.trim() || null,
           cleanup_script: cleanupScript.trim() || null,
           copy_files: copyFiles.trim() || null,
+          orchestrator_test_command: null,
+          orchestrator_cold_timeout_sec: null,
+          orchestrator_warm_timeout_sec: null,
+          orchestrator_test_env: null,
+          auto_approve_rules: autoApproveRules.trim() || null,
         };
 
         await projectsApi.create(createData);
@@ -154,6 +170,7 @@ export function ProjectForm({
       setDevScript('');
       setCleanupScript('');
       setCopyFiles('');
+      setAutoApproveRules('');
       setParentPath('');
       setFolderName('');
     } catch (error) {
@@ -170,12 +187,14 @@ export function ProjectForm({
       setSetupScript(project.setup_script ?? '');
       setDevScript(project.dev_script ?? '');
       setCopyFiles(project.copy_files ?? '');
+      setAutoApproveRules(project.auto_approve_rules ?? '');
     } else {
       setName('');
       setGitRepoPath('');
       setSetupScript('');
       setDevScript('');
       setCopyFiles('');
+      setAutoApproveRules('');
     }
     setParentPath('');
     setFolderName('');
@@ -228,6 +247,10 @@ export function ProjectForm({
                   setCleanupScript={setCleanupScript}
                   copyFiles={copyFiles}
                   setCopyFiles={setCopyFiles}
+              autoApproveRules={autoApproveRules}
+              setAutoApproveRules={setAutoApproveRules}
+                  autoApproveRules={autoApproveRules}
+                  setAutoApproveRules={setAutoApproveRules}
                   error={error}
                   projectId={(project as any)?.id}
                 />
@@ -339,6 +362,8 @@ export function ProjectForm({
               setCleanupScript={setCleanupScript}
               copyFiles={copyFiles}
               setCopyFiles={setCopyFiles}
+              autoApproveRules={autoApproveRules}
+              setAutoApproveRules={setAutoApproveRules}
               error={error}
               projectId={(project as any)?.id}
             />
@@ -162,6 +162,7 @@ function ProcessCard({ process }: ProcessCardProps) {
                       entry={entry}
                       expansionKey={`${process.id}:${index}`}
                       diffDeletable={false}
+                      executionProcessId={process.id}
                     />
                   ))
                 )}
@@ -11,7 +11,7 @@ import {
   useRef,
   useCallback,
 } from 'react';
-import { attemptsApi, imagesApi } from '@/lib/api.ts';
+import { attemptsApi, executionProcessesApi, imagesApi } from '@/lib/api.ts';
 import type { ImageResponse } from 'shared/types';
 import {
   TaskAttemptDataContext,
@@ -86,6 +86,18 @@ export function TaskFollowUpSection() {
     isSendingFollowUp,
     branchStatus?.merges,
   ]);
+
+  // A running coding agent can take messages before it exits
+  const runningAgentProcess = useMemo(
+    () =>
+      attemptData.processes.find(
+        (p) => p.run_reason === 'codingagent' && p.status === 'running'
+      ) ?? null,
+    [attemptData.processes]
+  );
+  const canSendMessage = !!runningAgentProcess && !isSendingFollowUp;
+  const canSend = canSendFollowUp || canSendMessage;
+
   const currentProfile = useMemo(() => {
     if (!selectedProfile || !profiles) return null;
     return profiles.find((p) => p.label === selectedProfile);
@@ -118,7 +130,26 @@ export function TaskFollowUpSection() {
     setIsAnimating,
   });
 
+  const onSendMessage = async () => {
+    if (!runningAgentProcess || !followUpMessage.trim()) return;
+
+    try {
+      setIsSendingFollowUp(true);
+      setFollowUpError(null);
+      await executionProcessesApi.sendMessage(runningAgentProcess.id, {
+        message: followUpMessage.trim(),
+      });
+      setFollowUpMessage('');
+    } catch (error: unknown) {
+      // @ts-expect-error it is type ApiError
+      setFollowUpError(`Failed to send message: ${error.message}`);
+    } finally {
+      setIsSendingFollowUp(false);
+    }
+  };
+
   const onSendFollowUp = async () => {
+    if (runningAgentProcess) return onSendMessage();
     if (!task || !selectedAttempt || !followUpMessage.trim()) return;
 
     try {
@@ -178,7 +209,11 @@ export function TaskFollowUpSection() {
             )}
             <div className="flex gap-2 items-start">
               <FileSearchTextarea
-                placeholder="Continue working on this task... Type @ to search files."
+                placeholder={
+                  runningAgentProcess
+                    ? 'Send a message to the running agent... Type @ to search files.'
+                    : 'Continue working on this task... Type @ to search files.'
+                }
                 value={followUpMessage}
                 onChange={(value) => {
                   setFollowUpMessage(value);
@@ -188,7 +223,7 @@ export function TaskFollowUpSection() {
                   if ((e.metaKey || e.ctrlKey) && e.key === 'Enter') {
                     e.preventDefault();
                     if (
-                      canSendFollowUp &&
+                      canSend &&
                       followUpMessage.trim() &&
                       !isSendingFollowUp
                     ) {
@@ -197,7 +232,7 @@ export function TaskFollowUpSection() {
                   }
                 }}
                 className="flex-1 min-h-[40px] resize-none"
-                disabled={!canSendFollowUp}
+                disabled={!canSend}
                 projectId={projectId}
                 rows={1}
                 maxRows={6}
@@ -286,9 +321,7 @@ export function TaskFollowUpSection() {
               <Button
                 onClick={onSendFollowUp}
                 disabled={
-                  !canSendFollowUp ||
-                  !followUpMessage.trim() ||
-                  isSendingFollowUp
+                  !canSend || !followUpMessage.trim() || isSendingFollowUp
                 }
                 size="sm"
               >
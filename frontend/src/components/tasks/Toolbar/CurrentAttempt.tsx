@@ -38,10 +38,11 @@ import {
   SetStateAction,
   useCallback,
   useContext,
+  useEffect,
   useMemo,
   useState,
 } from 'react';
-import type { GitBranch, TaskAttempt } from 'shared/types';
+import type { GitBranch, TaskAttempt, TokenSpend } from 'shared/types';
 import {
   TaskAttemptDataContext,
   TaskAttemptStoppingContext,
@@ -74,6 +75,10 @@ function getEditorDisplayName(editorType: string): string {
   }
 }
 
+function formatTokens(tokens: bigint): string {
+  return Number(tokens).toLocaleString();
+}
+
 type Props = {
   setError: Dispatch<SetStateAction<string | null>>;
   setShowCreatePRDialog: Dispatch<SetStateAction<boolean>>;
@@ -115,6 +120,18 @@ function CurrentAttempt({
   const [copied, setCopied] = useState(false);
   const [mergeSuccess, setMergeSuccess] = useState(false);
   const [pushSuccess, setPushSuccess] = useState(false);
+  const [spend, setSpend] = useState<TokenSpend | null>(null);
+
+  // Refresh spend whenever a process of the attempt starts or finishes
+  const processStates = attemptData.processes
+    .map((process) => process.status)
+    .join();
+  useEffect(() => {
+    attemptsApi
+      .getUsage(selectedAttempt.id)
+      .then(setSpend)
+      .catch(() => setSpend(null));
+  }, [selectedAttempt.id, processStates]);
 
   // Find running dev server in current project
   const runningDevServer = useMemo(() => {
@@ -452,6 +469,40 @@ function CurrentAttempt({
           <div className="text-sm font-medium">{selectedAttempt.profile}</div>
         </div>
 
+        {spend && spend.models.length > 0 && (
+          <div className="min-w-0">
+            <div className="text-xs font-medium text-muted-foreground uppercase tracking-wide mb-1">
+              Spend
+            </div>
+            <TooltipProvider>
+              <Tooltip>
+                <TooltipTrigger asChild>
+                  <div className="text-sm font-medium cursor-default">
+                    {spend.cost_usd === null
+                      ? 'Unpriced'
+                      : `$${spend.cost_usd.toFixed(2)}`}
+                  </div>
+                </TooltipTrigger>
+                <TooltipContent>
+                  {spend.models.map((model) => (
+                    <p key={model.model}>
+                      {model.model || 'Unknown model'}:{' '}
+                      {formatTokens(
+                        model.input_tokens +
+                          model.cache_read_tokens +
+                          model.cache_write_tokens
+                      )}{' '}
+                      in / {formatTokens(model.output_tokens)} out
+                      {model.cost_usd !== null &&
+                        ` ($${model.cost_usd.toFixed(2)})`}
+                    </p>
+                  ))}
+                </TooltipContent>
+              </Tooltip>
+            </TooltipProvider>
+          </div>
+        )}
+
         <div className="min-w-0">
           <div className="text-xs font-medium text-muted-foreground uppercase tracking-wide mb-1">
             Task Branch
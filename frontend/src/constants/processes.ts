@@ -10,6 +10,7 @@ export const PROCESS_RUN_REASONS = {
   CLEANUP_SCRIPT: 'cleanupscript' as ExecutionProcessRunReason,
   CODING_AGENT: 'codingagent' as ExecutionProcessRunReason,
   DEV_SERVER: 'devserver' as ExecutionProcessRunReason,
+  ORCHESTRATOR: 'orchestrator' as ExecutionProcessRunReason,
 } as const;
 
 // Process statuses
@@ -46,8 +46,10 @@ export function ProjectContextPanel({ projectId }: { projectId: string }) {
             <th className="py-1">Name</th>
             <th>Size</th>
             <th>Mime</th>
+            <th>Text</th>
             <th>Checksum</th>
             <th>Download</th>
+            <th></th>
           </tr>
         </thead>
         <tbody>
@@ -56,6 +58,7 @@ export function ProjectContextPanel({ projectId }: { projectId: string }) {
               <td className="py-1">{it.filename}</td>
               <td>{(it.size / 1024).toFixed(1)} KB</td>
               <td>{it.mime}</td>
+              <td>{it.extraction}</td>
               <td className="font-mono text-xs">{it.content_hash.slice(0, 12)}…</td>
               <td>
                 <a
@@ -66,11 +69,22 @@ export function ProjectContextPanel({ projectId }: { projectId: string }) {
                   Download
                 </a>
               </td>
+              <td>
+                <button
+                  className="text-red-600 hover:underline"
+                  onClick={async () => {
+                    await contextApi.deleteProject(projectId, it.id);
+                    await refresh();
+                  }}
+                >
+                  Delete
+                </button>
+              </td>
             </tr>
           ))}
           {!items.length && (
             <tr>
-              <td colSpan={5} className="text-center py-4 text-muted-foreground">
+              <td colSpan={7} className="text-center py-4 text-muted-foreground">
                 No context files yet.
               </td>
             </tr>
@@ -18,6 +18,16 @@ export function TaskAttachmentsPanel({ taskId, projectDocs }: { taskId: string;
     } finally { setBusy(false); }
   };
 
+  const attach = async (fileId: string) => {
+    await contextApi.attachToTask(taskId, fileId);
+    await refresh();
+  };
+
+  const detach = async (fileId: string) => {
+    await contextApi.detachTask(taskId, fileId);
+    await refresh();
+  };
+
   return (
     <div className="space-y-4">
       <div className="border rounded p-3 flex items-center justify-between text-sm text-muted-foreground">
@@ -30,7 +40,14 @@ export function TaskAttachmentsPanel({ taskId, projectDocs }: { taskId: string;
           <div className="text-xs uppercase text-muted-foreground mb-1">Project Context (read-only)</div>
           <ul className="text-sm list-disc pl-4">
             {projectDocs.map((d) => (
-              <li key={d.filename + d.content_hash}>{d.filename} — {d.mime}</li>
+              <li key={d.filename + d.content_hash}>
+                {d.filename} — {d.mime}{' '}
+                {!items.some((i) => i.content_hash === d.content_hash) && (
+                  <button className="text-blue-600 hover:underline" onClick={() => attach(d.id)}>
+                    Attach
+                  </button>
+                )}
+              </li>
             ))}
           </ul>
         </div>
@@ -40,7 +57,12 @@ export function TaskAttachmentsPanel({ taskId, projectDocs }: { taskId: string;
         <div className="text-xs uppercase text-muted-foreground mb-1">Task Attachments</div>
         <ul className="text-sm list-disc pl-4">
           {items.map((d) => (
-            <li key={d.filename + d.content_hash}>{d.filename} — {d.mime}</li>
+            <li key={d.filename + d.content_hash}>
+              {d.filename} — {d.mime}{' '}
+              <button className="text-red-600 hover:underline" onClick={() => detach(d.id)}>
+                Remove
+              </button>
+            </li>
           ))}
           {!items.length && <li className="text-muted-foreground">No attachments yet.</li>}
         </ul>
@@ -1,10 +1,12 @@
 export type UploadMeta = {
+  id: string;
   filename: string;
   mime: string;
   size: number;
   content_hash: string;
   stored_at: string;
   extracted_text: boolean;
+  extraction: 'pending' | 'done' | 'failed' | 'unsupported';
   dedup?: boolean;
 };
 
@@ -24,6 +26,8 @@ export const contextApi = {
     const json = await res.json();
     return json.data as UploadMeta;
   },
+  deleteProject: (projectId: string, fileId: string) =>
+    api<null>(`/api/projects/${projectId}/uploads/${fileId}`, { method: 'DELETE' }),
   listTask: (taskId: string) => api<UploadMeta[]>(`/api/tasks/${taskId}/uploads`),
   uploadTask: async (taskId: string, file: File) => {
     const fd = new FormData();
@@ -32,4 +36,12 @@ export const contextApi = {
     const json = await res.json();
     return json.data as UploadMeta;
   },
+  attachToTask: (taskId: string, projectFileId: string) =>
+    api<UploadMeta>(`/api/tasks/${taskId}/uploads/attach`, {
+      method: 'POST',
+      headers: { 'content-type': 'application/json' },
+      body: JSON.stringify({ project_file_id: projectFileId }),
+    }),
+  detachTask: (taskId: string, fileId: string) =>
+    api<null>(`/api/tasks/${taskId}/uploads/${fileId}`, { method: 'DELETE' }),
 };
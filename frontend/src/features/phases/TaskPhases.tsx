@@ -1,9 +1,10 @@
 import React, {useState} from 'react';
+import type { PhaseType } from 'shared/types';
 import { updatePhase } from './api';
 
 type Props = { phaseId: string };
 export function TaskPhases({phaseId}:Props){
-  const [tab,setTab]=useState<'prompt'|'fix'|'hardening'>('prompt');
+  const [tab,setTab]=useState<PhaseType>('prompt');
   const [agent,setAgent]=useState('');
   const [budget,setBudget]=useState<number|''>('');
   const [allowlist,setAllow]=useState('');
@@ -15,9 +16,9 @@ export function TaskPhases({phaseId}:Props){
       warm_kpi_budget: typeof budget==='number'? budget:null,
       allowlist: allowlist? JSON.parse(allowlist):[],
       denylist: denylist? JSON.parse(denylist):[],
-    } as any);
+    });
   };
-  const Tab = ({id,label}:{id:'prompt'|'fix'|'hardening',label:string})=> (
+  const Tab = ({id,label}:{id:PhaseType,label:string})=> (
     <button onClick={()=>setTab(id)} style={{marginRight:8, fontWeight: tab===id?'bold':undefined}}>{label}</button>
   );
   return (
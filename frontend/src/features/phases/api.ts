@@ -1,20 +1,68 @@
-export type Phase = { task_id: string; phase_id: string; type: 'prompt'|'fix'|'hardening'; status: 'idle'|'running'|'pass'|'fail'; allowlist: any[]; denylist: any[]; agent_override: string|null; warm_kpi_budget: number|null; created_at?: string; updated_at?: string };
+import type { AttemptArtifacts, CreatePhase, Phase, StartPipelineRequest, UpdatePhase } from 'shared/types';
 
-export async function createPhase(taskId: string): Promise<Phase> {
-  const r = await fetch(`/api/tasks/${taskId}/phases`, { method:'POST', headers:{'content-type':'application/json'}, body: JSON.stringify({})});
+export type { Phase };
+
+export async function createPhase(taskId: string, body: CreatePhase = { type: null }): Promise<Phase> {
+  const r = await fetch(`/api/tasks/${taskId}/phases`, { method:'POST', headers:{'content-type':'application/json'}, body: JSON.stringify(body)});
   const j = await r.json();
   return j.data as Phase;
 }
 
-export async function updatePhase(phaseId: string, patch: Partial<Phase>): Promise<Partial<Phase>> {
+export async function listPhases(taskId: string): Promise<Phase[]> {
+  const r = await fetch(`/api/tasks/${taskId}/phases`);
+  const j = await r.json();
+  return (j.data ?? []) as Phase[];
+}
+
+export async function getPhase(phaseId: string): Promise<Phase> {
+  const r = await fetch(`/api/phases/${phaseId}`);
+  const j = await r.json();
+  return j.data as Phase;
+}
+
+export async function updatePhase(phaseId: string, patch: Partial<UpdatePhase>): Promise<Phase> {
   const r = await fetch(`/api/phases/${phaseId}`, { method:'PATCH', headers:{'content-type':'application/json'}, body: JSON.stringify(patch)});
   const j = await r.json();
-  return j.data as Partial<Phase>;
+  if (!j.success) throw new Error(j.message ?? 'Failed to update phase');
+  return j.data as Phase;
+}
+
+export async function deletePhase(phaseId: string): Promise<void> {
+  const r = await fetch(`/api/phases/${phaseId}`, { method:'DELETE' });
+  const j = await r.json();
+  if (!j.success) throw new Error(j.message ?? 'Failed to delete phase');
+}
+
+/** Run the task's phases in order (prompt → fix → hardening) in the background */
+export async function startPipeline(taskId: string, body: Partial<StartPipelineRequest> = {}): Promise<Phase[]> {
+  const r = await fetch(`/api/tasks/${taskId}/pipeline`, { method:'POST', headers:{'content-type':'application/json'}, body: JSON.stringify(body)});
+  const j = await r.json();
+  if (!j.success) throw new Error(j.message ?? 'Failed to start pipeline');
+  return j.data as Phase[];
 }
 
-export type ArtifactsSummary = { [k:string]: string|any, summary?: { validator?: { scope?: boolean; dep?: boolean; api?: boolean|null; det?: boolean; kpi?: boolean } } };
-export async function getArtifactsSummary(attemptId: string): Promise<ArtifactsSummary> {
+export async function listArtifacts(attemptId: string): Promise<AttemptArtifacts> {
   const r = await fetch(`/api/attempts/${attemptId}/artifacts`);
   const j = await r.json();
-  return j.data as ArtifactsSummary;
+  if (!j.success) throw new Error(j.message ?? 'Failed to load artifacts');
+  return j.data as AttemptArtifacts;
+}
+
+/** URL serving one artifact's contents, suitable for links and `fetch` */
+export function artifactUrl(attemptId: string, artifactId: string): string {
+  return `/api/attempts/${attemptId}/artifacts/${artifactId}`;
+}
+
+/** URL of a tar.gz bundle with every artifact of one orchestrator run */
+export function runBundleUrl(attemptId: string, executionProcessId: string): string {
+  return `/api/attempts/${attemptId}/runs/${executionProcessId}/artifacts.tar.gz`;
+}
+
+/** Re-execute a run from its stored manifest and agent reply; `rerunAgent` asks the agent again. The replay's `replay_report.json` says whether outputs matched */
+export async function replayRun(attemptId: string, executionProcessId: string, rerunAgent = false): Promise<{ execution_process_id: string; config_sha256: string }> {
+  const query = rerunAgent ? '?rerun_agent=true' : '';
+  const r = await fetch(`/api/attempts/${attemptId}/runs/${executionProcessId}/replay${query}`, { method:'POST' });
+  const j = await r.json();
+  if (!r.ok) throw new Error(j.error ?? 'Failed to start replay');
+  return j;
 }
@@ -2,6 +2,7 @@
 
 import {
   ApiResponse,
+  ApprovalDecision,
   BranchStatus,
   CheckTokenResponse,
   Config,
@@ -21,10 +22,13 @@ import {
   RebaseTaskAttemptRequest,
   RepositoryInfo,
   SearchResult,
+  SendMessageRequest,
   Task,
   TaskAttempt,
   TaskTemplate,
   TaskWithAttemptStatus,
+  TokenSpend,
+  ToolApproval,
   UpdateProject,
   UpdateTask,
   UpdateTaskTemplate,
@@ -252,6 +256,11 @@ export const tasksApi = {
     return handleApiResponse<Task>(response);
   },
 
+  getUsage: async (taskId: string): Promise<TokenSpend> => {
+    const response = await makeRequest(`/api/tasks/${taskId}/usage`);
+    return handleApiResponse<TokenSpend>(response);
+  },
+
   create: async (data: CreateTask): Promise<Task> => {
     const response = await makeRequest(`/api/tasks`, {
       method: 'POST',
@@ -298,6 +307,13 @@ export const attemptsApi = {
     return handleApiResponse<TaskAttempt[]>(response);
   },
 
+  getUsage: async (attemptId: string): Promise<TokenSpend> => {
+    const response = await makeRequest(
+      `/api/task-attempts/${attemptId}/usage`
+    );
+    return handleApiResponse<TokenSpend>(response);
+  },
+
   create: async (data: CreateTaskAttemptBody): Promise<TaskAttempt> => {
     const response = await makeRequest(`/api/task-attempts`, {
       method: 'POST',
@@ -448,6 +464,41 @@ export const executionProcessesApi = {
     );
     return handleApiResponse<void>(response);
   },
+
+  sendMessage: async (
+    processId: string,
+    data: SendMessageRequest
+  ): Promise<void> => {
+    const response = await makeRequest(
+      `/api/execution-processes/${processId}/message`,
+      {
+        method: 'POST',
+        body: JSON.stringify(data),
+      }
+    );
+    return handleApiResponse<void>(response);
+  },
+
+  getPendingApprovals: async (processId: string): Promise<ToolApproval[]> => {
+    const response = await makeRequest(
+      `/api/execution-processes/${processId}/approvals`
+    );
+    return handleApiResponse<ToolApproval[]>(response);
+  },
+
+  respondToApproval: async (
+    processId: string,
+    data: ApprovalDecision
+  ): Promise<ToolApproval> => {
+    const response = await makeRequest(
+      `/api/execution-processes/${processId}/approvals`,
+      {
+        method: 'POST',
+        body: JSON.stringify(data),
+      }
+    );
+    return handleApiResponse<ToolApproval>(response);
+  },
 };
 
 // File System APIs
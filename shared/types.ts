@@ -8,13 +8,13 @@ export type DirectoryEntry = { name: string, path: string, is_directory: boolean
 
 export type DirectoryListResponse = { entries: Array<DirectoryEntry>, current_path: string, };
 
-export type Project = { id: string, name: string, git_repo_path: string, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, created_at: Date, updated_at: Date, };
+export type Project = { id: string, name: string, git_repo_path: string, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, orchestrator_test_command: string | null, orchestrator_cold_timeout_sec: bigint | null, orchestrator_warm_timeout_sec: bigint | null, orchestrator_test_env: string | null, auto_approve_rules: string | null, created_at: Date, updated_at: Date, };
 
-export type ProjectWithBranch = { id: string, name: string, git_repo_path: string, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, current_branch: string | null, created_at: Date, updated_at: Date, };
+export type ProjectWithBranch = { id: string, name: string, git_repo_path: string, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, orchestrator_test_command: string | null, orchestrator_cold_timeout_sec: bigint | null, orchestrator_warm_timeout_sec: bigint | null, orchestrator_test_env: string | null, auto_approve_rules: string | null, current_branch: string | null, created_at: Date, updated_at: Date, };
 
-export type CreateProject = { name: string, git_repo_path: string, use_existing_repo: boolean, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, };
+export type CreateProject = { name: string, git_repo_path: string, use_existing_repo: boolean, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, orchestrator_test_command: string | null, orchestrator_cold_timeout_sec: bigint | null, orchestrator_warm_timeout_sec: bigint | null, orchestrator_test_env: string | null, auto_approve_rules: string | null, };
 
-export type UpdateProject = { name: string | null, git_repo_path: string | null, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, };
+export type UpdateProject = { name: string | null, git_repo_path: string | null, setup_script: string | null, dev_script: string | null, cleanup_script: string | null, copy_files: string | null, orchestrator_test_command: string | null, orchestrator_cold_timeout_sec: bigint | null, orchestrator_warm_timeout_sec: bigint | null, orchestrator_test_env: string | null, auto_approve_rules: string | null, };
 
 export type SearchResult = { path: string, is_file: boolean, match_type: SearchMatchType, };
 
@@ -24,7 +24,7 @@ export type ExecutorAction = { typ: ExecutorActionType, next_action: ExecutorAct
 
 export type McpConfig = { servers: { [key in string]?: JsonValue }, servers_path: Array<string>, template: JsonValue, vibe_kanban: JsonValue, is_toml_config: boolean, };
 
-export type ExecutorActionType = { "type": "CodingAgentInitialRequest" } & CodingAgentInitialRequest | { "type": "CodingAgentFollowUpRequest" } & CodingAgentFollowUpRequest | { "type": "ScriptRequest" } & ScriptRequest;
+export type ExecutorActionType = { "type": "CodingAgentInitialRequest" } & CodingAgentInitialRequest | { "type": "CodingAgentFollowUpRequest" } & CodingAgentFollowUpRequest | { "type": "ScriptRequest" } & ScriptRequest | { "type": "OrchestratorRequest" } & OrchestratorRequest;
 
 export type ScriptContext = "SetupScript" | "CleanupScript" | "DevServer";
 
@@ -48,12 +48,93 @@ export type CreateTask = { project_id: string, title: string, description: strin
 
 export type UpdateTask = { title: string | null, description: string | null, status: TaskStatus | null, parent_task_attempt: string | null, image_ids: Array<string> | null, };
 
+export type PhaseType = "prompt" | "fix" | "hardening";
+
+export type PhaseStatus = "idle" | "running" | "pass" | "fail";
+
+export type Phase = { id: string, task_id: string, type: PhaseType, status: PhaseStatus, allowlist: Array<string>, denylist: Array<string>, agent_override: string | null, warm_kpi_budget: number | null, dep_policy: JsonValue | null, reject_out_of_scope: boolean, created_at: string, updated_at: string, };
+
+export type CreatePhase = { type: PhaseType | null, };
+
+export type UpdatePhase = { type: PhaseType | null, status: PhaseStatus | null, allowlist: Array<string> | null, denylist: Array<string> | null, agent_override?: string | null, warm_kpi_budget?: number | null, dep_policy?: JsonValue | null, reject_out_of_scope: boolean | null, };
+
+export type StartPipelineRequest = { 
+/**
+ * Branch the first phase starts from; defaults to the base branch of the
+ * task's latest attempt, else the repository's current branch
+ */
+base_branch: string | null, 
+/**
+ * Runs per fix phase before the pipeline stops, default 3
+ */
+max_fix_iterations: number | null, };
+
+export type MetricsFilter = { project_id: string | null, since: string | null, until: string | null, };
+
+export type MetricsSummary = { attempts: bigint, profiles: Array<ProfileMetrics>, durations: Array<DurationMetrics>, validators: ValidatorMetrics, tokens: TokenMetrics, test_times: TestTimeMetrics, };
+
+export type ProfileMetrics = { profile: string, attempts: bigint, 
+/**
+ * Attempts merged directly or through a merged PR
+ */
+merged: bigint, 
+/**
+ * Unmerged attempts whose latest execution failed
+ */
+failed: bigint, 
+/**
+ * `merged / (merged + failed)`; `None` until an attempt has settled
+ */
+success_rate: number | null, };
+
+export type DurationStats = { count: bigint, sum_sec: number, mean_sec: number | null, p50_sec: number | null, p90_sec: number | null, p99_sec: number | null, };
+
+export type DurationMetrics = { run_reason: ExecutionProcessRunReason, count: bigint, sum_sec: number, mean_sec: number | null, p50_sec: number | null, p90_sec: number | null, p99_sec: number | null, };
+
+export type ValidatorMetrics = { runs: bigint, scope: number | null, dep: number | null, api: number | null, det: number | null, kpi: number | null, };
+
+export type TokenMetrics = { prompt_tokens: bigint, completion_tokens: bigint, models: Array<ModelTokenUsage>, 
+/**
+ * Spend on the models with a price; filled in from the price table
+ */
+cost_usd: number | null, };
+
+export type TestTimeMetrics = { cold: DurationStats, warm: DurationStats, cache_hit_count: bigint, };
+
+export type ModelTokenUsage = { 
+/**
+ * Empty when the agent did not report a model
+ */
+model: string, turns: bigint, input_tokens: bigint, output_tokens: bigint, cache_read_tokens: bigint, cache_write_tokens: bigint, 
+/**
+ * Filled in from the price table; `None` when the model has no price
+ */
+cost_usd: number | null, };
+
+export type TokenSpend = { models: Array<ModelTokenUsage>, 
+/**
+ * Spend on the models with a price; `None` when none has one
+ */
+cost_usd: number | null, };
+
 export type Image = { id: string, file_path: string, original_name: string, mime_type: string | null, size_bytes: bigint, hash: string, created_at: string, updated_at: string, };
 
 export type CreateImage = { file_path: string, original_name: string, mime_type: string | null, size_bytes: bigint, hash: string, };
 
 export type ApiResponse<T, E = T> = { success: boolean, data: T | null, error_data: E | null, message: string | null, };
 
+export type ApprovalStatus = { "status": "pending" } | { "status": "approved" } | { "status": "denied", reason: string | null, } | { "status": "timed_out" };
+
+export type ToolApproval = { id: string, tool_name: string, input: JsonValue, status: ApprovalStatus, };
+
+export type ApprovalDecision = { approval_id: string, approved: boolean, 
+/**
+ * Passed to the agent when the call is denied
+ */
+reason: string | null, };
+
+export type SendMessageRequest = { message: string, };
+
 export type UserSystemInfo = { config: Config, environment: Environment, profiles: Array<ProfileConfig>, };
 
 export type Environment = { os_type: string, os_version: string, os_architecture: string, bitness: string, };
@@ -126,7 +207,15 @@ label: string,
 /**
  * Optional profile-specific MCP config file path (absolute; supports leading ~). Overrides the default `BaseCodingAgent` config path
  */
-mcp_config_path: string | null, } & ({ "CLAUDE_CODE": ClaudeCode } | { "AMP": Amp } | { "GEMINI": Gemini } | { "CODEX": Codex } | { "OPENCODE": Opencode } | { "CURSOR": Cursor });
+mcp_config_path: string | null, 
+/**
+ * Environment variables for the agent; values may reference secrets as `${secret:NAME}`
+ */
+env?: { [key in string]?: string }, 
+/**
+ * `.env` file loaded before `env` (absolute; supports leading ~)
+ */
+env_file?: string, } & ({ "CLAUDE_CODE": ClaudeCode } | { "AMP": Amp } | { "GEMINI": Gemini } | { "CODEX": Codex } | { "OPENCODE": Opencode } | { "CURSOR": Cursor } | { "CUSTOM": Custom });
 
 export type VariantAgentConfig = { 
 /**
@@ -136,27 +225,94 @@ label: string,
 /**
  * Optional profile-specific MCP config file path (absolute; supports leading ~). Overrides the default `BaseCodingAgent` config path
  */
-mcp_config_path: string | null, } & ({ "CLAUDE_CODE": ClaudeCode } | { "AMP": Amp } | { "GEMINI": Gemini } | { "CODEX": Codex } | { "OPENCODE": Opencode } | { "CURSOR": Cursor });
+mcp_config_path: string | null, 
+/**
+ * Environment variables for the agent; values may reference secrets as `${secret:NAME}`
+ */
+env?: { [key in string]?: string }, 
+/**
+ * `.env` file loaded before `env` (absolute; supports leading ~)
+ */
+env_file?: string, } & ({ "CLAUDE_CODE": ClaudeCode } | { "AMP": Amp } | { "GEMINI": Gemini } | { "CODEX": Codex } | { "OPENCODE": Opencode } | { "CURSOR": Cursor } | { "CUSTOM": Custom });
 
 export type ProfileConfigs = { profiles: Array<ProfileConfig>, };
 
-export type ClaudeCode = { command: CommandBuilder, append_prompt: string | null, plan: boolean, };
+export type ClaudeCode = { command: CommandBuilder, append_prompt: string | null, plan: boolean, 
+/**
+ * Ask the vibe-kanban backend before running tools that need permission,
+ * instead of skipping permission checks
+ */
+approvals: boolean, 
+/**
+ * Read the prompt as stream-json and keep stdin open, so messages can be
+ * sent while the agent runs
+ */
+streaming_input: boolean, };
 
 export type Gemini = { command: CommandBuilder, append_prompt: string | null, };
 
 export type Amp = { command: CommandBuilder, append_prompt: string | null, };
 
-export type Codex = { command: CommandBuilder, append_prompt: string | null, };
+export type Codex = { command: CommandBuilder, append_prompt: string | null, 
+/**
+ * The command runs `codex proto`: the prompt is submitted as a protocol
+ * message and stdin stays open to answer approval requests and take
+ * messages while the task runs
+ */
+approvals: boolean, };
 
 export type Cursor = { command: CommandBuilder, append_prompt: string | null, };
 
 export type Opencode = { command: CommandBuilder, append_prompt: string | null, };
 
+export type Custom = { command: CommandBuilder, append_prompt: string | null, prompt_delivery: PromptDelivery, 
+/**
+ * Arguments appended to the command to resume a session, with `{session_id}`
+ * replaced by the session to resume. Follow-ups are not supported when unset
+ */
+follow_up_args: Array<string> | null, output: OutputSchema, };
+
+export type PromptDelivery = "stdin" | "argv" | "file";
+
+export type OutputSchema = { "format": "plain_text" } | { "format": "jsonl", 
+/**
+ * JSON pointer to the session id, e.g. `/session_id`
+ */
+session_id: string | null, 
+/**
+ * Tried in order; the first rule that matches a line wins
+ */
+rules: Array<OutputRule>, };
+
+export type OutputRule = { 
+/**
+ * JSON pointers and the values they must hold; an empty map matches every line
+ */
+when: { [key in string]?: JsonValue }, entry: EntryKind, 
+/**
+ * Defaults to the tool name for tool uses and to the whole line otherwise
+ */
+content: string | null, tool_name: string | null, 
+/**
+ * What a tool use does; defaults to `other` with the rendered content
+ */
+action: ActionRule | null, };
+
+export type EntryKind = "user_message" | "assistant_message" | "tool_use" | "system_message" | "error_message" | "thinking" | "skip";
+
+export type ActionRule = { "action": "file_read", path: string, } | { "action": "file_edit", path: string, } | { "action": "command_run", command: string, } | { "action": "search", query: string, } | { "action": "web_fetch", url: string, } | { "action": "task_create", description: string, } | { "action": "plan_presentation", plan: string, } | { "action": "other", description: string, };
+
 export type CodingAgentInitialRequest = { prompt: string, profile_variant_label: ProfileVariantLabel, };
 
 export type CodingAgentFollowUpRequest = { prompt: string, session_id: string, profile_variant_label: ProfileVariantLabel, };
 
-export type CreateTaskAttemptBody = { task_id: string, profile_variant_label: ProfileVariantLabel | null, base_branch: string, };
+export type OrchestratorRequest = { prompt: string, profile_variant_label: ProfileVariantLabel, };
+
+export type CreateTaskAttemptBody = { task_id: string, profile_variant_label: ProfileVariantLabel | null, base_branch: string, 
+/**
+ * Phase of the task this attempt runs; the phase moves to running
+ */
+phase_id?: string, };
 
 export type RebaseTaskAttemptRequest = { new_base_branch: string | null, };
 
@@ -164,11 +320,41 @@ export type BranchStatus = { commits_behind: number | null, commits_ahead: numbe
 
 export type TaskAttempt = { id: string, task_id: string, container_ref: string | null, branch: string | null, base_branch: string, profile: string, worktree_deleted: boolean, setup_completed_at: string | null, created_at: string, updated_at: string, };
 
+export type AttemptTelemetry = { prompt_tokens: bigint | null, completion_tokens: bigint | null, cold_sec: number | null, warm_sec: number | null, cache_hit_count: bigint | null, scope_pass: boolean | null, dep_pass: boolean | null, api_pass: boolean | null, det_pass: boolean | null, kpi_pass: boolean | null, };
+
+export type AttemptArtifact = { id: string, attempt_id: string, 
+/**
+ * The run that wrote it; `None` for artifacts recorded before runs were kept apart
+ */
+execution_process_id: string | null, kind: string, size_bytes: bigint | null, created_at: string, };
+
+export type ArtifactRun = { 
+/**
+ * `None` groups artifacts recorded before runs were kept apart
+ */
+execution_process_id: string | null, status: ExecutionProcessStatus | null, started_at: string | null, completed_at: string | null, 
+/**
+ * The run's parsed summary.json, once written
+ */
+summary: JsonValue | null, artifacts: Array<AttemptArtifact>, };
+
+export type AttemptArtifacts = { 
+/**
+ * Newest run first
+ */
+runs: Array<ArtifactRun>, telemetry: AttemptTelemetry | null, };
+
+export type ReplayQuery = { 
+/**
+ * Run the agent again instead of applying the original run's recorded reply
+ */
+rerun_agent: boolean, };
+
 export type ExecutionProcess = { id: string, task_attempt_id: string, run_reason: ExecutionProcessRunReason, executor_action: ExecutorAction, status: ExecutionProcessStatus, exit_code: bigint | null, started_at: string, completed_at: string | null, created_at: string, updated_at: string, };
 
 export type ExecutionProcessStatus = "running" | "completed" | "failed" | "killed";
 
-export type ExecutionProcessRunReason = "setupscript" | "cleanupscript" | "codingagent" | "devserver";
+export type ExecutionProcessRunReason = "setupscript" | "cleanupscript" | "codingagent" | "devserver" | "orchestrator";
 
 export type Merge = { "type": "direct" } & DirectMerge | { "type": "pr" } & PrMerge;
 
@@ -184,13 +370,13 @@ export type EventPatch = { op: string, path: string, value: EventPatchInner, };
 
 export type EventPatchInner = { db_op: string, record: RecordTypes, };
 
-export type RecordTypes = { "type": "TASK", "data": Task } | { "type": "TASK_ATTEMPT", "data": TaskAttempt } | { "type": "EXECUTION_PROCESS", "data": ExecutionProcess } | { "type": "DELETED_TASK", "data": { rowid: bigint, } } | { "type": "DELETED_TASK_ATTEMPT", "data": { rowid: bigint, } } | { "type": "DELETED_EXECUTION_PROCESS", "data": { rowid: bigint, } };
+export type RecordTypes = { "type": "TASK", "data": Task } | { "type": "TASK_ATTEMPT", "data": TaskAttempt } | { "type": "EXECUTION_PROCESS", "data": ExecutionProcess } | { "type": "PHASE", "data": Phase } | { "type": "DELETED_TASK", "data": { rowid: bigint, } } | { "type": "DELETED_TASK_ATTEMPT", "data": { rowid: bigint, } } | { "type": "DELETED_EXECUTION_PROCESS", "data": { rowid: bigint, } } | { "type": "DELETED_PHASE", "data": { rowid: bigint, } };
 
 export type NormalizedConversation = { entries: Array<NormalizedEntry>, session_id: string | null, executor_type: string, prompt: string | null, summary: string | null, };
 
 export type NormalizedEntry = { timestamp: string | null, entry_type: NormalizedEntryType, content: string, };
 
-export type NormalizedEntryType = { "type": "user_message" } | { "type": "assistant_message" } | { "type": "tool_use", tool_name: string, action_type: ActionType, } | { "type": "system_message" } | { "type": "error_message" } | { "type": "thinking" };
+export type NormalizedEntryType = { "type": "user_message" } | { "type": "assistant_message" } | { "type": "tool_use", tool_name: string, action_type: ActionType, } | { "type": "system_message" } | { "type": "error_message" } | { "type": "thinking" } | { "type": "tool_approval", approval_id: string, tool_name: string, status: ApprovalStatus, };
 
 export type FileChange = { "action": "write", content: string, } | { "action": "delete" } | { "action": "rename", new_path: string, } | { "action": "edit", 
 /**